@@ -0,0 +1,627 @@
+//! Async DFXML reading and writing on tokio.
+//!
+//! [`AsyncDFXMLReader`] parses DFXML from any [`AsyncBufRead`] source, such
+//! as a socket or an upload body, and yields the same [`Event`]s as
+//! [`DFXMLReader`] as a [`Stream`]. Input is read as the parser needs it and
+//! parsed on the calling task, so the runtime is never blocked waiting for
+//! data and no `spawn_blocking` is needed.
+//!
+//! [`AsyncDFXMLWriter`] and [`AsyncDFXMLStreamWriter`] are the async
+//! counterparts of [`DFXMLWriter`] and [`DFXMLStreamWriter`], writing to any
+//! [`AsyncWrite`].
+//!
+//! # Example
+//!
+//! ```rust
+//! use dfxml_rs::async_io::{AsyncDFXMLReader, AsyncDFXMLStreamWriter};
+//! use dfxml_rs::objects::DFXMLObject;
+//! use dfxml_rs::reader::Event;
+//!
+//! # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+//! let xml = r#"<dfxml version="1.0">
+//!   <volume><fileobject><filename>a.txt</filename></fileobject></volume>
+//! </dfxml>"#;
+//!
+//! let mut reader = AsyncDFXMLReader::from_reader(xml.as_bytes());
+//! let mut writer = AsyncDFXMLStreamWriter::new(Vec::new());
+//! writer.start_document(&DFXMLObject::new()).await.unwrap();
+//! while let Some(event) = reader.next_event().await {
+//!     if let Event::FileObject(file) = event.unwrap() {
+//!         writer.write_file(&file).await.unwrap();
+//!     }
+//! }
+//! let output = writer.finish().await.unwrap();
+//! assert!(String::from_utf8(output).unwrap().contains("<filename>a.txt</filename>"));
+//! # });
+//! ```
+
+use crate::error::Result;
+use crate::objects::{
+    DFXMLObject, DiskImageObject, FileObject, PartitionObject, PartitionSystemObject, VolumeObject,
+};
+use crate::reader::{DFXMLReader, DocumentBuilder, Event, Position, Warning};
+use crate::writer::{DFXMLStreamWriter, DFXMLWriter, WriterConfig};
+use futures_core::Stream;
+use std::future::poll_fn;
+use std::io::{self, BufRead, Read};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncBufRead, AsyncWrite, AsyncWriteExt};
+
+/// Output a stream writer buffers before writing it to its destination.
+const WRITE_THRESHOLD: usize = 8 * 1024;
+
+/// Input received from an async source that the parser has not read yet.
+///
+/// The parser only reads from it once it holds the whole next XML token,
+/// so it never runs dry in the middle of one.
+#[derive(Default)]
+struct Feed {
+    buf: Vec<u8>,
+    pos: usize,
+    eof: bool,
+}
+
+impl Feed {
+    /// Appends received bytes, dropping the ones already read.
+    fn extend(&mut self, bytes: &[u8]) {
+        if self.pos > 0 && self.pos >= self.buf.len() / 2 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Returns true if the unread input holds the whole next XML token, or
+    /// the input has ended. `in_markup` is set when the `<` opening the
+    /// token has been read already.
+    fn holds_token(&self, in_markup: bool) -> bool {
+        if self.eof {
+            return true;
+        }
+        let data = &self.buf[self.pos..];
+        let markup = if in_markup {
+            data
+        } else {
+            // Text before markup, and the markup after it
+            match data.iter().position(|&b| b == b'<') {
+                Some(i) => &data[i + 1..],
+                None => return false,
+            }
+        };
+        markup_len(markup).is_some()
+    }
+}
+
+impl Read for Feed {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let n = self.fill_buf()?.read(out)?;
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for Feed {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.buf.len());
+    }
+}
+
+/// Returns the length of the markup at the start of `data`, which follows
+/// its `<`, up to and including its closing `>`, or `None` if it is not
+/// complete.
+fn markup_len(data: &[u8]) -> Option<usize> {
+    let find = |from: usize, end: &[u8]| {
+        data.get(from..)?
+            .windows(end.len())
+            .position(|w| w == end)
+            .map(|i| from + i + end.len())
+    };
+    const COMMENT: &[u8] = b"!--";
+    const CDATA: &[u8] = b"![CDATA[";
+    match data.first()? {
+        _ if data.starts_with(COMMENT) => find(COMMENT.len(), b"-->"),
+        _ if data.starts_with(CDATA) => find(CDATA.len(), b"]]>"),
+        // Too short to tell a comment or CDATA section from a DTD
+        b'!' if COMMENT.starts_with(data) || CDATA.starts_with(data) => None,
+        b'!' => {
+            // A DTD, with nested declarations
+            let mut depth = 1;
+            for (i, &b) in data.iter().enumerate() {
+                match b {
+                    b'<' => depth += 1,
+                    b'>' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(i + 1);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            None
+        }
+        b'?' => find(1, b"?>"),
+        _ => {
+            // A start or end tag, whose attribute values may hold `>`
+            let mut quote = None;
+            for (i, &b) in data.iter().enumerate() {
+                match (quote, b) {
+                    (None, b'"' | b'\'') => quote = Some(b),
+                    (Some(q), _) if q == b => quote = None,
+                    (None, b'>') => return Some(i + 1),
+                    _ => {}
+                }
+            }
+            None
+        }
+    }
+}
+
+/// A streaming DFXML parser over an async source.
+///
+/// Yields the same [`Event`]s as [`DFXMLReader`] as a [`Stream`], or one at
+/// a time from [`next_event`](Self::next_event). The source is read as the
+/// parser needs more input, and parsing never waits on it, so the reader
+/// can run on any tokio runtime.
+pub struct AsyncDFXMLReader<R> {
+    input: R,
+    reader: DFXMLReader<Feed>,
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncDFXMLReader<R> {
+    /// Creates a new DFXML reader from an async buffered reader.
+    pub fn from_reader(input: R) -> Self {
+        Self {
+            input,
+            reader: DFXMLReader::from_reader(Feed::default()),
+        }
+    }
+
+    /// Sets whether values that cannot be parsed are errors (see
+    /// [`DFXMLReader::with_strict_values`]).
+    pub fn with_strict_values(mut self, strict: bool) -> Self {
+        self.reader = self.reader.with_strict_values(strict);
+        self
+    }
+
+    /// Sets whether to read past problems in the input instead of failing
+    /// (see [`DFXMLReader::with_recovery`]).
+    pub fn with_recovery(mut self, recover: bool) -> Self {
+        self.reader = self.reader.with_recovery(recover);
+        self
+    }
+
+    /// Returns the next event, or `None` at the end of the document.
+    pub async fn next_event(&mut self) -> Option<Result<Event>> {
+        poll_fn(|cx| self.poll_event(cx)).await
+    }
+
+    /// Parses the next event, reading more input whenever the parser needs
+    /// it.
+    fn poll_event(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Event>>> {
+        loop {
+            let pending = self
+                .reader
+                .poll_event(|feed, in_markup| feed.holds_token(in_markup));
+            if let Poll::Ready(event) = pending {
+                return Poll::Ready(event);
+            }
+
+            let bytes = match ready!(Pin::new(&mut self.input).poll_fill_buf(cx)) {
+                Ok(bytes) => bytes,
+                Err(e) => return Poll::Ready(Some(Err(e.into()))),
+            };
+            let feed = self.reader.input_mut();
+            if bytes.is_empty() {
+                feed.eof = true;
+            } else {
+                feed.extend(bytes);
+            }
+            let n = bytes.len();
+            Pin::new(&mut self.input).consume(n);
+        }
+    }
+
+    /// Returns the problems read past so far in recovery mode.
+    pub fn warnings(&self) -> &[Warning] {
+        self.reader.warnings()
+    }
+
+    /// Returns the problems read past since the last call, and forgets them.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        self.reader.take_warnings()
+    }
+
+    /// Returns the document metadata read so far.
+    pub fn document(&self) -> Option<&DFXMLObject> {
+        self.reader.document()
+    }
+
+    /// Returns the innermost open disk image.
+    pub fn current_disk_image(&self) -> Option<&DiskImageObject> {
+        self.reader.current_disk_image()
+    }
+
+    /// Returns the innermost open partition system.
+    pub fn current_partition_system(&self) -> Option<&PartitionSystemObject> {
+        self.reader.current_partition_system()
+    }
+
+    /// Returns the innermost open partition.
+    pub fn current_partition(&self) -> Option<&PartitionObject> {
+        self.reader.current_partition()
+    }
+
+    /// Returns the innermost open volume.
+    pub fn current_volume(&self) -> Option<&VolumeObject> {
+        self.reader.current_volume()
+    }
+
+    /// Returns the position of the start tag of the element that produced
+    /// the last event.
+    pub fn event_position(&self) -> Position {
+        self.reader.event_position()
+    }
+
+    /// Returns the position of the last error.
+    pub fn error_position(&self) -> Option<Position> {
+        self.reader.error_position()
+    }
+
+    /// Returns the input being read.
+    pub fn get_ref(&self) -> &R {
+        &self.input
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Stream for AsyncDFXMLReader<R> {
+    type Item = Result<Event>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_event(cx)
+    }
+}
+
+/// Parses a whole DFXML document from an async source.
+pub async fn parse<R: AsyncBufRead + Unpin>(input: R) -> Result<DFXMLObject> {
+    let mut reader = AsyncDFXMLReader::from_reader(input);
+    let mut builder = DocumentBuilder::default();
+    while let Some(event) = reader.next_event().await {
+        builder.add(event?);
+    }
+    builder.finish()
+}
+
+/// Writes whole DFXML documents to async destinations.
+///
+/// The document is serialized in memory and then written out; use
+/// [`AsyncDFXMLStreamWriter`] to write file objects as they are produced.
+#[derive(Default)]
+pub struct AsyncDFXMLWriter {
+    inner: DFXMLWriter,
+}
+
+impl AsyncDFXMLWriter {
+    /// Creates a writer with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a writer with a custom configuration.
+    pub fn with_config(config: WriterConfig) -> Self {
+        Self {
+            inner: DFXMLWriter::with_config(config),
+        }
+    }
+
+    /// Writes a DFXMLObject to any `AsyncWrite` implementation, and flushes
+    /// it.
+    pub async fn write<W: AsyncWrite + Unpin>(
+        &self,
+        doc: &DFXMLObject,
+        mut writer: W,
+    ) -> Result<()> {
+        let mut buf = Vec::new();
+        self.inner.write(doc, &mut buf)?;
+        writer.write_all(&buf).await?;
+        writer.flush().await?;
+        Ok(())
+    }
+}
+
+/// Writes a DFXML document incrementally to an async destination.
+///
+/// The async counterpart of [`DFXMLStreamWriter`], with the same methods.
+/// Output is serialized into a small buffer and written out when it fills
+/// up, on [`flush`](Self::flush) and on [`finish`](Self::finish).
+pub struct AsyncDFXMLStreamWriter<W> {
+    writer: DFXMLStreamWriter<Vec<u8>>,
+    output: W,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncDFXMLStreamWriter<W> {
+    /// Creates a stream writer with the default configuration.
+    pub fn new(output: W) -> Self {
+        Self {
+            writer: DFXMLStreamWriter::new(Vec::new()),
+            output,
+        }
+    }
+
+    /// Creates a stream writer with a custom configuration. Fails if the
+    /// compressed output cannot be set up.
+    pub fn with_config(output: W, config: WriterConfig) -> Result<Self> {
+        Ok(Self {
+            writer: DFXMLStreamWriter::with_config(Vec::new(), config)?,
+            output,
+        })
+    }
+
+    /// Writes the XML declaration, the `<dfxml>` start tag and the document
+    /// metadata.
+    pub async fn start_document(&mut self, doc: &DFXMLObject) -> Result<()> {
+        self.writer.start_document(doc)?;
+        self.write_buffered(false).await
+    }
+
+    /// Opens a `<diskimageobject>` and writes its properties.
+    pub async fn start_disk_image(&mut self, di: &DiskImageObject) -> Result<()> {
+        self.writer.start_disk_image(di)?;
+        self.write_buffered(false).await
+    }
+
+    /// Closes the innermost `<diskimageobject>`.
+    pub async fn end_disk_image(&mut self) -> Result<()> {
+        self.writer.end_disk_image()?;
+        self.write_buffered(false).await
+    }
+
+    /// Opens a `<partitionsystemobject>` and writes its properties.
+    pub async fn start_partition_system(&mut self, ps: &PartitionSystemObject) -> Result<()> {
+        self.writer.start_partition_system(ps)?;
+        self.write_buffered(false).await
+    }
+
+    /// Closes the innermost `<partitionsystemobject>`.
+    pub async fn end_partition_system(&mut self) -> Result<()> {
+        self.writer.end_partition_system()?;
+        self.write_buffered(false).await
+    }
+
+    /// Opens a `<partitionobject>` and writes its properties.
+    pub async fn start_partition(&mut self, p: &PartitionObject) -> Result<()> {
+        self.writer.start_partition(p)?;
+        self.write_buffered(false).await
+    }
+
+    /// Closes the innermost `<partitionobject>`.
+    pub async fn end_partition(&mut self) -> Result<()> {
+        self.writer.end_partition()?;
+        self.write_buffered(false).await
+    }
+
+    /// Opens a `<volume>` and writes its properties.
+    pub async fn start_volume(&mut self, vol: &VolumeObject) -> Result<()> {
+        self.writer.start_volume(vol)?;
+        self.write_buffered(false).await
+    }
+
+    /// Closes the innermost `<volume>`.
+    pub async fn end_volume(&mut self) -> Result<()> {
+        self.writer.end_volume()?;
+        self.write_buffered(false).await
+    }
+
+    /// Writes a file object in the innermost open container.
+    pub async fn write_file(&mut self, file: &FileObject) -> Result<()> {
+        self.writer.write_file(file)?;
+        self.write_buffered(false).await
+    }
+
+    /// Writes out everything serialized so far and flushes the destination.
+    pub async fn flush(&mut self) -> Result<()> {
+        self.write_buffered(true).await?;
+        self.output.flush().await?;
+        Ok(())
+    }
+
+    /// Closes any open containers and the `<dfxml>` element, writes out the
+    /// rest of the document and returns the destination, flushed.
+    pub async fn finish(mut self) -> Result<W> {
+        let rest = self.writer.finish()?;
+        self.output.write_all(&rest).await?;
+        self.output.flush().await?;
+        Ok(self.output)
+    }
+
+    /// Writes the serialized output to the destination once there is
+    /// enough of it, or always if `all` is set.
+    async fn write_buffered(&mut self, all: bool) -> Result<()> {
+        // Compressed output is buffered here after the encoder
+        let buf = self.writer.get_mut();
+        if buf.len() >= WRITE_THRESHOLD || (all && !buf.is_empty()) {
+            self.output.write_all(buf).await?;
+            buf.clear();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::reader::parse as parse_sync;
+    use std::io::Cursor;
+    use tokio::io::{AsyncRead, BufReader, ReadBuf};
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- written by a test -->
+<dfxml xmlns="http://www.forensicswiki.org/wiki/Category:Digital_Forensics_XML" xmlns:ex="urn:example" version="1.0">
+  <metadata><dc:type xmlns:dc="http://purl.org/dc/elements/1.1/">Disk Image</dc:type></metadata>
+  <source><image_filename>disk.raw</image_filename></source>
+  <volume offset="1048576">
+    <ftype_str>ntfs</ftype_str>
+    <fileobject>
+      <filename><![CDATA[a > b.txt]]></filename>
+      <filesize>12</filesize>
+      <ex:note kind="x>y">kept</ex:note>
+      <hashdigest type="md5">d41d8cd98f00b204e9800998ecf8427e</hashdigest>
+    </fileobject>
+    <fileobject><filename>c.txt</filename><inode>7</inode></fileobject>
+  </volume>
+</dfxml>
+"#;
+
+    /// A source that returns at most `chunk` bytes per read, and is not
+    /// ready every other time it is polled.
+    struct Trickle {
+        data: Cursor<Vec<u8>>,
+        chunk: usize,
+        stalled: bool,
+    }
+
+    impl AsyncRead for Trickle {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            self.stalled = !self.stalled;
+            if self.stalled {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let mut chunk = vec![0; self.chunk.min(buf.remaining())];
+            let n = self.data.read(&mut chunk)?;
+            buf.put_slice(&chunk[..n]);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn trickle(xml: &str, chunk: usize) -> BufReader<Trickle> {
+        BufReader::with_capacity(
+            chunk,
+            Trickle {
+                data: Cursor::new(xml.as_bytes().to_vec()),
+                chunk,
+                stalled: false,
+            },
+        )
+    }
+
+    fn describe(event: Event, position: Position) -> String {
+        let event = format!("{:?} at {}", event, position);
+        // Namespaces are a hash map, so leave them out
+        match event.find("namespaces: {") {
+            Some(start) => {
+                let end = start + event[start..].find('}').unwrap();
+                format!("{}{}", &event[..start], &event[end..])
+            }
+            None => event,
+        }
+    }
+
+    async fn events<R: AsyncBufRead + Unpin>(input: R) -> Vec<String> {
+        let mut reader = AsyncDFXMLReader::from_reader(input);
+        let mut events = Vec::new();
+        while let Some(event) = reader.next_event().await {
+            events.push(describe(event.unwrap(), reader.event_position()));
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn test_events_match_blocking_reader() {
+        let expected: Vec<String> = DFXMLReader::from_reader(XML.as_bytes())
+            .with_positions()
+            .map(|e| {
+                let (event, position) = e.unwrap();
+                describe(event, position)
+            })
+            .collect();
+        for chunk in [1, 2, 3, 7, 64, 4096] {
+            assert_eq!(
+                events(trickle(XML, chunk)).await,
+                expected,
+                "chunk {}",
+                chunk
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_errors_and_recovery() {
+        let invalid = XML.replace("<filesize>12", "<filesize>1x");
+        let mut reader =
+            AsyncDFXMLReader::from_reader(trickle(&invalid, 5)).with_strict_values(true);
+        let mut error = None;
+        while let Some(event) = reader.next_event().await {
+            if let Err(e) = event {
+                error = Some(e);
+                break;
+            }
+        }
+        assert!(matches!(error, Some(Error::ParseInt(_))));
+        assert_eq!(reader.error_position().unwrap().line, 10);
+
+        // Without recovery a truncated input just ends
+        let truncated = &XML[..XML.find("<inode>").unwrap()];
+        let mut reader = AsyncDFXMLReader::from_reader(trickle(truncated, 5));
+        let mut files = 0;
+        while let Some(event) = reader.next_event().await {
+            if let Event::FileObject(_) = event.unwrap() {
+                files += 1;
+            }
+        }
+        assert_eq!(files, 1);
+
+        let mut reader = AsyncDFXMLReader::from_reader(trickle(truncated, 5)).with_recovery(true);
+        let mut files = 0;
+        while let Some(event) = reader.next_event().await {
+            if let Event::FileObject(_) = event.unwrap() {
+                files += 1;
+            }
+        }
+        assert_eq!(files, 1);
+        assert!(!reader.warnings().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_writers() {
+        let doc = parse(trickle(XML, 3)).await.unwrap();
+        assert_eq!(doc.iter_files().count(), 2);
+
+        let mut batch = Vec::new();
+        AsyncDFXMLWriter::new()
+            .write(&doc, &mut batch)
+            .await
+            .unwrap();
+        assert_eq!(
+            batch,
+            DFXMLWriter::new()
+                .write_to_string(&doc)
+                .unwrap()
+                .into_bytes()
+        );
+
+        let mut writer = AsyncDFXMLStreamWriter::new(Vec::new());
+        writer.start_document(&doc).await.unwrap();
+        writer
+            .start_volume(&VolumeObject::with_ftype("fat"))
+            .await
+            .unwrap();
+        for i in 0..1000 {
+            let file = FileObject::with_filename(format!("file{}.txt", i));
+            writer.write_file(&file).await.unwrap();
+        }
+        let output = writer.finish().await.unwrap();
+        let written = parse_sync(output.as_slice()).unwrap();
+        assert_eq!(written.iter_files().count(), 1000);
+    }
+}
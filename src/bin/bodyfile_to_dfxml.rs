@@ -0,0 +1,90 @@
+//! bodyfile_to_dfxml - Convert a Sleuth Kit body file to DFXML.
+//!
+//! Each body file line becomes a fileobject with its MD5, name, inode, mode,
+//! ownership, size and MAC times. Names marked ` (deleted)` are recorded as
+//! unallocated. Body files carry no volume information, so the fileobjects
+//! are written directly under the `<dfxml>` element.
+//!
+//! # Usage
+//!
+//! ```bash
+//! bodyfile_to_dfxml [OPTIONS] <FILENAME>
+//! ```
+//!
+//! # Examples
+//!
+//! ```bash
+//! # Convert a body file produced by fls -m
+//! bodyfile_to_dfxml disk.body > disk.dfxml
+//!
+//! # Output compact XML
+//! bodyfile_to_dfxml --compact disk.body > disk.dfxml
+//!
+//! # Convert a gzipped body file to gzipped DFXML
+//! bodyfile_to_dfxml --compress gzip disk.body.gz > disk.dfxml.gz
+//! ```
+
+use std::io::{self, BufWriter, Write};
+
+use clap::Parser;
+
+use dfxml_rs::bodyfile::read_bodyfile;
+use dfxml_rs::compression::{self, Compression, Encoder};
+use dfxml_rs::writer::{DFXMLWriter, WriterConfig};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Convert a Sleuth Kit body file to DFXML.
+#[derive(Parser, Debug)]
+#[command(name = "bodyfile_to_dfxml")]
+#[command(version = VERSION)]
+#[command(about = "Convert a Sleuth Kit body file to DFXML")]
+#[command(
+    long_about = "Reads a Sleuth Kit body file (mactime format) and writes a DFXML \
+    document with one fileobject per line."
+)]
+struct Args {
+    /// Input body file to convert
+    filename: String,
+
+    /// Output compact XML (no indentation)
+    #[arg(long)]
+    compact: bool,
+
+    /// Compress the output (none, gzip, zstd or xz)
+    #[arg(long, value_name = "FORMAT", default_value = "none")]
+    compress: Compression,
+
+    /// Enable debug output
+    #[arg(short, long)]
+    debug: bool,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let input = compression::open(&args.filename)?;
+    let mut doc = read_bodyfile(input)?;
+    doc.program = Some("bodyfile_to_dfxml".to_string());
+    doc.program_version = Some(VERSION.to_string());
+    doc.command_line = Some(std::env::args().collect::<Vec<_>>().join(" "));
+    doc.sources.push(args.filename.clone());
+
+    if args.debug {
+        eprintln!("Read {} fileobjects", doc.iter_files().count());
+    }
+
+    let config = if args.compact {
+        WriterConfig::compact()
+    } else {
+        WriterConfig::default()
+    };
+
+    let stdout = io::stdout();
+    let mut out = Encoder::new(BufWriter::new(stdout.lock()), args.compress)?;
+    DFXMLWriter::with_config(config).write(&doc, &mut out)?;
+    out.write_all(b"\n")?;
+    out.finish()?.flush()?;
+
+    Ok(())
+}
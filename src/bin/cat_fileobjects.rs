@@ -21,6 +21,9 @@
 //!
 //! # Enable debug output
 //! cat_fileobjects --debug input.dfxml > output.dfxml
+//!
+//! # Read gzipped input and write zstd-compressed output
+//! cat_fileobjects --compress zstd input.dfxml.gz > output.dfxml.zst
 //! ```
 //!
 //! # Output
@@ -31,14 +34,14 @@
 //! - Source image filename (the input file)
 //! - All fileobject elements from the input file
 
-use std::fs::File;
-use std::io::{self, BufReader};
+use std::io::{self, BufWriter, Write};
 
 use clap::Parser;
 
-use dfxml_rs::objects::{DFXMLObject, FileObject, DFXML_VERSION, XMLNS_DELTA, XMLNS_DFXML};
-use dfxml_rs::reader::{DFXMLReader, Event};
-use dfxml_rs::writer::{DFXMLWriter, WriterConfig};
+use dfxml_rs::compression::{self, Compression, Encoder};
+use dfxml_rs::objects::{DFXMLObject, FileObject};
+use dfxml_rs::reader::Event;
+use dfxml_rs::writer::{DFXMLStreamWriter, WriterConfig};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -76,31 +79,10 @@ struct Args {
     /// Output compact XML (no indentation)
     #[arg(long)]
     compact: bool,
-}
-
-/// Writes a single FileObject to stdout as XML.
-fn write_fileobject(file: &FileObject, config: &WriterConfig) -> io::Result<()> {
-    // Create a temporary DFXML document to use the writer infrastructure
-    // We'll extract just the fileobject portion
-    let mut temp_doc = DFXMLObject::new();
-    temp_doc.append_file(file.clone());
-
-    // Use the writer to generate XML, then extract the fileobject portion
-    let writer = DFXMLWriter::with_config(config.clone());
-    let xml = writer
-        .write_to_string(&temp_doc)
-        .map_err(|e| io::Error::other(e.to_string()))?;
-
-    // Find and extract the fileobject element
-    // Look for <fileobject> ... </fileobject>
-    if let Some(start) = xml.find("<fileobject") {
-        if let Some(end) = xml.rfind("</fileobject>") {
-            let fileobject_xml = &xml[start..end + "</fileobject>".len()];
-            println!("{}", fileobject_xml);
-        }
-    }
 
-    Ok(())
+    /// Compress the output (none, gzip, zstd or xz)
+    #[arg(long, value_name = "FORMAT", default_value = "none")]
+    compress: Compression,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -112,42 +94,28 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         eprintln!("Cache mode: {}", args.cache);
     }
 
+    // Open and parse the input file, which may be compressed
+    let dfxml_reader = compression::open_reader(&args.filename)?;
+
     // Build the output DFXML header
-    let command_line = std::env::args().collect::<Vec<_>>().join(" ");
-
-    // Print XML declaration and opening elements
-    println!(
-        r#"<?xml version="1.0" encoding="UTF-8"?>
-<dfxml
-  xmlns="{}"
-  xmlns:delta="{}"
-  version="{}">
-  <metadata/>
-  <creator>
-    <program>cat_fileobjects</program>
-    <version>{}</version>
-    <execution_environment>
-      <command_line>{}</command_line>
-    </execution_environment>
-  </creator>
-  <source>
-    <image_filename>{}</image_filename>
-  </source>"#,
-        XMLNS_DFXML, XMLNS_DELTA, DFXML_VERSION, VERSION, command_line, args.filename
-    );
-
-    // Open and parse the input file
-    let file = File::open(&args.filename)?;
-    let reader = BufReader::new(file);
-    let dfxml_reader = DFXMLReader::from_reader(reader);
-
-    // Configure writer for fileobject output
+    let mut doc = DFXMLObject::new();
+    doc.program = Some("cat_fileobjects".to_string());
+    doc.program_version = Some(VERSION.to_string());
+    doc.command_line = Some(std::env::args().collect::<Vec<_>>().join(" "));
+    doc.sources.push(args.filename.clone());
+
     let config = if args.compact {
         WriterConfig::compact()
     } else {
         WriterConfig::default()
     };
 
+    // Compress the trailing newline along with the document
+    let stdout = io::stdout();
+    let out = Encoder::new(BufWriter::new(stdout.lock()), args.compress)?;
+    let mut writer = DFXMLStreamWriter::with_config(out, config)?;
+    writer.start_document(&doc)?;
+
     if args.cache {
         // Cache mode: collect all fileobjects first, then print
         let mut file_objects: Vec<FileObject> = Vec::new();
@@ -175,7 +143,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             if args.debug {
                 eprintln!("Printing with cache: {:?}", file.filename);
             }
-            write_fileobject(file, &config)?;
+            writer.write_file(file)?;
         }
     } else {
         // Streaming mode: print each fileobject as it's parsed
@@ -186,7 +154,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         eprintln!("Processing: {:?}", file.filename);
                         eprintln!("Printing without cache: {:?}", file.filename);
                     }
-                    write_fileobject(&file, &config)?;
+                    writer.write_file(&file)?;
                 }
                 Ok(_) => {
                     // Ignore other events
@@ -200,7 +168,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Close the DFXML document
-    println!("</dfxml>");
+    let mut out = writer.finish()?;
+    out.write_all(b"\n")?;
+    out.finish()?.flush()?;
 
     Ok(())
 }
@@ -25,6 +25,12 @@
 //!
 //! # Enable debug output
 //! cat_partitions --debug 32256:part1.dfxml > combined.dfxml
+//!
+//! # Combine gzipped inputs into an xz-compressed document
+//! cat_partitions --compress xz 32256:part1.dfxml.gz 1073741824:part2.dfxml.gz > combined.dfxml.xz
+//!
+//! # Take the offsets of partitions 1 and 5 from disk_to_dfxml output
+//! cat_partitions --disk disk.dfxml 1:part1.dfxml 5:part5.dfxml > combined.dfxml
 //! ```
 //!
 //! # Input Format
@@ -33,6 +39,9 @@
 //! - `OFFSET` is the partition's byte offset from the start of the disk image
 //! - `PATH` is the path to the DFXML file for that partition
 //!
+//! With `--disk`, `OFFSET` is instead a partition number from the partition
+//! table that `disk_to_dfxml` wrote, and the offset is looked up there.
+//!
 //! # Output
 //!
 //! The output is a valid DFXML document with:
@@ -40,12 +49,13 @@
 //! - All file objects with updated `partition` numbers and byte run offsets
 //! - Accumulated namespaces from all input documents
 
-use std::fs::File;
-use std::io::BufReader;
+use std::io::{self, BufWriter, Write};
 
 use clap::Parser;
 
+use dfxml_rs::compression::{self, Compression, Encoder};
 use dfxml_rs::objects::{DFXMLObject, FileObject, LibraryObject, VolumeObject};
+use dfxml_rs::partition;
 use dfxml_rs::reader::parse;
 use dfxml_rs::writer::{to_string, DFXMLWriter, WriterConfig};
 
@@ -82,9 +92,18 @@ struct Args {
     #[arg(long)]
     image_path: Option<String>,
 
+    /// Partition table DFXML from disk_to_dfxml; inputs are then labeled
+    /// with partition numbers (INDEX:PATH) instead of byte offsets
+    #[arg(long, value_name = "DFXML")]
+    disk: Option<String>,
+
     /// Output compact XML (no indentation)
     #[arg(long)]
     compact: bool,
+
+    /// Compress the output (none, gzip, zstd or xz)
+    #[arg(long, value_name = "FORMAT", default_value = "none")]
+    compress: Compression,
 }
 
 /// Parsed input specification: offset and path.
@@ -119,6 +138,22 @@ fn parse_labeled_input(spec: &str) -> Result<LabeledInput, String> {
     Ok(LabeledInput { offset, path })
 }
 
+/// Replace partition numbers with the offsets of those partitions.
+fn resolve_partition_numbers(
+    inputs: &mut [LabeledInput],
+    offsets: &[(u32, u64)],
+) -> Result<(), String> {
+    for input in inputs {
+        let index = input.offset;
+        input.offset = offsets
+            .iter()
+            .find(|(i, _)| u64::from(*i) == index)
+            .map(|(_, offset)| *offset)
+            .ok_or_else(|| format!("No partition {} in the partition table", index))?;
+    }
+    Ok(())
+}
+
 /// Update byte run img_offsets based on fs_offset and partition offset.
 fn update_file_byte_runs(file: &mut FileObject, partition_offset: u64) {
     // Update data byte runs
@@ -169,6 +204,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    // Look up partition numbers in the partition table
+    let mut image_path = args.image_path.clone();
+    if let Some(ref disk_path) = args.disk {
+        let disk_doc = parse(compression::open(disk_path)?)?;
+        if let Err(e) =
+            resolve_partition_numbers(&mut inputs, &partition::partition_offsets(&disk_doc))
+        {
+            eprintln!("Error: {}: {}", e, disk_path);
+            std::process::exit(1);
+        }
+        if image_path.is_none() {
+            image_path = disk_doc
+                .disk_images()
+                .find_map(|di| di.image_filename.clone());
+        }
+    }
+
     // Sort by offset (matching Python behavior)
     inputs.sort_by_key(|i| i.offset);
 
@@ -189,8 +241,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     output_doc.add_creator_library(LibraryObject::new("dfxml-rs", dfxml_rs::VERSION));
 
     // Add source image if provided
-    if let Some(ref image_path) = args.image_path {
-        output_doc.sources.push(image_path.clone());
+    if let Some(image_path) = image_path {
+        output_doc.sources.push(image_path);
     }
 
     // Process each input file
@@ -205,9 +257,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
 
         // Parse the input DFXML
-        let file = File::open(&input.path)?;
-        let reader = BufReader::new(file);
-        let parsed_doc = parse(reader)?;
+        let parsed_doc = parse(compression::open(&input.path)?)?;
 
         // Check volume count (Python script assumes at most one volume per document)
         let volume_count = parsed_doc.volume_count();
@@ -277,14 +327,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         to_string(&output_doc)?
     };
 
-    println!("{}", xml);
+    let mut out = Encoder::new(BufWriter::new(io::stdout().lock()), args.compress)?;
+    writeln!(out, "{}", xml)?;
+    out.finish()?.flush()?;
 
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_labeled_input, update_file_byte_runs};
+    use super::{parse_labeled_input, resolve_partition_numbers, update_file_byte_runs};
     use dfxml_rs::objects::FileObject;
 
     #[test]
@@ -327,6 +379,21 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_resolve_partition_numbers() {
+        let offsets = [(1, 1048576), (5, 2129408)];
+        let mut inputs = vec![
+            parse_labeled_input("5:part5.dfxml").unwrap(),
+            parse_labeled_input("1:part1.dfxml").unwrap(),
+        ];
+        resolve_partition_numbers(&mut inputs, &offsets).unwrap();
+        assert_eq!(inputs[0].offset, 2129408);
+        assert_eq!(inputs[1].offset, 1048576);
+
+        let mut missing = vec![parse_labeled_input("2:part2.dfxml").unwrap()];
+        assert!(resolve_partition_numbers(&mut missing, &offsets).is_err());
+    }
+
     #[test]
     fn test_update_file_byte_runs() {
         use dfxml_rs::objects::{ByteRun, ByteRuns};
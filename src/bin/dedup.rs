@@ -1,15 +1,20 @@
-//! dedup - Detect and report duplicate files based on MD5 hashes in a DFXML file.
+//! dedup - Detect and report duplicate files based on hashes in DFXML files.
 //!
-//! This tool reads a DFXML file, groups files by their MD5 hash, and reports
-//! statistics about duplicates. It can optionally list distinct files (unique
-//! MD5s) or duplicate files.
+//! This tool reads one or more DFXML files, groups files by a hash (MD5 by
+//! default, any algorithm in `Hashes`, or the strongest one each file
+//! carries), and reports statistics about duplicates, including the bytes
+//! wasted by extra copies. Each input is a labelled source, so duplicates
+//! that span sources (e.g. custodians) can be reported. It can list distinct
+//! files or duplicate files as text, CSV, JSON or DFXML.
 //!
-//! This is a Rust port of the Python `dedup.py` tool from the dfxml_python project.
+//! This started as a Rust port of the Python `dedup.py` tool from the
+//! dfxml_python project. For a single source its text output matches that
+//! tool unless `--stats` adds the duplicate and wasted byte counts.
 //!
 //! # Usage
 //!
 //! ```bash
-//! dedup [OPTIONS] <DFXML_FILE>
+//! dedup [OPTIONS] <[LABEL=]DFXML_FILE>...
 //! ```
 //!
 //! # Examples
@@ -26,31 +31,66 @@
 //!
 //! # Filter output to files with a specific prefix
 //! dedup --dups --prefix /home/user input.dfxml
+//!
+//! # Duplicates shared between two custodians, by SHA-256, as CSV
+//! dedup --hash sha256 --cross-source -f csv alice=alice.dfxml bob=bob.dfxml
+//!
+//! # Use what can be read of a DFXML file left by a crashed tool
+//! dedup --recover --dups partial.dfxml
+//!
+//! # Read gzipped DFXML files
+//! dedup --dups alice.dfxml.gz bob.dfxml.zst
 //! ```
 
-use std::collections::HashMap;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::str::FromStr;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
-use dfxml_rs::objects::HashType;
-use dfxml_rs::reader::{DFXMLReader, Event};
+use dfxml_rs::compression::{self, Compression, Encoder};
+use dfxml_rs::dedup::{Dedup, DuplicateGroup, HashChoice, Member};
+use dfxml_rs::objects::{DFXMLObject, HashType};
+use dfxml_rs::reader::DFXMLReader;
+use dfxml_rs::writer::{DFXMLStreamWriter, WriterConfig};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-/// Detect and report duplicate files based on MD5 hashes in a DFXML file.
+/// Output format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Summary and `distinct:`/`dups:` lines
+    Text,
+    /// One row per file in the reported groups
+    Csv,
+    /// Summary and groups as one JSON document (requires the serde feature)
+    Json,
+    /// The file objects of the reported groups
+    Dfxml,
+}
+
+/// Detect and report duplicate files based on hashes in DFXML files.
 #[derive(Parser, Debug)]
 #[command(name = "dedup")]
 #[command(version = VERSION)]
-#[command(about = "Detect and report duplicate files based on MD5 hashes")]
+#[command(about = "Detect and report duplicate files based on hashes")]
 #[command(
-    long_about = "Reads a DFXML file, groups files by their MD5 hash, and reports \
-    statistics about duplicates. This is a Rust port of the Python dedup.py tool."
+    long_about = "Reads DFXML files, groups files by a hash, and reports statistics \
+    about duplicates within and across sources. Inputs may be given as LABEL=PATH to \
+    name each source. This started as a Rust port of the Python dedup.py tool."
 )]
 struct Args {
-    /// Input DFXML file to process
-    dfxml: String,
+    /// Input DFXML files to process, optionally as LABEL=PATH
+    #[arg(required = true, value_name = "[LABEL=]DFXML")]
+    dfxml: Vec<String>,
+
+    /// Hash to group by: md5, sha1, sha224, sha256, sha384, sha512, md6 or strongest
+    #[arg(long, value_name = "HASH", default_value = "md5", value_parser = parse_hash_choice)]
+    hash: HashChoice,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value = "text")]
+    format: Format,
 
     /// Enable verbose output
     #[arg(long)]
@@ -67,137 +107,318 @@ struct Args {
     /// Report the files that are duplicates, and give duplicate count
     #[arg(long)]
     dups: bool,
+
+    /// Only report duplicates with copies in more than one source
+    #[arg(long)]
+    cross_source: bool,
+
+    /// Add duplicate group, duplicate file and wasted byte counts to the text summary
+    #[arg(long)]
+    stats: bool,
+
+    /// Read past malformed file objects and truncated inputs, with warnings
+    #[arg(long)]
+    recover: bool,
+
+    /// Compress the output (none, gzip, zstd or xz)
+    #[arg(long, value_name = "FORMAT", default_value = "none")]
+    compress: Compression,
 }
 
-/// Tracks files grouped by their MD5 hash.
-struct Dedup {
-    /// Map from MD5 hash to list of filenames with that hash
-    seen: HashMap<String, Vec<String>>,
-    /// Total number of files processed
-    files: usize,
-    /// Number of files with MD5 hashes
-    md5s: usize,
+fn parse_hash_choice(s: &str) -> Result<HashChoice, String> {
+    if s.eq_ignore_ascii_case("strongest") {
+        return Ok(HashChoice::Strongest);
+    }
+    HashType::from_str(s)
+        .map(HashChoice::Type)
+        .map_err(|_| format!("unknown hash {:?}", s))
 }
 
-impl Dedup {
-    fn new() -> Self {
-        Self {
-            seen: HashMap::new(),
-            files: 0,
-            md5s: 0,
+/// Splits an input argument into its source label and path.
+fn parse_input(arg: &str) -> (String, String) {
+    match arg.split_once('=') {
+        Some((label, path)) if !label.is_empty() && !std::path::Path::new(arg).exists() => {
+            (label.to_string(), path.to_string())
         }
+        _ => (arg.to_string(), arg.to_string()),
+    }
+}
+
+/// Name of the hash in summary lines, e.g. "MD5s".
+fn hash_label(choice: HashChoice) -> String {
+    match choice {
+        HashChoice::Type(t) => format!("{}s", t.as_str().to_uppercase()),
+        HashChoice::Strongest => "hashes".to_string(),
     }
+}
 
-    /// Process a file, recording its MD5 hash if present.
-    fn process(&mut self, md5: Option<&str>, filename: Option<&str>) {
-        self.files += 1;
-        if let (Some(hash), Some(name)) = (md5, filename) {
-            self.seen
-                .entry(hash.to_string())
-                .or_default()
-                .push(name.to_string());
-            self.md5s += 1;
+impl Args {
+    /// Returns true if a member passes the `--prefix` filter.
+    fn shows(&self, member: &Member) -> bool {
+        match &self.prefix {
+            Some(prefix) => member
+                .file
+                .filename
+                .as_deref()
+                .is_some_and(|n| n.starts_with(prefix)),
+            None => true,
         }
     }
 
-    /// Returns the number of unique MD5 hashes seen.
-    fn unique_count(&self) -> usize {
-        self.seen.len()
+    /// Returns true if a group passes the `--cross-source` filter.
+    fn reports_group(&self, group: &DuplicateGroup) -> bool {
+        !self.cross_source || group.spans_sources()
     }
 
-    /// Iterate over entries matching a predicate.
-    fn report<F, C>(&self, predicate: F, mut callback: C)
-    where
-        F: Fn(&[String]) -> bool,
-        C: FnMut(&str, &[String]),
-    {
-        for (md5, names) in &self.seen {
-            if predicate(names) {
-                callback(md5, names);
+    /// Groups reported by the CSV, JSON and DFXML formats: duplicates unless
+    /// only `--distinct` is given, plus distinct files with `--distinct`.
+    fn selected<'a>(&self, dedup: &'a Dedup) -> Vec<&'a DuplicateGroup> {
+        let dups = self.dups || !self.distinct;
+        dedup
+            .groups()
+            .iter()
+            .filter(|g| {
+                if g.is_duplicate() {
+                    dups
+                } else {
+                    self.distinct
+                }
+            })
+            .filter(|g| self.reports_group(g))
+            .collect()
+    }
+}
+
+/// A file's name for text output, prefixed by its source when there are
+/// several.
+fn display_name(dedup: &Dedup, member: &Member) -> String {
+    let name = member.file.filename.as_deref().unwrap_or_default();
+    if dedup.sources().len() > 1 {
+        format!("{}:{}", dedup.sources()[member.source], name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn write_text<W: Write>(out: &mut W, args: &Args, dedup: &Dedup) -> io::Result<()> {
+    let stats = dedup.stats();
+    let label = hash_label(dedup.choice());
+    writeln!(
+        out,
+        "Total files: {}  total {} processed: {}  Unique {}: {}",
+        format_number(stats.files),
+        label,
+        format_number(stats.hashed),
+        label,
+        format_number(stats.unique)
+    )?;
+    if args.stats {
+        write!(
+            out,
+            "Duplicate groups: {}  duplicate files: {}  wasted bytes: {}",
+            format_number(stats.duplicate_groups),
+            format_number(stats.duplicate_files),
+            format_number(stats.wasted_bytes)
+        )?;
+        if dedup.sources().len() > 1 {
+            write!(
+                out,
+                "  cross-source groups: {}",
+                format_number(stats.cross_source_groups)
+            )?;
+        }
+        writeln!(out)?;
+    }
+
+    if args.distinct {
+        for group in dedup.distinct().filter(|g| args.reports_group(g)) {
+            for member in group.members.iter().filter(|m| args.shows(m)) {
+                writeln!(out, "distinct: {}", display_name(dedup, member))?;
+            }
+        }
+    }
+    if args.dups {
+        for group in dedup.duplicates().filter(|g| args.reports_group(g)) {
+            for member in group.members.iter().filter(|m| args.shows(m)) {
+                writeln!(
+                    out,
+                    "dups: {} {}",
+                    display_name(dedup, member),
+                    group.members.len()
+                )?;
             }
         }
     }
+    Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
 
-    if args.verbose {
-        eprintln!("Processing: {}", args.dfxml);
+fn write_csv<W: Write>(out: &mut W, args: &Args, dedup: &Dedup) -> io::Result<()> {
+    writeln!(
+        out,
+        "group,hash_type,digest,copies,cross_source,source,filename,inode,filesize"
+    )?;
+    for (index, group) in args.selected(dedup).into_iter().enumerate() {
+        for member in group.members.iter().filter(|m| args.shows(m)) {
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{},{},{}",
+                index + 1,
+                group.hash_type,
+                group.digest,
+                group.members.len(),
+                group.spans_sources(),
+                csv_field(&dedup.sources()[member.source]),
+                csv_field(member.file.filename.as_deref().unwrap_or_default()),
+                member.file.inode.map(|i| i.to_string()).unwrap_or_default(),
+                member
+                    .file
+                    .filesize
+                    .map(|s| s.to_string())
+                    .unwrap_or_default(),
+            )?;
+        }
     }
+    Ok(())
+}
 
-    let file = File::open(&args.dfxml)?;
-    let reader = BufReader::new(file);
-    let dfxml_reader = DFXMLReader::from_reader(reader);
+#[cfg(feature = "serde")]
+fn write_json<W: Write>(out: &mut W, args: &Args, dedup: &Dedup) -> io::Result<()> {
+    use serde_json::json;
+
+    let stats = dedup.stats();
+    let groups: Vec<_> = args
+        .selected(dedup)
+        .into_iter()
+        .map(|group| {
+            let files: Vec<_> = group
+                .members
+                .iter()
+                .filter(|m| args.shows(m))
+                .map(|m| {
+                    json!({
+                        "source": dedup.sources()[m.source],
+                        "filename": m.file.filename,
+                        "inode": m.file.inode,
+                        "filesize": m.file.filesize,
+                    })
+                })
+                .collect();
+            json!({
+                "hash_type": group.hash_type.as_str(),
+                "digest": group.digest,
+                "filesize": group.filesize(),
+                "copies": group.members.len(),
+                "wasted_bytes": group.wasted_bytes(),
+                "sources": group
+                    .sources()
+                    .into_iter()
+                    .map(|s| dedup.sources()[s].as_str())
+                    .collect::<Vec<_>>(),
+                "cross_source": group.spans_sources(),
+                "files": files,
+            })
+        })
+        .collect();
+    let report = json!({
+        "hash": match dedup.choice() {
+            HashChoice::Type(t) => t.as_str(),
+            HashChoice::Strongest => "strongest",
+        },
+        "sources": dedup.sources(),
+        "summary": {
+            "files": stats.files,
+            "hashed": stats.hashed,
+            "unique": stats.unique,
+            "duplicate_groups": stats.duplicate_groups,
+            "duplicate_files": stats.duplicate_files,
+            "wasted_bytes": stats.wasted_bytes,
+            "cross_source_groups": stats.cross_source_groups,
+        },
+        "groups": groups,
+    });
+    serde_json::to_writer_pretty(&mut *out, &report)?;
+    writeln!(out)
+}
 
-    let mut dedup = Dedup::new();
+#[cfg(not(feature = "serde"))]
+fn write_json<W: Write>(_out: &mut W, _args: &Args, _dedup: &Dedup) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "JSON output requires the serde feature",
+    ))
+}
 
-    for result in dfxml_reader {
-        match result {
-            Ok(Event::FileObject(fi)) => {
-                let md5 = fi.hashes.get(HashType::Md5);
-                let filename = fi.filename.as_deref();
-                dedup.process(md5, filename);
-            }
-            Ok(_) => {
-                // Ignore other events
-            }
-            Err(e) => {
-                // Match Python behavior: continue on parse errors
-                if args.verbose {
-                    eprintln!("Warning: Parse error: {}", e);
-                }
-                break;
-            }
+fn write_dfxml<W: Write>(out: W, args: &Args, dedup: &Dedup) -> dfxml_rs::Result<()> {
+    let mut doc = DFXMLObject::new();
+    doc.program = Some("dedup".to_string());
+    doc.program_version = Some(VERSION.to_string());
+    doc.command_line = Some(std::env::args().collect::<Vec<_>>().join(" "));
+    doc.sources = args.dfxml.iter().map(|a| parse_input(a).1).collect();
+
+    let mut writer = DFXMLStreamWriter::with_config(out, WriterConfig::default())?;
+    writer.start_document(&doc)?;
+    for group in args.selected(dedup) {
+        for member in group.members.iter().filter(|m| args.shows(m)) {
+            writer.write_file(&member.file)?;
         }
     }
+    writer.finish()?.flush()?;
+    Ok(())
+}
 
-    // Print summary statistics
-    println!(
-        "Total files: {}  total MD5s processed: {}  Unique MD5s: {}",
-        format_number(dedup.files),
-        format_number(dedup.md5s),
-        format_number(dedup.unique_count())
-    );
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
 
-    // Report distinct files if requested
-    if args.distinct {
-        dedup.report(
-            |names| names.len() == 1,
-            |_md5, names| {
-                let name = &names[0];
-                if let Some(ref prefix) = args.prefix {
-                    if !name.starts_with(prefix) {
-                        return;
-                    }
-                }
-                println!("distinct: {}", name);
-            },
-        );
+    let mut dedup = Dedup::new(args.hash);
+    for arg in &args.dfxml {
+        let (label, path) = parse_input(arg);
+        if args.verbose {
+            eprintln!("Processing: {} ({})", path, label);
+        }
+        let input = match File::open(&path)
+            .map_err(dfxml_rs::Error::from)
+            .and_then(|file| {
+                // Inputs may be compressed
+                compression::decompress(BufReader::new(file))
+            }) {
+            Ok(input) => input,
+            Err(e) => {
+                eprintln!("Warning: {}: {}", path, e);
+                continue;
+            }
+        };
+        // Keep the files read before a parse error and go on to the next source
+        let mut reader = DFXMLReader::from_reader(input).with_recovery(args.recover);
+        if let Err(e) = dedup.add_reader(label, &mut reader) {
+            eprintln!("Warning: Parse error in {}: {}", path, e);
+        }
+        for warning in reader.warnings() {
+            eprintln!("Warning: {}: {}", path, warning);
+        }
     }
 
-    // Report duplicate files if requested
-    if args.dups {
-        dedup.report(
-            |names| names.len() > 1,
-            |_md5, names| {
-                for name in names {
-                    if let Some(ref prefix) = args.prefix {
-                        if !name.starts_with(prefix) {
-                            continue;
-                        }
-                    }
-                    println!("dups: {} {}", name, names.len());
-                }
-            },
-        );
+    let mut out = Encoder::new(BufWriter::new(io::stdout().lock()), args.compress)?;
+    match args.format {
+        Format::Text => write_text(&mut out, &args, &dedup)?,
+        Format::Csv => write_csv(&mut out, &args, &dedup)?,
+        Format::Json => write_json(&mut out, &args, &dedup)?,
+        Format::Dfxml => write_dfxml(&mut out, &args, &dedup)?,
     }
+    out.finish()?.flush()?;
 
     Ok(())
 }
 
 /// Format a number with thousand separators (matching Python's {:,} format).
-fn format_number(n: usize) -> String {
+fn format_number(n: u64) -> String {
     let s = n.to_string();
     let mut result = String::new();
     for (i, c) in s.chars().rev().enumerate() {
@@ -212,6 +433,23 @@ fn format_number(n: usize) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use dfxml_rs::objects::FileObject;
+
+    fn file(name: &str, md5: Option<&str>) -> FileObject {
+        let mut file = FileObject::with_filename(name);
+        file.filesize = Some(10);
+        if let Some(md5) = md5 {
+            file.hashes.set(HashType::Md5, md5.to_string());
+        }
+        file
+    }
+
+    fn run(args: &[&str], dedup: &Dedup, format: fn(&mut Vec<u8>, &Args, &Dedup)) -> String {
+        let args = Args::parse_from(args);
+        let mut out = Vec::new();
+        format(&mut out, &args, dedup);
+        String::from_utf8(out).unwrap()
+    }
 
     #[test]
     fn test_format_number() {
@@ -226,67 +464,86 @@ mod tests {
     }
 
     #[test]
-    fn test_dedup_new() {
-        let dedup = Dedup::new();
-        assert_eq!(dedup.files, 0);
-        assert_eq!(dedup.md5s, 0);
-        assert_eq!(dedup.unique_count(), 0);
-    }
+    fn test_parse_args() {
+        assert_eq!(
+            parse_hash_choice("SHA256"),
+            Ok(HashChoice::Type(HashType::Sha256))
+        );
+        assert_eq!(parse_hash_choice("strongest"), Ok(HashChoice::Strongest));
+        assert!(parse_hash_choice("crc32").is_err());
 
-    #[test]
-    fn test_dedup_process() {
-        let mut dedup = Dedup::new();
-
-        // Process file with MD5
-        dedup.process(Some("abc123"), Some("/path/to/file1.txt"));
-        assert_eq!(dedup.files, 1);
-        assert_eq!(dedup.md5s, 1);
-        assert_eq!(dedup.unique_count(), 1);
-
-        // Process file without MD5
-        dedup.process(None, Some("/path/to/file2.txt"));
-        assert_eq!(dedup.files, 2);
-        assert_eq!(dedup.md5s, 1);
-        assert_eq!(dedup.unique_count(), 1);
-
-        // Process duplicate (same MD5)
-        dedup.process(Some("abc123"), Some("/path/to/file3.txt"));
-        assert_eq!(dedup.files, 3);
-        assert_eq!(dedup.md5s, 2);
-        assert_eq!(dedup.unique_count(), 1); // Still only one unique MD5
+        assert_eq!(
+            parse_input("alice=/nonexistent/a.dfxml"),
+            ("alice".to_string(), "/nonexistent/a.dfxml".to_string())
+        );
+        assert_eq!(
+            parse_input("b.dfxml"),
+            ("b.dfxml".to_string(), "b.dfxml".to_string())
+        );
     }
 
     #[test]
-    fn test_dedup_report_distinct() {
-        let mut dedup = Dedup::new();
-        dedup.process(Some("unique1"), Some("/unique/file.txt"));
-        dedup.process(Some("dup1"), Some("/dup/file1.txt"));
-        dedup.process(Some("dup1"), Some("/dup/file2.txt"));
-
-        let mut distinct_count = 0;
-        dedup.report(
-            |names| names.len() == 1,
-            |_md5, _names| {
-                distinct_count += 1;
-            },
+    fn test_text_report_single_source() {
+        let mut dedup = Dedup::new(HashChoice::Type(HashType::Md5));
+        let s = dedup.add_source("in.dfxml");
+        dedup.add_file(s, file("/unique/file.txt", Some("unique1")));
+        dedup.add_file(s, file("/dup/file1.txt", Some("dup1")));
+        dedup.add_file(s, file("/dup/file2.txt", Some("dup1")));
+        dedup.add_file(s, file("/nohash.txt", None));
+
+        let text = run(
+            &["dedup", "--distinct", "--dups", "in.dfxml"],
+            &dedup,
+            |out, args, dedup| write_text(out, args, dedup).unwrap(),
+        );
+        assert_eq!(
+            text,
+            "Total files: 4  total MD5s processed: 3  Unique MD5s: 2\n\
+             distinct: /unique/file.txt\n\
+             dups: /dup/file1.txt 2\n\
+             dups: /dup/file2.txt 2\n"
+        );
+
+        let text = run(
+            &["dedup", "--stats", "in.dfxml"],
+            &dedup,
+            |out, args, dedup| write_text(out, args, dedup).unwrap(),
+        );
+        assert_eq!(
+            text,
+            "Total files: 4  total MD5s processed: 3  Unique MD5s: 2\n\
+             Duplicate groups: 1  duplicate files: 2  wasted bytes: 10\n"
         );
-        assert_eq!(distinct_count, 1);
     }
 
     #[test]
-    fn test_dedup_report_dups() {
-        let mut dedup = Dedup::new();
-        dedup.process(Some("unique1"), Some("/unique/file.txt"));
-        dedup.process(Some("dup1"), Some("/dup/file1.txt"));
-        dedup.process(Some("dup1"), Some("/dup/file2.txt"));
-
-        let mut dup_count = 0;
-        dedup.report(
-            |names| names.len() > 1,
-            |_md5, names| {
-                dup_count += names.len();
-            },
+    fn test_cross_source_csv() {
+        let mut dedup = Dedup::new(HashChoice::Type(HashType::Md5));
+        let a = dedup.add_source("alice");
+        let b = dedup.add_source("bob, jr");
+        dedup.add_file(a, file("a1", Some("11")));
+        dedup.add_file(b, file("b1", Some("11")));
+        dedup.add_file(a, file("a2", Some("22")));
+        dedup.add_file(a, file("a3", Some("22")));
+
+        let csv = run(
+            &["dedup", "-f", "csv", "--cross-source", "x", "y"],
+            &dedup,
+            |out, args, dedup| write_csv(out, args, dedup).unwrap(),
+        );
+        assert_eq!(
+            csv,
+            "group,hash_type,digest,copies,cross_source,source,filename,inode,filesize\n\
+             1,md5,11,2,true,alice,a1,,10\n\
+             1,md5,11,2,true,\"bob, jr\",b1,,10\n"
+        );
+
+        let text = run(
+            &["dedup", "--dups", "--stats", "x", "y"],
+            &dedup,
+            |out, args, dedup| write_text(out, args, dedup).unwrap(),
         );
-        assert_eq!(dup_count, 2);
+        assert!(text.contains("cross-source groups: 1"));
+        assert!(text.contains("dups: bob, jr:b1 2"));
     }
 }
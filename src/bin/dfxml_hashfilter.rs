@@ -0,0 +1,208 @@
+//! dfxml_hashfilter - Filter DFXML file objects against known-file hash sets.
+//!
+//! This tool loads known-good and known-bad hash sets (NSRL RDSv3 databases,
+//! legacy NSRLFile.txt files, md5sum/sha1sum/sha256sum lists or other DFXML
+//! files), classifies every fileobject in a DFXML file, and either writes the
+//! DFXML with known files dropped or kept, or a tab-separated report.
+//! Reading RDSv3 databases requires building with the `nsrl` feature.
+//!
+//! # Usage
+//!
+//! ```bash
+//! dfxml_hashfilter [OPTIONS] <DFXML>
+//! ```
+//!
+//! # Examples
+//!
+//! ```bash
+//! # Drop NSRL files to cut down a review set (RDSv3 needs the nsrl feature)
+//! dfxml_hashfilter --good RDS_2024.03.1_modern_minimal.db fiwalk.dfxml > review.dfxml
+//!
+//! # Keep only files matching a list of known-bad hashes
+//! dfxml_hashfilter --bad bad.md5 --mode keep-bad fiwalk.dfxml > hits.dfxml
+//!
+//! # Report the classification of every file
+//! dfxml_hashfilter --good NSRLFile.txt --bad bad.sha256 --report fiwalk.dfxml
+//!
+//! # Filter gzipped DFXML into zstd-compressed DFXML
+//! dfxml_hashfilter --good NSRLFile.txt --compress zstd fiwalk.dfxml.gz > review.dfxml.zst
+//! ```
+
+use std::io::{self, BufWriter, Write};
+
+use clap::{Parser, ValueEnum};
+
+use dfxml_rs::compression::{self, Compression, Encoder};
+#[cfg(not(feature = "nsrl"))]
+use dfxml_rs::hashset::HashSetFormat;
+use dfxml_rs::hashset::{filter_files, Classification, Disposition, HashDb};
+use dfxml_rs::objects::FileObject;
+use dfxml_rs::reader::{DFXMLReader, Event};
+use dfxml_rs::writer::WriterConfig;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Which classified files to write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Mode {
+    /// Drop known-good files, keep unknown and known-bad files
+    DropGood,
+    /// Drop all known files, keep only unknown files
+    DropKnown,
+    /// Keep only known (good or bad) files
+    KeepKnown,
+    /// Keep only known-bad files
+    KeepBad,
+}
+
+impl Mode {
+    fn keeps(self, class: Classification) -> bool {
+        match self {
+            Mode::DropGood => class != Classification::KnownGood,
+            Mode::DropKnown => class == Classification::Unknown,
+            Mode::KeepKnown => class != Classification::Unknown,
+            Mode::KeepBad => class == Classification::KnownBad,
+        }
+    }
+}
+
+/// Filter DFXML file objects against known-file hash sets.
+#[derive(Parser, Debug)]
+#[command(name = "dfxml_hashfilter")]
+#[command(version = VERSION)]
+#[command(about = "Filter DFXML file objects against known-good and known-bad hash sets")]
+#[command(
+    long_about = "Classifies every fileobject as known-good, known-bad or unknown using \
+    NSRL RDS, hash list or DFXML hash sets, and writes the filtered DFXML or a report."
+)]
+struct Args {
+    /// Input DFXML file to filter
+    dfxml: String,
+
+    /// Known-good hash set (can be specified multiple times)
+    #[arg(short, long, value_name = "FILE")]
+    good: Vec<String>,
+
+    /// Known-bad hash set (can be specified multiple times)
+    #[arg(short, long, value_name = "FILE")]
+    bad: Vec<String>,
+
+    /// Which files to write
+    #[arg(short, long, value_enum, default_value = "drop-good")]
+    mode: Mode,
+
+    /// Write a tab-separated report (classification, inode, filename) instead of DFXML
+    #[arg(short, long)]
+    report: bool,
+
+    /// Output compact XML (no indentation)
+    #[arg(long)]
+    compact: bool,
+
+    /// Compress the output (none, gzip, zstd or xz)
+    #[arg(long, value_name = "FORMAT", default_value = "none")]
+    compress: Compression,
+
+    /// Enable debug output
+    #[arg(short, long)]
+    debug: bool,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    if args.good.is_empty() && args.bad.is_empty() {
+        eprintln!("Error: at least one --good or --bad hash set is required");
+        std::process::exit(2);
+    }
+
+    let mut db = HashDb::new();
+    for (paths, disposition) in [
+        (&args.good, Disposition::Good),
+        (&args.bad, Disposition::Bad),
+    ] {
+        for path in paths {
+            #[cfg(not(feature = "nsrl"))]
+            if HashSetFormat::detect(path)? == HashSetFormat::NsrlRds3 {
+                eprintln!(
+                    "Error: {} is an NSRL RDSv3 database; rebuild with the nsrl feature to read it",
+                    path
+                );
+                std::process::exit(2);
+            }
+            let count = db.load_file(path, None, disposition)?;
+            if args.debug {
+                eprintln!("Loaded {} digests from {}", count, path);
+            }
+        }
+    }
+    if args.debug {
+        eprintln!("Hash database holds {} distinct digests", db.len());
+    }
+
+    let input = compression::open(&args.dfxml)?;
+    let mut out = Encoder::new(BufWriter::new(io::stdout().lock()), args.compress)?;
+    let (mut good, mut bad, mut unknown) = (0u64, 0u64, 0u64);
+    let mut classify = |file: &FileObject| {
+        let class = db.classify(file);
+        match class {
+            Classification::KnownGood => good += 1,
+            Classification::KnownBad => bad += 1,
+            Classification::Unknown => unknown += 1,
+        }
+        class
+    };
+
+    if args.report {
+        for event in DFXMLReader::from_reader(input) {
+            if let Event::FileObject(file) = event? {
+                let class = classify(&file);
+                if args.mode.keeps(class) {
+                    writeln!(
+                        out,
+                        "{}\t{}\t{}",
+                        class,
+                        file.inode.map(|i| i.to_string()).unwrap_or_default(),
+                        file.filename.as_deref().unwrap_or_default()
+                    )?;
+                }
+            }
+        }
+    } else {
+        let config = if args.compact {
+            WriterConfig::compact()
+        } else {
+            WriterConfig::default()
+        };
+        filter_files(input, &mut out, config, |file| {
+            args.mode.keeps(classify(file))
+        })?;
+    }
+    out.finish()?.flush()?;
+
+    eprintln!(
+        "{} known-good, {} known-bad, {} unknown",
+        good, bad, unknown
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modes() {
+        use Classification::*;
+        let kept = |mode: Mode| {
+            [KnownGood, KnownBad, Unknown]
+                .into_iter()
+                .filter(|c| mode.keeps(*c))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kept(Mode::DropGood), [KnownBad, Unknown]);
+        assert_eq!(kept(Mode::DropKnown), [Unknown]);
+        assert_eq!(kept(Mode::KeepKnown), [KnownGood, KnownBad]);
+        assert_eq!(kept(Mode::KeepBad), [KnownBad]);
+    }
+}
@@ -0,0 +1,231 @@
+//! dfxml_index - Build a sidecar index of a DFXML file and look up files.
+//!
+//! This tool records the byte offset and key fields (filename, inode,
+//! partition, id, hashes) of every file object in a DFXML file, saved next
+//! to it as `<DFXML>.idx`. With lookup options it uses the index to seek
+//! straight to the matching file objects and writes them as DFXML, without
+//! rescanning the document. The index is rebuilt when the DFXML file's
+//! length or modification time changes.
+//!
+//! Compressed DFXML files are indexed by their decompressed offsets, and
+//! are decompressed into memory for lookups.
+//!
+//! # Usage
+//!
+//! ```bash
+//! dfxml_index [OPTIONS] <DFXML>
+//! ```
+//!
+//! # Examples
+//!
+//! ```bash
+//! # Build (or refresh) fiwalk.dfxml.idx
+//! dfxml_index fiwalk.dfxml
+//!
+//! # Print the file object for an inode
+//! dfxml_index --inode 1234 fiwalk.dfxml
+//!
+//! # Find a file by path or by hash
+//! dfxml_index --filename Windows/System32/cmd.exe fiwalk.dfxml
+//! dfxml_index --hash md5:d41d8cd98f00b204e9800998ecf8427e fiwalk.dfxml
+//!
+//! # Look up a file in a gzipped DFXML file, writing gzipped output
+//! dfxml_index --compress gzip --inode 1234 fiwalk.dfxml.gz > file.dfxml.gz
+//! ```
+
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Write};
+
+use clap::Parser;
+
+use dfxml_rs::compression::{self, Compression};
+use dfxml_rs::index::DFXMLIndex;
+use dfxml_rs::objects::{DFXMLObject, HashType};
+use dfxml_rs::writer::{DFXMLStreamWriter, WriterConfig};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Build a sidecar index of a DFXML file and look up files.
+#[derive(Parser, Debug)]
+#[command(name = "dfxml_index")]
+#[command(version = VERSION)]
+#[command(about = "Build a sidecar index of a DFXML file and look up files by offset")]
+#[command(
+    long_about = "Indexes the byte offset, filename, inode, partition, id and hashes of every \
+    file object into <DFXML>.idx. Lookup options print the matching file objects as DFXML, \
+    read directly from their offsets."
+)]
+struct Args {
+    /// Input DFXML file
+    dfxml: String,
+
+    /// Rebuild the index even if it is up to date
+    #[arg(long)]
+    rebuild: bool,
+
+    /// Look up files with this filename (can be specified multiple times)
+    #[arg(long, value_name = "NAME")]
+    filename: Vec<String>,
+
+    /// Look up files with this inode number (can be specified multiple times)
+    #[arg(long, value_name = "INODE")]
+    inode: Vec<u64>,
+
+    /// Look up files with this id (can be specified multiple times)
+    #[arg(long, value_name = "ID")]
+    id: Vec<u64>,
+
+    /// Look up files with this hash, as TYPE:DIGEST (can be specified multiple times)
+    #[arg(long, value_name = "TYPE:DIGEST", value_parser = parse_hash)]
+    hash: Vec<(HashType, String)>,
+
+    /// Compress the output (none, gzip, zstd or xz)
+    #[arg(long, value_name = "FORMAT", default_value = "none")]
+    compress: Compression,
+
+    /// Enable debug output
+    #[arg(short, long)]
+    debug: bool,
+}
+
+fn parse_hash(s: &str) -> Result<(HashType, String), String> {
+    let (hash_type, digest) = s
+        .split_once(':')
+        .ok_or_else(|| format!("expected TYPE:DIGEST, got {:?}", s))?;
+    let hash_type = hash_type
+        .parse()
+        .map_err(|e: dfxml_rs::Error| e.to_string())?;
+    Ok((hash_type, digest.to_string()))
+}
+
+impl Args {
+    /// Returns true if any lookup option was given.
+    fn has_lookups(&self) -> bool {
+        !(self.filename.is_empty()
+            && self.inode.is_empty()
+            && self.id.is_empty()
+            && self.hash.is_empty())
+    }
+
+    /// Returns the offsets of the entries matching any lookup, in document
+    /// order.
+    fn matches(&self, index: &DFXMLIndex) -> BTreeSet<u64> {
+        let mut offsets = BTreeSet::new();
+        for name in &self.filename {
+            offsets.extend(index.by_filename(name).map(|e| e.offset));
+        }
+        for &inode in &self.inode {
+            offsets.extend(index.by_inode(inode).map(|e| e.offset));
+        }
+        for &id in &self.id {
+            offsets.extend(index.by_id(id).map(|e| e.offset));
+        }
+        for (hash_type, digest) in &self.hash {
+            offsets.extend(index.by_hash(*hash_type, digest).map(|e| e.offset));
+        }
+        offsets
+    }
+}
+
+/// The indexed document, opened for lookups.
+enum Input {
+    File(BufReader<File>),
+    Memory(Cursor<Vec<u8>>),
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let sidecar = DFXMLIndex::sidecar_path(&args.dfxml);
+
+    let index = if args.rebuild {
+        let index = DFXMLIndex::build_file(&args.dfxml)?;
+        index.save(&sidecar)?;
+        index
+    } else {
+        DFXMLIndex::open(&args.dfxml)?
+    };
+    if args.debug {
+        eprintln!(
+            "{} file objects indexed in {}",
+            index.len(),
+            sidecar.display()
+        );
+    }
+
+    if !args.has_lookups() {
+        println!(
+            "Indexed {} file objects to {}",
+            index.len(),
+            sidecar.display()
+        );
+        return Ok(());
+    }
+
+    let offsets = args.matches(&index);
+    let mut input = BufReader::new(File::open(&args.dfxml)?);
+    // Compressed input cannot be seeked, so look up in the decompressed text
+    let mut input = if Compression::detect(input.fill_buf()?) == Compression::None {
+        Input::File(input)
+    } else {
+        let mut xml = Vec::new();
+        compression::decompress(input)?.read_to_end(&mut xml)?;
+        Input::Memory(Cursor::new(xml))
+    };
+    let mut doc = DFXMLObject::new();
+    doc.program = Some("dfxml_index".to_string());
+    doc.program_version = Some(VERSION.to_string());
+    doc.command_line = Some(std::env::args().collect::<Vec<_>>().join(" "));
+    doc.sources.push(args.dfxml.clone());
+
+    let out = BufWriter::new(io::stdout().lock());
+    let config = WriterConfig::default().with_compression(args.compress);
+    let mut writer = DFXMLStreamWriter::with_config(out, config)?;
+    writer.start_document(&doc)?;
+    for &offset in &offsets {
+        let file = match &mut input {
+            Input::File(input) => index.read_file(input, offset)?,
+            Input::Memory(input) => index.read_file(input, offset)?,
+        };
+        writer.write_file(&file)?;
+    }
+    writer.finish()?.flush()?;
+    if args.debug {
+        eprintln!("{} matching file objects", offsets.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookups() {
+        let xml = r#"<dfxml version="1.0">
+  <fileobject><id>1</id><filename>a</filename><inode>5</inode></fileobject>
+  <fileobject><id>2</id><filename>b</filename><inode>5</inode>
+    <hashdigest type="sha1">da39a3ee5e6b4b0d3255bfef95601890afd80709</hashdigest></fileobject>
+</dfxml>"#;
+        let index = DFXMLIndex::build(io::Cursor::new(xml)).unwrap();
+        let offsets: Vec<u64> = index.entries().iter().map(|e| e.offset).collect();
+
+        let args = Args::parse_from(["dfxml_index", "x.dfxml"]);
+        assert!(!args.has_lookups());
+
+        let args = Args::parse_from(["dfxml_index", "--inode", "5", "--filename", "b", "x.dfxml"]);
+        assert_eq!(
+            args.matches(&index),
+            BTreeSet::from([offsets[0], offsets[1]])
+        );
+
+        let args = Args::parse_from([
+            "dfxml_index",
+            "--hash",
+            "SHA1:DA39A3EE5E6B4B0D3255BFEF95601890AFD80709",
+            "x.dfxml",
+        ]);
+        assert_eq!(args.matches(&index), BTreeSet::from([offsets[1]]));
+        assert!(Args::try_parse_from(["dfxml_index", "--hash", "crc:00", "x"]).is_err());
+    }
+}
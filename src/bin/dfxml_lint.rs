@@ -0,0 +1,167 @@
+//! dfxml_lint - Check DFXML files for semantic problems.
+//!
+//! This tool streams a DFXML file and reports hash digests of the wrong
+//! length, overlapping or out-of-bounds byte runs, byte runs that do not
+//! match the file size, duplicate file ids, implausible timestamps and
+//! contradictory allocation flags. Unlike XSD validation it needs no system
+//! libraries.
+//!
+//! The exit status is 0 when no error-severity diagnostics were found, and
+//! 1 otherwise.
+//!
+//! # Usage
+//!
+//! ```bash
+//! dfxml_lint [OPTIONS] <DFXML>
+//! ```
+//!
+//! # Examples
+//!
+//! ```bash
+//! # Report every problem
+//! dfxml_lint fiwalk.dfxml
+//!
+//! # Only errors, ignoring timestamps
+//! dfxml_lint --min-severity error --disable timestamp-range fiwalk.dfxml
+//!
+//! # Flag timestamps outside the period of interest
+//! dfxml_lint --earliest 2015-01-01 --latest 2024-06-30 fiwalk.dfxml
+//!
+//! # Check a compressed file; positions refer to the decompressed XML
+//! dfxml_lint fiwalk.dfxml.xz
+//! ```
+
+use std::io::{self, BufWriter, Write};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::Parser;
+
+use dfxml_rs::compression::{self, Compression, Encoder};
+use dfxml_rs::lint::{lint_reader, LintConfig, Rule, Severity};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Check DFXML files for semantic problems.
+#[derive(Parser, Debug)]
+#[command(name = "dfxml_lint")]
+#[command(version = VERSION)]
+#[command(about = "Check DFXML files for semantic problems without libxml2")]
+#[command(
+    long_about = "Checks hash lengths, byte run overlap, bounds and coverage, duplicate ids, \
+    timestamp ranges and allocation flags, and prints one diagnostic per line."
+)]
+struct Args {
+    /// Input DFXML file to check
+    dfxml: String,
+
+    /// Skip a rule (can be specified multiple times)
+    #[arg(long, value_name = "RULE", value_parser = parse_rule)]
+    disable: Vec<Rule>,
+
+    /// Only print diagnostics of at least this severity (info, warning, error)
+    #[arg(long, value_name = "SEVERITY", default_value = "info", value_parser = parse_severity)]
+    min_severity: Severity,
+
+    /// Report timestamps before this date (YYYY-MM-DD or RFC 3339, default 1970-01-01)
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    earliest: Option<DateTime<Utc>>,
+
+    /// Report timestamps after this date (default one day from now)
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    latest: Option<DateTime<Utc>>,
+
+    /// Compress the output (none, gzip, zstd or xz)
+    #[arg(long, value_name = "FORMAT", default_value = "none")]
+    compress: Compression,
+
+    /// Enable debug output
+    #[arg(short, long)]
+    debug: bool,
+}
+
+fn parse_rule(s: &str) -> Result<Rule, String> {
+    s.parse().map_err(|e: dfxml_rs::Error| e.to_string())
+}
+
+fn parse_severity(s: &str) -> Result<Severity, String> {
+    s.parse().map_err(|e: dfxml_rs::Error| e.to_string())
+}
+
+/// Parses a date argument as UTC.
+fn parse_date(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map(|d| d.and_time(Default::default()).and_utc())
+        .map_err(|_| format!("invalid date: {}", s))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let mut config = LintConfig::new();
+    for rule in &args.disable {
+        config = config.without_rule(*rule);
+    }
+    if let Some(earliest) = args.earliest {
+        config = config.with_earliest(earliest);
+    }
+    if let Some(latest) = args.latest {
+        config = config.with_latest(latest);
+    }
+    if args.debug {
+        eprintln!(
+            "Checking timestamps from {} to {}",
+            config.earliest, config.latest
+        );
+    }
+
+    let input = compression::open(&args.dfxml)?;
+    let mut out = Encoder::new(BufWriter::new(io::stdout().lock()), args.compress)?;
+    let mut write_error = None;
+    let summary = lint_reader(input, &config, |diagnostic| {
+        if diagnostic.severity >= args.min_severity && write_error.is_none() {
+            write_error = writeln!(out, "{}", diagnostic).err();
+        }
+    })?;
+    if let Some(e) = write_error {
+        return Err(e.into());
+    }
+    out.finish()?.flush()?;
+
+    eprintln!(
+        "{} files: {} errors, {} warnings, {} infos",
+        summary.files, summary.errors, summary.warnings, summary.infos
+    );
+    if summary.errors > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args() {
+        let args = Args::parse_from([
+            "dfxml_lint",
+            "--disable",
+            "hash-length",
+            "--min-severity",
+            "Warning",
+            "--earliest",
+            "2020-01-01",
+            "in.dfxml",
+        ]);
+        assert_eq!(args.disable, [Rule::HashLength]);
+        assert_eq!(args.min_severity, Severity::Warning);
+        assert_eq!(
+            args.earliest.unwrap().to_rfc3339(),
+            "2020-01-01T00:00:00+00:00"
+        );
+        assert!(Args::try_parse_from(["dfxml_lint", "--disable", "nope", "in.dfxml"]).is_err());
+    }
+}
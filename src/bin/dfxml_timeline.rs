@@ -0,0 +1,366 @@
+//! dfxml_timeline - Build a timeline from the timestamps in a DFXML file.
+//!
+//! Every fileobject timestamp (mtime, atime, ctime, crtime, dtime and
+//! bkup_time) becomes a timeline event. Timestamps of one file that share the
+//! same time and precision are merged into a single event with MACB flags.
+//! Events can be filtered by date range and timestamp kind, shown in any
+//! IANA timezone, and written as mactime-style text, log2timeline CSV or
+//! JSON Lines.
+//!
+//! # Usage
+//!
+//! ```bash
+//! dfxml_timeline [OPTIONS] <FILENAME>
+//! ```
+//!
+//! # Examples
+//!
+//! ```bash
+//! # mactime-style text timeline in UTC
+//! dfxml_timeline fiwalk.dfxml
+//!
+//! # log2timeline CSV for January 2020, shown in New York time
+//! dfxml_timeline -f l2tcsv -z America/New_York \
+//!     --start 2020-01-01 --end 2020-02-01 fiwalk.dfxml > timeline.csv
+//!
+//! # Only deletion times, one JSON object per line
+//! dfxml_timeline -f jsonl -t dtime fiwalk.dfxml
+//!
+//! # Read gzipped DFXML and write a gzipped timeline
+//! dfxml_timeline --compress gzip fiwalk.dfxml.gz > timeline.txt.gz
+//! ```
+
+use std::io::{self, BufWriter, Write};
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone};
+use chrono_tz::Tz;
+use clap::{Parser, ValueEnum};
+
+use dfxml_rs::bodyfile::format_mode;
+use dfxml_rs::compression::{self, Compression, Encoder};
+use dfxml_rs::objects::{HashType, TimestampName};
+use dfxml_rs::reader::{DFXMLReader, Event};
+use dfxml_rs::timeline::{Timeline, TimelineConfig, TimelineEntry};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// log2timeline CSV header.
+const L2T_HEADER: &str = "date,time,timezone,MACB,source,sourcetype,type,user,host,short,desc,version,filename,inode,notes,format,extra";
+
+/// Output formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// mactime-style text
+    Mactime,
+    /// log2timeline CSV
+    L2tcsv,
+    /// One JSON object per event
+    Jsonl,
+}
+
+/// Build a timeline from the timestamps in a DFXML file.
+#[derive(Parser, Debug)]
+#[command(name = "dfxml_timeline")]
+#[command(version = VERSION)]
+#[command(about = "Build a timeline from the timestamps in a DFXML file")]
+#[command(
+    long_about = "Turns fileobject timestamps into a sorted timeline with MACB flags, \
+    written as mactime-style text, log2timeline CSV or JSON Lines."
+)]
+struct Args {
+    /// Input DFXML file
+    filename: String,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value = "mactime")]
+    format: Format,
+
+    /// Timezone for output and for dates without an offset (IANA name, e.g. Europe/Berlin)
+    #[arg(short = 'z', long, default_value = "UTC")]
+    timezone: String,
+
+    /// Only include events at or after this date (YYYY-MM-DD, YYYY-MM-DDTHH:MM:SS or RFC 3339)
+    #[arg(long, value_name = "DATE")]
+    start: Option<String>,
+
+    /// Only include events before this date
+    #[arg(long, value_name = "DATE")]
+    end: Option<String>,
+
+    /// Only include a timestamp kind (can be specified multiple times)
+    #[arg(short = 't', long = "type", value_name = "KIND")]
+    kinds: Vec<String>,
+
+    /// Emit one event per timestamp instead of merging into MACB groups
+    #[arg(long)]
+    separate: bool,
+
+    /// Compress the output (none, gzip, zstd or xz)
+    #[arg(long, value_name = "FORMAT", default_value = "none")]
+    compress: Compression,
+
+    /// Enable debug output
+    #[arg(short, long)]
+    debug: bool,
+}
+
+/// Parses a date argument, interpreting dates without an offset in `tz`.
+fn parse_date(s: &str, tz: &Tz) -> Result<DateTime<FixedOffset>, String> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Ok(dt);
+    }
+    let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|d| d.and_time(Default::default()))
+        })
+        .map_err(|_| format!("invalid date: {}", s))?;
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .map(|dt| dt.fixed_offset())
+        .ok_or_else(|| format!("date does not exist in {}: {}", tz, s))
+}
+
+/// Quotes a CSV field when needed.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+fn or_dash<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".to_string(), |v| v.to_string())
+}
+
+/// Describes the kinds of an event that have no MACB letter.
+fn extra_kinds(entry: &TimelineEntry) -> Vec<&'static str> {
+    let mut kinds = Vec::new();
+    if entry.event.contains(TimestampName::Dtime) {
+        kinds.push("Deletion Time");
+    }
+    if entry.event.contains(TimestampName::BkupTime) {
+        kinds.push("Backup Time");
+    }
+    kinds
+}
+
+/// Writes mactime-style text, printing each date only once.
+fn write_mactime<W: Write>(out: &mut W, entries: &[TimelineEntry], tz: &Tz) -> io::Result<()> {
+    let mut last_date = String::new();
+    for entry in entries {
+        let file = &entry.file;
+        let date = entry
+            .event
+            .time
+            .with_timezone(tz)
+            .format("%a %b %d %Y %H:%M:%S")
+            .to_string();
+        let shown = if date == last_date { "" } else { date.as_str() };
+
+        let mut name = file.filename.clone().unwrap_or_default();
+        let extra = extra_kinds(entry);
+        if !extra.is_empty() {
+            name = format!("{} [{}]", name, extra.join("; "));
+        }
+
+        writeln!(
+            out,
+            "{:>24} {:>8} {} {} {:<8} {:<8} {:<8} {}",
+            shown,
+            file.filesize.unwrap_or(0),
+            entry.event.macb().to_lowercase(),
+            format_mode(file),
+            file.uid.unwrap_or(0),
+            file.gid.unwrap_or(0),
+            file.inode.unwrap_or(0),
+            name
+        )?;
+        last_date = date;
+    }
+    Ok(())
+}
+
+/// Writes log2timeline CSV.
+fn write_l2tcsv<W: Write>(out: &mut W, entries: &[TimelineEntry], tz: &Tz) -> io::Result<()> {
+    writeln!(out, "{}", L2T_HEADER)?;
+    for entry in entries {
+        let file = &entry.file;
+        let time = entry.event.time.with_timezone(tz);
+        let filename = file.filename.clone().unwrap_or_default();
+
+        let mut extra = Vec::new();
+        if let Some(md5) = file.hashes.get(HashType::Md5) {
+            extra.push(format!("md5: {}", md5));
+        }
+        if let Some(alloc) = file.is_allocated() {
+            extra.push(format!("allocated: {}", alloc));
+        }
+
+        let fields = [
+            time.format("%m/%d/%Y").to_string(),
+            time.format("%H:%M:%S").to_string(),
+            tz.name().to_string(),
+            entry.event.macb(),
+            "FILE".to_string(),
+            "DFXML".to_string(),
+            entry.event.description(),
+            or_dash(file.uid),
+            "-".to_string(),
+            filename.clone(),
+            format!(
+                "{} Size: {} Inode: {}",
+                filename,
+                or_dash(file.filesize),
+                or_dash(file.inode)
+            ),
+            "2".to_string(),
+            filename,
+            or_dash(file.inode),
+            or_dash(entry.event.prec.map(|p| format!("Precision: {}", p))),
+            "dfxml".to_string(),
+            if extra.is_empty() {
+                "-".to_string()
+            } else {
+                extra.join("; ")
+            },
+        ];
+        let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        writeln!(out, "{}", line.join(","))?;
+    }
+    Ok(())
+}
+
+/// Writes one JSON object per event.
+fn write_jsonl<W: Write>(out: &mut W, entries: &[TimelineEntry], tz: &Tz) -> io::Result<()> {
+    for entry in entries {
+        let file = &entry.file;
+        let kinds: Vec<&str> = entry.event.kinds.iter().map(|k| k.as_str()).collect();
+        let record = serde_json::json!({
+            "time": entry
+                .event
+                .time
+                .with_timezone(tz)
+                .to_rfc3339_opts(SecondsFormat::AutoSi, false),
+            "timezone": tz.name(),
+            "macb": entry.event.macb(),
+            "kinds": kinds,
+            "description": entry.event.description(),
+            "precision": entry.event.prec.map(|p| p.to_string()),
+            "filename": file.filename,
+            "inode": file.inode,
+            "filesize": file.filesize,
+            "uid": file.uid,
+            "gid": file.gid,
+            "mode": format_mode(file),
+            "allocated": file.is_allocated(),
+            "md5": file.hashes.get(HashType::Md5),
+        });
+        writeln!(out, "{}", record)?;
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let tz: Tz = args
+        .timezone
+        .parse()
+        .map_err(|_| format!("unknown timezone: {}", args.timezone))?;
+
+    let mut config = TimelineConfig::new().with_separate(args.separate);
+    if let Some(start) = &args.start {
+        config = config.with_start(parse_date(start, &tz)?);
+    }
+    if let Some(end) = &args.end {
+        config = config.with_end(parse_date(end, &tz)?);
+    }
+    for kind in &args.kinds {
+        config = config.with_kind(kind.parse::<TimestampName>()?);
+    }
+
+    let input = compression::open(&args.filename)?;
+    let mut timeline = Timeline::with_config(config);
+    for event in DFXMLReader::from_reader(input) {
+        if let Event::FileObject(file) = event? {
+            timeline.add_file(*file);
+        }
+    }
+    timeline.sort();
+
+    if args.debug {
+        eprintln!("Timeline events: {}", timeline.len());
+    }
+
+    let stdout = io::stdout();
+    let mut out = Encoder::new(BufWriter::new(stdout.lock()), args.compress)?;
+    match args.format {
+        Format::Mactime => write_mactime(&mut out, timeline.entries(), &tz)?,
+        Format::L2tcsv => write_l2tcsv(&mut out, timeline.entries(), &tz)?,
+        Format::Jsonl => write_jsonl(&mut out, timeline.entries(), &tz)?,
+    }
+    out.finish()?.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dfxml_rs::objects::{FileObject, Timestamp};
+
+    fn entries() -> Vec<TimelineEntry> {
+        let time = Timestamp::parse_iso8601("2020-01-01T12:00:00Z").unwrap();
+        let mut file = FileObject::with_filename("a,b.txt");
+        file.filesize = Some(10);
+        for kind in [TimestampName::Mtime, TimestampName::Dtime] {
+            file.set_timestamp(kind, Timestamp::with_name_and_time(kind, time));
+        }
+        let mut timeline = Timeline::new();
+        timeline.add_file(file);
+        timeline.into_entries()
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        let dt = parse_date("2020-01-01", &berlin).unwrap();
+        assert_eq!(dt.to_rfc3339(), "2020-01-01T00:00:00+01:00");
+        let dt = parse_date("2020-07-01T12:00:00Z", &berlin).unwrap();
+        assert_eq!(dt.to_rfc3339(), "2020-07-01T12:00:00+00:00");
+        let dt = parse_date("2020-07-01T12:00:00", &berlin).unwrap();
+        assert_eq!(dt.to_rfc3339(), "2020-07-01T12:00:00+02:00");
+        assert!(parse_date("yesterday", &berlin).is_err());
+    }
+
+    #[test]
+    fn test_l2tcsv() {
+        let mut out = Vec::new();
+        write_l2tcsv(&mut out, &entries(), &Tz::UTC).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines[0], L2T_HEADER);
+        assert!(lines[1].starts_with(
+            "01/01/2020,12:00:00,UTC,M...,FILE,DFXML,Modification Time; Deletion Time,"
+        ));
+        assert!(lines[1].contains("\"a,b.txt\""));
+    }
+
+    #[test]
+    fn test_mactime_and_jsonl() {
+        let tokyo: Tz = "Asia/Tokyo".parse().unwrap();
+        let mut out = Vec::new();
+        write_mactime(&mut out, &entries(), &tokyo).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("Wed Jan 01 2020 21:00:00       10 m... "));
+        assert!(text.trim_end().ends_with("a,b.txt [Deletion Time]"));
+
+        let mut out = Vec::new();
+        write_jsonl(&mut out, &entries(), &tokyo).unwrap();
+        let record: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(record["time"], "2020-01-01T21:00:00+09:00");
+        assert_eq!(record["kinds"], serde_json::json!(["mtime", "dtime"]));
+    }
+}
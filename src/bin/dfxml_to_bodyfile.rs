@@ -0,0 +1,72 @@
+//! dfxml_to_bodyfile - Convert a DFXML file to a Sleuth Kit body file.
+//!
+//! Each fileobject becomes one body file line
+//! (`MD5|name|inode|mode_as_string|UID|GID|size|atime|mtime|ctime|crtime`),
+//! ready for `mactime` or other body-file based timeline tools. The input is
+//! streamed, so each line is written as soon as its fileobject is read.
+//!
+//! # Usage
+//!
+//! ```bash
+//! dfxml_to_bodyfile [OPTIONS] <FILENAME>
+//! ```
+//!
+//! # Examples
+//!
+//! ```bash
+//! # Convert fiwalk output to a body file
+//! dfxml_to_bodyfile fiwalk.dfxml > disk.body
+//!
+//! # Build a timeline with The Sleuth Kit
+//! dfxml_to_bodyfile fiwalk.dfxml | mactime -b - > timeline.txt
+//!
+//! # Read zstd-compressed DFXML and write a gzipped body file
+//! dfxml_to_bodyfile --compress gzip fiwalk.dfxml.zst > disk.body.gz
+//! ```
+
+use std::io::{self, BufWriter, Write};
+
+use clap::Parser;
+
+use dfxml_rs::bodyfile::dfxml_to_bodyfile;
+use dfxml_rs::compression::{self, Compression, Encoder};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Convert a DFXML file to a Sleuth Kit body file.
+#[derive(Parser, Debug)]
+#[command(name = "dfxml_to_bodyfile")]
+#[command(version = VERSION)]
+#[command(about = "Convert a DFXML file to a Sleuth Kit body file")]
+#[command(
+    long_about = "Streams the fileobjects of a DFXML file to the Sleuth Kit body file \
+    (mactime) format, one line per file."
+)]
+struct Args {
+    /// Input DFXML file to convert
+    filename: String,
+
+    /// Compress the output (none, gzip, zstd or xz)
+    #[arg(long, value_name = "FORMAT", default_value = "none")]
+    compress: Compression,
+
+    /// Enable debug output
+    #[arg(short, long)]
+    debug: bool,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let input = compression::open(&args.filename)?;
+    let stdout = io::stdout();
+    let mut out = Encoder::new(BufWriter::new(stdout.lock()), args.compress)?;
+    let count = dfxml_to_bodyfile(input, &mut out)?;
+    out.finish()?.flush()?;
+
+    if args.debug {
+        eprintln!("Wrote {} body file lines", count);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,80 @@
+//! dfxml_to_json - Convert a DFXML file to JSON.
+//!
+//! By default the document is streamed as JSON Lines: a document record
+//! with the metadata, then one record per fileobject carrying the properties
+//! of its enclosing disk image, partition system, partition and volume. With
+//! `--nested` the complete object tree is written as a single JSON document.
+//! The layout is described in the `dfxml_rs::json` module documentation.
+//!
+//! # Usage
+//!
+//! ```bash
+//! dfxml_to_json [OPTIONS] <FILENAME>
+//! ```
+//!
+//! # Examples
+//!
+//! ```bash
+//! # One JSON object per fileobject
+//! dfxml_to_json input.dfxml > files.jsonl
+//!
+//! # The whole document as indented JSON
+//! dfxml_to_json --nested --pretty input.dfxml > document.json
+//!
+//! # Read xz-compressed DFXML and write zstd-compressed JSON Lines
+//! dfxml_to_json --compress zstd input.dfxml.xz > files.jsonl.zst
+//! ```
+
+use std::io::{self, BufWriter, Write};
+
+use clap::Parser;
+
+use dfxml_rs::compression::{self, Compression, Encoder};
+use dfxml_rs::json::{dfxml_to_json, dfxml_to_json_lines};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Convert a DFXML file to JSON.
+#[derive(Parser, Debug)]
+#[command(name = "dfxml_to_json")]
+#[command(version = VERSION)]
+#[command(about = "Convert a DFXML file to JSON Lines or nested JSON")]
+#[command(
+    long_about = "Streams a DFXML file to JSON Lines, one fileobject per line with the \
+    context of its enclosing containers, or writes the whole document as nested JSON."
+)]
+struct Args {
+    /// Input DFXML file to convert
+    filename: String,
+
+    /// Write a single nested JSON document instead of JSON Lines
+    ///
+    /// The whole document is loaded into memory.
+    #[arg(long)]
+    nested: bool,
+
+    /// Indent nested JSON output
+    #[arg(long, requires = "nested")]
+    pretty: bool,
+
+    /// Compress the output (none, gzip, zstd or xz)
+    #[arg(long, value_name = "FORMAT", default_value = "none")]
+    compress: Compression,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let input = compression::open(&args.filename)?;
+    let stdout = io::stdout();
+    let mut output = Encoder::new(BufWriter::new(stdout.lock()), args.compress)?;
+
+    if args.nested {
+        dfxml_to_json(input, &mut output, args.pretty)?;
+    } else {
+        dfxml_to_json_lines(input, &mut output)?;
+    }
+    output.finish()?.flush()?;
+
+    Ok(())
+}
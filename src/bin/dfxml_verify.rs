@@ -0,0 +1,200 @@
+//! dfxml_verify - Verify DFXML hashes against the source image.
+//!
+//! This tool reads a DFXML file describing a raw disk image, recomputes the
+//! hashes recorded for each fileobject (and for each byte run that carries
+//! hashes) from the image, and writes a JSON Lines report: one `file` record
+//! per fileobject followed by a `summary` record.
+//!
+//! The exit status is 0 when every checked hash matched and all content was
+//! readable, and 1 otherwise.
+//!
+//! # Usage
+//!
+//! ```bash
+//! dfxml_verify [OPTIONS] <DFXML> <IMAGE>
+//! ```
+//!
+//! # Examples
+//!
+//! ```bash
+//! # Verify every file and keep the full report
+//! dfxml_verify -o report.jsonl fiwalk.dfxml disk.raw
+//!
+//! # Only report problems
+//! dfxml_verify --failures-only fiwalk.dfxml disk.raw
+//!
+//! # Read zstd-compressed DFXML and keep a gzipped report
+//! dfxml_verify --compress gzip -o report.jsonl.gz fiwalk.dfxml.zst disk.raw
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use clap::Parser;
+use serde_json::{json, Value};
+
+use dfxml_rs::compression::{self, Compression, Encoder};
+use dfxml_rs::verify::{
+    verify_dfxml, FileVerification, HashCheck, HashStatus, VerifyStatus, VerifySummary,
+};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Verify DFXML hashes against the source image.
+#[derive(Parser, Debug)]
+#[command(name = "dfxml_verify")]
+#[command(version = VERSION)]
+#[command(about = "Verify DFXML file and byte run hashes against a raw disk image")]
+#[command(
+    long_about = "Recomputes the hashes recorded in a DFXML file from the raw image it \
+    describes and writes a JSON Lines report of matches, mismatches and unreadable runs."
+)]
+struct Args {
+    /// DFXML file describing the image
+    dfxml: String,
+
+    /// Raw disk image to verify against
+    image: String,
+
+    /// Write the report to this file instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<String>,
+
+    /// Only report files that mismatched or could not be read
+    #[arg(long)]
+    failures_only: bool,
+
+    /// Compress the report (none, gzip, zstd or xz)
+    #[arg(long, value_name = "FORMAT", default_value = "none")]
+    compress: Compression,
+
+    /// Enable debug output
+    #[arg(short, long)]
+    debug: bool,
+}
+
+fn check_record(check: &HashCheck) -> Value {
+    let mut record = json!({
+        "algorithm": check.hash_type.as_str(),
+        "expected": check.expected,
+    });
+    match &check.status {
+        HashStatus::Match => record["status"] = json!("match"),
+        HashStatus::Mismatch(computed) => {
+            record["status"] = json!("mismatch");
+            record["computed"] = json!(computed);
+        }
+        HashStatus::Unsupported => record["status"] = json!("unsupported"),
+    }
+    record
+}
+
+fn file_record(result: &FileVerification) -> Value {
+    let runs: Vec<Value> = result
+        .runs
+        .iter()
+        .map(|run| {
+            json!({
+                "index": run.index,
+                "file_offset": run.file_offset,
+                "hashes": run.hashes.iter().map(check_record).collect::<Vec<_>>(),
+                "error": run.error,
+            })
+        })
+        .collect();
+    json!({
+        "record": "file",
+        "filename": result.filename,
+        "inode": result.inode,
+        "status": result.status().as_str(),
+        "hashes": result.hashes.iter().map(check_record).collect::<Vec<_>>(),
+        "runs": runs,
+        "error": result.error,
+    })
+}
+
+fn summary_record(summary: &VerifySummary) -> Value {
+    json!({
+        "record": "summary",
+        "files": summary.files,
+        "verified": summary.verified,
+        "mismatched": summary.mismatched,
+        "unreadable": summary.unreadable,
+        "no_hashes": summary.no_hashes,
+    })
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let input = compression::open(&args.dfxml)?;
+    let mut image = File::open(&args.image)?;
+    let out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    let mut out = Encoder::new(out, args.compress)?;
+
+    let mut write_error = None;
+    let summary = verify_dfxml(input, &mut image, |result| {
+        let status = result.status();
+        if args.debug {
+            eprintln!("{}: {:?}", status, result.filename);
+        }
+        if args.failures_only
+            && !matches!(status, VerifyStatus::Mismatch | VerifyStatus::Unreadable)
+        {
+            return;
+        }
+        if write_error.is_none() {
+            write_error = writeln!(out, "{}", file_record(result)).err();
+        }
+    })?;
+    if let Some(e) = write_error {
+        return Err(e.into());
+    }
+    writeln!(out, "{}", summary_record(&summary))?;
+    out.finish()?.flush()?;
+
+    eprintln!(
+        "{} files: {} verified, {} mismatched, {} unreadable, {} without hashes",
+        summary.files, summary.verified, summary.mismatched, summary.unreadable, summary.no_hashes
+    );
+    if !summary.is_clean() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dfxml_rs::objects::HashType;
+    use dfxml_rs::verify::RunVerification;
+
+    #[test]
+    fn test_file_record() {
+        let result = FileVerification {
+            filename: Some("a.txt".to_string()),
+            inode: Some(5),
+            hashes: vec![HashCheck {
+                hash_type: HashType::Md5,
+                expected: "00".to_string(),
+                status: HashStatus::Mismatch("11".to_string()),
+            }],
+            runs: vec![RunVerification {
+                index: 0,
+                file_offset: Some(0),
+                hashes: Vec::new(),
+                error: Some("image ends".to_string()),
+            }],
+            error: None,
+        };
+        let record = file_record(&result);
+        assert_eq!(record["record"], "file");
+        assert_eq!(record["status"], "mismatch");
+        assert_eq!(record["hashes"][0]["algorithm"], "md5");
+        assert_eq!(record["hashes"][0]["computed"], "11");
+        assert_eq!(record["runs"][0]["error"], "image ends");
+    }
+}
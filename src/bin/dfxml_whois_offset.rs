@@ -0,0 +1,191 @@
+//! dfxml_whois_offset - Find the files that own offsets in a disk image.
+//!
+//! This tool indexes every byte run in a DFXML file (such as fiwalk or
+//! image_to_dfxml output) by image offset, then reports which file's data,
+//! inode or name occupies each queried offset, and where in the file's
+//! content it falls. It answers the question TSK's `ifind` answers, for a
+//! carving or keyword hit, without access to the image.
+//!
+//! Each query is an offset, or `OFFSET+LEN` for a range, in decimal or
+//! `0x` hexadecimal. Queries are read one per line from standard input
+//! when none are given on the command line.
+//!
+//! # Usage
+//!
+//! ```bash
+//! dfxml_whois_offset [OPTIONS] <DFXML> [QUERY]...
+//! ```
+//!
+//! # Examples
+//!
+//! ```bash
+//! # Who owns byte 1234567 of the image?
+//! dfxml_whois_offset fiwalk.dfxml 1234567
+//!
+//! # Which files does a 64 KiB range touch?
+//! dfxml_whois_offset fiwalk.dfxml 0x200000+65536
+//!
+//! # Look up sector numbers, reporting only file content
+//! dfxml_whois_offset --block-size 512 --data-only fiwalk.dfxml 2048 4096
+//!
+//! # Look up the offsets of keyword hits
+//! cut -f1 hits.txt | dfxml_whois_offset fiwalk.dfxml
+//! ```
+//!
+//! # Output
+//!
+//! One tab-separated line per byte run that overlaps a query: the image
+//! offset and length of the overlap, the run's facet (`data`, `inode` or
+//! `name`), the offset into the file's content, the file's inode and its
+//! filename. Missing values are written as `-`, and a query that no run
+//! overlaps gives a line with only its offset and length.
+
+use std::io::{self, BufRead, BufWriter, Write};
+
+use clap::Parser;
+
+use dfxml_rs::compression;
+use dfxml_rs::objects::ByteRunFacet;
+use dfxml_rs::offset_index::OffsetIndex;
+use dfxml_rs::reader::parse;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Find the files that own offsets in a disk image.
+#[derive(Parser, Debug)]
+#[command(name = "dfxml_whois_offset")]
+#[command(version = VERSION)]
+#[command(about = "Find the files that own offsets in a disk image")]
+#[command(
+    long_about = "Indexes the byte runs of a DFXML file by image offset and reports the file, \
+    facet and file-relative offset that each queried offset or range belongs to."
+)]
+struct Args {
+    /// Input DFXML file
+    dfxml: String,
+
+    /// Offsets (OFFSET or OFFSET+LEN) to look up; read from stdin if none are given
+    #[arg(value_name = "QUERY")]
+    queries: Vec<String>,
+
+    /// Treat offsets and lengths as numbers of blocks of this many bytes
+    #[arg(short, long, value_name = "BYTES", default_value = "1")]
+    block_size: u64,
+
+    /// Only report data runs, not inode or name runs
+    #[arg(long)]
+    data_only: bool,
+
+    /// Enable debug output
+    #[arg(short, long)]
+    debug: bool,
+}
+
+/// Parses a number in decimal or `0x` hexadecimal.
+fn parse_number(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|_| format!("Invalid number '{}'", s))
+}
+
+/// Parses a query (OFFSET or OFFSET+LEN) in units of `block_size` bytes into
+/// a byte offset and length.
+fn parse_query(spec: &str, block_size: u64) -> Result<(u64, u64), String> {
+    let (offset, len) = match spec.split_once('+') {
+        Some((offset, len)) => (parse_number(offset)?, parse_number(len)?),
+        None => (parse_number(spec)?, 1),
+    };
+    match (offset.checked_mul(block_size), len.checked_mul(block_size)) {
+        (Some(offset), Some(len)) => Ok((offset, len)),
+        _ => Err(format!(
+            "Invalid query '{}': too large for block size {}",
+            spec.trim(),
+            block_size
+        )),
+    }
+}
+
+/// Writes the runs that overlap a query, exiting if the query is invalid.
+fn lookup<W: Write>(index: &OffsetIndex, args: &Args, spec: &str, out: &mut W) -> io::Result<()> {
+    let (offset, len) = match parse_query(spec, args.block_size) {
+        Ok(query) => query,
+        Err(e) => {
+            out.flush()?;
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut found = false;
+    for hit in index.lookup_range(offset, len) {
+        if args.data_only && hit.facet != ByteRunFacet::Data {
+            continue;
+        }
+        found = true;
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            hit.img_offset,
+            hit.len,
+            hit.facet,
+            hit.file_offset.map_or("-".to_string(), |o| o.to_string()),
+            hit.file.inode.map_or("-".to_string(), |i| i.to_string()),
+            hit.file.filename.as_deref().unwrap_or("-")
+        )?;
+    }
+    if !found {
+        writeln!(out, "{}\t{}\t-\t-\t-\t-", offset, len)?;
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    if args.block_size == 0 {
+        eprintln!("Error: --block-size must be at least 1");
+        std::process::exit(1);
+    }
+
+    let doc = parse(compression::open(&args.dfxml)?)?;
+    let index = OffsetIndex::new(&doc);
+    if args.debug {
+        eprintln!("Indexed {} byte runs", index.len());
+    }
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    if args.queries.is_empty() {
+        for line in io::stdin().lock().lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                lookup(&index, &args, &line, &mut out)?;
+            }
+        }
+    } else {
+        for spec in &args.queries {
+            lookup(&index, &args, spec, &mut out)?;
+        }
+    }
+    out.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_query;
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(parse_query("1234567", 1), Ok((1234567, 1)));
+        assert_eq!(parse_query("0x200000+65536", 1), Ok((0x200000, 65536)));
+        assert_eq!(parse_query(" 4096+0x10 ", 1), Ok((4096, 16)));
+        assert_eq!(parse_query("2048+2", 512), Ok((1048576, 1024)));
+        assert!(parse_query("sector9", 1).is_err());
+        assert!(parse_query("100+", 1).is_err());
+        assert!(parse_query("0x8000000000000000", 2).is_err());
+        assert!(parse_query("1+0xffffffffffffffff", 512).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! disk_to_dfxml - Read the partition table of a raw disk image and generate DFXML.
+//!
+//! This tool reads the MBR or GPT at the start of a raw image and writes a
+//! disk image object holding its partition system and partitions. Each
+//! partition carries its index, type, label, GUID, detected file system and
+//! a byte run covering its sectors.
+//!
+//! The output can label the per-partition walks given to `cat_partitions`
+//! with partition numbers instead of byte offsets (see its `--disk` option),
+//! and `--offsets` lists the offsets for `image_to_dfxml --offset`.
+//!
+//! # Usage
+//!
+//! ```bash
+//! disk_to_dfxml [OPTIONS] <IMAGE>
+//! ```
+//!
+//! # Examples
+//!
+//! ```bash
+//! # Describe the partitions of a disk image
+//! disk_to_dfxml disk.raw > disk.dfxml
+//!
+//! # Walk every partition and combine the walks
+//! for p in $(disk_to_dfxml --offsets disk.raw); do
+//!     image_to_dfxml --offset ${p#*:} disk.raw > part${p%:*}.dfxml
+//! done
+//! cat_partitions --disk disk.dfxml 1:part1.dfxml 5:part5.dfxml > combined.dfxml
+//!
+//! # Read a GPT disk with 4096-byte logical sectors
+//! disk_to_dfxml --sector-size 4096 disk.raw > disk.dfxml
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+
+use clap::Parser;
+
+use dfxml_rs::compression::{Compression, Encoder};
+use dfxml_rs::objects::{DFXMLObject, LibraryObject};
+use dfxml_rs::partition;
+use dfxml_rs::writer::{DFXMLWriter, WriterConfig};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Read the partition table of a raw disk image and generate DFXML.
+#[derive(Parser, Debug)]
+#[command(name = "disk_to_dfxml")]
+#[command(version = VERSION)]
+#[command(about = "Read the partition table of a raw disk image and generate DFXML")]
+#[command(
+    long_about = "Reads the MBR (with extended and logical partitions) or GPT of a raw image \
+    and outputs DFXML describing the partition system and the byte runs of each partition."
+)]
+struct Args {
+    /// Raw disk image to read
+    image: String,
+
+    /// Logical sector size in bytes, a power of two from 512 to 65536 (default: 512, or detected for GPT)
+    #[arg(long, value_name = "BYTES")]
+    sector_size: Option<u32>,
+
+    /// Print INDEX:OFFSET for each partition instead of DFXML (extended partitions are skipped)
+    #[arg(long)]
+    offsets: bool,
+
+    /// Output compact XML (no indentation)
+    #[arg(long)]
+    compact: bool,
+
+    /// Compress the output (none, gzip, zstd or xz)
+    #[arg(long, value_name = "FORMAT", default_value = "none")]
+    compress: Compression,
+
+    /// Enable debug output
+    #[arg(short, long)]
+    debug: bool,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let mut image = BufReader::new(File::open(&args.image)?);
+    let mut disk = partition::read_disk_image(&mut image, args.sector_size)?;
+    disk.image_filename = Some(args.image.clone());
+
+    let mut doc = DFXMLObject::new();
+    doc.program = Some("disk_to_dfxml".to_string());
+    doc.program_version = Some(VERSION.to_string());
+    doc.command_line = Some(std::env::args().collect::<Vec<_>>().join(" "));
+    doc.add_creator_library(LibraryObject {
+        name: Some("dfxml-rs".to_string()),
+        version: Some(dfxml_rs::VERSION.to_string()),
+    });
+
+    if args.debug {
+        for ps in disk.partition_systems() {
+            eprintln!(
+                "{} partition system, {} partitions, {}-byte sectors",
+                ps.pstype_str.as_deref().unwrap_or("unknown"),
+                ps.partitions().count(),
+                ps.block_size.unwrap_or(0)
+            );
+            if let Some(ref error) = ps.error {
+                eprintln!("Warning: {}", error);
+            }
+        }
+    }
+    doc.append_disk_image(disk);
+
+    if args.offsets {
+        let mut out = BufWriter::new(io::stdout().lock());
+        for (index, offset) in partition::partition_offsets(&doc) {
+            writeln!(out, "{}:{}", index, offset)?;
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    let config = if args.compact {
+        WriterConfig::compact()
+    } else {
+        WriterConfig::default()
+    };
+    let mut out = Encoder::new(BufWriter::new(io::stdout().lock()), args.compress)?;
+    DFXMLWriter::with_config(config).write(&doc, &mut out)?;
+    out.write_all(b"\n")?;
+    out.finish()?.flush()?;
+    Ok(())
+}
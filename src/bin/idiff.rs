@@ -0,0 +1,300 @@
+//! idiff - Report the differences between two DFXML documents.
+//!
+//! This tool compares two DFXML files (typically fiwalk output for disk images
+//! taken at different times) and reports the files that were created, deleted,
+//! renamed, modified, or had their metadata changed. It is a Rust port of the
+//! Python `idifference2.py` tool from the dfxml_python project.
+//!
+//! # Usage
+//!
+//! ```bash
+//! idiff [OPTIONS] <OLD> <NEW>
+//! ```
+//!
+//! # Examples
+//!
+//! ```bash
+//! # Full report of differences
+//! idiff before.dfxml after.dfxml
+//!
+//! # Summary counts only
+//! idiff --summary before.dfxml after.dfxml
+//!
+//! # Ignore access time changes
+//! idiff -i atime before.dfxml after.dfxml
+//!
+//! # Compare compressed documents
+//! idiff before.dfxml.gz after.dfxml.zst
+//!
+//! # Write the differential DFXML document, compressed
+//! idiff --xml --compress zstd before.dfxml after.dfxml > delta.dfxml.zst
+//! ```
+
+use std::io::{self, BufWriter, Write};
+
+use clap::Parser;
+
+use dfxml_rs::compression::{self, Compression, Encoder};
+use dfxml_rs::diff::{
+    DiffConfig, Differ, ANNO_CHANGED, ANNO_DELETED, ANNO_MODIFIED, ANNO_NEW, ANNO_RENAMED,
+};
+use dfxml_rs::objects::{DFXMLObject, FileObject};
+use dfxml_rs::reader::parse;
+use dfxml_rs::writer::DFXMLWriter;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Report the differences between two DFXML documents.
+#[derive(Parser, Debug)]
+#[command(name = "idiff")]
+#[command(version = VERSION)]
+#[command(about = "Report the differences between two DFXML documents")]
+#[command(
+    long_about = "Compares two DFXML files and reports new, deleted, renamed, modified \
+    and changed files. This is a Rust port of the Python idifference2.py tool."
+)]
+struct Args {
+    /// The earlier DFXML file
+    old: String,
+
+    /// The later DFXML file
+    new: String,
+
+    /// Ignore a file property when comparing (can be specified multiple times)
+    #[arg(short, long = "ignore", value_name = "PROPERTY")]
+    ignore_properties: Vec<String>,
+
+    /// Only print the count of files in each category
+    #[arg(long, conflicts_with = "xml")]
+    summary: bool,
+
+    /// Write the differential DFXML document instead of a text report
+    #[arg(long)]
+    xml: bool,
+
+    /// Compress the differential DFXML document (none, gzip, zstd or xz)
+    #[arg(long, value_name = "FORMAT", default_value = "none", requires = "xml")]
+    compress: Compression,
+
+    /// Enable debug output
+    #[arg(short, long)]
+    debug: bool,
+}
+
+/// Files of a differential document, grouped by annotation.
+#[derive(Default)]
+struct Report<'a> {
+    new: Vec<&'a FileObject>,
+    deleted: Vec<&'a FileObject>,
+    renamed: Vec<&'a FileObject>,
+    modified: Vec<&'a FileObject>,
+    changed: Vec<&'a FileObject>,
+}
+
+impl<'a> Report<'a> {
+    fn from_delta(delta: &'a DFXMLObject) -> Self {
+        let mut report = Self::default();
+        for file in delta.iter_files() {
+            if file.annos.contains(ANNO_NEW) {
+                report.new.push(file);
+            }
+            if file.annos.contains(ANNO_DELETED) {
+                report.deleted.push(file);
+            }
+            if file.annos.contains(ANNO_RENAMED) {
+                report.renamed.push(file);
+            }
+            if file.annos.contains(ANNO_MODIFIED) {
+                report.modified.push(file);
+            }
+            if file.annos.contains(ANNO_CHANGED) {
+                report.changed.push(file);
+            }
+        }
+        report
+    }
+
+    fn write_summary<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "New files: {}", self.new.len())?;
+        writeln!(out, "Deleted files: {}", self.deleted.len())?;
+        writeln!(out, "Renamed files: {}", self.renamed.len())?;
+        writeln!(out, "Files with modified contents: {}", self.modified.len())?;
+        writeln!(out, "Files with changed properties: {}", self.changed.len())
+    }
+
+    fn write_full<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write_section(out, "New files", &self.new, |f| {
+            format!("{}\t{}", name_of(f), size_of(f))
+        })?;
+        write_section(out, "Deleted files", &self.deleted, |f| {
+            format!("{}\t{}", name_of(f), size_of(f))
+        })?;
+        write_section(out, "Renamed files", &self.renamed, |f| {
+            let old = f.original_fileobject.as_deref().map(name_of);
+            format!("{} -> {}", old.unwrap_or("?"), name_of(f))
+        })?;
+        write_section(out, "Files with modified contents", &self.modified, |f| {
+            let old = f.original_fileobject.as_deref().map(size_of);
+            format!(
+                "{}\t{} -> {}",
+                name_of(f),
+                old.unwrap_or_else(|| "?".to_string()),
+                size_of(f)
+            )
+        })?;
+        write_section(out, "Files with changed properties", &self.changed, |f| {
+            let mut props: Vec<_> = f.diffs.iter().map(String::as_str).collect();
+            props.sort_unstable();
+            format!("{}\t{}", name_of(f), props.join(", "))
+        })?;
+        self.write_summary(out)
+    }
+}
+
+/// Writes a titled section with one line per file.
+fn write_section<W, F>(out: &mut W, title: &str, files: &[&FileObject], line: F) -> io::Result<()>
+where
+    W: Write,
+    F: Fn(&FileObject) -> String,
+{
+    if files.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "{}:", title)?;
+    for file in files {
+        writeln!(out, "  {}", line(file))?;
+    }
+    writeln!(out)
+}
+
+fn name_of(file: &FileObject) -> &str {
+    file.filename.as_deref().unwrap_or("<unnamed>")
+}
+
+fn size_of(file: &FileObject) -> String {
+    file.filesize
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| "?".to_string())
+}
+
+/// Writes a differential document as DFXML, with its annotations.
+fn write_xml<W: Write>(delta: &DFXMLObject, mut out: W) -> dfxml_rs::Result<()> {
+    DFXMLWriter::new().write(delta, &mut out)?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+fn load(path: &str) -> Result<DFXMLObject, Box<dyn std::error::Error>> {
+    Ok(parse(compression::open(path)?)?)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    if args.debug {
+        eprintln!("Old: {}", args.old);
+        eprintln!("New: {}", args.new);
+    }
+
+    let old = load(&args.old)?;
+    let new = load(&args.new)?;
+
+    let mut config = DiffConfig::new();
+    for prop in &args.ignore_properties {
+        config = config.ignore(prop.as_str());
+    }
+
+    let delta = Differ::with_config(config).diff(&old, &new);
+
+    if args.debug {
+        eprintln!("Differential files: {}", delta.iter_files().count());
+    }
+
+    let mut out = Encoder::new(BufWriter::new(io::stdout().lock()), args.compress)?;
+    if args.xml {
+        write_xml(&delta, &mut out)?;
+    } else {
+        let report = Report::from_delta(&delta);
+        if args.summary {
+            report.write_summary(&mut out)?;
+        } else {
+            report.write_full(&mut out)?;
+        }
+    }
+    out.finish()?.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dfxml_rs::diff::diff;
+    use std::io::Cursor;
+
+    const OLD: &str = r#"<?xml version="1.0"?>
+<dfxml version="1.0">
+  <volume>
+    <fileobject>
+      <filename>kept.txt</filename>
+      <inode>10</inode>
+      <filesize>5</filesize>
+    </fileobject>
+    <fileobject>
+      <filename>gone.txt</filename>
+      <inode>11</inode>
+    </fileobject>
+  </volume>
+</dfxml>"#;
+
+    const NEW: &str = r#"<?xml version="1.0"?>
+<dfxml version="1.0">
+  <volume>
+    <fileobject>
+      <filename>kept.txt</filename>
+      <inode>10</inode>
+      <filesize>7</filesize>
+    </fileobject>
+    <fileobject>
+      <filename>fresh.txt</filename>
+      <inode>12</inode>
+    </fileobject>
+  </volume>
+</dfxml>"#;
+
+    #[test]
+    fn test_report_groups() {
+        let old = parse(Cursor::new(OLD)).unwrap();
+        let new = parse(Cursor::new(NEW)).unwrap();
+        let delta = diff(&old, &new);
+        let report = Report::from_delta(&delta);
+
+        assert_eq!(report.new.len(), 1);
+        assert_eq!(report.deleted.len(), 1);
+        assert_eq!(report.modified.len(), 1);
+        assert!(report.renamed.is_empty());
+        assert_eq!(name_of(report.modified[0]), "kept.txt");
+    }
+
+    #[test]
+    fn test_xml_output() {
+        let old = parse(Cursor::new(OLD)).unwrap();
+        let new = parse(Cursor::new(NEW)).unwrap();
+        let delta = diff(&old, &new);
+
+        let mut xml = Vec::new();
+        write_xml(&delta, &mut xml).unwrap();
+        let reread = parse(Cursor::new(&xml)).unwrap();
+        let report = Report::from_delta(&reread);
+        assert_eq!(report.new.len(), 1);
+        assert_eq!(report.deleted.len(), 1);
+        assert_eq!(report.modified.len(), 1);
+        assert_eq!(
+            report.modified[0]
+                .original_fileobject
+                .as_deref()
+                .and_then(|f| f.filesize),
+            Some(5)
+        );
+    }
+}
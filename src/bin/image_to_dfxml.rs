@@ -0,0 +1,169 @@
+//! image_to_dfxml - Walk the file system in a raw disk image and generate DFXML.
+//!
+//! This tool reads file system metadata straight from a raw image, without
+//! mounting it, and writes a volume with one file object per name, deleted
+//! name and orphan inode. Each file object carries its inode's metadata and
+//! the image locations of its inode and its content as byte runs, in the
+//! manner of fiwalk.
+//!
+//! Supported file systems: ext2, ext3, ext4, FAT12, FAT16, FAT32 and exFAT.
+//!
+//! # Usage
+//!
+//! ```bash
+//! image_to_dfxml [OPTIONS] <IMAGE>
+//! ```
+//!
+//! # Examples
+//!
+//! ```bash
+//! # Walk a file system image
+//! image_to_dfxml ext4.raw > fiwalk.dfxml
+//!
+//! # Walk a USB stick image
+//! image_to_dfxml usb.dd > usb.dfxml
+//!
+//! # Walk the partition starting at sector 2048 of a disk image
+//! image_to_dfxml --offset 1048576 disk.raw > part1.dfxml
+//!
+//! # Leave out deleted names and unallocated orphans, compressing the output
+//! image_to_dfxml --allocated-only --compress zstd disk.raw > live.dfxml.zst
+//!
+//! # Add $Unalloc and $Slack virtual files for carving
+//! image_to_dfxml --unallocated usb.dd > usb.dfxml
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+
+use clap::Parser;
+
+use dfxml_rs::compression::Compression;
+use dfxml_rs::filesystem;
+use dfxml_rs::objects::{DFXMLObject, LibraryObject};
+use dfxml_rs::unallocated::{self, SpaceMap};
+use dfxml_rs::writer::{DFXMLStreamWriter, WriterConfig};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Walk the file system in a raw disk image and generate DFXML.
+#[derive(Parser, Debug)]
+#[command(name = "image_to_dfxml")]
+#[command(version = VERSION)]
+#[command(about = "Walk the file system in a raw disk image and generate DFXML")]
+#[command(
+    long_about = "Reads file system metadata directly from a raw image and outputs DFXML with \
+    deleted names, orphan inodes and the byte runs of each file's inode and content."
+)]
+struct Args {
+    /// Raw disk image to walk
+    image: String,
+
+    /// Byte offset of the file system in the image
+    #[arg(short, long, default_value = "0")]
+    offset: u64,
+
+    /// Only output files whose name and inode are both allocated
+    #[arg(long)]
+    allocated_only: bool,
+
+    /// Add $Unalloc and $Slack virtual files holding the volume's
+    /// unallocated space and file slack
+    #[arg(long)]
+    unallocated: bool,
+
+    /// Output compact XML (no indentation)
+    #[arg(long)]
+    compact: bool,
+
+    /// Compress the output (none, gzip, zstd or xz)
+    #[arg(long, value_name = "FORMAT", default_value = "none")]
+    compress: Compression,
+
+    /// Enable debug output
+    #[arg(short, long)]
+    debug: bool,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let image = BufReader::new(File::open(&args.image)?);
+    let mut fs = filesystem::open(image, args.offset)?;
+    let mut volume = fs.volume();
+    if args.allocated_only {
+        volume.allocated_only = Some(true);
+    }
+    if args.debug {
+        eprintln!(
+            "{} file system, {} blocks of {} bytes",
+            volume.ftype_str.as_deref().unwrap_or("unknown"),
+            volume.block_count.unwrap_or(0),
+            volume.block_size.unwrap_or(0)
+        );
+    }
+
+    let mut doc = DFXMLObject::new();
+    doc.program = Some("image_to_dfxml".to_string());
+    doc.program_version = Some(VERSION.to_string());
+    doc.command_line = Some(std::env::args().collect::<Vec<_>>().join(" "));
+    doc.sources.push(args.image.clone());
+    doc.add_creator_library(LibraryObject {
+        name: Some("dfxml-rs".to_string()),
+        version: Some(dfxml_rs::VERSION.to_string()),
+    });
+
+    let config = if args.compact {
+        WriterConfig::compact()
+    } else {
+        WriterConfig::default()
+    };
+    let out = BufWriter::new(io::stdout().lock());
+    let mut writer = DFXMLStreamWriter::with_config(out, config.with_compression(args.compress))?;
+    writer.start_document(&doc)?;
+    writer.start_volume(&volume)?;
+
+    let mut space = if args.unallocated {
+        let (offset, size) = fs.allocation_unit();
+        Some(SpaceMap::new(&volume)?.with_allocation_unit(offset, size))
+    } else {
+        None
+    };
+    let mut count = 0usize;
+    for file in fs.files() {
+        let file = match file {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("Warning: {}", e);
+                continue;
+            }
+        };
+        if let Some(ref mut space) = space {
+            space.add_file(&file);
+        }
+        if args.allocated_only && file.is_allocated() != Some(true) {
+            continue;
+        }
+        writer.write_file(&file)?;
+        count += 1;
+    }
+
+    if let Some(space) = space {
+        writer.write_file(&unallocated::virtual_file(
+            unallocated::UNALLOCATED_NAME,
+            space.unallocated(),
+        ))?;
+        writer.write_file(&unallocated::virtual_file(
+            unallocated::SLACK_NAME,
+            space.slack(),
+        ))?;
+        count += 2;
+    }
+
+    writer.end_volume()?;
+    writer.finish()?.flush()?;
+    if args.debug {
+        eprintln!("{} file objects written", count);
+    }
+    Ok(())
+}
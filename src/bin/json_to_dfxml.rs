@@ -0,0 +1,84 @@
+//! json_to_dfxml - Convert JSON produced by dfxml_to_json back to DFXML.
+//!
+//! JSON Lines input is streamed: consecutive fileobjects that share the same
+//! container context are written into the same containers. Nested JSON
+//! (`--nested`) is loaded into memory and written in full.
+//!
+//! # Usage
+//!
+//! ```bash
+//! json_to_dfxml [OPTIONS] <FILENAME>
+//! ```
+//!
+//! # Examples
+//!
+//! ```bash
+//! # Convert JSON Lines back to DFXML
+//! json_to_dfxml files.jsonl > output.dfxml
+//!
+//! # Convert a nested JSON document without indentation
+//! json_to_dfxml --nested --compact document.json > output.dfxml
+//!
+//! # Convert zstd-compressed JSON Lines to gzipped DFXML
+//! json_to_dfxml --compress gzip files.jsonl.zst > output.dfxml.gz
+//! ```
+
+use std::io::{self, BufWriter, Write};
+
+use clap::Parser;
+
+use dfxml_rs::compression::{self, Compression};
+use dfxml_rs::json::{json_lines_to_dfxml_with_config, json_to_dfxml};
+use dfxml_rs::writer::WriterConfig;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Convert JSON produced by dfxml_to_json back to DFXML.
+#[derive(Parser, Debug)]
+#[command(name = "json_to_dfxml")]
+#[command(version = VERSION)]
+#[command(about = "Convert JSON Lines or nested JSON back to DFXML")]
+#[command(
+    long_about = "Reads the JSON Lines or nested JSON layout written by dfxml_to_json \
+    and writes the equivalent DFXML document."
+)]
+struct Args {
+    /// Input JSON file to convert
+    filename: String,
+
+    /// Read a single nested JSON document instead of JSON Lines
+    #[arg(long)]
+    nested: bool,
+
+    /// Output compact XML (no indentation)
+    #[arg(long)]
+    compact: bool,
+
+    /// Compress the output (none, gzip, zstd or xz)
+    #[arg(long, value_name = "FORMAT", default_value = "none")]
+    compress: Compression,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    let config = if args.compact {
+        WriterConfig::compact()
+    } else {
+        WriterConfig::default()
+    }
+    .with_compression(args.compress);
+
+    let input = compression::open(&args.filename)?;
+    let stdout = io::stdout();
+    let mut output = BufWriter::new(stdout.lock());
+
+    if args.nested {
+        json_to_dfxml(input, &mut output, config)?;
+    } else {
+        json_lines_to_dfxml_with_config(input, &mut output, config)?;
+    }
+    output.flush()?;
+
+    Ok(())
+}
@@ -24,11 +24,14 @@
 //!
 //! # Ignore specific properties
 //! walk_to_dfxml -i inode -i mtime /path/to/directory
+//!
+//! # Write a zstd-compressed manifest
+//! walk_to_dfxml --compress zstd /path/to/directory > manifest.dfxml.zst
 //! ```
 
 use std::collections::{HashMap, HashSet};
 use std::fs::{self, File, Metadata};
-use std::io::{self, Read, Write};
+use std::io::{self, BufWriter, Read, Write};
 use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
@@ -39,13 +42,17 @@ use digest::Digest;
 use rayon::prelude::*;
 use walkdir::WalkDir;
 
+use dfxml_rs::compression::{Compression, Encoder};
 use dfxml_rs::objects::{
     DFXMLObject, FileObject, HashType, Hashes, LibraryObject, NameType, Timestamp, TimestampName,
 };
-use dfxml_rs::writer;
+use dfxml_rs::writer::{self, DFXMLStreamWriter};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Number of paths converted (and, with `--jobs`, hashed in parallel) at a time.
+const BATCH_SIZE: usize = 1024;
+
 /// Walk a directory tree and generate DFXML output.
 #[derive(Parser, Debug)]
 #[command(name = "walk_to_dfxml")]
@@ -84,6 +91,10 @@ struct Args {
     /// Output compact XML (no indentation)
     #[arg(long)]
     compact: bool,
+
+    /// Compress the output (none, gzip, zstd or xz)
+    #[arg(long, value_name = "FORMAT", default_value = "none")]
+    compress: Compression,
 }
 
 /// Properties that can be ignored
@@ -511,17 +522,60 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .canonicalize()
         .unwrap_or_else(|_| args.path.clone());
 
-    // Collect all paths first
-    let mut paths: Vec<PathBuf> = Vec::new();
+    // Build the DFXML document header
+    let mut dobj = DFXMLObject::new();
+    dobj.program = Some("walk_to_dfxml".to_string());
+    dobj.program_version = Some(VERSION.to_string());
+    dobj.command_line = Some(std::env::args().collect::<Vec<_>>().join(" "));
+
+    // Add creator libraries
+    dobj.add_creator_library(LibraryObject {
+        name: Some("Rust".to_string()),
+        version: Some(env!("CARGO_PKG_RUST_VERSION").to_string()),
+    });
+    dobj.add_creator_library(LibraryObject {
+        name: Some("dfxml-rs".to_string()),
+        version: Some(dfxml_rs::VERSION.to_string()),
+    });
+
+    // Output DFXML as the walk proceeds
+    let config = if args.compact {
+        writer::WriterConfig::compact()
+    } else {
+        writer::WriterConfig::default()
+    };
+
+    // Compress the trailing newline along with the document
+    let stdout = io::stdout();
+    let handle = Encoder::new(BufWriter::new(stdout.lock()), args.compress)?;
+    let mut out = DFXMLStreamWriter::with_config(handle, config)?;
+    out.start_document(&dobj)?;
+
+    if args.jobs > 1 {
+        // Configure rayon thread pool
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(args.jobs)
+            .build_global()
+            .ok();
+    }
 
     let walker = WalkDir::new(&base_path)
         .follow_links(args.follow_links)
         .sort_by_file_name();
 
+    // Process paths in batches so memory use does not grow with the tree
+    let mut batch: Vec<PathBuf> = Vec::with_capacity(BATCH_SIZE);
+    let mut path_count = 0usize;
+
     for entry in walker {
         match entry {
             Ok(e) => {
-                paths.push(e.path().to_path_buf());
+                batch.push(e.path().to_path_buf());
+                path_count += 1;
+                if batch.len() == BATCH_SIZE {
+                    write_batch(&mut out, &batch, &base_path, &ignore_config, args.jobs)?;
+                    batch.clear();
+                }
             }
             Err(e) => {
                 if args.debug {
@@ -530,74 +584,49 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
     }
+    write_batch(&mut out, &batch, &base_path, &ignore_config, args.jobs)?;
 
     if args.debug {
-        eprintln!("Found {} paths", paths.len());
+        eprintln!("Found {} paths", path_count);
     }
 
-    // Process paths (in parallel if jobs > 1)
-    let file_objects: Vec<FileObject> = if args.jobs > 1 {
-        // Configure rayon thread pool
-        rayon::ThreadPoolBuilder::new()
-            .num_threads(args.jobs)
-            .build_global()
-            .ok();
+    let mut handle = out.finish()?;
+    handle.write_all(b"\n")?;
+    handle.finish()?.flush()?;
+
+    Ok(())
+}
 
-        // Process in parallel
-        let results: Vec<_> = paths
+/// Converts a batch of paths to file objects and writes them in walk order.
+///
+/// With more than one job the batch is processed in parallel, and the
+/// results are sorted back into walk order so the output is the same for
+/// any number of jobs.
+fn write_batch<W: Write>(
+    out: &mut DFXMLStreamWriter<W>,
+    paths: &[PathBuf],
+    base_path: &Path,
+    ignore_config: &IgnoreConfig,
+    jobs: usize,
+) -> dfxml_rs::Result<()> {
+    let file_objects: Vec<_> = if jobs > 1 {
+        let mut results: Vec<_> = paths
             .par_iter()
-            .map(|path| path_to_fileobject(path, &base_path, &ignore_config))
+            .enumerate()
+            .map(|(i, path)| (i, path_to_fileobject(path, base_path, ignore_config)))
             .collect();
-
-        // Collect results, maintaining order by sorting by filename
-        let mut file_objects: Vec<FileObject> =
-            results.into_iter().filter_map(|r| r.ok()).collect();
-
-        // Sort by filename to ensure deterministic output
-        file_objects.sort_by(|a, b| a.filename.cmp(&b.filename));
-        file_objects
+        // Sort by walk position so the output does not depend on scheduling
+        results.sort_unstable_by_key(|(i, _)| *i);
+        results.into_iter().map(|(_, fobj)| fobj).collect()
     } else {
-        // Process sequentially
         paths
             .iter()
-            .filter_map(|path| path_to_fileobject(path, &base_path, &ignore_config).ok())
+            .map(|path| path_to_fileobject(path, base_path, ignore_config))
             .collect()
     };
 
-    // Build DFXML document
-    let mut dobj = DFXMLObject::new();
-    dobj.program = Some("walk_to_dfxml".to_string());
-    dobj.program_version = Some(VERSION.to_string());
-    dobj.command_line = Some(std::env::args().collect::<Vec<_>>().join(" "));
-
-    // Add creator libraries
-    dobj.add_creator_library(LibraryObject {
-        name: Some("Rust".to_string()),
-        version: Some(env!("CARGO_PKG_RUST_VERSION").to_string()),
-    });
-    dobj.add_creator_library(LibraryObject {
-        name: Some("dfxml-rs".to_string()),
-        version: Some(dfxml_rs::VERSION.to_string()),
-    });
-
-    // Add all file objects
-    for fobj in file_objects {
-        dobj.append_file(fobj);
+    for fobj in file_objects.into_iter().flatten() {
+        out.write_file(&fobj)?;
     }
-
-    // Output DFXML
-    let config = if args.compact {
-        writer::WriterConfig::compact()
-    } else {
-        writer::WriterConfig::default()
-    };
-
-    let xml = writer::DFXMLWriter::with_config(config).write_to_string(&dobj)?;
-
-    let stdout = io::stdout();
-    let mut handle = stdout.lock();
-    handle.write_all(xml.as_bytes())?;
-    handle.write_all(b"\n")?;
-
     Ok(())
 }
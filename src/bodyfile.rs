@@ -0,0 +1,427 @@
+//! Sleuth Kit body file (mactime) import and export.
+//!
+//! A body file has one line per file with eleven `|`-separated fields:
+//!
+//! ```text
+//! MD5|name|inode|mode_as_string|UID|GID|size|atime|mtime|ctime|crtime
+//! ```
+//!
+//! Times are Unix epoch seconds, and `0` marks an unknown MD5, time or mode.
+//! The mode is TSK's `ls`-style string prefixed by the name type, such as
+//! `r/rrw-r--r--` or `d/drwxr-xr-x`. As in TSK output, unallocated files get
+//! a ` (deleted)` suffix and symbolic links a ` -> target` suffix.
+//!
+//! Export streams through [`DFXMLReader`], writing each line as soon as its
+//! file object is read.
+//! Import builds a [`DFXMLObject`] with the files attached directly to the
+//! document, since body files carry no volume information.
+//!
+//! # Example
+//!
+//! ```rust
+//! use dfxml_rs::bodyfile;
+//! use dfxml_rs::objects::FileObject;
+//!
+//! let mut file = FileObject::with_filename("/etc/passwd");
+//! file.inode = Some(42);
+//! file.filesize = Some(1024);
+//!
+//! let line = bodyfile::format_line(&file);
+//! assert_eq!(line, "0|/etc/passwd|42|0|0|0|1024|0|0|0|0");
+//!
+//! let parsed = bodyfile::parse_line(&line).unwrap();
+//! assert_eq!(parsed.inode, Some(42));
+//! ```
+
+use crate::error::{Error, Result};
+use crate::objects::{
+    DFXMLObject, FileObject, HashType, MetaType, NameType, Timestamp, TimestampName,
+};
+use crate::reader::{DFXMLReader, Event};
+use chrono::DateTime;
+use std::io::{BufRead, Write};
+
+/// Number of fields in a body file line.
+const FIELD_COUNT: usize = 11;
+
+/// Suffix TSK appends to the names of unallocated files.
+const DELETED_SUFFIX: &str = " (deleted)";
+
+/// Separator TSK uses between a symbolic link's name and its target.
+const LINK_SEPARATOR: &str = " -> ";
+
+/// Formats a file object as a body file line (without a trailing newline).
+pub fn format_line(file: &FileObject) -> String {
+    let mut name = file.filename.clone().unwrap_or_default();
+    if file.is_allocated() == Some(false) {
+        name.push_str(DELETED_SUFFIX);
+    }
+    if let Some(target) = &file.link_target {
+        name.push_str(LINK_SEPARATOR);
+        name.push_str(target);
+    }
+
+    format!(
+        "{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}",
+        file.hashes.get(HashType::Md5).unwrap_or("0"),
+        name,
+        file.inode.unwrap_or(0),
+        format_mode(file),
+        file.uid.unwrap_or(0),
+        file.gid.unwrap_or(0),
+        file.filesize.unwrap_or(0),
+        epoch(&file.atime),
+        epoch(&file.mtime),
+        epoch(&file.ctime),
+        epoch(&file.crtime),
+    )
+}
+
+/// Parses a body file line into a file object.
+///
+/// Names containing `|` are recovered by treating every field between the
+/// MD5 and the last nine fields as part of the name.
+pub fn parse_line(line: &str) -> Result<FileObject> {
+    let fields: Vec<&str> = line.trim_end_matches(['\r', '\n']).split('|').collect();
+    if fields.len() < FIELD_COUNT {
+        return Err(Error::InvalidBodyfile(format!(
+            "expected {} fields, found {}",
+            FIELD_COUNT,
+            fields.len()
+        )));
+    }
+    let name_end = fields.len() - (FIELD_COUNT - 2);
+    let rest = &fields[name_end..];
+
+    let mut file = FileObject::new();
+
+    let md5 = fields[0].trim();
+    if !md5.is_empty() && md5 != "0" {
+        file.hashes.set(HashType::Md5, md5.to_lowercase());
+    }
+
+    parse_mode(rest[1], &mut file)?;
+
+    let mut name = fields[1..name_end].join("|");
+    if file.meta_type == Some(MetaType::SymbolicLink) {
+        if let Some(pos) = name.find(LINK_SEPARATOR) {
+            file.link_target = Some(name[pos + LINK_SEPARATOR.len()..].to_string());
+            name.truncate(pos);
+        }
+    }
+    if let Some(stripped) = name.strip_suffix(DELETED_SUFFIX) {
+        name = stripped.to_string();
+        file.alloc = Some(false);
+    }
+    file.filename = Some(name);
+
+    // NTFS inodes are written as "inode-type-id"; keep the inode number
+    let inode = rest[0].split('-').next().unwrap_or_default();
+    file.inode = parse_number::<u64>(inode, "inode")?.filter(|&i| i != 0);
+    file.uid = parse_number(rest[2], "UID")?;
+    file.gid = parse_number(rest[3], "GID")?;
+    file.filesize = parse_number(rest[4], "size")?;
+
+    for (field, name) in rest[5..].iter().zip([
+        TimestampName::Atime,
+        TimestampName::Mtime,
+        TimestampName::Ctime,
+        TimestampName::Crtime,
+    ]) {
+        if let Some(ts) = parse_time(field, name)? {
+            file.set_timestamp(name, ts);
+        }
+    }
+
+    Ok(file)
+}
+
+/// Streams the file objects of a DFXML document to a body file.
+///
+/// Returns the number of lines written.
+pub fn dfxml_to_bodyfile<R: BufRead, W: Write>(input: R, mut output: W) -> Result<u64> {
+    let mut count = 0;
+    for event in DFXMLReader::from_reader(input) {
+        if let Event::FileObject(file) = event? {
+            writeln!(output, "{}", format_line(&file))?;
+            count += 1;
+        }
+    }
+    output.flush()?;
+    Ok(count)
+}
+
+/// Reads a body file into a DFXML document.
+///
+/// Blank lines are skipped. Errors name the offending line number.
+pub fn read_bodyfile<R: BufRead>(input: R) -> Result<DFXMLObject> {
+    let mut doc = DFXMLObject::new();
+    for (index, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let file = parse_line(&line).map_err(|e| match e {
+            Error::InvalidBodyfile(msg) => {
+                Error::InvalidBodyfile(format!("line {}: {}", index + 1, msg))
+            }
+            other => other,
+        })?;
+        doc.append_file(file);
+    }
+    Ok(doc)
+}
+
+/// Formats a file's type and permissions as a TSK mode string, e.g.
+/// `r/rrw-r--r--`, or `0` when nothing is known.
+pub fn format_mode(file: &FileObject) -> String {
+    if file.name_type.is_none() && file.meta_type.is_none() && file.mode.is_none() {
+        return "0".to_string();
+    }
+
+    let name_type = file.name_type.map_or("-", |t| t.as_str());
+    let meta_type = file.meta_type.map_or("-", |t| t.as_str());
+    let mode = file.mode.unwrap_or(0);
+
+    let mut perms = [b'-'; 9];
+    for (i, c) in b"rwxrwxrwx".iter().enumerate() {
+        if mode & (0o400 >> i) != 0 {
+            perms[i] = *c;
+        }
+    }
+    // setuid, setgid and sticky bits replace the matching execute position
+    for (bit, pos, set) in [(0o4000, 2, b's'), (0o2000, 5, b's'), (0o1000, 8, b't')] {
+        if mode & bit != 0 {
+            perms[pos] = if perms[pos] == b'x' {
+                set
+            } else {
+                set.to_ascii_uppercase()
+            };
+        }
+    }
+
+    format!(
+        "{}/{}{}",
+        name_type,
+        meta_type,
+        String::from_utf8_lossy(&perms)
+    )
+}
+
+/// Parses the mode field into the file's name type, meta type and mode.
+fn parse_mode(field: &str, file: &mut FileObject) -> Result<()> {
+    let field = field.trim();
+    if field.is_empty() || field == "0" {
+        return Ok(());
+    }
+
+    let invalid = || Error::InvalidBodyfile(format!("invalid mode string {:?}", field));
+    let (name_type, ls) = field.split_once('/').ok_or_else(invalid)?;
+    let mut ls = ls.chars();
+    let meta_char = ls.next().ok_or_else(invalid)?;
+    let perms: Vec<char> = ls.collect();
+    if name_type.chars().count() != 1 || perms.len() != 9 {
+        return Err(invalid());
+    }
+
+    // TSK uses "-" for an undefined type
+    if name_type != "-" {
+        file.name_type = name_type.chars().next().and_then(NameType::from_char);
+    }
+    if meta_char != '-' {
+        file.meta_type = MetaType::from_char(meta_char);
+    }
+
+    let mut mode = 0;
+    for (i, c) in perms.iter().enumerate() {
+        let special = match (i, c) {
+            (2, 's' | 'S') => 0o4000,
+            (5, 's' | 'S') => 0o2000,
+            (8, 't' | 'T') => 0o1000,
+            _ => 0,
+        };
+        mode |= special;
+        if matches!(c, 'r' | 'w' | 'x' | 's' | 't') {
+            mode |= 0o400 >> i;
+        }
+    }
+    file.mode = Some(mode);
+    Ok(())
+}
+
+/// Returns a timestamp as Unix epoch seconds, or 0 when unset.
+fn epoch(ts: &Option<Timestamp>) -> i64 {
+    ts.as_ref()
+        .and_then(|ts| ts.time)
+        .map_or(0, |t| t.timestamp())
+}
+
+/// Parses Unix epoch seconds (optionally fractional); 0 means unset.
+fn parse_time(field: &str, name: TimestampName) -> Result<Option<Timestamp>> {
+    let field = field.trim();
+    let (secs, frac) = field.split_once('.').unwrap_or((field, ""));
+    let secs: i64 = secs
+        .parse()
+        .map_err(|_| Error::InvalidBodyfile(format!("invalid {} {:?}", name.as_str(), field)))?;
+
+    let digits: String = frac.chars().take(9).collect();
+    let nanos = if digits.is_empty() {
+        0
+    } else {
+        let scale = 10u32.pow(9 - digits.len() as u32);
+        digits
+            .parse::<u32>()
+            .map_err(|_| Error::InvalidBodyfile(format!("invalid {} {:?}", name.as_str(), field)))?
+            * scale
+    };
+
+    if secs == 0 && nanos == 0 {
+        return Ok(None);
+    }
+    let time = DateTime::from_timestamp(secs, nanos).ok_or_else(|| {
+        Error::InvalidBodyfile(format!("{} out of range: {:?}", name.as_str(), field))
+    })?;
+    Ok(Some(Timestamp::with_name_and_time(
+        name,
+        time.fixed_offset(),
+    )))
+}
+
+/// Parses a numeric field, treating an empty field as unknown.
+fn parse_number<T: std::str::FromStr>(field: &str, what: &str) -> Result<Option<T>> {
+    let field = field.trim();
+    if field.is_empty() {
+        return Ok(None);
+    }
+    field
+        .parse()
+        .map(Some)
+        .map_err(|_| Error::InvalidBodyfile(format!("invalid {} {:?}", what, field)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_parse_tsk_line() {
+        let line = "d41d8cd98f00b204e9800998ecf8427e|/home/user/notes.txt|1234|r/rrw-r--r--|1000|100|512|1600000000|1600000100|1600000200|0";
+        let file = parse_line(line).unwrap();
+
+        assert_eq!(file.filename.as_deref(), Some("/home/user/notes.txt"));
+        assert_eq!(
+            file.hashes.get(HashType::Md5),
+            Some("d41d8cd98f00b204e9800998ecf8427e")
+        );
+        assert_eq!(file.inode, Some(1234));
+        assert_eq!(file.name_type, Some(NameType::Regular));
+        assert_eq!(file.meta_type, Some(MetaType::Regular));
+        assert_eq!(file.mode, Some(0o644));
+        assert_eq!(file.uid, Some(1000));
+        assert_eq!(file.gid, Some(100));
+        assert_eq!(file.filesize, Some(512));
+        assert_eq!(
+            file.atime.as_ref().unwrap().time.unwrap().timestamp(),
+            1600000000
+        );
+        assert_eq!(
+            file.ctime.as_ref().unwrap().time.unwrap().timestamp(),
+            1600000200
+        );
+        assert!(file.crtime.is_none());
+    }
+
+    #[test]
+    fn test_parse_special_names() {
+        let file = parse_line("0|/tmp/a|b (deleted)|5-128-1|r/rrwsr-xr-T|0|0|0|0|0|0|0").unwrap();
+        assert_eq!(file.filename.as_deref(), Some("/tmp/a|b"));
+        assert_eq!(file.alloc, Some(false));
+        assert_eq!(file.inode, Some(5));
+        assert_eq!(file.mode, Some(0o5754));
+
+        let link = parse_line("0|/bin/sh -> dash|7|l/lrwxrwxrwx|0|0|4|0|0|0|0").unwrap();
+        assert_eq!(link.filename.as_deref(), Some("/bin/sh"));
+        assert_eq!(link.link_target.as_deref(), Some("dash"));
+    }
+
+    #[test]
+    fn test_format_roundtrip() {
+        let line =
+            "0123456789abcdef0123456789abcdef|/var/log (deleted)|99|d/drwxr-x--t|0|4|4096|1|2|3|4";
+        let file = parse_line(line).unwrap();
+        assert_eq!(format_line(&file), line);
+    }
+
+    #[test]
+    fn test_export_from_dfxml() {
+        let xml = r#"<dfxml version="1.0">
+  <fileobject>
+    <filename>/etc/shadow</filename>
+    <name_type>r</name_type>
+    <filesize>900</filesize>
+    <alloc>1</alloc>
+    <inode>17</inode>
+    <meta_type>1</meta_type>
+    <mode>0640</mode>
+    <uid>0</uid>
+    <gid>42</gid>
+    <mtime>1970-01-01T00:00:20Z</mtime>
+    <ctime>1970-01-01T00:00:30Z</ctime>
+    <atime>1970-01-01T00:00:10Z</atime>
+    <crtime>1970-01-01T00:00:40Z</crtime>
+  </fileobject>
+</dfxml>"#;
+
+        let mut out = Vec::new();
+        dfxml_to_bodyfile(Cursor::new(xml), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "0|/etc/shadow|17|r/rrw-r-----|0|42|900|10|20|30|40\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_lines() {
+        assert!(parse_line("0|name|1|r/rrw-r--r--").is_err());
+        assert!(parse_line("0|name|x|r/rrw-r--r--|0|0|0|0|0|0|0").is_err());
+        assert!(parse_line("0|name|1|bogus|0|0|0|0|0|0|0").is_err());
+
+        let input = "0|ok|1|0|0|0|0|0|0|0|0\n0|bad|1|0|0|0|size|0|0|0|0\n";
+        let err = read_bodyfile(Cursor::new(input)).unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+    }
+
+    #[test]
+    fn test_dfxml_to_bodyfile() {
+        let xml = r#"<?xml version="1.0"?>
+<dfxml version="1.0">
+  <volume>
+    <fileobject>
+      <filename>a.txt</filename>
+      <inode>12</inode>
+      <filesize>3</filesize>
+      <mtime>2020-09-13T12:26:40Z</mtime>
+    </fileobject>
+  </volume>
+  <fileobject>
+    <filename>b.txt</filename>
+  </fileobject>
+</dfxml>"#;
+        let mut out = Vec::new();
+        let count = dfxml_to_bodyfile(Cursor::new(xml), &mut out).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "0|a.txt|12|0|0|0|3|0|1600000000|0|0\n0|b.txt|0|0|0|0|0|0|0|0|0\n"
+        );
+
+        let doc = read_bodyfile(Cursor::new("0|a.txt|12|0|0|0|3|0|1600000000|0|0\n\n")).unwrap();
+        let file = doc.iter_files().next().unwrap();
+        assert_eq!(file.filename.as_deref(), Some("a.txt"));
+        assert_eq!(
+            file.mtime.as_ref().unwrap().time.unwrap().timestamp(),
+            1600000000
+        );
+    }
+}
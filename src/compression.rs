@@ -0,0 +1,368 @@
+//! Transparent compression of DFXML input and output.
+//!
+//! Case archives often keep DFXML as `.xml.gz`, `.xml.zst` or `.xml.xz`.
+//! [`decompress`] detects gzip, zstd and xz input from its first bytes and
+//! decompresses it on the fly, passing plain XML through unchanged, so any
+//! DFXML file can be handed to [`DFXMLReader`] or [`parse`](crate::reader::parse)
+//! the same way. Positions reported by the reader are offsets into the
+//! decompressed document.
+//!
+//! Output is compressed by configuring the writer with
+//! [`WriterConfig::with_compression`], or by wrapping any writer in an
+//! [`Encoder`].
+//!
+//! # Example
+//!
+//! ```rust
+//! use dfxml_rs::compression::{self, Compression};
+//! use dfxml_rs::objects::{DFXMLObject, FileObject};
+//! use dfxml_rs::writer::{DFXMLWriter, WriterConfig};
+//!
+//! let mut doc = DFXMLObject::new();
+//! doc.append_file(FileObject::with_filename("a.txt"));
+//!
+//! let config = WriterConfig::default().with_compression(Compression::Zstd);
+//! let mut zst = Vec::new();
+//! DFXMLWriter::with_config(config).write(&doc, &mut zst).unwrap();
+//! assert_eq!(Compression::detect(&zst), Compression::Zstd);
+//!
+//! let input = compression::decompress(zst.as_slice()).unwrap();
+//! let doc = dfxml_rs::reader::parse(input).unwrap();
+//! assert_eq!(doc.files().count(), 1);
+//! ```
+//!
+//! [`WriterConfig::with_compression`]: crate::writer::WriterConfig::with_compression
+
+use crate::error::{Error, Result};
+use crate::reader::DFXMLReader;
+use flate2::bufread::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+use xz2::bufread::XzDecoder;
+use xz2::write::XzEncoder;
+
+/// Bytes needed to recognize every supported format.
+const MAGIC_LEN: usize = 6;
+
+/// xz compression level, matching the `xz` command's default.
+const XZ_LEVEL: u32 = 6;
+
+/// A compression format for DFXML input or output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Compression {
+    /// Plain XML
+    #[default]
+    None,
+    /// gzip (`.gz`)
+    Gzip,
+    /// Zstandard (`.zst`)
+    Zstd,
+    /// xz (`.xz`)
+    Xz,
+}
+
+impl Compression {
+    /// Detects the format of data from its first bytes. Anything that is
+    /// not gzip, zstd or xz is taken to be plain XML.
+    pub fn detect(magic: &[u8]) -> Self {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Returns the format conventionally used for a file name, from its
+    /// extension.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        let extension = path.as_ref().extension().and_then(|e| e.to_str());
+        match extension.map(str::to_ascii_lowercase).as_deref() {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            Some("xz") => Compression::Xz,
+            _ => Compression::None,
+        }
+    }
+
+    /// Returns the format's name.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Xz => "xz",
+        }
+    }
+
+    /// Returns the file name extension for the format, without the dot.
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gz"),
+            Compression::Zstd => Some("zst"),
+            Compression::Xz => Some("xz"),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            "xz" => Ok(Compression::Xz),
+            _ => Err(Error::Compression(format!(
+                "unknown format {:?} (expected none, gzip, zstd or xz)",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Returns the input decompressed, detecting its format from its first
+/// bytes. Plain input is passed through.
+pub fn decompress<'a, R: BufRead + 'a>(mut input: R) -> Result<Box<dyn BufRead + 'a>> {
+    let mut magic = Vec::with_capacity(MAGIC_LEN);
+    (&mut input)
+        .take(MAGIC_LEN as u64)
+        .read_to_end(&mut magic)?;
+    let compression = Compression::detect(&magic);
+    let input = Cursor::new(magic).chain(input);
+    Ok(match compression {
+        Compression::None => Box::new(input),
+        // Concatenated members and frames, as written by `cat a.gz b.gz`
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(input))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(input)?)),
+        Compression::Xz => Box::new(BufReader::new(XzDecoder::new_multi_decoder(input))),
+    })
+}
+
+/// Opens a file, decompressing it if it is compressed.
+pub fn open<P: AsRef<Path>>(path: P) -> Result<Box<dyn BufRead>> {
+    decompress(BufReader::new(File::open(path)?))
+}
+
+/// Creates a [`DFXMLReader`] for possibly compressed input.
+pub fn reader<'a, R: BufRead + 'a>(input: R) -> Result<DFXMLReader<Box<dyn BufRead + 'a>>> {
+    Ok(DFXMLReader::from_reader(decompress(input)?))
+}
+
+/// Creates a [`DFXMLReader`] for a possibly compressed file.
+pub fn open_reader<P: AsRef<Path>>(path: P) -> Result<DFXMLReader<Box<dyn BufRead>>> {
+    Ok(DFXMLReader::from_reader(open(path)?))
+}
+
+/// A writer that compresses what is written to it.
+///
+/// [`finish`](Self::finish) must be called to write the end of the
+/// compressed stream.
+pub enum Encoder<W: Write> {
+    /// Writes through uncompressed
+    None(W),
+    /// Compresses with gzip
+    Gzip(GzEncoder<W>),
+    /// Compresses with Zstandard
+    Zstd(zstd::Encoder<'static, W>),
+    /// Compresses with xz
+    Xz(XzEncoder<W>),
+}
+
+impl<W: Write> Encoder<W> {
+    /// Creates an encoder writing `compression` to `writer`, at the
+    /// format's default level.
+    pub fn new(writer: W, compression: Compression) -> Result<Self> {
+        Ok(match compression {
+            Compression::None => Encoder::None(writer),
+            Compression::Gzip => {
+                Encoder::Gzip(GzEncoder::new(writer, flate2::Compression::default()))
+            }
+            Compression::Zstd => {
+                Encoder::Zstd(zstd::Encoder::new(writer, zstd::DEFAULT_COMPRESSION_LEVEL)?)
+            }
+            Compression::Xz => Encoder::Xz(XzEncoder::new(writer, XZ_LEVEL)),
+        })
+    }
+
+    /// Returns the format being written.
+    pub fn compression(&self) -> Compression {
+        match self {
+            Encoder::None(_) => Compression::None,
+            Encoder::Gzip(_) => Compression::Gzip,
+            Encoder::Zstd(_) => Compression::Zstd,
+            Encoder::Xz(_) => Compression::Xz,
+        }
+    }
+
+    /// Returns a mutable reference to the underlying writer. Anything
+    /// written to it directly bypasses the compression.
+    pub fn get_mut(&mut self) -> &mut W {
+        match self {
+            Encoder::None(w) => w,
+            Encoder::Gzip(e) => e.get_mut(),
+            Encoder::Zstd(e) => e.get_mut(),
+            Encoder::Xz(e) => e.get_mut(),
+        }
+    }
+
+    /// Writes the end of the compressed stream and returns the underlying
+    /// writer.
+    pub fn finish(self) -> Result<W> {
+        Ok(match self {
+            Encoder::None(w) => w,
+            Encoder::Gzip(e) => e.finish()?,
+            Encoder::Zstd(e) => e.finish()?,
+            Encoder::Xz(e) => e.finish()?,
+        })
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Encoder::None(w) => w.write(buf),
+            Encoder::Gzip(e) => e.write(buf),
+            Encoder::Zstd(e) => e.write(buf),
+            Encoder::Xz(e) => e.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Encoder::None(w) => w.flush(),
+            Encoder::Gzip(e) => e.flush(),
+            Encoder::Zstd(e) => e.flush(),
+            Encoder::Xz(e) => e.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::Event;
+
+    const XML: &str = r#"<?xml version="1.0"?>
+<dfxml version="1.0">
+  <volume><fileobject><filename>a.txt</filename></fileobject></volume>
+</dfxml>"#;
+
+    fn compress(data: &[u8], compression: Compression) -> Vec<u8> {
+        let mut encoder = Encoder::new(Vec::new(), compression).unwrap();
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for compression in [
+            Compression::None,
+            Compression::Gzip,
+            Compression::Zstd,
+            Compression::Xz,
+        ] {
+            let data = compress(XML.as_bytes(), compression);
+            assert_eq!(Compression::detect(&data), compression);
+
+            let mut xml = String::new();
+            decompress(data.as_slice())
+                .unwrap()
+                .read_to_string(&mut xml)
+                .unwrap();
+            assert_eq!(xml, XML);
+
+            let files = reader(data.as_slice())
+                .unwrap()
+                .filter(|e| matches!(e, Ok(Event::FileObject(_))))
+                .count();
+            assert_eq!(files, 1);
+        }
+    }
+
+    #[test]
+    fn test_short_and_concatenated_input() {
+        // Shorter than the magic bytes
+        let mut xml = String::new();
+        decompress(&b"<a/>"[..])
+            .unwrap()
+            .read_to_string(&mut xml)
+            .unwrap();
+        assert_eq!(xml, "<a/>");
+
+        let mut data = compress(b"<dfxml>", Compression::Gzip);
+        data.extend(compress(b"</dfxml>", Compression::Gzip));
+        let mut xml = String::new();
+        decompress(data.as_slice())
+            .unwrap()
+            .read_to_string(&mut xml)
+            .unwrap();
+        assert_eq!(xml, "<dfxml></dfxml>");
+    }
+
+    #[test]
+    fn test_compressed_writers() {
+        use crate::objects::{DFXMLObject, FileObject, VolumeObject};
+        use crate::writer::{DFXMLStreamWriter, DFXMLWriter, WriterConfig};
+
+        let mut doc = DFXMLObject::new();
+        let mut vol = VolumeObject::with_ftype("ntfs");
+        vol.append_file(FileObject::with_filename("a.txt"));
+        doc.append_volume(vol.clone());
+        let plain = DFXMLWriter::new().write_to_string(&doc).unwrap();
+
+        let config = WriterConfig::default().with_compression(Compression::Gzip);
+        let mut batch = Vec::new();
+        DFXMLWriter::with_config(config.clone())
+            .write(&doc, &mut batch)
+            .unwrap();
+        // Strings stay uncompressed
+        let string = DFXMLWriter::with_config(config.clone())
+            .write_to_string(&doc)
+            .unwrap();
+        assert_eq!(string, plain);
+
+        let mut writer = DFXMLStreamWriter::with_config(Vec::new(), config).unwrap();
+        writer.start_document(&doc).unwrap();
+        writer.start_volume(&vol).unwrap();
+        writer
+            .write_file(&FileObject::with_filename("a.txt"))
+            .unwrap();
+        let stream = writer.finish().unwrap();
+
+        for data in [batch, stream] {
+            assert_eq!(Compression::detect(&data), Compression::Gzip);
+            let mut xml = String::new();
+            decompress(data.as_slice())
+                .unwrap()
+                .read_to_string(&mut xml)
+                .unwrap();
+            assert_eq!(xml, plain);
+        }
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!("GZ".parse::<Compression>().unwrap(), Compression::Gzip);
+        assert_eq!("zstd".parse::<Compression>().unwrap(), Compression::Zstd);
+        assert!("bzip2".parse::<Compression>().is_err());
+        assert_eq!(Compression::from_path("fiwalk.xml.zst"), Compression::Zstd);
+        assert_eq!(Compression::from_path("fiwalk.xml"), Compression::None);
+        assert_eq!(Compression::Xz.extension(), Some("xz"));
+    }
+}
@@ -0,0 +1,352 @@
+//! Duplicate detection across one or more DFXML documents.
+//!
+//! A [`Dedup`] groups file objects by a hash digest. Each input is added as
+//! a labelled source (e.g. one per custodian), so groups can report which
+//! sources hold copies of the same content and how many bytes the extra
+//! copies waste.
+//!
+//! Files are grouped by one algorithm chosen with [`HashChoice`]. With
+//! [`HashChoice::Strongest`] each file is keyed by the strongest hash it
+//! carries, so two files are only compared when their strongest hashes use
+//! the same algorithm.
+//!
+//! # Example
+//!
+//! ```rust
+//! use dfxml_rs::dedup::{Dedup, HashChoice};
+//! use dfxml_rs::objects::{FileObject, HashType};
+//!
+//! let mut dedup = Dedup::new(HashChoice::Type(HashType::Sha256));
+//! let alice = dedup.add_source("alice");
+//! let bob = dedup.add_source("bob");
+//!
+//! for (source, name) in [(alice, "report.pdf"), (bob, "copy of report.pdf")] {
+//!     let mut file = FileObject::with_filename(name);
+//!     file.filesize = Some(1000);
+//!     file.hashes.set(
+//!         HashType::Sha256,
+//!         "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08".to_string(),
+//!     );
+//!     dedup.add_file(source, file);
+//! }
+//!
+//! let stats = dedup.stats();
+//! assert_eq!(stats.duplicate_groups, 1);
+//! assert_eq!(stats.wasted_bytes, 1000);
+//! assert_eq!(stats.cross_source_groups, 1);
+//! ```
+
+use crate::error::Result;
+use crate::objects::{FileObject, HashType};
+use crate::reader::{DFXMLReader, Event};
+use std::collections::{BTreeSet, HashMap};
+use std::io::BufRead;
+
+/// Hash algorithms from strongest to weakest, as used by
+/// [`HashChoice::Strongest`].
+pub const STRENGTH_ORDER: [HashType; 7] = [
+    HashType::Sha512,
+    HashType::Sha384,
+    HashType::Sha256,
+    HashType::Sha224,
+    HashType::Sha1,
+    HashType::Md5,
+    HashType::Md6,
+];
+
+/// Which hash to group files by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashChoice {
+    /// Always use this algorithm; files without it are not grouped
+    Type(HashType),
+    /// Use the strongest algorithm each file carries
+    Strongest,
+}
+
+impl HashChoice {
+    /// Returns the algorithm and digest to key a file by, if any.
+    pub fn select<'a>(&self, file: &'a FileObject) -> Option<(HashType, &'a str)> {
+        match self {
+            HashChoice::Type(hash_type) => file.hashes.get(*hash_type).map(|d| (*hash_type, d)),
+            HashChoice::Strongest => STRENGTH_ORDER
+                .iter()
+                .find_map(|t| file.hashes.get(*t).map(|d| (*t, d))),
+        }
+    }
+}
+
+/// A file object and the index of the source it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    /// Index into [`Dedup::sources`]
+    pub source: usize,
+    /// The file
+    pub file: FileObject,
+}
+
+/// Files sharing one digest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateGroup {
+    /// Algorithm of the digest
+    pub hash_type: HashType,
+    /// Lowercase hex digest
+    pub digest: String,
+    /// Files with this digest, in the order they were added
+    pub members: Vec<Member>,
+}
+
+impl DuplicateGroup {
+    /// Returns true if the group holds more than one file.
+    pub fn is_duplicate(&self) -> bool {
+        self.members.len() > 1
+    }
+
+    /// Returns the indices of the sources holding a copy.
+    pub fn sources(&self) -> BTreeSet<usize> {
+        self.members.iter().map(|m| m.source).collect()
+    }
+
+    /// Returns true if copies exist in more than one source.
+    pub fn spans_sources(&self) -> bool {
+        self.members
+            .iter()
+            .any(|m| m.source != self.members[0].source)
+    }
+
+    /// Returns the size of the content, from the first member that records
+    /// one.
+    pub fn filesize(&self) -> Option<u64> {
+        self.members.iter().find_map(|m| m.file.filesize)
+    }
+
+    /// Returns the bytes taken by every copy beyond the first.
+    pub fn wasted_bytes(&self) -> u64 {
+        let copies = self.members.len().saturating_sub(1) as u64;
+        copies * self.filesize().unwrap_or(0)
+    }
+}
+
+/// Summary counts of a [`Dedup`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    /// File objects added
+    pub files: u64,
+    /// File objects that had the chosen hash
+    pub hashed: u64,
+    /// Distinct digests
+    pub unique: u64,
+    /// Digests shared by more than one file
+    pub duplicate_groups: u64,
+    /// Files in duplicate groups
+    pub duplicate_files: u64,
+    /// Bytes taken by copies beyond the first of each group
+    pub wasted_bytes: u64,
+    /// Duplicate groups with copies in more than one source
+    pub cross_source_groups: u64,
+}
+
+/// Groups file objects from labelled sources by digest.
+#[derive(Debug, Clone)]
+pub struct Dedup {
+    choice: HashChoice,
+    sources: Vec<String>,
+    groups: Vec<DuplicateGroup>,
+    index: HashMap<(HashType, String), usize>,
+    files: u64,
+    hashed: u64,
+}
+
+impl Dedup {
+    /// Creates an empty deduplicator.
+    pub fn new(choice: HashChoice) -> Self {
+        Self {
+            choice,
+            sources: Vec::new(),
+            groups: Vec::new(),
+            index: HashMap::new(),
+            files: 0,
+            hashed: 0,
+        }
+    }
+
+    /// Returns the hash choice.
+    pub fn choice(&self) -> HashChoice {
+        self.choice
+    }
+
+    /// Registers a source and returns its index.
+    pub fn add_source(&mut self, label: impl Into<String>) -> usize {
+        self.sources.push(label.into());
+        self.sources.len() - 1
+    }
+
+    /// Returns the source labels, by index.
+    pub fn sources(&self) -> &[String] {
+        &self.sources
+    }
+
+    /// Adds a file from a source. Returns false if the file lacks the
+    /// chosen hash and was only counted.
+    pub fn add_file(&mut self, source: usize, file: FileObject) -> bool {
+        self.files += 1;
+        let Some((hash_type, digest)) = self.choice.select(&file) else {
+            return false;
+        };
+        self.hashed += 1;
+        let key = (hash_type, digest.trim().to_ascii_lowercase());
+        let member = Member { source, file };
+        match self.index.get(&key) {
+            Some(&i) => self.groups[i].members.push(member),
+            None => {
+                self.index.insert(key.clone(), self.groups.len());
+                self.groups.push(DuplicateGroup {
+                    hash_type: key.0,
+                    digest: key.1,
+                    members: vec![member],
+                });
+            }
+        }
+        true
+    }
+
+    /// Registers a source and adds every file object of a DFXML document.
+    ///
+    /// Returns the number of files read. Files read before a parse error
+    /// are kept, so the error can be reported and other sources still added.
+    pub fn add_dfxml<R: BufRead>(&mut self, label: impl Into<String>, input: R) -> Result<u64> {
+        self.add_reader(label, &mut DFXMLReader::from_reader(input))
+    }
+
+    /// Registers a source and adds every file object from a reader, e.g.
+    /// one built [`with_recovery`](DFXMLReader::with_recovery) to read a
+    /// malformed document. The reader's warnings are left for the caller.
+    pub fn add_reader<R: BufRead>(
+        &mut self,
+        label: impl Into<String>,
+        reader: &mut DFXMLReader<R>,
+    ) -> Result<u64> {
+        let source = self.add_source(label);
+        let mut count = 0;
+        for event in reader {
+            if let Event::FileObject(file) = event? {
+                self.add_file(source, *file);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Returns every group, including single files, in first-seen order.
+    pub fn groups(&self) -> &[DuplicateGroup] {
+        &self.groups
+    }
+
+    /// Iterates over groups with more than one file.
+    pub fn duplicates(&self) -> impl Iterator<Item = &DuplicateGroup> {
+        self.groups.iter().filter(|g| g.is_duplicate())
+    }
+
+    /// Iterates over groups with exactly one file.
+    pub fn distinct(&self) -> impl Iterator<Item = &DuplicateGroup> {
+        self.groups.iter().filter(|g| !g.is_duplicate())
+    }
+
+    /// Returns summary counts.
+    pub fn stats(&self) -> DedupStats {
+        let mut stats = DedupStats {
+            files: self.files,
+            hashed: self.hashed,
+            unique: self.groups.len() as u64,
+            ..Default::default()
+        };
+        for group in self.duplicates() {
+            stats.duplicate_groups += 1;
+            stats.duplicate_files += group.members.len() as u64;
+            stats.wasted_bytes += group.wasted_bytes();
+            if group.spans_sources() {
+                stats.cross_source_groups += 1;
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn file(name: &str, hashes: &[(HashType, &str)], size: u64) -> FileObject {
+        let mut file = FileObject::with_filename(name);
+        file.filesize = Some(size);
+        for (t, d) in hashes {
+            file.hashes.set(*t, d.to_string());
+        }
+        file
+    }
+
+    #[test]
+    fn test_choice() {
+        let f = file("a", &[(HashType::Md5, "aa"), (HashType::Sha256, "bb")], 1);
+        assert_eq!(
+            HashChoice::Strongest.select(&f),
+            Some((HashType::Sha256, "bb"))
+        );
+        assert_eq!(
+            HashChoice::Type(HashType::Md5).select(&f),
+            Some((HashType::Md5, "aa"))
+        );
+        assert_eq!(HashChoice::Type(HashType::Sha1).select(&f), None);
+    }
+
+    #[test]
+    fn test_groups_and_stats() {
+        let mut dedup = Dedup::new(HashChoice::Type(HashType::Md5));
+        let a = dedup.add_source("a");
+        let b = dedup.add_source("b");
+        dedup.add_file(a, file("x1", &[(HashType::Md5, "11")], 100));
+        dedup.add_file(a, file("x2", &[(HashType::Md5, "11")], 100));
+        dedup.add_file(b, file("x3", &[(HashType::Md5, "11")], 100));
+        dedup.add_file(a, file("y1", &[(HashType::Md5, "22")], 10));
+        dedup.add_file(a, file("y2", &[(HashType::Md5, "22")], 10));
+        dedup.add_file(b, file("z", &[(HashType::Md5, "33")], 5));
+        assert!(!dedup.add_file(b, file("none", &[], 5)));
+
+        let stats = dedup.stats();
+        assert_eq!(stats.files, 7);
+        assert_eq!(stats.hashed, 6);
+        assert_eq!(stats.unique, 3);
+        assert_eq!(stats.duplicate_groups, 2);
+        assert_eq!(stats.duplicate_files, 5);
+        assert_eq!(stats.wasted_bytes, 210);
+        assert_eq!(stats.cross_source_groups, 1);
+
+        let first = &dedup.groups()[0];
+        assert_eq!(first.sources(), BTreeSet::from([a, b]));
+        assert_eq!(dedup.distinct().count(), 1);
+    }
+
+    #[test]
+    fn test_add_dfxml_keeps_files_before_error() {
+        let good = r#"<?xml version="1.0"?>
+<dfxml version="1.0">
+  <fileobject><filename>a</filename><hashdigest type="md5">0cc175b9c0f1b6a831c399e269772661</hashdigest></fileobject>
+</dfxml>"#;
+        let broken = r#"<?xml version="1.0"?>
+<dfxml version="1.0">
+  <fileobject><filename>b</filename><hashdigest type="md5">0CC175B9C0F1B6A831C399E269772661</hashdigest></fileobject>
+  <fileobject><filename>c</filename></wrong>"#;
+
+        let mut dedup = Dedup::new(HashChoice::Strongest);
+        assert_eq!(dedup.add_dfxml("good", Cursor::new(good)).unwrap(), 1);
+        assert!(dedup.add_dfxml("broken", Cursor::new(broken)).is_err());
+        assert_eq!(dedup.stats().files, 2);
+
+        assert_eq!(dedup.sources(), ["good", "broken"]);
+        assert_eq!(dedup.stats().cross_source_groups, 1);
+
+        let mut reader = DFXMLReader::from_reader(Cursor::new(broken)).with_recovery(true);
+        assert_eq!(dedup.add_reader("recovered", &mut reader).unwrap(), 1);
+        assert_eq!(reader.warnings().len(), 3);
+    }
+}
@@ -0,0 +1,637 @@
+//! Differential DFXML.
+//!
+//! This module compares two DFXML documents (for example, two fiwalk runs over
+//! disk images taken at different times) and produces a differential document
+//! whose file objects are annotated with what happened to them between the two.
+//! It is a port of the `make_differential_dfxml` logic from the Python DFXML
+//! library.
+//!
+//! Files are paired across the two documents in three passes:
+//!
+//! 1. By partition and inode number
+//! 2. By partition and filename
+//! 3. By hash value, to detect renamed files whose inode also changed
+//!
+//! Paired files that differ are emitted with their `original_fileobject` set to
+//! the file from the old document and `diffs` set to the differing properties.
+//! Unpaired files are emitted as new or deleted.
+//!
+//! # Example
+//!
+//! ```rust
+//! use dfxml_rs::diff::{self, ANNO_DELETED, ANNO_NEW};
+//! use dfxml_rs::objects::{DFXMLObject, FileObject};
+//!
+//! let mut old = DFXMLObject::new();
+//! old.append_file(FileObject::with_filename("removed.txt"));
+//!
+//! let mut new = DFXMLObject::new();
+//! new.append_file(FileObject::with_filename("added.txt"));
+//!
+//! let delta = diff::diff(&old, &new);
+//! for file in delta.iter_files() {
+//!     if file.annos.contains(ANNO_NEW) {
+//!         println!("new: {:?}", file.filename);
+//!     } else if file.annos.contains(ANNO_DELETED) {
+//!         println!("deleted: {:?}", file.filename);
+//!     }
+//! }
+//! ```
+
+use crate::objects::{
+    DFXMLObject, DiskImageObject, FileObject, HashType, PartitionObject, PartitionSystemObject,
+    VolumeObject,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Annotation for a file or volume that only exists in the new document.
+pub const ANNO_NEW: &str = "new";
+
+/// Annotation for a file or volume that only exists in the old document.
+pub const ANNO_DELETED: &str = "deleted";
+
+/// Annotation for a file whose name changed.
+pub const ANNO_RENAMED: &str = "renamed";
+
+/// Annotation for a file whose content (size or hashes) changed.
+pub const ANNO_MODIFIED: &str = "modified";
+
+/// Annotation for a file or volume whose metadata changed.
+pub const ANNO_CHANGED: &str = "changed";
+
+/// Annotation for a file that was paired but did not change.
+pub const ANNO_MATCHED: &str = "matched";
+
+/// Properties whose difference indicates changed file content.
+const CONTENT_PROPERTIES: [&str; 2] = ["filesize", "hashes"];
+
+/// Hash types in the order they are tried when pairing renamed files.
+const HASH_PREFERENCE: [HashType; 7] = [
+    HashType::Sha512,
+    HashType::Sha384,
+    HashType::Sha256,
+    HashType::Sha224,
+    HashType::Sha1,
+    HashType::Md6,
+    HashType::Md5,
+];
+
+/// Configuration options for the differ.
+#[derive(Debug, Clone, Default)]
+pub struct DiffConfig {
+    /// Whether to include files that did not change in the output
+    pub retain_unchanged: bool,
+    /// Whether to annotate retained unchanged files as matched
+    pub annotate_matches: bool,
+    /// Properties to ignore when comparing paired files
+    pub ignore_properties: HashSet<String>,
+}
+
+impl DiffConfig {
+    /// Creates a new configuration with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether unchanged files are retained in the output.
+    pub fn with_retain_unchanged(mut self, retain: bool) -> Self {
+        self.retain_unchanged = retain;
+        self
+    }
+
+    /// Sets whether retained unchanged files are annotated as matched.
+    pub fn with_annotate_matches(mut self, annotate: bool) -> Self {
+        self.annotate_matches = annotate;
+        self
+    }
+
+    /// Adds a property to ignore when comparing files.
+    pub fn ignore(mut self, property: impl Into<String>) -> Self {
+        self.ignore_properties.insert(property.into());
+        self
+    }
+}
+
+/// Compares DFXML documents and builds differential documents.
+pub struct Differ {
+    config: DiffConfig,
+}
+
+impl Differ {
+    /// Creates a new differ with default configuration.
+    pub fn new() -> Self {
+        Self {
+            config: DiffConfig::default(),
+        }
+    }
+
+    /// Creates a new differ with the specified configuration.
+    pub fn with_config(config: DiffConfig) -> Self {
+        Self { config }
+    }
+
+    /// Compares two documents, returning a differential document.
+    ///
+    /// The properties listed in `diff_file_ignores` of either input document
+    /// are ignored in addition to those in the configuration, and the union is
+    /// recorded in the `diff_file_ignores` of the result.
+    pub fn diff(&self, old: &DFXMLObject, new: &DFXMLObject) -> DFXMLObject {
+        let ignores: HashSet<String> = self
+            .config
+            .ignore_properties
+            .iter()
+            .chain(old.diff_file_ignores.iter())
+            .chain(new.diff_file_ignores.iter())
+            .cloned()
+            .collect();
+
+        let old_scopes = Scopes::collect(old);
+        let new_scopes = Scopes::collect(new);
+
+        let mut result = DFXMLObject::new();
+        result.sources = old.sources.iter().chain(&new.sources).cloned().collect();
+        result.diff_file_ignores = ignores.clone();
+
+        // Pair volumes, then lay out one output volume per volume of either document
+        let volume_pairs = pair_volumes(&old_scopes.volumes, &new_scopes.volumes);
+        let mut out_volumes: Vec<VolumeObject> = Vec::new();
+        let mut new_slot = vec![0; new_scopes.volumes.len()];
+        let mut old_slot = vec![0; old_scopes.volumes.len()];
+
+        for (new_idx, new_vol) in new_scopes.volumes.iter().enumerate() {
+            let mut vol = volume_shell(new_vol);
+            match volume_pairs.get(&new_idx) {
+                Some(&old_idx) => {
+                    let old_vol = old_scopes.volumes[old_idx];
+                    let diffs = old_vol.compare_to(new_vol);
+                    if !diffs.is_empty() {
+                        vol.annos.insert(ANNO_CHANGED.to_string());
+                        vol.diffs = diffs;
+                        vol.original_volume = Some(Box::new(volume_shell(old_vol)));
+                    }
+                    old_slot[old_idx] = out_volumes.len();
+                }
+                None => {
+                    vol.annos.insert(ANNO_NEW.to_string());
+                }
+            }
+            new_slot[new_idx] = out_volumes.len();
+            out_volumes.push(vol);
+        }
+
+        let paired_old: HashSet<usize> = volume_pairs.values().copied().collect();
+        for (old_idx, old_vol) in old_scopes.volumes.iter().enumerate() {
+            if !paired_old.contains(&old_idx) {
+                let mut vol = volume_shell(old_vol);
+                vol.annos.insert(ANNO_DELETED.to_string());
+                old_slot[old_idx] = out_volumes.len();
+                out_volumes.push(vol);
+            }
+        }
+
+        // Pair files and place each output file in its output volume
+        let file_pairs = pair_files(&old_scopes.files, &new_scopes.files);
+        let mut volume_files: Vec<Vec<FileObject>> = vec![Vec::new(); out_volumes.len()];
+        let mut root_files: Vec<FileObject> = Vec::new();
+
+        let mut place = |scope: Option<usize>, slots: &[usize], file: FileObject| match scope {
+            Some(idx) => volume_files[slots[idx]].push(file),
+            None => root_files.push(file),
+        };
+
+        let mut paired_old_files = HashSet::new();
+        for (new_idx, (scope, new_file)) in new_scopes.files.iter().enumerate() {
+            let out = match file_pairs.get(&new_idx) {
+                Some(&old_idx) => {
+                    paired_old_files.insert(old_idx);
+                    let old_file = old_scopes.files[old_idx].1;
+                    self.annotate_pair(old_file, new_file, &ignores)
+                }
+                None => {
+                    let mut file = (*new_file).clone();
+                    file.annos.insert(ANNO_NEW.to_string());
+                    Some(file)
+                }
+            };
+            if let Some(file) = out {
+                place(*scope, &new_slot, file);
+            }
+        }
+
+        for (old_idx, (scope, old_file)) in old_scopes.files.iter().enumerate() {
+            if !paired_old_files.contains(&old_idx) {
+                let mut file = (*old_file).clone();
+                file.annos.insert(ANNO_DELETED.to_string());
+                place(*scope, &old_slot, file);
+            }
+        }
+
+        for (mut vol, files) in out_volumes.into_iter().zip(volume_files) {
+            for file in files {
+                vol.append_file(file);
+            }
+            result.append_volume(vol);
+        }
+        for file in root_files {
+            result.append_file(file);
+        }
+
+        result
+    }
+
+    /// Builds the output file for a pair, or `None` if it should be dropped.
+    fn annotate_pair(
+        &self,
+        old: &FileObject,
+        new: &FileObject,
+        ignores: &HashSet<String>,
+    ) -> Option<FileObject> {
+        let mut diffs = old.compare_to(new);
+        diffs.retain(|d| !ignores.contains(d));
+
+        let mut file = new.clone();
+        if diffs.is_empty() {
+            if !self.config.retain_unchanged {
+                return None;
+            }
+            if self.config.annotate_matches {
+                file.annos.insert(ANNO_MATCHED.to_string());
+            }
+            return Some(file);
+        }
+
+        if diffs.contains("filename") {
+            file.annos.insert(ANNO_RENAMED.to_string());
+        }
+        if CONTENT_PROPERTIES.iter().any(|p| diffs.contains(*p)) {
+            file.annos.insert(ANNO_MODIFIED.to_string());
+        }
+        if diffs
+            .iter()
+            .any(|d| d != "filename" && !CONTENT_PROPERTIES.contains(&d.as_str()))
+        {
+            file.annos.insert(ANNO_CHANGED.to_string());
+        }
+
+        file.diffs = diffs;
+        file.original_fileobject = Some(Box::new(old.clone()));
+        Some(file)
+    }
+}
+
+impl Default for Differ {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Convenience function to diff two documents with default configuration.
+pub fn diff(old: &DFXMLObject, new: &DFXMLObject) -> DFXMLObject {
+    Differ::new().diff(old, new)
+}
+
+/// Files and volumes of a document, with each file tagged by the index of
+/// its nearest enclosing volume (`None` for files outside any volume).
+struct Scopes<'a> {
+    volumes: Vec<&'a VolumeObject>,
+    files: Vec<(Option<usize>, &'a FileObject)>,
+}
+
+impl<'a> Scopes<'a> {
+    fn collect(doc: &'a DFXMLObject) -> Self {
+        let mut scopes = Self {
+            volumes: Vec::new(),
+            files: Vec::new(),
+        };
+        for di in doc.disk_images() {
+            scopes.visit_disk_image(di, None);
+        }
+        for ps in doc.partition_systems() {
+            scopes.visit_partition_system(ps, None);
+        }
+        for p in doc.partitions() {
+            scopes.visit_partition(p, None);
+        }
+        for v in doc.volumes() {
+            scopes.visit_volume(v);
+        }
+        scopes.push_files(doc.files(), None);
+        scopes
+    }
+
+    fn push_files(&mut self, files: impl Iterator<Item = &'a FileObject>, scope: Option<usize>) {
+        self.files.extend(files.map(|f| (scope, f)));
+    }
+
+    fn visit_volume(&mut self, vol: &'a VolumeObject) {
+        let scope = Some(self.volumes.len());
+        self.volumes.push(vol);
+        for di in vol.disk_images() {
+            self.visit_disk_image(di, scope);
+        }
+        for v in vol.volumes() {
+            self.visit_volume(v);
+        }
+        self.push_files(vol.files(), scope);
+    }
+
+    fn visit_disk_image(&mut self, di: &'a DiskImageObject, scope: Option<usize>) {
+        for ps in di.partition_systems() {
+            self.visit_partition_system(ps, scope);
+        }
+        for p in di.partitions() {
+            self.visit_partition(p, scope);
+        }
+        for v in di.volumes() {
+            self.visit_volume(v);
+        }
+        self.push_files(di.files(), scope);
+    }
+
+    fn visit_partition_system(&mut self, ps: &'a PartitionSystemObject, scope: Option<usize>) {
+        for p in ps.partitions() {
+            self.visit_partition(p, scope);
+        }
+        self.push_files(ps.files(), scope);
+    }
+
+    fn visit_partition(&mut self, p: &'a PartitionObject, scope: Option<usize>) {
+        for ps in p.partition_systems() {
+            self.visit_partition_system(ps, scope);
+        }
+        for sub in p.partitions() {
+            self.visit_partition(sub, scope);
+        }
+        for v in p.volumes() {
+            self.visit_volume(v);
+        }
+        self.push_files(p.files(), scope);
+    }
+}
+
+/// Copies a volume's own properties, without its children.
+fn volume_shell(vol: &VolumeObject) -> VolumeObject {
+    let mut shell = VolumeObject::new();
+    shell.partition_offset = vol.partition_offset;
+    shell.sector_size = vol.sector_size;
+    shell.block_size = vol.block_size;
+    shell.block_count = vol.block_count;
+    shell.first_block = vol.first_block;
+    shell.last_block = vol.last_block;
+    shell.ftype = vol.ftype;
+    shell.ftype_str = vol.ftype_str.clone();
+    shell.allocated_only = vol.allocated_only;
+    shell.error = vol.error.clone();
+    shell.byte_runs = vol.byte_runs.clone();
+    shell.externals = vol.externals.clone();
+    shell
+}
+
+/// Pairs volumes by partition offset, in document order.
+///
+/// Returns a map from new volume index to old volume index.
+fn pair_volumes(old: &[&VolumeObject], new: &[&VolumeObject]) -> HashMap<usize, usize> {
+    let mut by_offset: HashMap<Option<u64>, Vec<usize>> = HashMap::new();
+    for (idx, vol) in old.iter().enumerate().rev() {
+        by_offset.entry(vol.partition_offset).or_default().push(idx);
+    }
+
+    let mut pairs = HashMap::new();
+    for (new_idx, vol) in new.iter().enumerate() {
+        if let Some(old_idx) = by_offset
+            .get_mut(&vol.partition_offset)
+            .and_then(|v| v.pop())
+        {
+            pairs.insert(new_idx, old_idx);
+        }
+    }
+    pairs
+}
+
+/// Pairs files across documents by inode, then filename, then hash.
+///
+/// Returns a map from new file index to old file index.
+fn pair_files(
+    old: &[(Option<usize>, &FileObject)],
+    new: &[(Option<usize>, &FileObject)],
+) -> HashMap<usize, usize> {
+    let mut pairs: HashMap<usize, usize> = HashMap::new();
+    let mut taken = vec![false; old.len()];
+
+    // Pass 1: partition and inode. Among candidates sharing an inode (hard
+    // links, reallocated inodes), prefer the one with the same filename.
+    let mut by_inode: HashMap<(Option<u32>, u64), Vec<usize>> = HashMap::new();
+    for (idx, (_, file)) in old.iter().enumerate() {
+        if let Some(inode) = file.inode {
+            by_inode
+                .entry((file.partition, inode))
+                .or_default()
+                .push(idx);
+        }
+    }
+    for (new_idx, (_, file)) in new.iter().enumerate() {
+        let Some(inode) = file.inode else {
+            continue;
+        };
+        let Some(candidates) = by_inode.get(&(file.partition, inode)) else {
+            continue;
+        };
+        let available = || candidates.iter().copied().filter(|&i| !taken[i]);
+        let chosen = available()
+            .find(|&i| old[i].1.filename == file.filename)
+            .or_else(|| available().next());
+        if let Some(old_idx) = chosen {
+            taken[old_idx] = true;
+            pairs.insert(new_idx, old_idx);
+        }
+    }
+
+    // Pass 2: partition and filename
+    let mut by_name: HashMap<(Option<u32>, &str), Vec<usize>> = HashMap::new();
+    for (idx, (_, file)) in old.iter().enumerate().rev() {
+        if taken[idx] {
+            continue;
+        }
+        if let Some(ref name) = file.filename {
+            by_name
+                .entry((file.partition, name.as_str()))
+                .or_default()
+                .push(idx);
+        }
+    }
+    for (new_idx, (_, file)) in new.iter().enumerate() {
+        if pairs.contains_key(&new_idx) {
+            continue;
+        }
+        let Some(ref name) = file.filename else {
+            continue;
+        };
+        if let Some(candidates) = by_name.get_mut(&(file.partition, name.as_str())) {
+            while let Some(old_idx) = candidates.pop() {
+                if !taken[old_idx] {
+                    taken[old_idx] = true;
+                    pairs.insert(new_idx, old_idx);
+                    break;
+                }
+            }
+        }
+    }
+
+    // Pass 3: content hash, for renames that also moved to a new inode
+    let mut by_hash: HashMap<(HashType, &str), Vec<usize>> = HashMap::new();
+    for (idx, (_, file)) in old.iter().enumerate().rev() {
+        if taken[idx] {
+            continue;
+        }
+        for (hash_type, value) in file.hashes.iter() {
+            by_hash.entry((hash_type, value)).or_default().push(idx);
+        }
+    }
+    for (new_idx, (_, file)) in new.iter().enumerate() {
+        if pairs.contains_key(&new_idx) {
+            continue;
+        }
+        for hash_type in HASH_PREFERENCE {
+            let Some(value) = file.hashes.get(hash_type) else {
+                continue;
+            };
+            let Some(candidates) = by_hash.get_mut(&(hash_type, value)) else {
+                continue;
+            };
+            candidates.retain(|&i| !taken[i]);
+            if let Some(old_idx) = candidates.pop() {
+                taken[old_idx] = true;
+                pairs.insert(new_idx, old_idx);
+                break;
+            }
+        }
+    }
+
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, inode: u64, size: u64, md5: &str) -> FileObject {
+        let mut f = FileObject::with_filename(name);
+        f.inode = Some(inode);
+        f.filesize = Some(size);
+        f.hashes.set(HashType::Md5, md5.to_string());
+        f
+    }
+
+    fn doc_with(files: Vec<FileObject>) -> DFXMLObject {
+        let mut doc = DFXMLObject::new();
+        let mut vol = VolumeObject::with_ftype("ext4");
+        vol.partition_offset = Some(1048576);
+        for f in files {
+            vol.append_file(f);
+        }
+        doc.append_volume(vol);
+        doc
+    }
+
+    fn find<'a>(doc: &'a DFXMLObject, name: &str) -> &'a FileObject {
+        doc.iter_files()
+            .find(|f| f.filename.as_deref() == Some(name))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_diff_new_and_deleted() {
+        let old = doc_with(vec![file("a.txt", 10, 1, "aa")]);
+        let new = doc_with(vec![file("b.txt", 11, 1, "bb")]);
+
+        let delta = diff(&old, &new);
+        assert_eq!(delta.volume_count(), 1);
+        assert!(find(&delta, "a.txt").annos.contains(ANNO_DELETED));
+        assert!(find(&delta, "b.txt").annos.contains(ANNO_NEW));
+    }
+
+    #[test]
+    fn test_diff_unchanged_dropped_by_default() {
+        let old = doc_with(vec![file("a.txt", 10, 1, "aa")]);
+        let new = doc_with(vec![file("a.txt", 10, 1, "aa")]);
+
+        assert_eq!(diff(&old, &new).iter_files().count(), 0);
+
+        let config = DiffConfig::new()
+            .with_retain_unchanged(true)
+            .with_annotate_matches(true);
+        let delta = Differ::with_config(config).diff(&old, &new);
+        assert!(find(&delta, "a.txt").annos.contains(ANNO_MATCHED));
+    }
+
+    #[test]
+    fn test_diff_modified_and_changed() {
+        let old = doc_with(vec![file("a.txt", 10, 1, "aa")]);
+        let mut changed = file("a.txt", 10, 2, "ab");
+        changed.uid = Some(1000);
+        let new = doc_with(vec![changed]);
+
+        let delta = diff(&old, &new);
+        let f = find(&delta, "a.txt");
+        assert!(f.annos.contains(ANNO_MODIFIED));
+        assert!(f.annos.contains(ANNO_CHANGED));
+        assert!(!f.annos.contains(ANNO_RENAMED));
+        assert!(f.diffs.contains("filesize"));
+        assert!(f.diffs.contains("uid"));
+        assert_eq!(f.original_fileobject.as_ref().unwrap().filesize, Some(1));
+    }
+
+    #[test]
+    fn test_diff_renamed_by_inode_and_hash() {
+        let old = doc_with(vec![
+            file("old_inode.txt", 10, 1, "aa"),
+            file("old_hash.txt", 20, 1, "cc"),
+        ]);
+        let new = doc_with(vec![
+            file("new_inode.txt", 10, 1, "aa"),
+            file("new_hash.txt", 30, 1, "cc"),
+        ]);
+
+        let delta = diff(&old, &new);
+        assert_eq!(delta.iter_files().count(), 2);
+
+        let by_inode = find(&delta, "new_inode.txt");
+        assert!(by_inode.annos.contains(ANNO_RENAMED));
+        assert!(!by_inode.annos.contains(ANNO_MODIFIED));
+
+        let by_hash = find(&delta, "new_hash.txt");
+        assert!(by_hash.annos.contains(ANNO_RENAMED));
+        assert_eq!(
+            by_hash.original_fileobject.as_ref().unwrap().filename,
+            Some("old_hash.txt".to_string())
+        );
+    }
+
+    #[test]
+    fn test_diff_respects_ignores() {
+        let mut old = doc_with(vec![file("a.txt", 10, 1, "aa")]);
+        let mut f = file("a.txt", 10, 1, "aa");
+        f.uid = Some(1000);
+        let new = doc_with(vec![f]);
+
+        old.diff_file_ignores.insert("uid".to_string());
+        let delta = diff(&old, &new);
+        assert_eq!(delta.iter_files().count(), 0);
+        assert!(delta.diff_file_ignores.contains("uid"));
+    }
+
+    #[test]
+    fn test_diff_volume_annotations() {
+        let old = doc_with(vec![]);
+        let mut new = doc_with(vec![]);
+        let mut vol = VolumeObject::with_ftype("fat32");
+        vol.partition_offset = Some(512);
+        new.append_volume(vol);
+
+        let delta = diff(&old, &new);
+        let vols: Vec<_> = delta.volumes().collect();
+        assert_eq!(vols.len(), 2);
+        assert!(vols[0].annos.is_empty());
+        assert!(vols[1].annos.contains(ANNO_NEW));
+    }
+}
@@ -2,6 +2,8 @@
 
 use thiserror::Error;
 
+use crate::reader::Position;
+
 /// Errors that can occur when working with DFXML data.
 #[derive(Error, Debug)]
 pub enum Error {
@@ -58,9 +60,82 @@ pub enum Error {
     #[error("Invalid facet value: {0}")]
     InvalidFacet(String),
 
+    /// Malformed body file line
+    #[error("Invalid body file line: {0}")]
+    InvalidBodyfile(String),
+
+    /// Malformed hash set entry or unrecognized hash set file
+    #[error("Invalid hash set: {0}")]
+    InvalidHashSet(String),
+
+    /// Malformed index sidecar, or an offset that is not a file object
+    #[error("Invalid index: {0}")]
+    InvalidIndex(String),
+
+    /// Unrecognized or corrupt file system in a disk image
+    #[error("Invalid file system: {0}")]
+    InvalidFilesystem(String),
+
+    /// Missing or corrupt partition table in a disk image
+    #[error("Invalid partition table: {0}")]
+    InvalidPartitionTable(String),
+
+    /// Unknown compression format name
+    #[error("Invalid compression: {0}")]
+    Compression(String),
+
+    /// JSON document tagged with a schema this crate does not read
+    #[error("Unsupported JSON schema: {0}")]
+    UnsupportedSchema(String),
+
+    /// Unknown lint rule or severity name
+    #[error("Invalid lint option: {0}")]
+    InvalidLintOption(String),
+
+    /// Streaming writer used out of order (e.g. closing a container that is not open)
+    #[error("Invalid writer state: {0}")]
+    WriterState(String),
+
+    /// JSON conversion error (requires `serde` feature)
+    #[cfg(feature = "serde")]
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// SQLite error reading an NSRL RDSv3 database (requires `nsrl` feature)
+    #[cfg(feature = "nsrl")]
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
     /// XSD validation error (requires `validation` feature)
     #[error("Validation error: {0}")]
     Validation(String),
+
+    /// An error while reading a document, with where in the input it occurred
+    #[error("{source} at {position}")]
+    AtPosition {
+        /// Start of the element (or markup) that caused the error
+        position: Position,
+        /// The underlying error
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Returns the input position of the error, if known.
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            Error::AtPosition { position, .. } => Some(*position),
+            _ => None,
+        }
+    }
+
+    /// Returns the error without its position.
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::AtPosition { source, .. } => source.inner(),
+            other => other,
+        }
+    }
 }
 
 /// Result type alias for DFXML operations.
@@ -0,0 +1,382 @@
+//! File content extraction from raw disk images.
+//!
+//! A [`ContentReader`] uses a file object's data byte runs to read the file's
+//! logical content from a raw image (or any `Read + Seek` source):
+//!
+//! - Runs with an `img_offset` are read from that image offset.
+//! - Runs with only an `fs_offset` are read relative to the partition offset
+//!   of the enclosing volume.
+//! - Runs with a `fill` byte produce that byte without touching the image.
+//! - Gaps between runs (sparse regions) read as zeros.
+//!
+//! The content is truncated (or zero-extended) to the file's `filesize`
+//! when it is known.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use dfxml_rs::extract::ContentReader;
+//! use dfxml_rs::reader::parse;
+//! use std::fs::File;
+//! use std::io::{self, BufReader};
+//!
+//! let doc = parse(BufReader::new(File::open("fiwalk.dfxml").unwrap())).unwrap();
+//! for vol in doc.volumes() {
+//!     let offset = vol.partition_offset.unwrap_or(0);
+//!     for file in vol.files() {
+//!         let image = File::open("disk.raw").unwrap();
+//!         let mut content = ContentReader::with_partition_offset(image, file, offset).unwrap();
+//!         io::copy(&mut content, &mut io::sink()).unwrap();
+//!     }
+//! }
+//! ```
+
+use crate::error::{Error, Result};
+use crate::objects::FileObject;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Where the bytes of a segment come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    /// Read from this absolute image offset
+    Image(u64),
+    /// Repeat a fill byte
+    Fill(u8),
+}
+
+/// A contiguous range of the logical file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    file_offset: u64,
+    len: u64,
+    source: Source,
+}
+
+impl Segment {
+    /// Returns the file offset after the segment, which [`segments`] has
+    /// checked fits in a `u64`.
+    fn end(&self) -> u64 {
+        self.file_offset.saturating_add(self.len)
+    }
+}
+
+/// A reader over the logical content of a file in a disk image.
+///
+/// `ContentReader` implements [`Read`] and [`Seek`]; positions are logical
+/// file offsets.
+#[derive(Debug)]
+pub struct ContentReader<R: Read + Seek> {
+    image: R,
+    segments: Vec<Segment>,
+    size: u64,
+    pos: u64,
+}
+
+impl<R: Read + Seek> ContentReader<R> {
+    /// Creates a reader for a file whose byte runs use image offsets.
+    ///
+    /// Runs with only a file system offset are read as if the file system
+    /// started at the beginning of the image.
+    pub fn new(image: R, file: &FileObject) -> Result<Self> {
+        Self::with_partition_offset(image, file, 0)
+    }
+
+    /// Creates a reader, resolving file system offsets against the
+    /// partition offset of the enclosing volume.
+    pub fn with_partition_offset(
+        image: R,
+        file: &FileObject,
+        partition_offset: u64,
+    ) -> Result<Self> {
+        let segments = segments(file, partition_offset)?;
+        let size = file
+            .filesize
+            .unwrap_or_else(|| segments.last().map_or(0, Segment::end));
+        Ok(Self {
+            image,
+            segments,
+            size,
+            pos: 0,
+        })
+    }
+
+    /// Returns the logical size of the content.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Consumes the reader, returning the underlying image.
+    pub fn into_inner(self) -> R {
+        self.image
+    }
+}
+
+impl<R: Read + Seek> Read for ContentReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+        let remaining = self.size - self.pos;
+
+        // Segments are sorted and do not overlap
+        let index = self.segments.partition_point(|s| s.end() <= self.pos);
+        let (available, source) = match self.segments.get(index) {
+            Some(seg) if seg.file_offset <= self.pos => {
+                let skip = self.pos - seg.file_offset;
+                let source = match seg.source {
+                    Source::Image(offset) => match offset.checked_add(skip) {
+                        Some(offset) => Source::Image(offset),
+                        None => {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                format!("image offset {} + {} overflows", offset, skip),
+                            ))
+                        }
+                    },
+                    fill => fill,
+                };
+                (seg.end() - self.pos, source)
+            }
+            // Sparse gap up to the next segment, or to the end of the file
+            Some(seg) => (seg.file_offset - self.pos, Source::Fill(0)),
+            None => (remaining, Source::Fill(0)),
+        };
+
+        let want = available.min(remaining).min(buf.len() as u64) as usize;
+        let n = match source {
+            Source::Fill(byte) => {
+                buf[..want].fill(byte);
+                want
+            }
+            Source::Image(offset) => {
+                self.image.seek(SeekFrom::Start(offset))?;
+                let n = self.image.read(&mut buf[..want])?;
+                if n == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!("image ends before offset {}", offset),
+                    ));
+                }
+                n
+            }
+        };
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for ContentReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.size.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        match target {
+            Some(target) => {
+                self.pos = target;
+                Ok(target)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// Lays out a file's data byte runs as sorted, non-overlapping segments.
+fn segments(file: &FileObject, partition_offset: u64) -> Result<Vec<Segment>> {
+    let Some(runs) = file.byte_runs() else {
+        return Ok(Vec::new());
+    };
+
+    let mut segments = Vec::with_capacity(runs.len());
+    let mut next_offset = 0;
+    for run in runs.iter() {
+        let file_offset = run.file_offset.unwrap_or(next_offset);
+        let len = run.len.ok_or_else(|| {
+            Error::InvalidByteRun(format!("run at file offset {} has no length", file_offset))
+        })?;
+        if run.uncompressed_len.is_some_and(|u| u != len) {
+            return Err(Error::InvalidByteRun(format!(
+                "run at file offset {} is compressed, which is not supported",
+                file_offset
+            )));
+        }
+
+        let overflow = || {
+            Error::InvalidByteRun(format!(
+                "run at file offset {} with length {} overflows",
+                file_offset, len
+            ))
+        };
+        let source = match (run.fill, run.img_offset, run.fs_offset) {
+            (Some(fill), _, _) => Source::Fill(fill),
+            (None, Some(img), _) => Source::Image(img),
+            (None, None, Some(fs)) => {
+                Source::Image(partition_offset.checked_add(fs).ok_or_else(overflow)?)
+            }
+            (None, None, None) => {
+                return Err(Error::InvalidByteRun(format!(
+                    "run at file offset {} has no image or file system offset",
+                    file_offset
+                )))
+            }
+        };
+        if matches!(source, Source::Image(offset) if offset.checked_add(len).is_none()) {
+            return Err(overflow());
+        }
+
+        next_offset = file_offset.checked_add(len).ok_or_else(overflow)?;
+        if len > 0 {
+            segments.push(Segment {
+                file_offset,
+                len,
+                source,
+            });
+        }
+    }
+
+    // Runs are normally in file order; later runs lose any overlap
+    segments.sort_by_key(|s| s.file_offset);
+    let mut laid_out: Vec<Segment> = Vec::with_capacity(segments.len());
+    for mut seg in segments {
+        if let Some(prev) = laid_out.last() {
+            let overlap = prev.end().saturating_sub(seg.file_offset);
+            if overlap >= seg.len {
+                continue;
+            }
+            seg.file_offset += overlap;
+            seg.len -= overlap;
+            if let Source::Image(offset) = seg.source {
+                seg.source = Source::Image(offset + overlap);
+            }
+        }
+        laid_out.push(seg);
+    }
+    Ok(laid_out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{ByteRun, ByteRuns};
+    use std::io::Cursor;
+
+    fn image() -> Cursor<Vec<u8>> {
+        Cursor::new((0..=255u8).cycle().take(4096).collect())
+    }
+
+    fn file_with_runs(runs: Vec<ByteRun>, filesize: Option<u64>) -> FileObject {
+        let mut brs = ByteRuns::new();
+        for run in runs {
+            brs.push(run);
+        }
+        let mut file = FileObject::with_filename("f");
+        file.filesize = filesize;
+        file.set_byte_runs(brs);
+        file
+    }
+
+    fn read_all(mut reader: ContentReader<Cursor<Vec<u8>>>) -> Vec<u8> {
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_img_offset_runs_and_truncation() {
+        let file = file_with_runs(
+            vec![
+                ByteRun::with_img_offset(10, 4),
+                ByteRun::with_img_offset(100, 4),
+            ],
+            Some(6),
+        );
+        let content = read_all(ContentReader::new(image(), &file).unwrap());
+        assert_eq!(content, [10, 11, 12, 13, 100, 101]);
+    }
+
+    #[test]
+    fn test_fill_sparse_and_fs_offset() {
+        let fill = ByteRun {
+            file_offset: Some(0),
+            len: Some(2),
+            fill: Some(0xff),
+            ..Default::default()
+        };
+        let fs = ByteRun {
+            file_offset: Some(4),
+            fs_offset: Some(8),
+            len: Some(2),
+            ..Default::default()
+        };
+        let file = file_with_runs(vec![fill, fs], Some(8));
+        let reader = ContentReader::with_partition_offset(image(), &file, 512).unwrap();
+        assert_eq!(reader.size(), 8);
+        // fill, sparse gap, partition 512 + fs 8 = image byte 520 (520 % 256 = 8), zero tail
+        assert_eq!(read_all(reader), [0xff, 0xff, 0, 0, 8, 9, 0, 0]);
+    }
+
+    #[test]
+    fn test_overflowing_runs_are_rejected() {
+        let past_end = ByteRun {
+            file_offset: Some(u64::MAX),
+            img_offset: Some(0),
+            len: Some(10),
+            ..Default::default()
+        };
+        let image_end = ByteRun::with_img_offset(u64::MAX - 5, 10);
+        let fs = ByteRun {
+            fs_offset: Some(u64::MAX - 100),
+            len: Some(10),
+            ..Default::default()
+        };
+        for (run, partition_offset) in [(past_end, 0), (image_end, 0), (fs, 512)] {
+            let file = file_with_runs(vec![run], None);
+            let err =
+                ContentReader::with_partition_offset(image(), &file, partition_offset).unwrap_err();
+            assert!(matches!(err, Error::InvalidByteRun(_)));
+        }
+    }
+
+    #[test]
+    fn test_seek_and_size_without_filesize() {
+        let run = ByteRun {
+            file_offset: Some(0),
+            img_offset: Some(200),
+            len: Some(10),
+            ..Default::default()
+        };
+        let file = file_with_runs(vec![run], None);
+        let mut reader = ContentReader::new(image(), &file).unwrap();
+        assert_eq!(reader.size(), 10);
+        reader.seek(SeekFrom::End(-2)).unwrap();
+        assert_eq!(read_all(reader), [208, 209]);
+    }
+
+    #[test]
+    fn test_unreadable_runs() {
+        let no_offset = ByteRun {
+            len: Some(4),
+            ..Default::default()
+        };
+        let file = file_with_runs(vec![no_offset], None);
+        assert!(ContentReader::new(image(), &file).is_err());
+
+        let compressed = ByteRun {
+            img_offset: Some(0),
+            len: Some(4),
+            uncompressed_len: Some(16),
+            ..Default::default()
+        };
+        let file = file_with_runs(vec![compressed], None);
+        assert!(ContentReader::new(image(), &file).is_err());
+
+        let past_end = file_with_runs(vec![ByteRun::with_img_offset(5000, 4)], None);
+        let mut reader = ContentReader::new(image(), &past_end).unwrap();
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,1245 @@
+//! ext2, ext3 and ext4 file systems.
+//!
+//! [`ExtFileSystem`] reads the superblock and group descriptors when it is
+//! opened, then walks the directory tree from the root inode. Each name
+//! becomes a [`FileObject`] carrying its inode's metadata, the inode's own
+//! location as `inode_brs`, and the file content's location as `data_brs`
+//! (from the extent tree on ext4, or the direct and indirect block map on
+//! ext2/3). Byte runs carry both `fs_offset` and `img_offset` and are cut
+//! at the file size.
+//!
+//! Besides the live tree, the walk reports:
+//!
+//! - Deleted names, recovered from the slack after directory entries, with
+//!   `alloc_name` false. Their inodes are described as they are now, so a
+//!   reused inode describes its new file.
+//! - Orphans: inodes in use (or once used and not wiped) that no name
+//!   points at, named `$OrphanFiles/OrphanFile-<inode>` as in The Sleuth
+//!   Kit, with `orphan` set.
+//!
+//! File systems with `meta_bg` group descriptors are not supported.
+
+use super::{le_u16, le_u32, read_at, FileSystem};
+use crate::error::{Error, Result};
+use crate::extract::ContentReader;
+use crate::objects::{
+    ByteRun, ByteRunFacet, ByteRunType, ByteRuns, FileObject, MetaType, NameType, Precision,
+    TimeUnit, Timestamp, TimestampName, VolumeObject,
+};
+use chrono::DateTime;
+use std::collections::HashSet;
+use std::io::{Read, Seek, SeekFrom};
+
+/// Superblock location, relative to the start of the file system.
+const SUPERBLOCK_OFFSET: u64 = 1024;
+/// Superblock magic number.
+const MAGIC: u16 = 0xef53;
+/// Root directory inode.
+const ROOT_INODE: u64 = 2;
+
+const COMPAT_HAS_JOURNAL: u32 = 0x4;
+const INCOMPAT_COMPRESSION: u32 = 0x1;
+const INCOMPAT_FILETYPE: u32 = 0x2;
+const INCOMPAT_JOURNAL_DEV: u32 = 0x8;
+const INCOMPAT_META_BG: u32 = 0x10;
+const INCOMPAT_EXTENTS: u32 = 0x40;
+const INCOMPAT_64BIT: u32 = 0x80;
+const INCOMPAT_FLEX_BG: u32 = 0x200;
+const RO_COMPAT_HUGE_FILE: u32 = 0x8;
+const RO_COMPAT_GDT_CSUM: u32 = 0x10;
+const RO_COMPAT_DIR_NLINK: u32 = 0x20;
+const RO_COMPAT_EXTRA_ISIZE: u32 = 0x40;
+const RO_COMPAT_METADATA_CSUM: u32 = 0x400;
+
+/// Group descriptor flag: the group's inode table is not initialized.
+const BG_INODE_UNINIT: u16 = 0x1;
+
+const INODE_INDEX_FL: u32 = 0x1000;
+const INODE_EXTENTS_FL: u32 = 0x80000;
+const INODE_INLINE_DATA_FL: u32 = 0x1000_0000;
+
+/// Extent tree node magic number.
+const EXTENT_MAGIC: u16 = 0xf30a;
+/// Deepest extent tree accepted; the kernel limits trees to 5 levels.
+const MAX_EXTENT_DEPTH: u16 = 5;
+/// Extent lengths above this mark uninitialized (preallocated) extents.
+const EXTENT_INIT_MAX_LEN: u16 = 32768;
+
+/// Largest directory read, which bounds the damage from a corrupt size.
+const MAX_DIRECTORY_SIZE: u64 = 64 * 1024 * 1024;
+/// Bytes of block map or extent root stored in the inode.
+const I_BLOCK_LEN: usize = 60;
+
+/// The superblock fields the walker uses.
+#[derive(Debug, Clone)]
+struct Superblock {
+    inodes_count: u32,
+    blocks_count: u64,
+    first_data_block: u32,
+    block_size: u32,
+    blocks_per_group: u32,
+    inodes_per_group: u32,
+    first_ino: u32,
+    inode_size: u16,
+    desc_size: u16,
+    feature_compat: u32,
+    feature_incompat: u32,
+    feature_ro_compat: u32,
+}
+
+impl Superblock {
+    fn parse(buf: &[u8]) -> Result<Self> {
+        if le_u16(buf, 0x38) != MAGIC {
+            return Err(Error::InvalidFilesystem(
+                "bad ext superblock magic".to_string(),
+            ));
+        }
+        let log_block_size = le_u32(buf, 0x18);
+        if log_block_size > 6 {
+            return Err(Error::InvalidFilesystem(format!(
+                "unsupported block size 2^{}",
+                log_block_size + 10
+            )));
+        }
+        let dynamic = le_u32(buf, 0x4c) >= 1;
+        let feature_incompat = if dynamic { le_u32(buf, 0x60) } else { 0 };
+        let sb = Self {
+            inodes_count: le_u32(buf, 0x00),
+            blocks_count: u64::from(le_u32(buf, 0x04))
+                | if feature_incompat & INCOMPAT_64BIT != 0 {
+                    u64::from(le_u32(buf, 0x150)) << 32
+                } else {
+                    0
+                },
+            first_data_block: le_u32(buf, 0x14),
+            block_size: 1024 << log_block_size,
+            blocks_per_group: le_u32(buf, 0x20),
+            inodes_per_group: le_u32(buf, 0x28),
+            first_ino: if dynamic { le_u32(buf, 0x54) } else { 11 },
+            inode_size: if dynamic { le_u16(buf, 0x58) } else { 128 },
+            desc_size: if feature_incompat & INCOMPAT_64BIT != 0 {
+                le_u16(buf, 0xfe)
+            } else {
+                32
+            },
+            feature_compat: if dynamic { le_u32(buf, 0x5c) } else { 0 },
+            feature_incompat,
+            feature_ro_compat: if dynamic { le_u32(buf, 0x64) } else { 0 },
+        };
+
+        if sb.blocks_per_group == 0 || sb.inodes_per_group == 0 {
+            return Err(Error::InvalidFilesystem(
+                "superblock has empty block groups".to_string(),
+            ));
+        }
+        if sb.inode_size < 128
+            || !sb.inode_size.is_power_of_two()
+            || u32::from(sb.inode_size) > sb.block_size
+        {
+            return Err(Error::InvalidFilesystem(format!(
+                "invalid inode size {}",
+                sb.inode_size
+            )));
+        }
+        if sb.desc_size < 32 || !sb.desc_size.is_power_of_two() {
+            return Err(Error::InvalidFilesystem(format!(
+                "invalid group descriptor size {}",
+                sb.desc_size
+            )));
+        }
+        let unsupported =
+            sb.feature_incompat & (INCOMPAT_COMPRESSION | INCOMPAT_JOURNAL_DEV | INCOMPAT_META_BG);
+        if unsupported != 0 {
+            return Err(Error::InvalidFilesystem(format!(
+                "unsupported incompatible features {:#x}",
+                unsupported
+            )));
+        }
+        Ok(sb)
+    }
+
+    /// Returns "ext2", "ext3" or "ext4", going by the features in use.
+    fn ftype_str(&self) -> &'static str {
+        let ext4_incompat = INCOMPAT_EXTENTS | INCOMPAT_64BIT | INCOMPAT_FLEX_BG;
+        let ext4_ro_compat = RO_COMPAT_HUGE_FILE
+            | RO_COMPAT_GDT_CSUM
+            | RO_COMPAT_DIR_NLINK
+            | RO_COMPAT_EXTRA_ISIZE
+            | RO_COMPAT_METADATA_CSUM;
+        if self.feature_incompat & ext4_incompat != 0
+            || self.feature_ro_compat & ext4_ro_compat != 0
+        {
+            "ext4"
+        } else if self.feature_compat & COMPAT_HAS_JOURNAL != 0 {
+            "ext3"
+        } else {
+            "ext2"
+        }
+    }
+
+    fn group_count(&self) -> u64 {
+        (self
+            .blocks_count
+            .saturating_sub(u64::from(self.first_data_block)))
+        .div_ceil(u64::from(self.blocks_per_group))
+    }
+
+    /// Returns true if group descriptors record uninitialized inode tables.
+    fn has_group_checksums(&self) -> bool {
+        self.feature_ro_compat & (RO_COMPAT_GDT_CSUM | RO_COMPAT_METADATA_CSUM) != 0
+    }
+}
+
+/// The group descriptor fields the walker uses.
+#[derive(Debug, Clone, Copy)]
+struct GroupDesc {
+    inode_bitmap: u64,
+    inode_table: u64,
+    flags: u16,
+    itable_unused: u32,
+}
+
+impl GroupDesc {
+    fn parse(buf: &[u8], wide: bool) -> Self {
+        let hi = |lo: u32, at: usize| {
+            u64::from(lo)
+                | if wide {
+                    u64::from(le_u32(buf, at)) << 32
+                } else {
+                    0
+                }
+        };
+        Self {
+            inode_bitmap: hi(le_u32(buf, 0x04), 0x24),
+            inode_table: hi(le_u32(buf, 0x08), 0x28),
+            flags: le_u16(buf, 0x12),
+            itable_unused: u32::from(le_u16(buf, 0x1c))
+                | if wide {
+                    u32::from(le_u16(buf, 0x32)) << 16
+                } else {
+                    0
+                },
+        }
+    }
+}
+
+/// A raw inode and where it is in the image.
+#[derive(Debug, Clone)]
+struct Inode {
+    number: u64,
+    fs_offset: u64,
+    raw: Vec<u8>,
+}
+
+impl Inode {
+    fn mode(&self) -> u16 {
+        le_u16(&self.raw, 0x00)
+    }
+
+    fn size(&self) -> u64 {
+        u64::from(le_u32(&self.raw, 0x04)) | u64::from(le_u32(&self.raw, 0x6c)) << 32
+    }
+
+    fn flags(&self) -> u32 {
+        le_u32(&self.raw, 0x20)
+    }
+
+    fn i_block(&self) -> &[u8] {
+        &self.raw[0x28..0x28 + I_BLOCK_LEN]
+    }
+
+    fn is_unused(&self) -> bool {
+        self.mode() == 0 && le_u16(&self.raw, 0x1a) == 0 && le_u32(&self.raw, 0x14) == 0
+    }
+
+    fn meta_type(&self) -> MetaType {
+        match self.mode() >> 12 {
+            0x1 => MetaType::Fifo,
+            0x2 => MetaType::CharacterDevice,
+            0x4 => MetaType::Directory,
+            0x6 => MetaType::BlockDevice,
+            0x8 => MetaType::Regular,
+            0xa => MetaType::SymbolicLink,
+            0xc => MetaType::Socket,
+            _ => MetaType::Unknown,
+        }
+    }
+
+    /// Returns the size of the fields past the 128-byte base inode.
+    fn extra_isize(&self) -> usize {
+        if self.raw.len() > 0x82 {
+            usize::from(le_u16(&self.raw, 0x80)).min(self.raw.len() - 0x80)
+        } else {
+            0
+        }
+    }
+
+    /// Decodes a timestamp, with its nanosecond and epoch extension when
+    /// the inode is large enough to hold one at `extra`.
+    fn timestamp(&self, name: TimestampName, at: usize, extra: Option<usize>) -> Option<Timestamp> {
+        let seconds = le_u32(&self.raw, at) as i32;
+        let extra = extra
+            .filter(|&e| e + 4 <= 0x80 + self.extra_isize())
+            .map(|e| le_u32(&self.raw, e));
+        if seconds == 0 && extra.unwrap_or(0) == 0 {
+            return None;
+        }
+        let (seconds, nanos, prec) = match extra {
+            Some(extra) => (
+                i64::from(seconds) + (i64::from(extra & 0x3) << 32),
+                extra >> 2,
+                Precision::new(1, TimeUnit::Nanosecond),
+            ),
+            None => (i64::from(seconds), 0, Precision::new(1, TimeUnit::Second)),
+        };
+        let time = DateTime::from_timestamp(seconds, nanos)?;
+        Some(Timestamp {
+            name: Some(name),
+            time: Some(time.fixed_offset()),
+            prec: Some(prec),
+        })
+    }
+
+    /// Returns true for a symbolic link whose target is stored in `i_block`.
+    fn is_fast_symlink(&self, block_size: u32) -> bool {
+        if self.meta_type() != MetaType::SymbolicLink
+            || self.flags() & (INODE_EXTENTS_FL | INODE_INLINE_DATA_FL) != 0
+        {
+            return false;
+        }
+        // i_blocks counts 512-byte sectors, including any extended attribute block
+        let sectors = le_u32(&self.raw, 0x1c);
+        let xattr_sectors = if le_u32(&self.raw, 0x68) != 0 {
+            block_size / 512
+        } else {
+            0
+        };
+        sectors == xattr_sectors && self.size() < I_BLOCK_LEN as u64
+    }
+}
+
+/// A contiguous range of file blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Extent {
+    logical: u64,
+    physical: u64,
+    len: u64,
+    uninit: bool,
+}
+
+/// A directory entry, live or recovered from directory slack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DirEntry {
+    inode: u64,
+    name: String,
+    file_type: u8,
+    allocated: bool,
+}
+
+/// An ext2, ext3 or ext4 file system in a raw image.
+#[derive(Debug)]
+pub struct ExtFileSystem<R: Read + Seek> {
+    image: R,
+    offset: u64,
+    sb: Superblock,
+    groups: Vec<GroupDesc>,
+    /// The most recently read inode bitmap, by group
+    bitmap: Option<(u64, Vec<u8>)>,
+}
+
+/// Returns the error for an offset computed from on-disk values that does
+/// not fit in 64 bits.
+fn overflow(what: &str) -> Error {
+    Error::InvalidFilesystem(format!("{} overflows", what))
+}
+
+/// Returns true if an ext superblock is at `offset`.
+pub fn probe<R: Read + Seek>(image: &mut R, offset: u64) -> Result<bool> {
+    let mut magic = [0u8; 2];
+    match read_at(image, offset + SUPERBLOCK_OFFSET + 0x38, &mut magic) {
+        Ok(()) => Ok(u16::from_le_bytes(magic) == MAGIC),
+        Err(Error::InvalidFilesystem(_)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+impl<R: Read + Seek> ExtFileSystem<R> {
+    /// Opens the file system that starts `offset` bytes into an image.
+    pub fn open(mut image: R, offset: u64) -> Result<Self> {
+        let mut buf = vec![0u8; 1024];
+        let sb_offset = offset
+            .checked_add(SUPERBLOCK_OFFSET)
+            .ok_or_else(|| overflow("superblock offset"))?;
+        read_at(&mut image, sb_offset, &mut buf)?;
+        let sb = Superblock::parse(&buf)?;
+
+        // The descriptor table follows the block holding the superblock
+        let table_block = u64::from(sb.first_data_block) + 1;
+        let table_offset = table_block
+            .checked_mul(u64::from(sb.block_size))
+            .and_then(|o| o.checked_add(offset))
+            .ok_or_else(|| overflow("group descriptor table offset"))?;
+        let desc_size = usize::from(sb.desc_size);
+        let too_many =
+            || Error::InvalidFilesystem(format!("too many block groups: {}", sb.group_count()));
+        let table_len = sb
+            .group_count()
+            .checked_mul(u64::from(sb.desc_size))
+            .ok_or_else(too_many)?;
+        // Check the table fits in the image before allocating it
+        let image_len = image.seek(SeekFrom::End(0))?;
+        if table_offset.saturating_add(table_len) > image_len {
+            return Err(too_many());
+        }
+        let mut table = vec![0u8; usize::try_from(table_len).map_err(|_| too_many())?];
+        read_at(&mut image, table_offset, &mut table)?;
+        let wide = sb.feature_incompat & INCOMPAT_64BIT != 0 && desc_size >= 64;
+        let groups = table
+            .chunks_exact(desc_size)
+            .map(|desc| GroupDesc::parse(desc, wide))
+            .collect();
+
+        Ok(Self {
+            image,
+            offset,
+            sb,
+            groups,
+            bitmap: None,
+        })
+    }
+
+    /// Consumes the file system, returning the underlying image.
+    pub fn into_inner(self) -> R {
+        self.image
+    }
+
+    fn block_size(&self) -> u64 {
+        u64::from(self.sb.block_size)
+    }
+
+    fn read_block(&mut self, block: u64) -> Result<Vec<u8>> {
+        if block >= self.sb.blocks_count {
+            return Err(Error::InvalidFilesystem(format!(
+                "block {} is past the end of the file system",
+                block
+            )));
+        }
+        let mut buf = vec![0u8; self.sb.block_size as usize];
+        let offset = self.image_offset(self.fs_offset(block, 0)?)?;
+        read_at(&mut self.image, offset, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Returns the group and index within the group of an inode.
+    fn locate(&self, number: u64) -> Result<(u64, u64)> {
+        if number == 0 || number > u64::from(self.sb.inodes_count) {
+            return Err(Error::InvalidFilesystem(format!(
+                "inode {} is out of range",
+                number
+            )));
+        }
+        let per_group = u64::from(self.sb.inodes_per_group);
+        Ok(((number - 1) / per_group, (number - 1) % per_group))
+    }
+
+    /// Returns the descriptor of a block group.
+    fn group(&self, group: u64) -> Result<GroupDesc> {
+        usize::try_from(group)
+            .ok()
+            .and_then(|g| self.groups.get(g))
+            .copied()
+            .ok_or_else(|| {
+                Error::InvalidFilesystem(format!("block group {} is out of range", group))
+            })
+    }
+
+    /// Returns the file system offset `within` bytes into a block.
+    fn fs_offset(&self, block: u64, within: u64) -> Result<u64> {
+        block
+            .checked_mul(self.block_size())
+            .and_then(|o| o.checked_add(within))
+            .ok_or_else(|| overflow(&format!("offset of block {}", block)))
+    }
+
+    /// Returns the image offset of a file system offset.
+    fn image_offset(&self, fs_offset: u64) -> Result<u64> {
+        fs_offset
+            .checked_add(self.offset)
+            .ok_or_else(|| overflow(&format!("image offset of {}", fs_offset)))
+    }
+
+    fn read_inode(&mut self, number: u64) -> Result<Inode> {
+        let (group, index) = self.locate(number)?;
+        let desc = self.group(group)?;
+        let inode_size = u64::from(self.sb.inode_size);
+        let fs_offset = self.fs_offset(desc.inode_table, index * inode_size)?;
+        let mut raw = vec![0u8; inode_size as usize];
+        let offset = self.image_offset(fs_offset)?;
+        read_at(&mut self.image, offset, &mut raw)?;
+        Ok(Inode {
+            number,
+            fs_offset,
+            raw,
+        })
+    }
+
+    /// Returns true if the inode bitmap marks an inode in use.
+    fn inode_allocated(&mut self, number: u64) -> Result<bool> {
+        let (group, index) = self.locate(number)?;
+        let desc = self.group(group)?;
+        if self.sb.has_group_checksums() && desc.flags & BG_INODE_UNINIT != 0 {
+            return Ok(false);
+        }
+        if self.bitmap.as_ref().is_none_or(|(g, _)| *g != group) {
+            let bitmap = self.read_block(desc.inode_bitmap)?;
+            self.bitmap = Some((group, bitmap));
+        }
+        let bitmap = &self.bitmap.as_ref().unwrap().1;
+        let byte = bitmap.get((index / 8) as usize).copied().unwrap_or(0);
+        Ok(byte & (1 << (index % 8)) != 0)
+    }
+
+    /// Returns true if an inode lies in the initialized part of its group's
+    /// inode table.
+    fn inode_initialized(&self, number: u64) -> bool {
+        if !self.sb.has_group_checksums() {
+            return true;
+        }
+        let Ok((group, index)) = self.locate(number) else {
+            return false;
+        };
+        let Ok(desc) = self.group(group) else {
+            return false;
+        };
+        desc.flags & BG_INODE_UNINIT == 0
+            && index < u64::from(self.sb.inodes_per_group.saturating_sub(desc.itable_unused))
+    }
+
+    /// Maps an inode's content to file system blocks.
+    fn extents(&mut self, inode: &Inode) -> Result<Vec<Extent>> {
+        let blocks_needed = inode.size().div_ceil(self.block_size());
+        let mut extents = Vec::new();
+        if inode.flags() & INODE_EXTENTS_FL != 0 {
+            let root = inode.i_block().to_vec();
+            self.extent_node(&root, MAX_EXTENT_DEPTH, &mut extents)?;
+        } else {
+            let i_block = inode.i_block();
+            let pointers: Vec<u64> = (0..15).map(|i| u64::from(le_u32(i_block, i * 4))).collect();
+            let mut logical = 0;
+            for &block in &pointers[..12] {
+                push_block(&mut extents, logical, block);
+                logical += 1;
+            }
+            for (level, &block) in pointers[12..].iter().enumerate() {
+                if logical >= blocks_needed {
+                    break;
+                }
+                self.indirect(
+                    block,
+                    level as u32,
+                    &mut logical,
+                    blocks_needed,
+                    &mut extents,
+                )?;
+            }
+        }
+        extents.sort_by_key(|e| e.logical);
+        Ok(extents)
+    }
+
+    /// Collects the extents under one extent tree node.
+    fn extent_node(&mut self, node: &[u8], max_depth: u16, out: &mut Vec<Extent>) -> Result<()> {
+        if node.len() < 12 || le_u16(node, 0) != EXTENT_MAGIC {
+            return Err(Error::InvalidFilesystem(
+                "bad extent header magic".to_string(),
+            ));
+        }
+        let entries = usize::from(le_u16(node, 2)).min((node.len() - 12) / 12);
+        let depth = le_u16(node, 6);
+        if depth > max_depth {
+            return Err(Error::InvalidFilesystem(format!(
+                "extent tree deeper than {} levels",
+                MAX_EXTENT_DEPTH
+            )));
+        }
+        for entry in node[12..].chunks_exact(12).take(entries) {
+            if depth == 0 {
+                let len = le_u16(entry, 4);
+                let (len, uninit) = if len > EXTENT_INIT_MAX_LEN {
+                    (len - EXTENT_INIT_MAX_LEN, true)
+                } else {
+                    (len, false)
+                };
+                out.push(Extent {
+                    logical: u64::from(le_u32(entry, 0)),
+                    physical: u64::from(le_u16(entry, 6)) << 32 | u64::from(le_u32(entry, 8)),
+                    len: u64::from(len),
+                    uninit,
+                });
+            } else {
+                let child = u64::from(le_u16(entry, 8)) << 32 | u64::from(le_u32(entry, 4));
+                let block = self.read_block(child)?;
+                self.extent_node(&block, depth - 1, out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Collects the blocks under an indirect block; `level` 0 is a single
+    /// indirect block.
+    fn indirect(
+        &mut self,
+        block: u64,
+        level: u32,
+        logical: &mut u64,
+        blocks_needed: u64,
+        out: &mut Vec<Extent>,
+    ) -> Result<()> {
+        let per_block = self.block_size() / 4;
+        if block == 0 {
+            // A hole the size of everything below this pointer
+            *logical += per_block.pow(level + 1);
+            return Ok(());
+        }
+        let buf = self.read_block(block)?;
+        for pointer in buf.chunks_exact(4) {
+            if *logical >= blocks_needed {
+                break;
+            }
+            let pointer = u64::from(le_u32(pointer, 0));
+            if level == 0 {
+                push_block(out, *logical, pointer);
+                *logical += 1;
+            } else {
+                self.indirect(pointer, level - 1, logical, blocks_needed, out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Describes an inode's content as byte runs, cut at the file size.
+    fn data_runs(&mut self, inode: &Inode) -> Result<Option<ByteRuns>> {
+        let size = inode.size();
+        match inode.meta_type() {
+            MetaType::Regular | MetaType::Directory | MetaType::SymbolicLink => {}
+            _ => return Ok(None),
+        }
+        if inode.is_fast_symlink(self.sb.block_size) {
+            return Ok(None);
+        }
+
+        let mut runs = ByteRuns::with_facet(ByteRunFacet::Data);
+        if inode.flags() & INODE_INLINE_DATA_FL != 0 {
+            let fs_offset = inode.fs_offset + 0x28;
+            runs.push(ByteRun {
+                img_offset: Some(self.offset + fs_offset),
+                fs_offset: Some(fs_offset),
+                file_offset: Some(0),
+                len: Some(size.min(I_BLOCK_LEN as u64)),
+                run_type: Some(ByteRunType::Resident),
+                ..Default::default()
+            });
+            return Ok(Some(runs));
+        }
+
+        let block_size = self.block_size();
+        for extent in self.extents(inode)? {
+            if extent.physical.saturating_add(extent.len) > self.sb.blocks_count {
+                return Err(Error::InvalidFilesystem(format!(
+                    "inode {} maps blocks past the end of the file system",
+                    inode.number
+                )));
+            }
+            let file_offset = extent.logical * block_size;
+            if file_offset >= size {
+                continue;
+            }
+            let fs_offset = self.fs_offset(extent.physical, 0)?;
+            runs.glom(ByteRun {
+                img_offset: Some(self.image_offset(fs_offset)?),
+                fs_offset: Some(fs_offset),
+                file_offset: Some(file_offset),
+                len: Some((extent.len * block_size).min(size - file_offset)),
+                fill: extent.uninit.then_some(0),
+                ..Default::default()
+            });
+        }
+        Ok(Some(runs))
+    }
+
+    /// Fills in a file object from an inode.
+    fn describe(&mut self, file: &mut FileObject, inode: &Inode) -> Result<()> {
+        let raw = &inode.raw;
+        let meta_type = inode.meta_type();
+        file.inode = Some(inode.number);
+        file.alloc_inode = Some(self.inode_allocated(inode.number)?);
+        file.meta_type = Some(meta_type);
+        file.mode = Some(u32::from(inode.mode() & 0o7777));
+        file.uid = Some(u32::from(le_u16(raw, 0x02)) | u32::from(le_u16(raw, 0x78)) << 16);
+        file.gid = Some(u32::from(le_u16(raw, 0x18)) | u32::from(le_u16(raw, 0x7a)) << 16);
+        file.nlink = Some(u32::from(le_u16(raw, 0x1a)));
+        file.filesize = Some(inode.size());
+
+        file.atime = inode.timestamp(TimestampName::Atime, 0x08, Some(0x8c));
+        file.ctime = inode.timestamp(TimestampName::Ctime, 0x0c, Some(0x84));
+        file.mtime = inode.timestamp(TimestampName::Mtime, 0x10, Some(0x88));
+        if inode.extra_isize() >= 0x18 {
+            file.crtime = inode.timestamp(TimestampName::Crtime, 0x90, Some(0x94));
+        }
+        // Inodes in use keep the orphan list in dtime
+        if file.alloc_inode == Some(false) {
+            file.dtime = inode.timestamp(TimestampName::Dtime, 0x14, None);
+        }
+
+        let mut inode_brs = ByteRuns::with_facet(ByteRunFacet::Inode);
+        inode_brs.push(ByteRun {
+            img_offset: Some(self.offset + inode.fs_offset),
+            fs_offset: Some(inode.fs_offset),
+            len: Some(raw.len() as u64),
+            ..Default::default()
+        });
+        file.inode_brs = Some(inode_brs);
+
+        match self.data_runs(inode) {
+            Ok(runs) => file.data_brs = runs,
+            Err(e) => file.error = Some(e.to_string()),
+        }
+
+        if meta_type == MetaType::SymbolicLink {
+            if inode.is_fast_symlink(self.sb.block_size) {
+                let len = inode.size() as usize;
+                file.link_target =
+                    Some(String::from_utf8_lossy(&inode.i_block()[..len]).into_owned());
+            } else if file.data_brs.is_some() {
+                match self.content(file) {
+                    Ok(target) => {
+                        file.link_target = Some(String::from_utf8_lossy(&target).into_owned())
+                    }
+                    Err(e) => file.error = Some(e.to_string()),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a file's content through its data byte runs.
+    fn content(&mut self, file: &FileObject) -> Result<Vec<u8>> {
+        if file.filesize.unwrap_or(0) > MAX_DIRECTORY_SIZE {
+            return Err(Error::InvalidFilesystem(format!(
+                "inode {} is too large to read",
+                file.inode.unwrap_or(0)
+            )));
+        }
+        let mut reader = ContentReader::new(&mut self.image, file)?;
+        let mut content = Vec::with_capacity(reader.size() as usize);
+        reader.read_to_end(&mut content)?;
+        Ok(content)
+    }
+
+    /// Reads a directory's entries, including deleted entries in slack.
+    fn read_dir(&mut self, file: &FileObject, inode: &Inode) -> Result<Vec<DirEntry>> {
+        if inode.flags() & INODE_INLINE_DATA_FL != 0 {
+            // The first four bytes of inline directory data hold the parent
+            let size = (inode.size() as usize).min(I_BLOCK_LEN);
+            return Ok(self.parse_dir_block(&inode.i_block()[4.min(size)..size], false));
+        }
+
+        let data = self.content(file)?;
+        let htree = inode.flags() & INODE_INDEX_FL != 0;
+        let mut entries = Vec::new();
+        for (index, block) in data.chunks(self.sb.block_size as usize).enumerate() {
+            // Hashed directories keep their index in what looks like slack
+            // after ".." in the first block, and in empty-looking blocks
+            let index_node = htree
+                && (index == 0
+                    || (le_u32(block, 0) == 0 && usize::from(le_u16(block, 4)) == block.len()));
+            entries.extend(self.parse_dir_block(block, !index_node));
+        }
+        Ok(entries)
+    }
+
+    /// Parses the entries of one directory block.
+    fn parse_dir_block(&self, block: &[u8], scan_slack: bool) -> Vec<DirEntry> {
+        let filetype = self.sb.feature_incompat & INCOMPAT_FILETYPE != 0;
+        let mut entries = Vec::new();
+        let mut pos = 0;
+        while pos + 8 <= block.len() {
+            let rec_len = usize::from(le_u16(block, pos + 4));
+            if rec_len < 8 || rec_len % 4 != 0 || pos + rec_len > block.len() {
+                break;
+            }
+            let end = pos + rec_len;
+            let used = match parse_entry(block, pos, end, filetype) {
+                Some((entry, used)) => {
+                    // Entries with a zero inode were deleted from the start of a block
+                    let allocated = entry.inode != 0;
+                    if entry.name != "." && entry.name != ".." {
+                        entries.push(DirEntry { allocated, ..entry });
+                    }
+                    used
+                }
+                None => 8,
+            };
+            if scan_slack {
+                let mut slack = pos + used;
+                while slack + 8 <= end {
+                    match parse_entry(block, slack, end, filetype) {
+                        Some((entry, used)) if entry.inode <= u64::from(self.sb.inodes_count) => {
+                            entries.push(DirEntry {
+                                allocated: false,
+                                ..entry
+                            });
+                            slack += used;
+                        }
+                        _ => slack += 4,
+                    }
+                }
+            }
+            pos = end;
+        }
+        entries
+    }
+}
+
+/// Parses the directory entry at `pos`, which must end by `end`, returning
+/// it and the bytes it needs.
+fn parse_entry(block: &[u8], pos: usize, end: usize, filetype: bool) -> Option<(DirEntry, usize)> {
+    let (name_len, file_type) = if filetype {
+        (usize::from(block[pos + 6]), block[pos + 7])
+    } else {
+        (usize::from(le_u16(block, pos + 6)), 0)
+    };
+    let used = (8 + name_len).next_multiple_of(4);
+    if name_len == 0 || pos + 8 + name_len > end || file_type > 7 {
+        return None;
+    }
+    let name = &block[pos + 8..pos + 8 + name_len];
+    if name.iter().any(|&b| b == 0 || b == b'/') {
+        return None;
+    }
+    let entry = DirEntry {
+        inode: u64::from(le_u32(block, pos)),
+        name: String::from_utf8_lossy(name).into_owned(),
+        file_type,
+        allocated: true,
+    };
+    Some((entry, used))
+}
+
+/// Appends one mapped block, extending the last extent when contiguous.
+fn push_block(extents: &mut Vec<Extent>, logical: u64, physical: u64) {
+    if physical == 0 {
+        return;
+    }
+    if let Some(last) = extents.last_mut() {
+        if last.logical + last.len == logical && last.physical + last.len == physical {
+            last.len += 1;
+            return;
+        }
+    }
+    extents.push(Extent {
+        logical,
+        physical,
+        len: 1,
+        uninit: false,
+    });
+}
+
+/// Maps a directory entry file type to a name type.
+fn name_type(file_type: u8) -> Option<NameType> {
+    match file_type {
+        1 => Some(NameType::Regular),
+        2 => Some(NameType::Directory),
+        3 => Some(NameType::CharacterDevice),
+        4 => Some(NameType::BlockDevice),
+        5 => Some(NameType::Fifo),
+        6 => Some(NameType::Socket),
+        7 => Some(NameType::SymbolicLink),
+        _ => None,
+    }
+}
+
+/// Maps an inode's type to the name type a directory entry would record.
+fn name_type_of(meta_type: MetaType) -> NameType {
+    match meta_type {
+        MetaType::Regular => NameType::Regular,
+        MetaType::Directory => NameType::Directory,
+        MetaType::SymbolicLink => NameType::SymbolicLink,
+        MetaType::BlockDevice => NameType::BlockDevice,
+        MetaType::CharacterDevice => NameType::CharacterDevice,
+        MetaType::Fifo => NameType::Fifo,
+        MetaType::Socket => NameType::Socket,
+        _ => NameType::Unknown,
+    }
+}
+
+impl<R: Read + Seek> FileSystem for ExtFileSystem<R> {
+    fn volume(&self) -> VolumeObject {
+        let mut volume = VolumeObject::with_ftype(self.sb.ftype_str());
+        volume.partition_offset = Some(self.offset);
+        volume.block_size = Some(self.sb.block_size);
+        volume.block_count = Some(self.sb.blocks_count);
+        volume.first_block = Some(0);
+        volume.last_block = Some(self.sb.blocks_count.saturating_sub(1));
+        volume.allocated_only = Some(false);
+        volume
+    }
+
+    fn files(&mut self) -> Box<dyn Iterator<Item = Result<FileObject>> + '_> {
+        Box::new(Walk {
+            fs: self,
+            frames: Vec::new(),
+            started: false,
+            named: HashSet::new(),
+            next_orphan: None,
+            next_id: 1,
+        })
+    }
+
+    fn allocation_unit(&self) -> (u64, u64) {
+        (0, self.block_size())
+    }
+}
+
+/// The entries of a directory still to be reported, in reverse order.
+struct Frame {
+    path: String,
+    entries: Vec<DirEntry>,
+}
+
+/// Depth-first walk of the directory tree, then of the orphan inodes.
+struct Walk<'a, R: Read + Seek> {
+    fs: &'a mut ExtFileSystem<R>,
+    frames: Vec<Frame>,
+    started: bool,
+    /// Inodes some name (live or deleted) points at
+    named: HashSet<u64>,
+    /// Next inode to check for being an orphan, once the tree is walked
+    next_orphan: Option<u64>,
+    next_id: u64,
+}
+
+impl<R: Read + Seek> Walk<'_, R> {
+    /// Reads the root directory into the first frame.
+    fn start(&mut self) -> Result<()> {
+        self.started = true;
+        self.named.insert(ROOT_INODE);
+        let inode = self.fs.read_inode(ROOT_INODE)?;
+        let mut root = FileObject::new();
+        self.fs.describe(&mut root, &inode)?;
+        let mut entries = self.fs.read_dir(&root, &inode)?;
+        entries.reverse();
+        self.frames.push(Frame {
+            path: String::new(),
+            entries,
+        });
+        Ok(())
+    }
+
+    /// Describes a directory entry, descending into live directories.
+    fn entry(&mut self, path: String, entry: DirEntry) -> Result<FileObject> {
+        let mut file = FileObject::with_filename(path.clone());
+        file.name_type = name_type(entry.file_type);
+        file.alloc_name = Some(entry.allocated);
+        if entry.inode == 0 {
+            return Ok(file);
+        }
+        let first_visit = self.named.insert(entry.inode);
+
+        let inode = self.fs.read_inode(entry.inode)?;
+        self.fs.describe(&mut file, &inode)?;
+        if file.name_type.is_none() {
+            file.name_type = Some(name_type_of(inode.meta_type()));
+        }
+
+        if entry.allocated && first_visit && inode.meta_type() == MetaType::Directory {
+            match self.fs.read_dir(&file, &inode) {
+                Ok(mut entries) => {
+                    entries.reverse();
+                    self.frames.push(Frame { path, entries });
+                }
+                Err(e) => file.error = Some(e.to_string()),
+            }
+        }
+        Ok(file)
+    }
+
+    /// Returns the next orphan inode, if any are left.
+    fn orphan(&mut self) -> Option<Result<FileObject>> {
+        let last = u64::from(self.fs.sb.inodes_count);
+        let mut number = *self
+            .next_orphan
+            .get_or_insert(u64::from(self.fs.sb.first_ino));
+        while number <= last {
+            self.next_orphan = Some(number + 1);
+            if !self.named.contains(&number) && self.fs.inode_initialized(number) {
+                let inode = match self.fs.read_inode(number) {
+                    Ok(inode) => inode,
+                    Err(e) => return Some(Err(e)),
+                };
+                if !inode.is_unused() {
+                    let mut file =
+                        FileObject::with_filename(format!("$OrphanFiles/OrphanFile-{}", number));
+                    file.alloc_name = Some(false);
+                    file.orphan = Some(true);
+                    file.name_type = Some(name_type_of(inode.meta_type()));
+                    return Some(self.fs.describe(&mut file, &inode).map(|()| file));
+                }
+            }
+            number += 1;
+        }
+        None
+    }
+}
+
+impl<R: Read + Seek> Iterator for Walk<'_, R> {
+    type Item = Result<FileObject>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            if let Err(e) = self.start() {
+                return Some(Err(e));
+            }
+        }
+
+        let result = loop {
+            let Some(frame) = self.frames.last_mut() else {
+                break self.orphan()?;
+            };
+            let Some(entry) = frame.entries.pop() else {
+                self.frames.pop();
+                continue;
+            };
+            let path = if frame.path.is_empty() {
+                entry.name.clone()
+            } else {
+                format!("{}/{}", frame.path, entry.name)
+            };
+            break self.entry(path, entry);
+        };
+
+        Some(result.map(|mut file| {
+            file.id = Some(self.next_id);
+            self.next_id += 1;
+            file
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::ContentReader;
+    use std::io::Cursor;
+
+    const EXT2: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/ext2.img"));
+    const EXT4: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/ext4.img"));
+
+    fn walk(image: &[u8]) -> (VolumeObject, Vec<FileObject>) {
+        let mut fs = ExtFileSystem::open(Cursor::new(image), 0).unwrap();
+        let files = fs.files().collect::<Result<Vec<_>>>().unwrap();
+        (fs.volume(), files)
+    }
+
+    fn find<'a>(files: &'a [FileObject], name: &str) -> &'a FileObject {
+        files
+            .iter()
+            .find(|f| f.filename.as_deref() == Some(name))
+            .unwrap_or_else(|| panic!("no file named {}", name))
+    }
+
+    fn content(image: &[u8], file: &FileObject) -> Vec<u8> {
+        let mut out = Vec::new();
+        ContentReader::new(Cursor::new(image), file)
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_volume() {
+        let (ext2, _) = walk(EXT2);
+        assert_eq!(ext2.ftype_str.as_deref(), Some("ext2"));
+        assert_eq!(ext2.block_size, Some(1024));
+        assert_eq!(ext2.block_count, Some(512));
+
+        let (ext4, _) = walk(EXT4);
+        assert_eq!(ext4.ftype_str.as_deref(), Some("ext4"));
+        assert_eq!(ext4.partition_offset, Some(0));
+    }
+
+    #[test]
+    fn test_tree_and_content() {
+        for image in [EXT2, EXT4] {
+            let (_, files) = walk(image);
+            let hello = find(&files, "hello.txt");
+            assert_eq!(hello.alloc_name, Some(true));
+            assert_eq!(hello.alloc_inode, Some(true));
+            assert_eq!(hello.name_type, Some(NameType::Regular));
+            assert_eq!(hello.filesize, Some(13));
+            assert_eq!(hello.mode, Some(0o644));
+            assert_eq!(hello.mtime.as_ref().unwrap().timestamp(), Some(1704164645));
+            assert_eq!(content(image, hello), b"hello, world\n");
+
+            let notes = find(&files, "docs/deep/notes.txt");
+            assert_eq!(content(image, notes), "notes\n".repeat(100).as_bytes());
+            assert_eq!(find(&files, "docs").name_type, Some(NameType::Directory));
+            assert_eq!(find(&files, "empty").meta_type, Some(MetaType::Directory));
+
+            let big = find(&files, "docs/big.bin");
+            assert_eq!(big.data_brs.as_ref().unwrap().total_len(), Some(40000));
+            let expected: Vec<u8> =
+                b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ\n"
+                    .iter()
+                    .copied()
+                    .cycle()
+                    .take(40000)
+                    .collect();
+            assert_eq!(content(image, big), expected);
+
+            let inode_run = big.inode_brs.as_ref().unwrap().get(0).unwrap().clone();
+            assert_eq!(inode_run.len, Some(256));
+            assert_eq!(inode_run.img_offset, inode_run.fs_offset);
+
+            assert_eq!(
+                find(&files, "link").link_target.as_deref(),
+                Some("hello.txt")
+            );
+            let longlink = find(&files, "longlink");
+            assert!(longlink
+                .link_target
+                .as_deref()
+                .unwrap()
+                .ends_with("notes.txt-long-target-name"));
+            assert!(longlink.data_brs.is_some());
+
+            // Parent entries are not reported, and ids follow walk order
+            assert!(files
+                .iter()
+                .all(|f| !f.filename.as_deref().unwrap().ends_with('.')));
+            let ids: Vec<u64> = files.iter().map(|f| f.id.unwrap()).collect();
+            assert_eq!(ids, (1..=files.len() as u64).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_sparse_file() {
+        for image in [EXT2, EXT4] {
+            let (_, files) = walk(image);
+            let sparse = find(&files, "sparse.bin");
+            let runs: Vec<_> = sparse.data_brs.as_ref().unwrap().iter().cloned().collect();
+            assert_eq!(runs.len(), 2);
+            assert_eq!(runs[0].file_offset, Some(0));
+            assert_eq!(runs[1].file_offset, Some(29 * 1024));
+            assert_eq!(runs[1].len, Some(30100 - 29 * 1024));
+
+            let data = content(image, sparse);
+            assert_eq!(data.len(), 30100);
+            assert!(data[..100].iter().all(|&b| b == b'A'));
+            assert!(data[100..30000].iter().all(|&b| b == 0));
+            assert!(data[30000..].iter().all(|&b| b == b'Z'));
+        }
+    }
+
+    #[test]
+    fn test_deleted_and_orphans() {
+        for image in [EXT2, EXT4] {
+            let (_, files) = walk(image);
+
+            // The name survives in slack, still pointing at its freed inode
+            let deleted = find(&files, "deleted.txt");
+            assert_eq!(deleted.alloc_name, Some(false));
+            assert_eq!(deleted.alloc_inode, Some(false));
+            assert_eq!(
+                deleted.dtime.as_ref().unwrap().timestamp(),
+                Some(1704164645)
+            );
+            assert_eq!(deleted.orphan, None);
+
+            // Names whose inode numbers were cleared
+            let orphan_name = find(&files, "orphan.txt");
+            assert_eq!(orphan_name.alloc_name, Some(false));
+            assert_eq!(orphan_name.inode, None);
+
+            let orphans: Vec<&FileObject> =
+                files.iter().filter(|f| f.orphan == Some(true)).collect();
+            assert_eq!(orphans.len(), 2);
+            let allocated = orphans
+                .iter()
+                .find(|f| f.alloc_inode == Some(true))
+                .unwrap();
+            assert_eq!(allocated.filesize, Some(90));
+            assert!(allocated.dtime.is_none());
+            assert_eq!(
+                content(image, allocated),
+                "orphaned\n".repeat(10).as_bytes()
+            );
+            let freed = orphans
+                .iter()
+                .find(|f| f.alloc_inode == Some(false))
+                .unwrap();
+            assert_eq!(freed.filesize, Some(190));
+            assert!(freed.dtime.is_some());
+            assert_eq!(
+                freed.filename,
+                Some(format!("$OrphanFiles/OrphanFile-{}", freed.inode.unwrap()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_partition_offset_and_probe() {
+        let mut image = vec![0u8; 4096];
+        image.extend_from_slice(EXT4);
+        assert!(probe(&mut Cursor::new(&image), 4096).unwrap());
+        assert!(!probe(&mut Cursor::new(&image), 0).unwrap());
+        assert!(!probe(&mut Cursor::new(&image[..100]), 0).unwrap());
+
+        let mut fs = super::super::open(Cursor::new(&image), 4096).unwrap();
+        assert_eq!(fs.volume().partition_offset, Some(4096));
+        let files = fs.files().collect::<Result<Vec<_>>>().unwrap();
+        let hello = find(&files, "hello.txt");
+        let run = hello.data_brs.as_ref().unwrap().get(0).unwrap();
+        assert_eq!(run.img_offset, Some(run.fs_offset.unwrap() + 4096));
+        assert_eq!(content(&image, hello), b"hello, world\n");
+
+        assert!(matches!(
+            super::super::open(Cursor::new(&image), 0),
+            Err(Error::InvalidFilesystem(_))
+        ));
+    }
+
+    #[test]
+    fn test_corrupt_group_fields() {
+        let patched = |fields: &[(usize, u32)]| {
+            let mut image = EXT4.to_vec();
+            for &(pos, value) in fields {
+                let pos = SUPERBLOCK_OFFSET as usize + pos;
+                image[pos..pos + 4].copy_from_slice(&value.to_le_bytes());
+            }
+            image
+        };
+
+        // More block groups than the image can hold descriptors for
+        let image = patched(&[(0x04, u32::MAX), (0x20, 1)]);
+        assert!(matches!(
+            ExtFileSystem::open(Cursor::new(&image), 0),
+            Err(Error::InvalidFilesystem(_))
+        ));
+
+        // Inodes in block groups that have no descriptor
+        let image = patched(&[(0x28, 1)]);
+        let mut fs = ExtFileSystem::open(Cursor::new(&image), 0).unwrap();
+        let last = u64::from(fs.sb.inodes_count);
+        assert!(matches!(
+            fs.read_inode(last),
+            Err(Error::InvalidFilesystem(_))
+        ));
+        assert!(matches!(
+            fs.inode_allocated(last),
+            Err(Error::InvalidFilesystem(_))
+        ));
+        let _ = fs.files().count();
+    }
+}
@@ -0,0 +1,1520 @@
+//! FAT12, FAT16, FAT32 and exFAT file systems.
+//!
+//! [`FatFileSystem`] reads the boot sector and the first FAT when it is
+//! opened, then walks the directory tree from the root directory. Each
+//! directory entry becomes a [`FileObject`] whose `data_brs` follow the
+//! entry's cluster chain, and whose `inode_brs` locate the entry itself.
+//! The FAT variant is chosen by cluster count, as the specification
+//! requires.
+//!
+//! As in The Sleuth Kit, volumes are measured in sectors, and directory
+//! entries are numbered as inodes: the entry at byte `n` past the FATs is
+//! inode `3 + n / 32` (inode 2 being the root directory).
+//!
+//! Deleted entries (first byte 0xE5 on FAT, a cleared in-use bit on exFAT)
+//! are reported with `alloc_name` and `alloc_inode` false. Deleting a file
+//! frees its cluster chain, so its content is assumed to be contiguous
+//! from its first cluster, which is true of most files on removable media.
+//! Deleted directories are walked when their first cluster still holds a
+//! `.` entry.
+//!
+//! Timestamps keep the precision the format records: 2 seconds for FAT
+//! modification times, 10 milliseconds for creation times and exFAT
+//! modification times, and a day for FAT access dates. FAT times are local
+//! times with no time zone and are reported as UTC; exFAT times use the
+//! UTC offset stored with them when there is one.
+
+use super::{le_u16, le_u32, read_at, FileSystem};
+use crate::error::{Error, Result};
+use crate::objects::{
+    ByteRun, ByteRunFacet, ByteRuns, FileObject, MetaType, NameType, Precision, TimeUnit,
+    Timestamp, TimestampName, VolumeObject,
+};
+use chrono::{FixedOffset, NaiveDate, TimeZone};
+use std::collections::HashSet;
+use std::io::{Read, Seek, SeekFrom};
+
+/// Inode number of the first directory entry.
+const FIRST_INODE: u64 = 3;
+/// Size of a directory entry.
+const DIR_ENTRY_LEN: usize = 32;
+
+/// Directory entry attribute bits.
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_LONG_NAME: u8 = 0x0f;
+
+/// First byte of a deleted FAT directory entry.
+const DELETED: u8 = 0xe5;
+/// Stands in for a leading 0xE5 byte in a short name.
+const KANJI_E5: u8 = 0x05;
+/// Short name case flags (set by Windows NT and later).
+const LOWER_BASE: u8 = 0x08;
+const LOWER_EXT: u8 = 0x10;
+
+/// exFAT directory entry types, with the in-use bit clear.
+const EXFAT_FILE: u8 = 0x05;
+const EXFAT_STREAM: u8 = 0x40;
+const EXFAT_NAME: u8 = 0x41;
+/// exFAT directory entry type of the allocation bitmap.
+const EXFAT_BITMAP: u8 = 0x81;
+/// In-use bit of an exFAT directory entry type.
+const EXFAT_IN_USE: u8 = 0x80;
+/// exFAT stream extension flag: the content is contiguous, with no FAT chain.
+const EXFAT_NO_FAT_CHAIN: u8 = 0x02;
+
+/// Largest directory read, which bounds the damage from a corrupt chain.
+const MAX_DIRECTORY_SIZE: u64 = 64 * 1024 * 1024;
+
+/// The member of the FAT family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    /// 12-bit FAT entries
+    Fat12,
+    /// 16-bit FAT entries
+    Fat16,
+    /// 28-bit FAT entries
+    Fat32,
+    /// exFAT
+    ExFat,
+}
+
+impl FatType {
+    /// Returns the file system name used for `ftype_str`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FatType::Fat12 => "fat12",
+            FatType::Fat16 => "fat16",
+            FatType::Fat32 => "fat32",
+            FatType::ExFat => "exfat",
+        }
+    }
+
+    /// Returns the smallest FAT entry value that ends a chain.
+    fn end_of_chain(&self) -> u32 {
+        match self {
+            FatType::Fat12 => 0xff8,
+            FatType::Fat16 => 0xfff8,
+            FatType::Fat32 => 0x0fff_fff8,
+            FatType::ExFat => 0xffff_fff8,
+        }
+    }
+}
+
+/// Where the root directory is.
+#[derive(Debug, Clone, Copy)]
+enum Root {
+    /// FAT12/16: a fixed region between the FATs and the clusters
+    Region { fs_offset: u64, len: u64 },
+    /// FAT32 and exFAT: a cluster chain
+    Cluster(u32),
+}
+
+/// A directory entry, live or deleted.
+#[derive(Debug, Clone)]
+struct Entry {
+    name: String,
+    inode: u64,
+    allocated: bool,
+    directory: bool,
+    first_cluster: u32,
+    size: u64,
+    /// exFAT content stored without a FAT chain
+    contiguous: bool,
+    /// Location of the entry (or exFAT entry set)
+    fs_offset: u64,
+    len: u64,
+    crtime: Option<Timestamp>,
+    mtime: Option<Timestamp>,
+    atime: Option<Timestamp>,
+}
+
+/// A FAT12, FAT16, FAT32 or exFAT file system in a raw image.
+#[derive(Debug)]
+pub struct FatFileSystem<R: Read + Seek> {
+    image: R,
+    offset: u64,
+    /// Length of the whole image
+    image_len: u64,
+    fat_type: FatType,
+    sector_size: u32,
+    sector_count: u64,
+    cluster_size: u64,
+    cluster_count: u32,
+    /// File system offset of the first byte after the FATs
+    data_start: u64,
+    /// File system offset of cluster 2
+    heap_start: u64,
+    root: Root,
+    fat: Vec<u32>,
+    /// exFAT cluster allocation bitmap
+    bitmap: Option<Vec<u8>>,
+}
+
+/// Returns true if a FAT or exFAT boot sector is at `offset`.
+pub fn probe<R: Read + Seek>(image: &mut R, offset: u64) -> Result<bool> {
+    let mut boot = [0u8; 512];
+    match read_at(image, offset, &mut boot) {
+        Ok(()) => Ok(Geometry::parse(&boot).is_ok()),
+        Err(Error::InvalidFilesystem(_)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// The layout described by a boot sector, in bytes from the start of the
+/// file system.
+#[derive(Debug, Clone, Copy)]
+struct Geometry {
+    fat_type: FatType,
+    sector_size: u32,
+    sector_count: u64,
+    cluster_size: u64,
+    cluster_count: u32,
+    fat_offset: u64,
+    fat_len: u64,
+    data_start: u64,
+    heap_start: u64,
+    root: Root,
+}
+
+impl Geometry {
+    fn parse(boot: &[u8]) -> Result<Self> {
+        if boot[510..512] != [0x55, 0xaa] {
+            return Err(Error::InvalidFilesystem(
+                "missing boot sector signature".to_string(),
+            ));
+        }
+        if &boot[3..11] == b"EXFAT   " {
+            Self::parse_exfat(boot)
+        } else {
+            Self::parse_fat(boot)
+        }
+    }
+
+    fn parse_fat(boot: &[u8]) -> Result<Self> {
+        let invalid = |what: &str| Err(Error::InvalidFilesystem(format!("invalid FAT {}", what)));
+        if boot[0] != 0xeb && boot[0] != 0xe9 {
+            return invalid("jump instruction");
+        }
+        let sector_size = u32::from(le_u16(boot, 11));
+        let sectors_per_cluster = u32::from(boot[13]);
+        let reserved = u64::from(le_u16(boot, 14));
+        let fat_count = u64::from(boot[16]);
+        let root_entries = u64::from(le_u16(boot, 17));
+        if !(512..=4096).contains(&sector_size) || !sector_size.is_power_of_two() {
+            return invalid("sector size");
+        }
+        if sectors_per_cluster == 0 || !sectors_per_cluster.is_power_of_two() {
+            return invalid("cluster size");
+        }
+        if reserved == 0 || fat_count == 0 {
+            return invalid("reserved sector or FAT count");
+        }
+        let sector_count = match le_u16(boot, 19) {
+            0 => u64::from(le_u32(boot, 32)),
+            n => u64::from(n),
+        };
+        let fat_sectors = match le_u16(boot, 22) {
+            0 => u64::from(le_u32(boot, 36)),
+            n => u64::from(n),
+        };
+        if fat_sectors == 0 {
+            return invalid("FAT size");
+        }
+
+        let sector = u64::from(sector_size);
+        let root_len = root_entries * DIR_ENTRY_LEN as u64;
+        let data_start = (reserved + fat_count * fat_sectors) * sector;
+        let heap_start = data_start + root_len.div_ceil(sector) * sector;
+        let cluster_size = u64::from(sectors_per_cluster) * sector;
+        let clusters = (sector_count * sector).saturating_sub(heap_start) / cluster_size;
+        if sector_count * sector <= heap_start || clusters == 0 {
+            return invalid("volume size");
+        }
+        let cluster_count = u32::try_from(clusters)
+            .map_err(|_| Error::InvalidFilesystem(format!("too many clusters: {}", clusters)))?;
+
+        let fat_type = if cluster_count < 4085 {
+            FatType::Fat12
+        } else if cluster_count < 65525 {
+            FatType::Fat16
+        } else {
+            FatType::Fat32
+        };
+        let root = if fat_type == FatType::Fat32 {
+            Root::Cluster(le_u32(boot, 44))
+        } else if root_entries == 0 {
+            return invalid("root directory size");
+        } else {
+            Root::Region {
+                fs_offset: data_start,
+                len: root_len,
+            }
+        };
+
+        Ok(Self {
+            fat_type,
+            sector_size,
+            sector_count,
+            cluster_size,
+            cluster_count,
+            fat_offset: reserved * sector,
+            fat_len: fat_sectors * sector,
+            data_start,
+            heap_start,
+            root,
+        })
+    }
+
+    fn parse_exfat(boot: &[u8]) -> Result<Self> {
+        let invalid = |what: &str| Err(Error::InvalidFilesystem(format!("invalid exFAT {}", what)));
+        let sector_shift = u32::from(boot[108]);
+        let cluster_shift = u32::from(boot[109]);
+        if !(9..=12).contains(&sector_shift) || sector_shift + cluster_shift > 25 {
+            return invalid("sector or cluster size");
+        }
+        if boot[110] == 0 {
+            return invalid("FAT count");
+        }
+        let sector = 1u64 << sector_shift;
+        let sector_count = u64::from_le_bytes(boot[72..80].try_into().unwrap());
+        let heap_start = u64::from(le_u32(boot, 88)) * sector;
+        let cluster_size = sector << cluster_shift;
+        let cluster_count = le_u32(boot, 92);
+        if cluster_count == 0 {
+            return invalid("cluster count");
+        }
+        // The cluster heap must fit in the volume
+        let heap_end = heap_start + u64::from(cluster_count) * cluster_size;
+        if sector_count
+            .checked_mul(sector)
+            .is_none_or(|volume_len| heap_end > volume_len)
+        {
+            return invalid("volume size");
+        }
+        Ok(Self {
+            fat_type: FatType::ExFat,
+            sector_size: 1 << sector_shift,
+            sector_count,
+            cluster_size,
+            cluster_count,
+            fat_offset: u64::from(le_u32(boot, 80)) * sector,
+            fat_len: u64::from(le_u32(boot, 84)) * sector,
+            data_start: heap_start,
+            heap_start,
+            root: Root::Cluster(le_u32(boot, 96)),
+        })
+    }
+}
+
+impl<R: Read + Seek> FatFileSystem<R> {
+    /// Opens the file system that starts `offset` bytes into an image.
+    pub fn open(mut image: R, offset: u64) -> Result<Self> {
+        let mut boot = [0u8; 512];
+        read_at(&mut image, offset, &mut boot)?;
+        let geometry = Geometry::parse(&boot)?;
+
+        // Only the entries for clusters that exist are read
+        let entries = u64::from(geometry.cluster_count) + 2;
+        let needed = match geometry.fat_type {
+            FatType::Fat12 => (entries * 3).div_ceil(2),
+            FatType::Fat16 => entries * 2,
+            FatType::Fat32 | FatType::ExFat => entries * 4,
+        };
+        if needed > geometry.fat_len {
+            return Err(Error::InvalidFilesystem(format!(
+                "FAT of {} bytes is too small for {} clusters",
+                geometry.fat_len, geometry.cluster_count
+            )));
+        }
+        let image_len = image.seek(SeekFrom::End(0))?;
+        let fat_start = offset.saturating_add(geometry.fat_offset);
+        if fat_start.saturating_add(needed) > image_len {
+            return Err(Error::InvalidFilesystem(format!(
+                "FAT for {} clusters extends past the end of the image",
+                geometry.cluster_count
+            )));
+        }
+        let mut raw = vec![0u8; needed as usize];
+        read_at(&mut image, fat_start, &mut raw)?;
+        let fat = (0..entries as usize)
+            .map(|c| match geometry.fat_type {
+                FatType::Fat12 => {
+                    let pair = le_u16(&raw, c * 3 / 2);
+                    if c % 2 == 0 {
+                        u32::from(pair & 0xfff)
+                    } else {
+                        u32::from(pair >> 4)
+                    }
+                }
+                FatType::Fat16 => u32::from(le_u16(&raw, c * 2)),
+                FatType::Fat32 => le_u32(&raw, c * 4) & 0x0fff_ffff,
+                FatType::ExFat => le_u32(&raw, c * 4),
+            })
+            .collect();
+
+        let mut fs = Self {
+            image,
+            offset,
+            image_len,
+            fat_type: geometry.fat_type,
+            sector_size: geometry.sector_size,
+            sector_count: geometry.sector_count,
+            cluster_size: geometry.cluster_size,
+            cluster_count: geometry.cluster_count,
+            data_start: geometry.data_start,
+            heap_start: geometry.heap_start,
+            root: geometry.root,
+            fat,
+            bitmap: None,
+        };
+        if fs.fat_type == FatType::ExFat {
+            fs.bitmap = Some(fs.read_bitmap()?);
+        }
+        Ok(fs)
+    }
+
+    /// Returns the FAT variant.
+    pub fn fat_type(&self) -> FatType {
+        self.fat_type
+    }
+
+    /// Consumes the file system, returning the underlying image.
+    pub fn into_inner(self) -> R {
+        self.image
+    }
+
+    fn is_cluster(&self, cluster: u32) -> bool {
+        cluster >= 2 && cluster - 2 < self.cluster_count
+    }
+
+    fn cluster_offset(&self, cluster: u32) -> u64 {
+        self.heap_start + u64::from(cluster - 2) * self.cluster_size
+    }
+
+    /// Returns true if a cluster is in use.
+    fn cluster_allocated(&self, cluster: u32) -> bool {
+        if !self.is_cluster(cluster) {
+            return false;
+        }
+        match &self.bitmap {
+            Some(bitmap) => {
+                let index = (cluster - 2) as usize;
+                bitmap
+                    .get(index / 8)
+                    .is_some_and(|byte| byte & (1 << (index % 8)) != 0)
+            }
+            None => self.fat[cluster as usize] != 0,
+        }
+    }
+
+    /// Follows a cluster chain through the FAT.
+    fn chain(&self, first: u32) -> Result<Vec<u32>> {
+        let mut clusters = Vec::new();
+        let mut cluster = first;
+        while self.is_cluster(cluster) {
+            if clusters.len() > self.cluster_count as usize {
+                return Err(Error::InvalidFilesystem(format!(
+                    "cluster chain from {} loops",
+                    first
+                )));
+            }
+            clusters.push(cluster);
+            cluster = self.fat[cluster as usize];
+        }
+        if cluster < self.fat_type.end_of_chain() && !clusters.is_empty() {
+            return Err(Error::InvalidFilesystem(format!(
+                "cluster chain from {} ends at invalid entry {:#x}",
+                first, cluster
+            )));
+        }
+        Ok(clusters)
+    }
+
+    /// Returns the clusters that hold `size` bytes from `first` on.
+    fn contiguous(&self, first: u32, size: u64) -> Vec<u32> {
+        let count = size.div_ceil(self.cluster_size);
+        (0..count)
+            .map_while(|n| u32::try_from(u64::from(first) + n).ok())
+            .take_while(|&c| self.is_cluster(c))
+            .collect()
+    }
+
+    /// Returns the clusters holding an entry's content.
+    fn clusters(&self, entry: &Entry) -> Result<Vec<u32>> {
+        if !self.is_cluster(entry.first_cluster) {
+            return Ok(Vec::new());
+        }
+        if entry.contiguous {
+            return Ok(self.contiguous(entry.first_cluster, entry.size));
+        }
+        if entry.allocated {
+            return self.chain(entry.first_cluster);
+        }
+        // exFAT leaves the chains of deleted files in the FAT
+        if self.fat_type == FatType::ExFat && self.fat[entry.first_cluster as usize] != 0 {
+            if let Ok(chain) = self.chain(entry.first_cluster) {
+                return Ok(chain);
+            }
+        }
+        let size = if entry.directory && entry.size == 0 {
+            self.cluster_size
+        } else {
+            entry.size
+        };
+        Ok(self.contiguous(entry.first_cluster, size))
+    }
+
+    /// Describes clusters as byte runs holding `size` bytes.
+    fn runs(&self, clusters: &[u32], size: u64) -> ByteRuns {
+        let mut runs = ByteRuns::with_facet(ByteRunFacet::Data);
+        for (index, &cluster) in clusters.iter().enumerate() {
+            let file_offset = index as u64 * self.cluster_size;
+            if file_offset >= size {
+                break;
+            }
+            let fs_offset = self.cluster_offset(cluster);
+            runs.glom(ByteRun {
+                img_offset: Some(self.offset + fs_offset),
+                fs_offset: Some(fs_offset),
+                file_offset: Some(file_offset),
+                len: Some(self.cluster_size.min(size - file_offset)),
+                ..Default::default()
+            });
+        }
+        runs
+    }
+
+    /// Reads the content of clusters.
+    fn read_clusters(&mut self, clusters: &[u32]) -> Result<Vec<u8>> {
+        let len = (clusters.len() as u64)
+            .checked_mul(self.cluster_size)
+            .filter(|&len| len <= self.image_len)
+            .ok_or_else(|| {
+                Error::InvalidFilesystem(format!(
+                    "{} clusters are larger than the image",
+                    clusters.len()
+                ))
+            })?;
+        let cluster_size = self.cluster_size as usize;
+        let mut data = vec![0u8; len as usize];
+        for (chunk, &cluster) in data.chunks_mut(cluster_size).zip(clusters) {
+            let offset = self.offset + self.cluster_offset(cluster);
+            read_at(&mut self.image, offset, chunk)?;
+        }
+        Ok(data)
+    }
+
+    /// Reads the exFAT allocation bitmap named in the root directory.
+    fn read_bitmap(&mut self) -> Result<Vec<u8>> {
+        let Root::Cluster(root) = self.root else {
+            unreachable!("exFAT roots are cluster chains");
+        };
+        let clusters = self.chain(root)?;
+        let data = self.read_clusters(&clusters)?;
+        for entry in data.chunks_exact(DIR_ENTRY_LEN) {
+            // The second FAT's bitmap, if any, has bit 0 of its flags set
+            if entry[0] == EXFAT_BITMAP && entry[1] & 1 == 0 {
+                let first = le_u32(entry, 20);
+                let len = u64::from_le_bytes(entry[24..32].try_into().unwrap());
+                if len < u64::from(self.cluster_count).div_ceil(8) {
+                    break;
+                }
+                let clusters = self.contiguous(first, len);
+                let mut bitmap = self.read_clusters(&clusters)?;
+                bitmap.truncate(len as usize);
+                return Ok(bitmap);
+            }
+        }
+        Err(Error::InvalidFilesystem(
+            "exFAT root directory has no allocation bitmap".to_string(),
+        ))
+    }
+
+    /// Reads a directory, returning its entries and their locations.
+    fn read_dir(&mut self, clusters: &[u32]) -> Result<Vec<Entry>> {
+        if clusters.len() as u64 * self.cluster_size > MAX_DIRECTORY_SIZE {
+            return Err(Error::InvalidFilesystem(format!(
+                "directory at cluster {} is too large to read",
+                clusters[0]
+            )));
+        }
+        let data = self.read_clusters(clusters)?;
+        let cluster_size = self.cluster_size as usize;
+        let locate = |pos: usize| {
+            self.cluster_offset(clusters[pos / cluster_size]) + (pos % cluster_size) as u64
+        };
+        Ok(self.parse_dir(&data, locate))
+    }
+
+    /// Reads the root directory.
+    fn read_root(&mut self) -> Result<Vec<Entry>> {
+        match self.root {
+            Root::Region { fs_offset, len } => {
+                let mut data = vec![0u8; len as usize];
+                read_at(&mut self.image, self.offset + fs_offset, &mut data)?;
+                Ok(self.parse_dir(&data, |pos| fs_offset + pos as u64))
+            }
+            Root::Cluster(first) => {
+                let clusters = self.chain(first)?;
+                self.read_dir(&clusters)
+            }
+        }
+    }
+
+    fn parse_dir(&self, data: &[u8], locate: impl Fn(usize) -> u64) -> Vec<Entry> {
+        if self.fat_type == FatType::ExFat {
+            self.parse_exfat_dir(data, locate)
+        } else {
+            self.parse_fat_dir(data, locate)
+        }
+    }
+
+    /// Returns the inode number of the directory entry at `fs_offset`.
+    fn inode(&self, fs_offset: u64) -> u64 {
+        FIRST_INODE + (fs_offset - self.data_start) / DIR_ENTRY_LEN as u64
+    }
+
+    fn parse_fat_dir(&self, data: &[u8], locate: impl Fn(usize) -> u64) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        // Long name entries seen since the last short entry, in disk order
+        let mut long_name: Vec<&[u8]> = Vec::new();
+        for (index, raw) in data.chunks_exact(DIR_ENTRY_LEN).enumerate() {
+            if raw[0] == 0 {
+                break;
+            }
+            let attr = raw[11];
+            if attr & 0x3f == ATTR_LONG_NAME {
+                long_name.push(raw);
+                continue;
+            }
+            let parts = std::mem::take(&mut long_name);
+            if attr & ATTR_VOLUME_ID != 0 || raw[0] == b'.' {
+                continue;
+            }
+
+            let allocated = raw[0] != DELETED;
+            let short = short_name(raw);
+            let checksum = raw[..11]
+                .iter()
+                .fold(0u8, |sum, &b| sum.rotate_right(1).wrapping_add(b));
+            let name = long_name_of(&parts, allocated, checksum).unwrap_or(short);
+
+            let pos = index * DIR_ENTRY_LEN;
+            let fs_offset = locate(pos);
+            let high = if self.fat_type == FatType::Fat32 {
+                u32::from(le_u16(raw, 20)) << 16
+            } else {
+                0
+            };
+            let utc = FixedOffset::east_opt(0);
+            entries.push(Entry {
+                name,
+                inode: self.inode(fs_offset),
+                allocated,
+                directory: attr & ATTR_DIRECTORY != 0,
+                first_cluster: high | u32::from(le_u16(raw, 26)),
+                size: u64::from(le_u32(raw, 28)),
+                contiguous: false,
+                fs_offset,
+                len: DIR_ENTRY_LEN as u64,
+                crtime: dos_timestamp(
+                    TimestampName::Crtime,
+                    le_u16(raw, 16),
+                    le_u16(raw, 14),
+                    raw[13],
+                    utc,
+                    Precision::new(10, TimeUnit::Millisecond),
+                ),
+                mtime: dos_timestamp(
+                    TimestampName::Mtime,
+                    le_u16(raw, 24),
+                    le_u16(raw, 22),
+                    0,
+                    utc,
+                    Precision::new(2, TimeUnit::Second),
+                ),
+                atime: dos_timestamp(
+                    TimestampName::Atime,
+                    le_u16(raw, 18),
+                    0,
+                    0,
+                    utc,
+                    Precision::new(1, TimeUnit::Day),
+                ),
+            });
+        }
+        entries
+    }
+
+    fn parse_exfat_dir(&self, data: &[u8], locate: impl Fn(usize) -> u64) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        let raws: Vec<&[u8]> = data.chunks_exact(DIR_ENTRY_LEN).collect();
+        let mut index = 0;
+        while index < raws.len() {
+            let raw = raws[index];
+            if raw[0] == 0 {
+                break;
+            }
+            let Some(entry) = self.parse_exfat_set(&raws[index..], &locate, index) else {
+                index += 1;
+                continue;
+            };
+            index += entry.len as usize / DIR_ENTRY_LEN;
+            entries.push(entry);
+        }
+        entries
+    }
+
+    /// Parses the exFAT file entry set at the start of `raws`, which is
+    /// entry `index` of its directory.
+    fn parse_exfat_set(
+        &self,
+        raws: &[&[u8]],
+        locate: impl Fn(usize) -> u64,
+        index: usize,
+    ) -> Option<Entry> {
+        let file = raws[0];
+        if file[0] & !EXFAT_IN_USE != EXFAT_FILE {
+            return None;
+        }
+        let in_use = file[0] & EXFAT_IN_USE;
+        let secondary = usize::from(file[1]);
+        if secondary < 2 || secondary >= raws.len() {
+            return None;
+        }
+        let stream = raws[1];
+        if stream[0] != EXFAT_STREAM | in_use {
+            return None;
+        }
+        let name_len = usize::from(stream[3]);
+        let mut name = Vec::with_capacity(name_len);
+        for part in &raws[2..=secondary] {
+            if part[0] != EXFAT_NAME | in_use {
+                return None;
+            }
+            name.extend(part[2..].chunks_exact(2).map(|c| le_u16(c, 0)));
+        }
+        name.truncate(name_len);
+
+        let fs_offset = locate(index * DIR_ENTRY_LEN);
+        let timestamp = |name, at: usize, centis: u8, utc_offset: u8, prec| {
+            let stamp = le_u32(file, at);
+            dos_timestamp(
+                name,
+                (stamp >> 16) as u16,
+                stamp as u16,
+                centis,
+                exfat_utc_offset(utc_offset),
+                prec,
+            )
+        };
+        let centis = Precision::new(10, TimeUnit::Millisecond);
+        Some(Entry {
+            name: String::from_utf16_lossy(&name),
+            inode: self.inode(fs_offset),
+            allocated: in_use != 0,
+            directory: le_u16(file, 4) & u16::from(ATTR_DIRECTORY) != 0,
+            first_cluster: le_u32(stream, 20),
+            size: u64::from_le_bytes(stream[24..32].try_into().unwrap()),
+            contiguous: stream[1] & EXFAT_NO_FAT_CHAIN != 0,
+            fs_offset,
+            len: ((secondary + 1) * DIR_ENTRY_LEN) as u64,
+            crtime: timestamp(TimestampName::Crtime, 8, file[20], file[22], centis),
+            mtime: timestamp(TimestampName::Mtime, 12, file[21], file[23], centis),
+            atime: timestamp(
+                TimestampName::Atime,
+                16,
+                0,
+                file[24],
+                Precision::new(2, TimeUnit::Second),
+            ),
+        })
+    }
+
+    /// Returns true if a cluster starts with a `.` directory entry.
+    fn looks_like_directory(&mut self, cluster: u32) -> bool {
+        if !self.is_cluster(cluster) {
+            return false;
+        }
+        if self.fat_type == FatType::ExFat {
+            // exFAT directories have no `.` entry to check
+            return true;
+        }
+        let mut first = [0u8; DIR_ENTRY_LEN];
+        let offset = self.offset + self.cluster_offset(cluster);
+        read_at(&mut self.image, offset, &mut first).is_ok()
+            && &first[..11] == b".          "
+            && first[11] & ATTR_DIRECTORY != 0
+    }
+
+    /// Describes a directory entry, returning the clusters of a directory
+    /// to walk.
+    fn describe(&mut self, file: &mut FileObject, entry: &Entry) -> Option<Vec<u32>> {
+        let kind = if entry.directory {
+            (NameType::Directory, MetaType::Directory)
+        } else {
+            (NameType::Regular, MetaType::Regular)
+        };
+        file.name_type = Some(kind.0);
+        file.meta_type = Some(kind.1);
+        file.inode = Some(entry.inode);
+        file.alloc_name = Some(entry.allocated);
+        file.alloc_inode = Some(entry.allocated);
+        file.crtime = entry.crtime.clone();
+        file.mtime = entry.mtime.clone();
+        file.atime = entry.atime.clone();
+
+        let mut inode_brs = ByteRuns::with_facet(ByteRunFacet::Inode);
+        inode_brs.push(ByteRun {
+            img_offset: Some(self.offset + entry.fs_offset),
+            fs_offset: Some(entry.fs_offset),
+            len: Some(entry.len),
+            ..Default::default()
+        });
+        file.inode_brs = Some(inode_brs);
+
+        let clusters = match self.clusters(entry) {
+            Ok(clusters) => clusters,
+            Err(e) => {
+                file.error = Some(e.to_string());
+                return None;
+            }
+        };
+        if !entry.allocated && clusters.iter().any(|&c| self.cluster_allocated(c)) {
+            // Another file has the clusters now
+            file.filesize = Some(entry.size);
+            return None;
+        }
+
+        // FAT directories record no size
+        let size = if entry.directory && self.fat_type != FatType::ExFat {
+            clusters.len() as u64 * self.cluster_size
+        } else {
+            entry.size
+        };
+        file.filesize = Some(size);
+        if !clusters.is_empty() {
+            file.data_brs = Some(self.runs(&clusters, size));
+        }
+
+        let walk = entry.directory
+            && !clusters.is_empty()
+            && (entry.allocated || self.looks_like_directory(clusters[0]));
+        walk.then_some(clusters)
+    }
+}
+
+/// Decodes an 8.3 short name, applying the case flags.
+fn short_name(raw: &[u8]) -> String {
+    let mut base: Vec<u8> = raw[..8].to_vec();
+    match base[0] {
+        DELETED => base[0] = b'_',
+        KANJI_E5 => base[0] = DELETED,
+        _ => {}
+    }
+    let latin1 = |bytes: &[u8], lower: bool| -> String {
+        let text: String = bytes.iter().map(|&b| char::from(b)).collect();
+        let text = text.trim_end_matches(' ');
+        if lower {
+            text.to_lowercase()
+        } else {
+            text.to_string()
+        }
+    };
+    let base = latin1(&base, raw[12] & LOWER_BASE != 0);
+    let ext = latin1(&raw[8..11], raw[12] & LOWER_EXT != 0);
+    if ext.is_empty() {
+        base
+    } else {
+        format!("{}.{}", base, ext)
+    }
+}
+
+/// Assembles a long name from its entries, which are stored last part
+/// first. Deleted entries lose their sequence numbers, so only their
+/// checksums are compared with each other.
+fn long_name_of(parts: &[&[u8]], allocated: bool, checksum: u8) -> Option<String> {
+    let last = parts.first()?;
+    let consistent = parts
+        .iter()
+        .all(|p| p[13] == last[13] && (p[0] == DELETED) != allocated);
+    if !consistent || (allocated && (last[13] != checksum || last[0] & 0x40 == 0)) {
+        return None;
+    }
+    let mut units = Vec::with_capacity(parts.len() * 13);
+    for part in parts.iter().rev() {
+        for range in [1..11, 14..26, 28..32] {
+            units.extend(part[range].chunks_exact(2).map(|c| le_u16(c, 0)));
+        }
+    }
+    let end = units.iter().position(|&u| u == 0).unwrap_or(units.len());
+    units.truncate(end);
+    Some(String::from_utf16_lossy(&units))
+}
+
+/// Decodes the UTC offset byte of an exFAT timestamp.
+fn exfat_utc_offset(byte: u8) -> Option<FixedOffset> {
+    if byte & 0x80 == 0 {
+        return FixedOffset::east_opt(0);
+    }
+    // A signed 7-bit count of 15-minute intervals
+    let quarters = i32::from(((byte << 1) as i8) >> 1);
+    FixedOffset::east_opt(quarters * 15 * 60)
+}
+
+/// Decodes a DOS date and time, plus a count of 10 ms units.
+fn dos_timestamp(
+    name: TimestampName,
+    date: u16,
+    time: u16,
+    centis: u8,
+    offset: Option<FixedOffset>,
+    prec: Precision,
+) -> Option<Timestamp> {
+    if date == 0 {
+        return None;
+    }
+    let day = NaiveDate::from_ymd_opt(
+        1980 + i32::from(date >> 9),
+        u32::from((date >> 5) & 0xf),
+        u32::from(date & 0x1f),
+    )?;
+    let local = day.and_hms_milli_opt(
+        u32::from(time >> 11),
+        u32::from((time >> 5) & 0x3f),
+        u32::from(time & 0x1f) * 2 + u32::from(centis) / 100,
+        u32::from(centis % 100) * 10,
+    )?;
+    let time = offset?.from_local_datetime(&local).single()?;
+    Some(Timestamp {
+        name: Some(name),
+        time: Some(time),
+        prec: Some(prec),
+    })
+}
+
+impl<R: Read + Seek> FileSystem for FatFileSystem<R> {
+    fn volume(&self) -> VolumeObject {
+        let mut volume = VolumeObject::with_ftype(self.fat_type.as_str());
+        volume.partition_offset = Some(self.offset);
+        volume.sector_size = Some(self.sector_size);
+        volume.block_size = Some(self.sector_size);
+        volume.block_count = Some(self.sector_count);
+        volume.first_block = Some(0);
+        volume.last_block = Some(self.sector_count.saturating_sub(1));
+        volume.allocated_only = Some(false);
+        volume
+    }
+
+    fn files(&mut self) -> Box<dyn Iterator<Item = Result<FileObject>> + '_> {
+        Box::new(Walk {
+            fs: self,
+            frames: Vec::new(),
+            started: false,
+            visited: HashSet::new(),
+            next_id: 1,
+        })
+    }
+
+    fn allocation_unit(&self) -> (u64, u64) {
+        (self.heap_start, self.cluster_size)
+    }
+}
+
+/// The entries of a directory still to be reported, in reverse order.
+struct Frame {
+    path: String,
+    entries: Vec<Entry>,
+}
+
+/// Depth-first walk of the directory tree.
+struct Walk<'a, R: Read + Seek> {
+    fs: &'a mut FatFileSystem<R>,
+    frames: Vec<Frame>,
+    started: bool,
+    /// First clusters of the directories walked so far
+    visited: HashSet<u32>,
+    next_id: u64,
+}
+
+impl<R: Read + Seek> Walk<'_, R> {
+    fn push_frame(&mut self, path: String, mut entries: Vec<Entry>) {
+        entries.reverse();
+        self.frames.push(Frame { path, entries });
+    }
+}
+
+impl<R: Read + Seek> Iterator for Walk<'_, R> {
+    type Item = Result<FileObject>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            if let Root::Cluster(root) = self.fs.root {
+                self.visited.insert(root);
+            }
+            match self.fs.read_root() {
+                Ok(entries) => self.push_frame(String::new(), entries),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        loop {
+            let frame = self.frames.last_mut()?;
+            let Some(entry) = frame.entries.pop() else {
+                self.frames.pop();
+                continue;
+            };
+            let path = if frame.path.is_empty() {
+                entry.name.clone()
+            } else {
+                format!("{}/{}", frame.path, entry.name)
+            };
+
+            let mut file = FileObject::with_filename(path.clone());
+            file.id = Some(self.next_id);
+            self.next_id += 1;
+            if let Some(clusters) = self.fs.describe(&mut file, &entry) {
+                if self.visited.insert(clusters[0]) {
+                    match self.fs.read_dir(&clusters) {
+                        Ok(entries) => self.push_frame(path, entries),
+                        Err(e) => file.error = Some(e.to_string()),
+                    }
+                }
+            }
+            return Some(Ok(file));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::ContentReader;
+    use std::io::Cursor;
+
+    const SECTOR: usize = 512;
+
+    /// Builds FAT images one sector per cluster.
+    struct Builder {
+        image: Vec<u8>,
+        fat_type: FatType,
+        fat_offset: usize,
+        heap_start: usize,
+        root_offset: usize,
+    }
+
+    impl Builder {
+        fn new(fat_type: FatType) -> Self {
+            // (total sectors, reserved sectors, FAT sectors, root entries)
+            let (sectors, reserved, fat_sectors, root_entries) = match fat_type {
+                FatType::Fat12 => (400u32, 1u16, 2u32, 64u16),
+                FatType::Fat16 => (4200, 1, 17, 64),
+                FatType::Fat32 => (66200, 32, 518, 0),
+                FatType::ExFat => unreachable!(),
+            };
+            let mut image = vec![0u8; sectors as usize * SECTOR];
+            let boot = &mut image[..SECTOR];
+            boot[..3].copy_from_slice(&[0xeb, 0x3c, 0x90]);
+            boot[3..11].copy_from_slice(b"MSWIN4.1");
+            boot[11..13].copy_from_slice(&(SECTOR as u16).to_le_bytes());
+            boot[13] = 1;
+            boot[14..16].copy_from_slice(&reserved.to_le_bytes());
+            boot[16] = 1;
+            boot[17..19].copy_from_slice(&root_entries.to_le_bytes());
+            boot[32..36].copy_from_slice(&sectors.to_le_bytes());
+            if fat_type == FatType::Fat32 {
+                boot[36..40].copy_from_slice(&fat_sectors.to_le_bytes());
+                boot[44..48].copy_from_slice(&2u32.to_le_bytes());
+            } else {
+                boot[22..24].copy_from_slice(&(fat_sectors as u16).to_le_bytes());
+            }
+            boot[510..512].copy_from_slice(&[0x55, 0xaa]);
+
+            let fat_offset = usize::from(reserved) * SECTOR;
+            let root_offset = fat_offset + fat_sectors as usize * SECTOR;
+            let heap_start = root_offset + usize::from(root_entries) * DIR_ENTRY_LEN;
+            let mut builder = Self {
+                image,
+                fat_type,
+                fat_offset,
+                heap_start,
+                root_offset,
+            };
+            builder.set_fat(0, 0xffff_fff8);
+            builder.set_fat(1, 0xffff_ffff);
+            if fat_type == FatType::Fat32 {
+                builder.chain(&[2]);
+            }
+            builder
+        }
+
+        fn set_fat(&mut self, cluster: u32, value: u32) {
+            let c = cluster as usize;
+            match self.fat_type {
+                FatType::Fat12 => {
+                    let at = self.fat_offset + c * 3 / 2;
+                    let mut pair = le_u16(&self.image, at);
+                    pair = if c.is_multiple_of(2) {
+                        (pair & 0xf000) | (value as u16 & 0xfff)
+                    } else {
+                        (pair & 0x000f) | ((value as u16) << 4)
+                    };
+                    self.image[at..at + 2].copy_from_slice(&pair.to_le_bytes());
+                }
+                FatType::Fat16 => {
+                    let at = self.fat_offset + c * 2;
+                    self.image[at..at + 2].copy_from_slice(&(value as u16).to_le_bytes());
+                }
+                _ => {
+                    let at = self.fat_offset + c * 4;
+                    self.image[at..at + 4].copy_from_slice(&value.to_le_bytes());
+                }
+            }
+        }
+
+        fn chain(&mut self, clusters: &[u32]) {
+            for pair in clusters.windows(2) {
+                self.set_fat(pair[0], pair[1]);
+            }
+            self.set_fat(*clusters.last().unwrap(), 0xffff_ffff);
+        }
+
+        fn cluster(&self, cluster: u32) -> usize {
+            self.heap_start + (cluster as usize - 2) * SECTOR
+        }
+
+        fn write(&mut self, at: usize, data: &[u8]) {
+            self.image[at..at + data.len()].copy_from_slice(data);
+        }
+
+        /// Writes a file's content across its clusters.
+        fn write_content(&mut self, clusters: &[u32], data: &[u8]) {
+            for (&cluster, chunk) in clusters.iter().zip(data.chunks(SECTOR)) {
+                let at = self.cluster(cluster);
+                self.write(at, chunk);
+            }
+        }
+
+        fn root(&self) -> usize {
+            if self.fat_type == FatType::Fat32 {
+                self.cluster(2)
+            } else {
+                self.root_offset
+            }
+        }
+    }
+
+    /// A short directory entry, modified 2024-01-02 03:04:06 and created
+    /// 2024-01-02 03:04:05.5.
+    fn short_entry(name: &[u8; 11], attr: u8, case: u8, cluster: u32, size: u32) -> Vec<u8> {
+        let mut raw = vec![0u8; DIR_ENTRY_LEN];
+        raw[..11].copy_from_slice(name);
+        raw[11] = attr;
+        raw[12] = case;
+        let date: u16 = (44 << 9) | (1 << 5) | 2;
+        raw[13] = 150;
+        raw[14..16].copy_from_slice(&((3 << 11) | (4 << 5) | 2u16).to_le_bytes());
+        raw[16..18].copy_from_slice(&date.to_le_bytes());
+        raw[18..20].copy_from_slice(&date.to_le_bytes());
+        raw[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+        raw[22..24].copy_from_slice(&((3 << 11) | (4 << 5) | 3u16).to_le_bytes());
+        raw[24..26].copy_from_slice(&date.to_le_bytes());
+        raw[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+        raw[28..32].copy_from_slice(&size.to_le_bytes());
+        raw
+    }
+
+    /// Long name entries for a short name, last part first.
+    fn long_entries(name: &str, short: &[u8; 11]) -> Vec<u8> {
+        let checksum = short
+            .iter()
+            .fold(0u8, |sum, &b| sum.rotate_right(1).wrapping_add(b));
+        let mut units: Vec<u16> = name.encode_utf16().collect();
+        units.push(0);
+        while !units.len().is_multiple_of(13) {
+            units.push(0xffff);
+        }
+        let parts: Vec<&[u16]> = units.chunks(13).collect();
+        let mut out = Vec::new();
+        for (seq, part) in parts.iter().enumerate().rev() {
+            let mut raw = vec![0u8; DIR_ENTRY_LEN];
+            raw[0] = (seq as u8 + 1) | if seq == parts.len() - 1 { 0x40 } else { 0 };
+            raw[11] = ATTR_LONG_NAME;
+            raw[13] = checksum;
+            let bytes: Vec<u8> = part.iter().flat_map(|u| u.to_le_bytes()).collect();
+            raw[1..11].copy_from_slice(&bytes[..10]);
+            raw[14..26].copy_from_slice(&bytes[10..22]);
+            raw[28..32].copy_from_slice(&bytes[22..26]);
+            out.extend(raw);
+        }
+        out
+    }
+
+    fn deleted(mut entries: Vec<u8>) -> Vec<u8> {
+        for raw in entries.chunks_mut(DIR_ENTRY_LEN) {
+            raw[0] = DELETED;
+        }
+        entries
+    }
+
+    fn fat_image(fat_type: FatType) -> Vec<u8> {
+        let mut b = Builder::new(fat_type);
+        let mut root = short_entry(b"FIXTURE    ", ATTR_VOLUME_ID, 0, 0, 0);
+        root.extend(short_entry(
+            b"HELLO   TXT",
+            0x20,
+            LOWER_BASE | LOWER_EXT,
+            3,
+            13,
+        ));
+        b.chain(&[3]);
+        b.write_content(&[3], b"hello, world\n");
+
+        let long = b"ALONGF~1TXT";
+        root.extend(long_entries("A long file name.txt", long));
+        root.extend(short_entry(long, 0x20, 0, 4, 5));
+        b.chain(&[4]);
+        b.write_content(&[4], b"long\n");
+
+        root.extend(short_entry(b"DOCS       ", ATTR_DIRECTORY, 0, 5, 0));
+        b.chain(&[5]);
+        let mut docs = short_entry(b".          ", ATTR_DIRECTORY, 0, 5, 0);
+        docs.extend(short_entry(b"..         ", ATTR_DIRECTORY, 0, 0, 0));
+        docs.extend(short_entry(b"FRAG    BIN", 0x20, 0, 6, 1300));
+        let frag: Vec<u8> = (0..1300u32).map(|i| (i % 251) as u8).collect();
+        b.chain(&[6, 9, 7]);
+        b.write_content(&[6, 9, 7], &frag);
+        let at = b.cluster(5);
+        b.write(at, &docs);
+
+        // Deleted: its FAT entries are cleared, so clusters 10 and 11 are free
+        let gone = b"DELETE~1TXT";
+        root.extend(deleted(long_entries("Deleted file.txt", gone)));
+        root.extend(deleted(short_entry(gone, 0x20, 0, 10, 700)));
+        b.write_content(&[10, 11], &[b'x'; 700]);
+
+        let at = b.root();
+        b.write(at, &root);
+        b.image
+    }
+
+    fn walk(image: &[u8]) -> (VolumeObject, Vec<FileObject>) {
+        let mut fs = crate::filesystem::open(Cursor::new(image), 0).unwrap();
+        let files = fs.files().collect::<Result<Vec<_>>>().unwrap();
+        (fs.volume(), files)
+    }
+
+    fn find<'a>(files: &'a [FileObject], name: &str) -> &'a FileObject {
+        files
+            .iter()
+            .find(|f| f.filename.as_deref() == Some(name))
+            .unwrap_or_else(|| panic!("no file named {}", name))
+    }
+
+    fn content(image: &[u8], file: &FileObject) -> Vec<u8> {
+        let mut out = Vec::new();
+        ContentReader::new(Cursor::new(image), file)
+            .unwrap()
+            .read_to_end(&mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_fat_types() {
+        for (fat_type, name) in [
+            (FatType::Fat12, "fat12"),
+            (FatType::Fat16, "fat16"),
+            (FatType::Fat32, "fat32"),
+        ] {
+            let image = fat_image(fat_type);
+            let (volume, files) = walk(&image);
+            assert_eq!(volume.ftype_str.as_deref(), Some(name));
+            assert_eq!(volume.block_size, Some(512));
+            assert_eq!(volume.block_count, Some((image.len() / SECTOR) as u64));
+
+            // Data runs start on allocation unit boundaries
+            let fs = FatFileSystem::open(Cursor::new(&image), 0).unwrap();
+            let (unit_offset, unit_size) = fs.allocation_unit();
+            assert_eq!(unit_size, fs.cluster_size);
+            for run in files.iter().flat_map(|f| f.data_brs.iter().flatten()) {
+                assert_eq!((run.fs_offset.unwrap() - unit_offset) % unit_size, 0);
+            }
+
+            let names: Vec<&str> = files
+                .iter()
+                .map(|f| f.filename.as_deref().unwrap())
+                .collect();
+            assert_eq!(
+                names,
+                [
+                    "hello.txt",
+                    "A long file name.txt",
+                    "DOCS",
+                    "DOCS/FRAG.BIN",
+                    "Deleted file.txt"
+                ]
+            );
+
+            let hello = find(&files, "hello.txt");
+            assert_eq!(hello.alloc_name, Some(true));
+            assert_eq!(hello.filesize, Some(13));
+            assert_eq!(content(&image, hello), b"hello, world\n");
+            assert_eq!(
+                content(&image, find(&files, "A long file name.txt")),
+                b"long\n"
+            );
+
+            let docs = find(&files, "DOCS");
+            assert_eq!(docs.name_type, Some(NameType::Directory));
+            assert_eq!(docs.filesize, Some(512));
+
+            let frag = find(&files, "DOCS/FRAG.BIN");
+            let runs: Vec<&ByteRun> = frag.data_brs.as_ref().unwrap().iter().collect();
+            assert_eq!(runs.len(), 3);
+            assert_eq!(runs[2].len, Some(1300 - 1024));
+            let expected: Vec<u8> = (0..1300u32).map(|i| (i % 251) as u8).collect();
+            assert_eq!(content(&image, frag), expected);
+
+            let inode_run = frag.inode_brs.as_ref().unwrap().get(0).unwrap();
+            assert_eq!(inode_run.len, Some(32));
+            assert_eq!(
+                &image[inode_run.img_offset.unwrap() as usize..][..8],
+                b"FRAG    "
+            );
+        }
+    }
+
+    #[test]
+    fn test_deleted_entry() {
+        let image = fat_image(FatType::Fat16);
+        let (_, files) = walk(&image);
+        let gone = find(&files, "Deleted file.txt");
+        assert_eq!(gone.alloc_name, Some(false));
+        assert_eq!(gone.alloc_inode, Some(false));
+        assert_eq!(gone.filesize, Some(700));
+        assert_eq!(gone.data_brs.as_ref().unwrap().len(), 1);
+        assert_eq!(content(&image, gone), [b'x'; 700]);
+    }
+
+    #[test]
+    fn test_timestamps_and_inodes() {
+        let image = fat_image(FatType::Fat12);
+        let (_, files) = walk(&image);
+        let hello = find(&files, "hello.txt");
+
+        let mtime = hello.mtime.as_ref().unwrap();
+        assert_eq!(
+            mtime.time.unwrap().to_rfc3339(),
+            "2024-01-02T03:04:06+00:00"
+        );
+        assert_eq!(mtime.prec, Some(Precision::new(2, TimeUnit::Second)));
+        let crtime = hello.crtime.as_ref().unwrap();
+        assert_eq!(
+            crtime.time.unwrap().to_rfc3339(),
+            "2024-01-02T03:04:05.500+00:00"
+        );
+        assert_eq!(crtime.prec, Some(Precision::new(10, TimeUnit::Millisecond)));
+        let atime = hello.atime.as_ref().unwrap();
+        assert_eq!(atime.prec, Some(Precision::new(1, TimeUnit::Day)));
+
+        // The label is entry 0 of the root directory, just past the FATs
+        assert_eq!(hello.inode, Some(4));
+        let ids: Vec<u64> = files.iter().map(|f| f.id.unwrap()).collect();
+        assert_eq!(ids, [1, 2, 3, 4, 5]);
+    }
+
+    /// Builds a small exFAT image with 512-byte clusters.
+    fn exfat_image() -> Vec<u8> {
+        const FAT: usize = 24 * SECTOR;
+        const HEAP: usize = 32 * SECTOR;
+        const CLUSTERS: u32 = 500;
+        let mut image = vec![0u8; HEAP + CLUSTERS as usize * SECTOR];
+        let boot = &mut image[..SECTOR];
+        boot[..3].copy_from_slice(&[0xeb, 0x76, 0x90]);
+        boot[3..11].copy_from_slice(b"EXFAT   ");
+        let sectors = (HEAP / SECTOR) as u64 + u64::from(CLUSTERS);
+        boot[72..80].copy_from_slice(&sectors.to_le_bytes());
+        boot[80..84].copy_from_slice(&24u32.to_le_bytes());
+        boot[84..88].copy_from_slice(&8u32.to_le_bytes());
+        boot[88..92].copy_from_slice(&32u32.to_le_bytes());
+        boot[92..96].copy_from_slice(&CLUSTERS.to_le_bytes());
+        boot[96..100].copy_from_slice(&4u32.to_le_bytes());
+        boot[108] = 9;
+        boot[109] = 0;
+        boot[110] = 1;
+        boot[510..512].copy_from_slice(&[0x55, 0xaa]);
+
+        let cluster = |c: u32| HEAP + (c as usize - 2) * SECTOR;
+        let set_fat = |image: &mut Vec<u8>, c: u32, v: u32| {
+            let at = FAT + c as usize * 4;
+            image[at..at + 4].copy_from_slice(&v.to_le_bytes());
+        };
+        // Bitmap, up-case table, root, hello.txt, Docs, and Docs/frag.bin
+        for c in [2, 3, 4, 6] {
+            set_fat(&mut image, c, 0xffff_ffff);
+        }
+        set_fat(&mut image, 7, 9);
+        set_fat(&mut image, 9, 8);
+        set_fat(&mut image, 8, 0xffff_ffff);
+        for c in [2u32, 3, 4, 5, 6, 7, 8, 9] {
+            let bit = (c - 2) as usize;
+            image[cluster(2) + bit / 8] |= 1 << (bit % 8);
+        }
+
+        fn file_set(
+            name: &str,
+            attr: u16,
+            in_use: bool,
+            flags: u8,
+            first: u32,
+            size: u64,
+        ) -> Vec<u8> {
+            let units: Vec<u16> = name.encode_utf16().collect();
+            let names = units.len().div_ceil(15);
+            let bit = if in_use { EXFAT_IN_USE } else { 0 };
+            let mut file = vec![0u8; DIR_ENTRY_LEN];
+            file[0] = EXFAT_FILE | bit;
+            file[1] = 1 + names as u8;
+            file[4..6].copy_from_slice(&attr.to_le_bytes());
+            // 2024-01-02 03:04:06 at UTC-05:00, plus 1.23 s on the modification time
+            let stamp: u32 = ((44 << 9 | 1 << 5 | 2) << 16) | (3 << 11 | 4 << 5 | 3);
+            for at in [8, 12, 16] {
+                file[at..at + 4].copy_from_slice(&stamp.to_le_bytes());
+            }
+            file[21] = 123;
+            file[22..25].fill(0x80 | (-20i8 as u8 & 0x7f));
+            let mut stream = vec![0u8; DIR_ENTRY_LEN];
+            stream[0] = EXFAT_STREAM | bit;
+            stream[1] = 0x01 | flags;
+            stream[3] = units.len() as u8;
+            stream[8..16].copy_from_slice(&size.to_le_bytes());
+            stream[20..24].copy_from_slice(&first.to_le_bytes());
+            stream[24..32].copy_from_slice(&size.to_le_bytes());
+            let mut out = [file, stream].concat();
+            for part in units.chunks(15) {
+                let mut raw = vec![0u8; DIR_ENTRY_LEN];
+                raw[0] = EXFAT_NAME | bit;
+                for (i, u) in part.iter().enumerate() {
+                    raw[2 + i * 2..4 + i * 2].copy_from_slice(&u.to_le_bytes());
+                }
+                out.extend(raw);
+            }
+            out
+        }
+
+        let mut root = vec![0u8; 3 * DIR_ENTRY_LEN];
+        root[0] = 0x83;
+        root[32] = EXFAT_BITMAP;
+        root[52..56].copy_from_slice(&2u32.to_le_bytes());
+        root[56..64].copy_from_slice(&u64::from(CLUSTERS.div_ceil(8)).to_le_bytes());
+        root[64] = 0x82;
+        root[84..88].copy_from_slice(&3u32.to_le_bytes());
+        root.extend(file_set("hello.txt", 0x20, true, EXFAT_NO_FAT_CHAIN, 5, 13));
+        root.extend(file_set("Docs", 0x10, true, 0, 6, SECTOR as u64));
+        root.extend(file_set(
+            "a deleted file with a long name.txt",
+            0x20,
+            false,
+            EXFAT_NO_FAT_CHAIN,
+            10,
+            700,
+        ));
+        image[cluster(4)..][..root.len()].copy_from_slice(&root);
+        image[cluster(5)..][..13].copy_from_slice(b"hello, world\n");
+
+        let docs = file_set("frag.bin", 0x20, true, 0, 7, 1300);
+        image[cluster(6)..][..docs.len()].copy_from_slice(&docs);
+        let frag: Vec<u8> = (0..1300u32).map(|i| (i % 251) as u8).collect();
+        for (c, chunk) in [7, 9, 8].into_iter().zip(frag.chunks(SECTOR)) {
+            image[cluster(c)..][..chunk.len()].copy_from_slice(chunk);
+        }
+        image[cluster(10)..][..700].fill(b'x');
+        image
+    }
+
+    #[test]
+    fn test_exfat() {
+        let image = exfat_image();
+        let (volume, files) = walk(&image);
+        assert_eq!(volume.ftype_str.as_deref(), Some("exfat"));
+
+        let names: Vec<&str> = files
+            .iter()
+            .map(|f| f.filename.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "hello.txt",
+                "Docs",
+                "Docs/frag.bin",
+                "a deleted file with a long name.txt"
+            ]
+        );
+        assert_eq!(
+            content(&image, find(&files, "hello.txt")),
+            b"hello, world\n"
+        );
+        let frag = find(&files, "Docs/frag.bin");
+        let expected: Vec<u8> = (0..1300u32).map(|i| (i % 251) as u8).collect();
+        assert_eq!(content(&image, frag), expected);
+        assert_eq!(frag.inode_brs.as_ref().unwrap().total_len(), Some(96));
+
+        let mtime = frag.mtime.as_ref().unwrap();
+        assert_eq!(
+            mtime.time.unwrap().to_rfc3339(),
+            "2024-01-02T03:04:07.230-05:00"
+        );
+        assert_eq!(mtime.prec, Some(Precision::new(10, TimeUnit::Millisecond)));
+
+        let gone = find(&files, "a deleted file with a long name.txt");
+        assert_eq!(gone.alloc_name, Some(false));
+        assert_eq!(content(&image, gone), [b'x'; 700]);
+    }
+
+    #[test]
+    fn test_corrupt_exfat_geometry() {
+        let boot = |sector_count: u64| {
+            let mut image = vec![0u8; 4096];
+            image[..3].copy_from_slice(&[0xeb, 0x76, 0x90]);
+            image[3..11].copy_from_slice(b"EXFAT   ");
+            image[72..80].copy_from_slice(&sector_count.to_le_bytes());
+            image[80..84].copy_from_slice(&1u32.to_le_bytes());
+            image[84..88].copy_from_slice(&u32::MAX.to_le_bytes());
+            image[88..92].copy_from_slice(&2u32.to_le_bytes());
+            image[92..96].copy_from_slice(&(u32::MAX - 2).to_le_bytes());
+            image[96..100].copy_from_slice(&4u32.to_le_bytes());
+            image[108] = 12;
+            image[110] = 1;
+            image[510..512].copy_from_slice(&[0x55, 0xaa]);
+            image
+        };
+
+        // A FAT far larger than the image
+        let image = boot(u64::MAX / 4096);
+        assert!(matches!(
+            crate::filesystem::open(Cursor::new(&image), 0),
+            Err(Error::InvalidFilesystem(_))
+        ));
+
+        // A cluster heap larger than the volume
+        let image = boot(1);
+        assert!(!probe(&mut Cursor::new(&image), 0).unwrap());
+        assert!(matches!(
+            FatFileSystem::open(Cursor::new(&image), 0),
+            Err(Error::InvalidFilesystem(_))
+        ));
+    }
+
+    #[test]
+    fn test_probe() {
+        let image = fat_image(FatType::Fat12);
+        assert!(probe(&mut Cursor::new(&image), 0).unwrap());
+        assert!(probe(&mut Cursor::new(exfat_image()), 0).unwrap());
+        assert!(!probe(&mut Cursor::new(&image), 512).unwrap());
+        assert!(!probe(&mut Cursor::new(&image[..100]), 0).unwrap());
+    }
+}
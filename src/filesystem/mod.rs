@@ -0,0 +1,102 @@
+//! File system walkers for raw disk images.
+//!
+//! These read file system metadata straight from an image (or any
+//! `Read + Seek` source) instead of going through a mounted directory, so
+//! they can describe what [`walk_to_dfxml`]-style walks cannot: deleted
+//! names, orphan inodes, and the image locations of each file's data and
+//! metadata as byte runs.
+//!
+//! [`open`] detects the file system at a partition offset. Supported file
+//! systems:
+//!
+//! - ext2, ext3 and ext4 ([`ext`])
+//! - FAT12, FAT16, FAT32 and exFAT ([`fat`])
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use dfxml_rs::filesystem;
+//! use std::fs::File;
+//!
+//! let image = File::open("disk.raw").unwrap();
+//! let mut fs = filesystem::open(image, 0).unwrap();
+//! println!("{:?}", fs.volume().ftype_str);
+//! for file in fs.files() {
+//!     let file = file.unwrap();
+//!     println!("{:?} {:?}", file.filename, file.data_brs);
+//! }
+//! ```
+//!
+//! [`walk_to_dfxml`]: https://github.com/kamwoods/dfxml-rs#walk_to_dfxml
+
+pub mod ext;
+pub mod fat;
+
+use crate::error::{Error, Result};
+use crate::objects::{FileObject, VolumeObject};
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// A file system that can be walked into DFXML.
+pub trait FileSystem {
+    /// Returns the volume the file system occupies, without its files.
+    fn volume(&self) -> VolumeObject;
+
+    /// Returns the file system's files, allocated and deleted.
+    ///
+    /// An error for one file does not end the walk; later calls to `next`
+    /// carry on with the files after it.
+    fn files(&mut self) -> Box<dyn Iterator<Item = Result<FileObject>> + '_>;
+
+    /// Returns the file system offset of the first allocation unit (block or
+    /// cluster) and the size of each, which file slack is measured to.
+    fn allocation_unit(&self) -> (u64, u64);
+}
+
+/// Opens the file system that starts `offset` bytes into an image,
+/// detecting its type.
+pub fn open<'a, R: Read + Seek + 'a>(
+    mut image: R,
+    offset: u64,
+) -> Result<Box<dyn FileSystem + 'a>> {
+    if ext::probe(&mut image, offset)? {
+        return Ok(Box::new(ext::ExtFileSystem::open(image, offset)?));
+    }
+    if fat::probe(&mut image, offset)? {
+        return Ok(Box::new(fat::FatFileSystem::open(image, offset)?));
+    }
+    Err(Error::InvalidFilesystem(format!(
+        "no supported file system at offset {}",
+        offset
+    )))
+}
+
+/// Reads exactly `buf.len()` bytes at an image offset.
+pub(crate) fn read_at<R: Read + Seek>(image: &mut R, offset: u64, buf: &mut [u8]) -> Result<()> {
+    image.seek(SeekFrom::Start(offset))?;
+    image.read_exact(buf).map_err(|e| {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            Error::InvalidFilesystem(format!(
+                "image ends before {} bytes at offset {}",
+                buf.len(),
+                offset
+            ))
+        } else {
+            e.into()
+        }
+    })
+}
+
+/// Reads a little-endian `u16` at `pos`.
+pub(crate) fn le_u16(buf: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([buf[pos], buf[pos + 1]])
+}
+
+/// Reads a little-endian `u32` at `pos`.
+pub(crate) fn le_u32(buf: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(buf[pos..pos + 4].try_into().unwrap())
+}
+
+/// Reads a little-endian `u64` at `pos`.
+pub(crate) fn le_u64(buf: &[u8], pos: usize) -> u64 {
+    u64::from_le_bytes(buf[pos..pos + 8].try_into().unwrap())
+}
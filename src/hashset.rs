@@ -0,0 +1,759 @@
+//! Known-file hash sets.
+//!
+//! A [`HashDb`] holds digests of known files, keyed by [`HashType`], each
+//! marked as known-good (e.g. NSRL operating system files) or known-bad
+//! (e.g. contraband or malware). File objects are classified by looking up
+//! every hash they carry.
+//!
+//! Hash sets can be loaded from:
+//!
+//! - Plain hash lists as written by `md5sum`, `sha1sum` and `sha256sum`
+//!   (the algorithm is inferred from the digest length), including BSD-style
+//!   `MD5 (name) = digest` lines
+//! - Legacy NSRL RDS `NSRLFile.txt` files
+//! - NSRL RDSv3 SQLite databases (requires the `nsrl` feature)
+//! - Other DFXML documents
+//!
+//! # Example
+//!
+//! ```rust
+//! use dfxml_rs::hashset::{Classification, Disposition, HashDb};
+//! use dfxml_rs::objects::{FileObject, HashType};
+//! use std::io::Cursor;
+//!
+//! let mut db = HashDb::new();
+//! let list = "d41d8cd98f00b204e9800998ecf8427e  empty.txt\n";
+//! db.load_hash_list(Cursor::new(list), Disposition::Good).unwrap();
+//!
+//! let mut file = FileObject::with_filename("blank");
+//! file.hashes.set(HashType::Md5, "d41d8cd98f00b204e9800998ecf8427e".to_string());
+//! assert_eq!(db.classify(&file), Classification::KnownGood);
+//! ```
+
+use crate::error::{Error, Result};
+use crate::objects::{ChildObject, DFXMLObject, FileObject, HashType};
+use crate::reader::{DFXMLReader, Event};
+use crate::writer::{DFXMLStreamWriter, WriterConfig};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+/// Whether a hash set entry marks a file as good or bad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Disposition {
+    /// Known-good (ignorable) file
+    Good,
+    /// Known-bad (notable) file
+    Bad,
+}
+
+/// Classification of a file object against a [`HashDb`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Classification {
+    /// At least one hash is known-good and none is known-bad
+    KnownGood,
+    /// At least one hash is known-bad
+    KnownBad,
+    /// No hash is in the database
+    Unknown,
+}
+
+impl Classification {
+    /// Returns the classification name used in reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Classification::KnownGood => "known_good",
+            Classification::KnownBad => "known_bad",
+            Classification::Unknown => "unknown",
+        }
+    }
+}
+
+impl std::fmt::Display for Classification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Hash set file formats understood by [`HashDb::load_file`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashSetFormat {
+    /// `md5sum`-style lists, one digest per line
+    HashList,
+    /// Legacy NSRL RDS `NSRLFile.txt` (quoted CSV)
+    NsrlLegacy,
+    /// NSRL RDSv3 SQLite database
+    NsrlRds3,
+    /// A DFXML document
+    Dfxml,
+}
+
+impl HashSetFormat {
+    /// Guesses the format of a file from its first bytes.
+    pub fn detect(path: impl AsRef<Path>) -> Result<Self> {
+        let mut head = Vec::with_capacity(512);
+        File::open(path)?.take(512).read_to_end(&mut head)?;
+        Ok(Self::detect_bytes(&head))
+    }
+
+    /// Guesses the format from the start of a file's content.
+    pub fn detect_bytes(head: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(head);
+        let text = text.trim_start_matches('\u{feff}').trim_start();
+        if head.starts_with(b"SQLite format 3\0") {
+            HashSetFormat::NsrlRds3
+        } else if text.starts_with("<?xml") || text.starts_with("<dfxml") {
+            HashSetFormat::Dfxml
+        } else if text.starts_with("\"SHA-1\"") || text.starts_with("\"SHA1\"") {
+            HashSetFormat::NsrlLegacy
+        } else {
+            HashSetFormat::HashList
+        }
+    }
+}
+
+/// An in-memory set of known file hashes.
+///
+/// Digests are stored as raw bytes in one map per algorithm. When the same
+/// digest is loaded as both good and bad, bad wins.
+#[derive(Debug, Clone, Default)]
+pub struct HashDb {
+    sets: HashMap<HashType, HashMap<Box<[u8]>, Disposition>>,
+}
+
+impl HashDb {
+    /// Creates an empty database.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a hex digest. Returns true if the digest was not present yet.
+    pub fn insert(
+        &mut self,
+        hash_type: HashType,
+        digest: &str,
+        disposition: Disposition,
+    ) -> Result<bool> {
+        let key = decode_digest(hash_type, digest)?;
+        let set = self.sets.entry(hash_type).or_default();
+        match set.get_mut(&key) {
+            Some(existing) => {
+                *existing = (*existing).max(disposition);
+                Ok(false)
+            }
+            None => {
+                set.insert(key, disposition);
+                Ok(true)
+            }
+        }
+    }
+
+    /// Looks up a hex digest.
+    ///
+    /// Malformed digests are reported as not present.
+    pub fn lookup(&self, hash_type: HashType, digest: &str) -> Option<Disposition> {
+        let set = self.sets.get(&hash_type)?;
+        let key = decode_digest(hash_type, digest).ok()?;
+        set.get(&key).copied()
+    }
+
+    /// Classifies a file object by every hash it carries.
+    pub fn classify(&self, file: &FileObject) -> Classification {
+        let mut known_good = false;
+        for (hash_type, digest) in file.hashes.iter() {
+            match self.lookup(hash_type, digest) {
+                Some(Disposition::Bad) => return Classification::KnownBad,
+                Some(Disposition::Good) => known_good = true,
+                None => {}
+            }
+        }
+        if known_good {
+            Classification::KnownGood
+        } else {
+            Classification::Unknown
+        }
+    }
+
+    /// Returns the total number of digests.
+    pub fn len(&self) -> usize {
+        self.sets.values().map(HashMap::len).sum()
+    }
+
+    /// Returns true if the database holds no digests.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of digests of one algorithm.
+    pub fn count(&self, hash_type: HashType) -> usize {
+        self.sets.get(&hash_type).map_or(0, HashMap::len)
+    }
+
+    /// Loads a hash set file, detecting its format when `format` is `None`.
+    ///
+    /// Returns the number of digests read.
+    pub fn load_file(
+        &mut self,
+        path: impl AsRef<Path>,
+        format: Option<HashSetFormat>,
+        disposition: Disposition,
+    ) -> Result<u64> {
+        let path = path.as_ref();
+        let format = match format {
+            Some(format) => format,
+            None => HashSetFormat::detect(path)?,
+        };
+        match format {
+            HashSetFormat::HashList => {
+                self.load_hash_list(BufReader::new(File::open(path)?), disposition)
+            }
+            HashSetFormat::NsrlLegacy => {
+                self.load_nsrl_legacy(BufReader::new(File::open(path)?), disposition)
+            }
+            HashSetFormat::Dfxml => self.load_dfxml(BufReader::new(File::open(path)?), disposition),
+            #[cfg(feature = "nsrl")]
+            HashSetFormat::NsrlRds3 => self.load_nsrl_rds3(path, disposition),
+            #[cfg(not(feature = "nsrl"))]
+            HashSetFormat::NsrlRds3 => Err(Error::InvalidHashSet(format!(
+                "{}: reading NSRL RDSv3 databases requires the nsrl feature",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Loads an `md5sum`/`sha1sum`/`sha256sum`-style list.
+    ///
+    /// Each line holds a digest, optionally followed by whitespace and a file
+    /// name; the algorithm is inferred from the digest length. BSD-style
+    /// `SHA256 (name) = digest` lines are also accepted. Blank lines and
+    /// lines starting with `#` are skipped.
+    pub fn load_hash_list<R: BufRead>(
+        &mut self,
+        input: R,
+        disposition: Disposition,
+    ) -> Result<u64> {
+        let mut count = 0;
+        for (index, line) in input.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let digest = match line.rsplit_once(") = ") {
+                Some((_, digest)) if line.contains(" (") => digest,
+                // GNU tools prefix lines with escaped names with a backslash
+                _ => line
+                    .trim_start_matches('\\')
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default(),
+            };
+            let hash_type = hash_type_for_len(digest.len()).ok_or_else(|| {
+                Error::InvalidHashSet(format!(
+                    "line {}: digest of unknown length {}",
+                    index + 1,
+                    digest.len()
+                ))
+            })?;
+            self.insert(hash_type, digest, disposition)
+                .map_err(|e| at_line(e, index + 1))?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Loads a legacy NSRL RDS `NSRLFile.txt`.
+    ///
+    /// The header line names the columns; the `SHA-1`, `MD5` and (where
+    /// present) `SHA-256` columns are loaded.
+    pub fn load_nsrl_legacy<R: BufRead>(
+        &mut self,
+        input: R,
+        disposition: Disposition,
+    ) -> Result<u64> {
+        let mut lines = input.lines().enumerate();
+        let header = match lines.next() {
+            Some((_, line)) => line?,
+            None => return Ok(0),
+        };
+        let columns: Vec<(usize, HashType)> = split_csv(header.trim_start_matches('\u{feff}'))
+            .iter()
+            .enumerate()
+            .filter_map(|(i, name)| {
+                let hash_type = match name.to_ascii_uppercase().as_str() {
+                    "SHA-1" | "SHA1" => HashType::Sha1,
+                    "MD5" => HashType::Md5,
+                    "SHA-256" | "SHA256" => HashType::Sha256,
+                    _ => return None,
+                };
+                Some((i, hash_type))
+            })
+            .collect();
+        if columns.is_empty() {
+            return Err(Error::InvalidHashSet(
+                "NSRL header names no hash columns".to_string(),
+            ));
+        }
+
+        let mut count = 0;
+        for (index, line) in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let fields = split_csv(&line);
+            for &(column, hash_type) in &columns {
+                let digest = fields.get(column).ok_or_else(|| {
+                    Error::InvalidHashSet(format!("line {}: missing column {}", index + 1, column))
+                })?;
+                if digest.is_empty() {
+                    continue;
+                }
+                self.insert(hash_type, digest, disposition)
+                    .map_err(|e| at_line(e, index + 1))?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Loads the `FILE` table of an NSRL RDSv3 SQLite database.
+    ///
+    /// The `sha256`, `sha1` and `md5` columns are loaded.
+    #[cfg(feature = "nsrl")]
+    pub fn load_nsrl_rds3(
+        &mut self,
+        path: impl AsRef<Path>,
+        disposition: Disposition,
+    ) -> Result<u64> {
+        use rusqlite::{Connection, OpenFlags};
+
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut stmt = conn.prepare("SELECT sha256, sha1, md5 FROM FILE")?;
+        let mut rows = stmt.query([])?;
+        let mut count = 0;
+        while let Some(row) = rows.next()? {
+            for (column, hash_type) in [HashType::Sha256, HashType::Sha1, HashType::Md5]
+                .into_iter()
+                .enumerate()
+            {
+                if let Some(digest) = row.get::<_, Option<String>>(column)? {
+                    if !digest.is_empty() {
+                        self.insert(hash_type, &digest, disposition)?;
+                        count += 1;
+                    }
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Loads every file hash from a DFXML document.
+    pub fn load_dfxml<R: BufRead>(&mut self, input: R, disposition: Disposition) -> Result<u64> {
+        let mut count = 0;
+        for event in DFXMLReader::from_reader(input) {
+            if let Event::FileObject(file) = event? {
+                for (hash_type, digest) in file.hashes.iter() {
+                    self.insert(hash_type, digest, disposition)?;
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
+}
+
+/// Returns the algorithm whose hex digests have this length.
+fn hash_type_for_len(len: usize) -> Option<HashType> {
+    [
+        HashType::Md5,
+        HashType::Sha1,
+        HashType::Sha224,
+        HashType::Sha256,
+        HashType::Sha384,
+        HashType::Sha512,
+    ]
+    .into_iter()
+    .find(|t| t.expected_hex_len() == len)
+}
+
+/// Decodes a hex digest, checking its length for fixed-size algorithms.
+fn decode_digest(hash_type: HashType, digest: &str) -> Result<Box<[u8]>> {
+    let digest = digest.trim();
+    let invalid = |message: &str| Error::InvalidHash {
+        hash_type: hash_type.as_str().to_string(),
+        message: format!("{} ({:?})", message, digest),
+    };
+    if hash_type != HashType::Md6 && digest.len() != hash_type.expected_hex_len() {
+        return Err(invalid("wrong length"));
+    }
+    if !digest.len().is_multiple_of(2) {
+        return Err(invalid("odd number of hex digits"));
+    }
+    digest
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let hex = std::str::from_utf8(pair).map_err(|_| invalid("not hexadecimal"))?;
+            u8::from_str_radix(hex, 16).map_err(|_| invalid("not hexadecimal"))
+        })
+        .collect()
+}
+
+/// Splits a line of comma-separated, optionally double-quoted fields.
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim_end_matches(['\r', '\n']).chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Prefixes an invalid-entry error with its line number.
+fn at_line(error: Error, line: usize) -> Error {
+    match error {
+        Error::InvalidHash { hash_type, message } => Error::InvalidHashSet(format!(
+            "line {}: invalid {} digest: {}",
+            line, hash_type, message
+        )),
+        other => other,
+    }
+}
+
+/// Numbers of file objects kept and dropped by [`filter_files`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FilterCounts {
+    /// File objects written to the output
+    pub kept: u64,
+    /// File objects left out
+    pub dropped: u64,
+}
+
+/// A container read by [`filter_files`] that may not be written yet.
+struct PendingContainer {
+    /// Properties read before the container's first child
+    props: Option<ChildObject>,
+    started: bool,
+}
+
+/// Streams a DFXML document to `output`, keeping only the file objects for
+/// which `keep` returns true.
+///
+/// The document and every container are copied even when none of their file
+/// objects are kept. Each container is written with the properties read
+/// before its first child, which for DFXML written by this crate or fiwalk
+/// is all of them.
+pub fn filter_files<R, W, F>(
+    input: R,
+    output: W,
+    config: WriterConfig,
+    mut keep: F,
+) -> Result<FilterCounts>
+where
+    R: BufRead,
+    W: Write,
+    F: FnMut(&FileObject) -> bool,
+{
+    let mut reader = DFXMLReader::from_reader(input);
+    let mut writer = DFXMLStreamWriter::with_config(output, config)?;
+    let mut doc: Option<DFXMLObject> = None;
+    let mut started = false;
+    let mut stack: Vec<PendingContainer> = Vec::new();
+    let mut counts = FilterCounts::default();
+
+    while let Some(event) = reader.next() {
+        let event = event?;
+        if matches!(event, Event::DFXMLStart(_)) {
+            continue;
+        }
+
+        // Anything after a start tag means the properties before it are read
+        if doc.is_none() {
+            doc = reader.document().cloned();
+        }
+        if let Some(top) = stack.last_mut() {
+            if top.props.is_none() {
+                top.props = current_container(&reader, &event);
+            }
+        }
+
+        match event {
+            Event::DFXMLStart(_) => {}
+            Event::DiskImageStart(_)
+            | Event::PartitionSystemStart(_)
+            | Event::PartitionStart(_)
+            | Event::VolumeStart(_) => stack.push(PendingContainer {
+                props: None,
+                started: false,
+            }),
+            Event::FileObject(file) => {
+                if keep(&file) {
+                    start_pending(&mut writer, &mut started, &doc, &mut stack)?;
+                    writer.write_file(&file)?;
+                    counts.kept += 1;
+                } else {
+                    counts.dropped += 1;
+                }
+            }
+            Event::DiskImageEnd(_)
+            | Event::PartitionSystemEnd(_)
+            | Event::PartitionEnd(_)
+            | Event::VolumeEnd(_) => {
+                start_pending(&mut writer, &mut started, &doc, &mut stack)?;
+                match stack.pop().and_then(|c| c.props) {
+                    Some(ChildObject::DiskImage(_)) => writer.end_disk_image()?,
+                    Some(ChildObject::PartitionSystem(_)) => writer.end_partition_system()?,
+                    Some(ChildObject::Partition(_)) => writer.end_partition()?,
+                    Some(ChildObject::Volume(_)) => writer.end_volume()?,
+                    Some(ChildObject::File(_)) | None => {}
+                }
+            }
+            Event::DFXMLEnd(end) => {
+                if !started {
+                    writer.start_document(&end)?;
+                    started = true;
+                }
+            }
+        }
+    }
+
+    if !started {
+        writer.start_document(&doc.unwrap_or_default())?;
+    }
+    writer.finish()?.flush()?;
+    Ok(counts)
+}
+
+/// Returns the properties of the container enclosing `event`, preferring
+/// the completed object carried by an end event.
+fn current_container<R: BufRead>(reader: &DFXMLReader<R>, event: &Event) -> Option<ChildObject> {
+    match event {
+        Event::DiskImageEnd(di) => {
+            return Some(ChildObject::DiskImage(di.without_children()));
+        }
+        Event::PartitionSystemEnd(ps) => {
+            return Some(ChildObject::PartitionSystem(ps.without_children()));
+        }
+        Event::PartitionEnd(p) => return Some(ChildObject::Partition(p.without_children())),
+        Event::VolumeEnd(vol) => return Some(ChildObject::Volume(vol.without_children())),
+        Event::DiskImageStart(_)
+        | Event::PartitionSystemStart(_)
+        | Event::PartitionStart(_)
+        | Event::VolumeStart(_) => return reader.open_container(1),
+        _ => {}
+    }
+    reader.open_container(0)
+}
+
+/// Writes the document header and any containers not written yet.
+fn start_pending<W: Write>(
+    writer: &mut DFXMLStreamWriter<W>,
+    started: &mut bool,
+    doc: &Option<DFXMLObject>,
+    stack: &mut [PendingContainer],
+) -> Result<()> {
+    if !*started {
+        writer.start_document(doc.as_ref().unwrap_or(&DFXMLObject::new()))?;
+        *started = true;
+    }
+    for container in stack.iter_mut().filter(|c| !c.started) {
+        match &container.props {
+            Some(ChildObject::DiskImage(di)) => writer.start_disk_image(di)?,
+            Some(ChildObject::PartitionSystem(ps)) => writer.start_partition_system(ps)?,
+            Some(ChildObject::Partition(p)) => writer.start_partition(p)?,
+            Some(ChildObject::Volume(vol)) => writer.start_volume(vol)?,
+            Some(ChildObject::File(_)) | None => {}
+        }
+        container.started = true;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::VolumeObject;
+    use std::io::Cursor;
+
+    const MD5_A: &str = "0cc175b9c0f1b6a831c399e269772661";
+    const SHA1_A: &str = "86f7e437faa5a7fce15d1ddcb9eaeaea377667b8";
+    const SHA256_A: &str = "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb";
+
+    fn file_with(hash_type: HashType, digest: &str) -> FileObject {
+        let mut file = FileObject::with_filename("f");
+        file.hashes.set(hash_type, digest.to_string());
+        file
+    }
+
+    #[test]
+    fn test_hash_lists() {
+        let list = format!(
+            "# comment\n{}  a.txt\n\\{} *b\\nc\nSHA256 (a.txt) = {}\n\n",
+            MD5_A, SHA1_A, SHA256_A
+        );
+        let mut db = HashDb::new();
+        assert_eq!(
+            db.load_hash_list(Cursor::new(list), Disposition::Good)
+                .unwrap(),
+            3
+        );
+        assert_eq!(db.count(HashType::Md5), 1);
+        assert_eq!(db.count(HashType::Sha1), 1);
+        assert_eq!(db.count(HashType::Sha256), 1);
+        assert_eq!(
+            db.lookup(HashType::Md5, &MD5_A.to_uppercase()),
+            Some(Disposition::Good)
+        );
+
+        let err = db
+            .load_hash_list(Cursor::new("abc  x\n"), Disposition::Good)
+            .unwrap_err();
+        assert!(err.to_string().contains("line 1"));
+    }
+
+    #[test]
+    fn test_classify_bad_wins() {
+        let mut db = HashDb::new();
+        db.insert(HashType::Md5, MD5_A, Disposition::Good).unwrap();
+        db.insert(HashType::Sha1, SHA1_A, Disposition::Bad).unwrap();
+        db.insert(HashType::Md5, MD5_A, Disposition::Good).unwrap();
+        assert_eq!(db.len(), 2);
+
+        let mut file = file_with(HashType::Md5, MD5_A);
+        assert_eq!(db.classify(&file), Classification::KnownGood);
+        file.hashes.set(HashType::Sha1, SHA1_A.to_string());
+        assert_eq!(db.classify(&file), Classification::KnownBad);
+        assert_eq!(
+            db.classify(&file_with(HashType::Sha256, SHA256_A)),
+            Classification::Unknown
+        );
+        assert_eq!(db.classify(&FileObject::new()), Classification::Unknown);
+    }
+
+    #[test]
+    fn test_nsrl_legacy() {
+        let txt = format!(
+            "\"SHA-1\",\"MD5\",\"CRC32\",\"FileName\",\"FileSize\",\"ProductCode\",\"OpSystemCode\",\"SpecialCode\"\r\n\
+             \"{}\",\"{}\",\"E8B7BE43\",\"a, \"\"quoted\"\".txt\",1,1234,\"WIN\",\"\"\r\n",
+            SHA1_A.to_uppercase(),
+            MD5_A.to_uppercase()
+        );
+        assert_eq!(
+            HashSetFormat::detect_bytes(txt.as_bytes()),
+            HashSetFormat::NsrlLegacy
+        );
+        let mut db = HashDb::new();
+        assert_eq!(
+            db.load_nsrl_legacy(Cursor::new(txt), Disposition::Good)
+                .unwrap(),
+            2
+        );
+        assert_eq!(db.lookup(HashType::Sha1, SHA1_A), Some(Disposition::Good));
+        assert_eq!(split_csv("\"a, \"\"b\"\"\",1"), ["a, \"b\"", "1"]);
+    }
+
+    #[test]
+    fn test_dfxml_hash_set() {
+        let xml = format!(
+            r#"<?xml version="1.0"?>
+<dfxml version="1.0">
+  <fileobject>
+    <filename>malware.exe</filename>
+    <hashdigest type="md5">{}</hashdigest>
+  </fileobject>
+</dfxml>"#,
+            MD5_A
+        );
+        assert_eq!(
+            HashSetFormat::detect_bytes(xml.as_bytes()),
+            HashSetFormat::Dfxml
+        );
+        let mut db = HashDb::new();
+        db.load_dfxml(Cursor::new(xml), Disposition::Bad).unwrap();
+        assert_eq!(
+            db.classify(&file_with(HashType::Md5, MD5_A)),
+            Classification::KnownBad
+        );
+    }
+
+    #[cfg(feature = "nsrl")]
+    #[test]
+    fn test_nsrl_rds3() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("RDS.db");
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(&format!(
+            "CREATE TABLE FILE (sha256 VARCHAR NOT NULL, sha1 VARCHAR NOT NULL, \
+             md5 VARCHAR NOT NULL, crc32 VARCHAR NOT NULL, file_name VARCHAR NOT NULL, \
+             file_size INTEGER NOT NULL, package_id INTEGER NOT NULL);
+             INSERT INTO FILE VALUES ('{}', '{}', '{}', '00000000', 'a', 1, 1);",
+            SHA256_A.to_uppercase(),
+            SHA1_A.to_uppercase(),
+            MD5_A.to_uppercase()
+        ))
+        .unwrap();
+        drop(conn);
+
+        let mut db = HashDb::new();
+        assert_eq!(db.load_file(&path, None, Disposition::Good).unwrap(), 3);
+        assert_eq!(
+            db.classify(&file_with(HashType::Sha256, SHA256_A)),
+            Classification::KnownGood
+        );
+    }
+
+    #[test]
+    fn test_filter_files() {
+        let mut inner = VolumeObject::with_ftype("ntfs");
+        inner.block_size = Some(4096);
+        inner.append_file(FileObject::with_filename("drop.txt"));
+        let mut outer = VolumeObject::with_ftype("ext4");
+        outer.append_file(FileObject::with_filename("keep.txt"));
+        outer.append_volume(inner);
+        let mut doc = DFXMLObject::new();
+        doc.program = Some("test".to_string());
+        doc.append_volume(outer);
+        doc.append_file(FileObject::with_filename("drop2.txt"));
+        let xml = crate::writer::to_string(&doc).unwrap();
+
+        let mut out = Vec::new();
+        let counts = filter_files(
+            Cursor::new(xml.as_bytes()),
+            &mut out,
+            WriterConfig::default(),
+            |f| f.filename.as_deref() == Some("keep.txt"),
+        )
+        .unwrap();
+        assert_eq!(
+            counts,
+            FilterCounts {
+                kept: 1,
+                dropped: 2
+            }
+        );
+
+        // Same structure and metadata, minus the dropped files
+        let parsed = crate::reader::parse(Cursor::new(out)).unwrap();
+        assert_eq!(parsed.program.as_deref(), Some("test"));
+        let outer = parsed.volumes().next().unwrap();
+        assert_eq!(outer.ftype_str.as_deref(), Some("ext4"));
+        assert_eq!(outer.files().count(), 1);
+        let inner = outer.volumes().next().unwrap();
+        assert_eq!(inner.block_size, Some(4096));
+        assert_eq!(inner.files().count(), 0);
+        assert_eq!(parsed.files().count(), 0);
+    }
+}
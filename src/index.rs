@@ -0,0 +1,629 @@
+//! Byte-offset index for random access into large DFXML documents.
+//!
+//! A [`DFXMLIndex`] records where each `<fileobject>` starts in a document,
+//! together with the fields files are usually looked up by: filename, inode,
+//! partition, id and hashes. The index can be saved to a sidecar file next
+//! to the document, and [`DFXMLIndex::read_file`] then seeks to one entry
+//! and parses just that file object instead of streaming the whole document
+//! again.
+//!
+//! The namespace declarations in scope at each file object, including those
+//! on enclosing containers, are kept in the index, so file objects with
+//! prefixed child elements parse the same way as when the document is
+//! streamed.
+//!
+//! # Sidecar Format
+//!
+//! The sidecar is a UTF-8 text file of tab-separated lines:
+//!
+//! ```text
+//! dfxml-index  2  <document length in bytes>  <modification time>
+//! ns    <prefix>  <uri>
+//! scope <prefix>  <uri>  <prefix>  <uri> ...
+//! file  <offset>  <scope>  <id>  <partition>  <inode>  <type:digest,...>  <filename>
+//! ```
+//!
+//! `ns` lines hold the declarations of the document element. Each `scope`
+//! line lists the declarations in scope at some of the file objects, which
+//! refer to it by its number, counting from 0. The modification time is
+//! seconds and nanoseconds since the Unix epoch, as `<secs>.<nanos>`.
+//! Missing values are empty fields. Backslashes, tabs and line breaks in
+//! prefixes, URIs and filenames are escaped as `\\`, `\t`, `\n` and `\r`.
+//!
+//! # Example
+//!
+//! ```rust
+//! use dfxml_rs::index::DFXMLIndex;
+//! use std::io::Cursor;
+//!
+//! let xml = r#"<dfxml version="1.0">
+//!   <fileobject><filename>a.txt</filename><inode>12</inode></fileobject>
+//!   <fileobject><filename>b.txt</filename><inode>13</inode></fileobject>
+//! </dfxml>"#;
+//!
+//! let index = DFXMLIndex::build(Cursor::new(xml)).unwrap();
+//! let entry = index.by_filename("b.txt").next().unwrap();
+//! assert_eq!(entry.inode, Some(13));
+//!
+//! let file = index.read_file(&mut Cursor::new(xml), entry.offset).unwrap();
+//! assert_eq!(file.filename.as_deref(), Some("b.txt"));
+//! ```
+
+use crate::error::{Error, Result};
+use crate::objects::{FileObject, HashType, Hashes};
+use crate::reader::{root_tag, DFXMLReader, Event};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// First field of the sidecar header line.
+const MAGIC: &str = "dfxml-index";
+
+/// Sidecar format version.
+const VERSION: u32 = 2;
+
+/// Where a file object starts and the fields it can be found by.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// Byte offset of the `<` of the `<fileobject>` tag
+    pub offset: u64,
+    /// Namespace scope of the file object (see [`DFXMLIndex::scope`])
+    pub scope: usize,
+    /// File object id
+    pub id: Option<u64>,
+    /// Partition number
+    pub partition: Option<u32>,
+    /// Inode number
+    pub inode: Option<u64>,
+    /// File path/name
+    pub filename: Option<String>,
+    /// Hashes of the file content
+    pub hashes: Hashes,
+}
+
+impl IndexEntry {
+    /// Creates an entry for a file object starting at `offset`.
+    pub fn new(offset: u64, file: &FileObject) -> Self {
+        Self {
+            offset,
+            scope: 0,
+            id: file.id,
+            partition: file.partition,
+            inode: file.inode,
+            filename: file.filename.clone(),
+            hashes: file.hashes.clone(),
+        }
+    }
+}
+
+/// Index of the file objects of one DFXML document.
+#[derive(Debug, Clone, Default)]
+pub struct DFXMLIndex {
+    entries: Vec<IndexEntry>,
+    namespaces: Vec<(String, String)>,
+    scopes: Vec<Vec<(String, String)>>,
+    source_len: Option<u64>,
+    source_modified: Option<SystemTime>,
+    by_filename: HashMap<String, Vec<usize>>,
+    by_inode: HashMap<u64, Vec<usize>>,
+    by_id: HashMap<u64, Vec<usize>>,
+    by_hash: HashMap<(HashType, String), Vec<usize>>,
+}
+
+impl DFXMLIndex {
+    /// Creates an empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Indexes every file object of a document.
+    pub fn build<R: BufRead>(input: R) -> Result<Self> {
+        Self::build_from(&mut DFXMLReader::from_reader(input))
+    }
+
+    /// Indexes the file objects from a reader, e.g. one built
+    /// [`with_recovery`](DFXMLReader::with_recovery) to index a malformed
+    /// document.
+    pub fn build_from<R: BufRead>(reader: &mut DFXMLReader<R>) -> Result<Self> {
+        let mut index = Self::new();
+        while let Some(event) = reader.next() {
+            match event? {
+                Event::DFXMLStart(doc) => {
+                    index.namespaces = doc
+                        .namespaces()
+                        .map(|(prefix, uri)| (prefix.to_string(), uri.to_string()))
+                        .collect();
+                }
+                Event::FileObject(file) => {
+                    let mut entry = IndexEntry::new(reader.event_position().byte_offset, &file);
+                    entry.scope = index.add_scope(reader.namespace_scope()?);
+                    index.push(entry);
+                }
+                _ => {}
+            }
+        }
+        Ok(index)
+    }
+
+    /// Indexes a DFXML file, recording its length and modification time so
+    /// a saved index can be recognized as out of date.
+    ///
+    /// With the `compression` feature, compressed files are indexed by
+    /// their decompressed offsets.
+    pub fn build_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let metadata = fs::metadata(path)?;
+        #[cfg(feature = "compression")]
+        let mut index = Self::build(crate::compression::open(path)?)?;
+        #[cfg(not(feature = "compression"))]
+        let mut index = Self::build(BufReader::new(File::open(path)?))?;
+        index.source_len = Some(metadata.len());
+        index.source_modified = metadata.modified().ok();
+        Ok(index)
+    }
+
+    /// Returns the sidecar path of a DFXML file: its path with `.idx`
+    /// appended.
+    pub fn sidecar_path(path: impl AsRef<Path>) -> PathBuf {
+        let mut sidecar = path.as_ref().as_os_str().to_owned();
+        sidecar.push(".idx");
+        PathBuf::from(sidecar)
+    }
+
+    /// Loads the sidecar index of a DFXML file, or builds and saves it if
+    /// it is missing, unreadable or was built for a file of another length
+    /// or modification time.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let sidecar = Self::sidecar_path(path);
+        let metadata = fs::metadata(path)?;
+        if let Ok(index) = Self::load(&sidecar) {
+            if index.source_len == Some(metadata.len())
+                && index.source_modified == metadata.modified().ok()
+            {
+                return Ok(index);
+            }
+        }
+        let index = Self::build_file(path)?;
+        index.save(&sidecar)?;
+        Ok(index)
+    }
+
+    /// Returns the number of a namespace scope, adding it if it is new.
+    fn add_scope(&mut self, scope: Vec<(String, String)>) -> usize {
+        // File objects mostly share the scope of the one before them
+        match self.scopes.iter().rposition(|s| *s == scope) {
+            Some(n) => n,
+            None => {
+                self.scopes.push(scope);
+                self.scopes.len() - 1
+            }
+        }
+    }
+
+    /// Adds an entry.
+    pub fn push(&mut self, entry: IndexEntry) {
+        let i = self.entries.len();
+        if let Some(ref filename) = entry.filename {
+            self.by_filename
+                .entry(filename.clone())
+                .or_default()
+                .push(i);
+        }
+        if let Some(inode) = entry.inode {
+            self.by_inode.entry(inode).or_default().push(i);
+        }
+        if let Some(id) = entry.id {
+            self.by_id.entry(id).or_default().push(i);
+        }
+        for (hash_type, digest) in entry.hashes.iter() {
+            self.by_hash
+                .entry((hash_type, digest.trim().to_ascii_lowercase()))
+                .or_default()
+                .push(i);
+        }
+        self.entries.push(entry);
+    }
+
+    /// Returns the entries in document order.
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// Returns the number of indexed file objects.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no file objects are indexed.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the length of the indexed file, if it was indexed from a path.
+    pub fn source_len(&self) -> Option<u64> {
+        self.source_len
+    }
+
+    /// Returns the modification time of the indexed file, if it was
+    /// indexed from a path.
+    pub fn source_modified(&self) -> Option<SystemTime> {
+        self.source_modified
+    }
+
+    /// Returns the namespace declarations of the indexed document.
+    pub fn namespaces(&self) -> &[(String, String)] {
+        &self.namespaces
+    }
+
+    /// Returns the namespace declarations in scope at the file objects of
+    /// an [`IndexEntry::scope`], or `None` if there is no such scope.
+    pub fn scope(&self, scope: usize) -> Option<&[(String, String)]> {
+        self.scopes.get(scope).map(Vec::as_slice)
+    }
+
+    fn lookup<'a>(&'a self, hits: Option<&'a Vec<usize>>) -> impl Iterator<Item = &'a IndexEntry> {
+        hits.into_iter().flatten().map(|&i| &self.entries[i])
+    }
+
+    /// Iterates over the entries with a filename.
+    pub fn by_filename(&self, filename: &str) -> impl Iterator<Item = &IndexEntry> {
+        self.lookup(self.by_filename.get(filename))
+    }
+
+    /// Iterates over the entries with an inode number, in any partition.
+    pub fn by_inode(&self, inode: u64) -> impl Iterator<Item = &IndexEntry> {
+        self.lookup(self.by_inode.get(&inode))
+    }
+
+    /// Iterates over the entries with an id.
+    pub fn by_id(&self, id: u64) -> impl Iterator<Item = &IndexEntry> {
+        self.lookup(self.by_id.get(&id))
+    }
+
+    /// Iterates over the entries with a digest (compared case-insensitively).
+    pub fn by_hash(&self, hash_type: HashType, digest: &str) -> impl Iterator<Item = &IndexEntry> {
+        let key = (hash_type, digest.trim().to_ascii_lowercase());
+        self.lookup(self.by_hash.get(&key))
+    }
+
+    /// Seeks the indexed document to `offset` and parses the file object
+    /// that starts there.
+    pub fn read_file<R: Read + Seek>(&self, input: &mut R, offset: u64) -> Result<FileObject> {
+        input.seek(SeekFrom::Start(offset))?;
+        // Re-declare the namespaces in scope around the file object, or the
+        // document's for an offset that is not indexed
+        let scope = self
+            .entries
+            .binary_search_by_key(&offset, |e| e.offset)
+            .ok()
+            .and_then(|i| self.scope(self.entries[i].scope))
+            .unwrap_or(&self.namespaces);
+        let root = root_tag(scope.iter().map(|(p, u)| (p.as_str(), u.as_str())));
+
+        let input = Cursor::new(root.into_bytes()).chain(BufReader::new(input));
+        for event in DFXMLReader::from_reader(input) {
+            match event? {
+                Event::DFXMLStart(_) => {}
+                Event::FileObject(file) => return Ok(*file),
+                _ => break,
+            }
+        }
+        Err(Error::InvalidIndex(format!(
+            "no file object at offset {}",
+            offset
+        )))
+    }
+
+    /// Writes the index in the sidecar format.
+    pub fn write_to<W: Write>(&self, mut out: W) -> Result<()> {
+        let modified = self
+            .source_modified
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| format!("{}.{:09}", d.as_secs(), d.subsec_nanos()))
+            .unwrap_or_default();
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            MAGIC,
+            VERSION,
+            optional(self.source_len),
+            modified
+        )?;
+        for (prefix, uri) in &self.namespaces {
+            writeln!(out, "ns\t{}\t{}", escape_field(prefix), escape_field(uri))?;
+        }
+        for scope in &self.scopes {
+            write!(out, "scope")?;
+            for (prefix, uri) in scope {
+                write!(out, "\t{}\t{}", escape_field(prefix), escape_field(uri))?;
+            }
+            writeln!(out)?;
+        }
+        for entry in &self.entries {
+            let hashes: Vec<String> = entry
+                .hashes
+                .iter()
+                .map(|(t, d)| format!("{}:{}", t, d))
+                .collect();
+            writeln!(
+                out,
+                "file\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                entry.offset,
+                entry.scope,
+                optional(entry.id),
+                optional(entry.partition),
+                optional(entry.inode),
+                hashes.join(","),
+                escape_field(entry.filename.as_deref().unwrap_or_default())
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Reads an index written by [`write_to`](Self::write_to).
+    pub fn read_from<R: BufRead>(input: R) -> Result<Self> {
+        let mut lines = input.lines();
+        let header = lines.next().transpose()?.unwrap_or_default();
+        let fields: Vec<&str> = header.split('\t').collect();
+        match fields.as_slice() {
+            [MAGIC, version, len, modified] if version.parse() == Ok(VERSION) => {
+                let mut index = Self::new();
+                index.source_len =
+                    parse_field(len, "document length").map_err(Error::InvalidIndex)?;
+                index.source_modified = parse_time(modified).map_err(Error::InvalidIndex)?;
+                for (n, line) in lines.enumerate() {
+                    index
+                        .read_line(&line?)
+                        .map_err(|e| Error::InvalidIndex(format!("line {}: {}", n + 2, e)))?;
+                }
+                Ok(index)
+            }
+            _ => Err(Error::InvalidIndex(format!(
+                "not a version {} {} file",
+                VERSION, MAGIC
+            ))),
+        }
+    }
+
+    /// Parses one line after the header.
+    fn read_line(&mut self, line: &str) -> std::result::Result<(), String> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields.as_slice() {
+            [] | [""] => {}
+            ["ns", prefix, uri] => self
+                .namespaces
+                .push((unescape_field(prefix), unescape_field(uri))),
+            ["scope", declarations @ ..] if declarations.len() % 2 == 0 => {
+                let scope = declarations
+                    .chunks(2)
+                    .map(|d| (unescape_field(d[0]), unescape_field(d[1])))
+                    .collect();
+                self.scopes.push(scope);
+            }
+            ["file", offset, scope, id, partition, inode, hashes, filename] => {
+                let scope = parse_field(scope, "scope")?.ok_or("missing scope")?;
+                if scope >= self.scopes.len() {
+                    return Err(format!("unknown scope {}", scope));
+                }
+                let mut entry = IndexEntry {
+                    offset: parse_field(offset, "offset")?.ok_or("missing offset")?,
+                    scope,
+                    id: parse_field(id, "id")?,
+                    partition: parse_field(partition, "partition")?,
+                    inode: parse_field(inode, "inode")?,
+                    ..Default::default()
+                };
+                if !filename.is_empty() {
+                    entry.filename = Some(unescape_field(filename));
+                }
+                for hash in hashes.split(',').filter(|h| !h.is_empty()) {
+                    let (hash_type, digest) = hash
+                        .split_once(':')
+                        .ok_or_else(|| format!("bad hash {:?}", hash))?;
+                    let hash_type = hash_type.parse().map_err(|e: Error| e.to_string())?;
+                    entry.hashes.set(hash_type, digest.to_string());
+                }
+                self.push(entry);
+            }
+            _ => return Err(format!("unrecognized line {:?}", line)),
+        }
+        Ok(())
+    }
+
+    /// Saves the index to a sidecar file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_to(&mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Loads an index from a sidecar file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+}
+
+/// Formats an optional number as a field, empty when missing.
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Parses an optional number field.
+fn parse_field<T: std::str::FromStr>(
+    field: &str,
+    what: &str,
+) -> std::result::Result<Option<T>, String> {
+    if field.is_empty() {
+        return Ok(None);
+    }
+    field
+        .parse()
+        .map(Some)
+        .map_err(|_| format!("bad {} {:?}", what, field))
+}
+
+/// Parses an optional `<secs>.<nanos>` time since the Unix epoch.
+fn parse_time(field: &str) -> std::result::Result<Option<SystemTime>, String> {
+    if field.is_empty() {
+        return Ok(None);
+    }
+    let bad = || format!("bad modification time {:?}", field);
+    let (secs, nanos) = field.split_once('.').ok_or_else(bad)?;
+    let secs = secs.parse().map_err(|_| bad())?;
+    let nanos = nanos.parse().map_err(|_| bad())?;
+    UNIX_EPOCH
+        .checked_add(Duration::new(secs, nanos))
+        .map(Some)
+        .ok_or_else(bad)
+}
+
+/// Escapes the characters that would break the line format.
+fn escape_field(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverses [`escape_field`].
+fn unescape_field(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<?xml version="1.0"?>
+<dfxml version="1.0" xmlns="http://www.forensicswiki.org/wiki/Category:Digital_Forensics_XML" xmlns:ex="http://example.com/ns">
+  <volume xmlns:v="http://example.com/volume">
+    <partition_offset>1048576</partition_offset>
+    <fileobject>
+      <id>1</id>
+      <filename>docs/a.txt</filename>
+      <partition>1</partition>
+      <inode>12</inode>
+      <hashdigest type="md5">0CC175B9C0F1B6A831C399E269772661</hashdigest>
+      <ex:tag note="x">value</ex:tag>
+      <v:tag>volume scoped</v:tag>
+    </fileobject>
+    <fileobject>
+      <id>2</id>
+      <filename>odd	name
+.txt</filename>
+      <inode>12</inode>
+    </fileobject>
+  </volume>
+</dfxml>"#;
+
+    fn streamed() -> Vec<FileObject> {
+        crate::reader::parse_file_objects(Cursor::new(XML)).unwrap()
+    }
+
+    #[test]
+    fn test_build_and_lookup() {
+        let index = DFXMLIndex::build(Cursor::new(XML)).unwrap();
+        assert_eq!(index.len(), 2);
+        for entry in index.entries() {
+            assert!(XML[entry.offset as usize..].starts_with("<fileobject>"));
+        }
+        assert_eq!(index.by_inode(12).count(), 2);
+        assert_eq!(
+            index.by_id(2).next().unwrap().filename.as_deref(),
+            Some("odd\tname\n.txt")
+        );
+        let hit = index
+            .by_hash(HashType::Md5, "0cc175b9c0f1b6a831c399e269772661")
+            .next()
+            .unwrap();
+        assert_eq!(hit.filename.as_deref(), Some("docs/a.txt"));
+        assert_eq!(hit.partition, Some(1));
+        assert_eq!(index.by_filename("missing").count(), 0);
+
+        let files = streamed();
+        assert_eq!(files[0].externals.len(), 2);
+        let mut input = Cursor::new(XML);
+        for (entry, file) in index.entries().iter().zip(&files) {
+            assert_eq!(&index.read_file(&mut input, entry.offset).unwrap(), file);
+        }
+        assert!(index.read_file(&mut input, 0).is_err());
+    }
+
+    #[test]
+    fn test_sidecar_round_trip() {
+        let mut index = DFXMLIndex::build(Cursor::new(XML)).unwrap();
+        let modified = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+        index.source_len = Some(XML.len() as u64);
+        index.source_modified = Some(modified);
+        let mut out = Vec::new();
+        index.write_to(&mut out).unwrap();
+
+        let loaded = DFXMLIndex::read_from(Cursor::new(&out)).unwrap();
+        assert_eq!(loaded.entries(), index.entries());
+        assert_eq!(loaded.namespaces(), index.namespaces());
+        assert_eq!(loaded.source_len(), Some(XML.len() as u64));
+        assert_eq!(loaded.source_modified(), Some(modified));
+        assert_eq!(loaded.scope(0), index.scope(0));
+        assert_eq!(loaded.by_inode(12).count(), 2);
+
+        let file = loaded
+            .read_file(&mut Cursor::new(XML), loaded.entries()[0].offset)
+            .unwrap();
+        assert_eq!(file, streamed()[0]);
+
+        assert!(DFXMLIndex::read_from(Cursor::new("something else\n")).is_err());
+        let bad = String::from_utf8(out)
+            .unwrap()
+            .replacen("file\t", "file\tx", 1);
+        let err = DFXMLIndex::read_from(Cursor::new(bad)).unwrap_err();
+        assert!(err.to_string().contains("line"));
+    }
+
+    #[test]
+    fn test_open_rebuilds_stale_sidecar() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("doc.dfxml");
+        let set_modified = |secs| {
+            let file = File::options().write(true).open(&path).unwrap();
+            file.set_modified(UNIX_EPOCH + Duration::from_secs(secs))
+                .unwrap();
+        };
+        fs::write(&path, XML).unwrap();
+        set_modified(1_700_000_000);
+        assert_eq!(DFXMLIndex::open(&path).unwrap().by_inode(12).count(), 2);
+
+        // Same length, new content and modification time
+        fs::write(&path, XML.replace("<inode>12</inode>", "<inode>34</inode>")).unwrap();
+        set_modified(1_700_000_100);
+        let index = DFXMLIndex::open(&path).unwrap();
+        assert_eq!(index.by_inode(34).count(), 2);
+        let saved = DFXMLIndex::load(DFXMLIndex::sidecar_path(&path)).unwrap();
+        assert_eq!(saved.source_modified(), index.source_modified());
+    }
+}
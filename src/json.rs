@@ -0,0 +1,444 @@
+//! JSON conversion for DFXML documents (requires `serde` feature).
+//!
+//! Two layouts are supported, both tagged with [`SCHEMA`] so consumers can
+//! detect incompatible changes:
+//!
+//! - **JSON Lines**: one [`Record`] per line. The first line is a
+//!   [`Record::Document`] holding the document metadata, followed by one
+//!   [`Record::File`] per file object with the properties of its enclosing
+//!   disk image, partition system, partition and volume. Each record is
+//!   written as soon as its file object is read, and converting back to
+//!   DFXML runs in constant memory, but the reader still keeps the document
+//!   tree it has read so far in memory.
+//! - **Nested JSON**: a single [`JsonDocument`] holding the complete object
+//!   tree, exactly as the `serde` derives serialize it.
+//!
+//! JSON Lines only records the innermost container of each kind, so
+//! converting back rebuilds containers in disk image, partition system,
+//! partition, volume order and drops containers without files. Use nested
+//! JSON when the round-trip must be lossless.
+//!
+//! # Example
+//!
+//! ```rust
+//! use dfxml_rs::json;
+//! use std::io::Cursor;
+//!
+//! let xml = r#"<dfxml version="1.0">
+//!   <volume>
+//!     <ftype_str>ntfs</ftype_str>
+//!     <fileobject><filename>a.txt</filename></fileobject>
+//!   </volume>
+//! </dfxml>"#;
+//!
+//! let mut lines = Vec::new();
+//! json::dfxml_to_json_lines(Cursor::new(xml), &mut lines).unwrap();
+//!
+//! let mut xml_out = Vec::new();
+//! json::json_lines_to_dfxml(Cursor::new(lines), &mut xml_out).unwrap();
+//! ```
+
+use crate::error::{Error, Result};
+use crate::objects::{
+    DFXMLObject, DiskImageObject, FileObject, PartitionObject, PartitionSystemObject, VolumeObject,
+};
+use crate::reader::{parse, DFXMLReader, Event};
+use crate::writer::{DFXMLStreamWriter, WriterConfig};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Read, Write};
+
+/// Schema identifier written into every JSON document and document record.
+pub const SCHEMA: &str = "dfxml-json/1";
+
+/// A DFXML document in JSON form, with its schema identifier.
+///
+/// In nested JSON this holds the complete object tree. As the first record
+/// of a JSON Lines stream it holds only the document metadata.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonDocument {
+    /// Schema identifier, always [`SCHEMA`] when written by this crate
+    pub schema: String,
+    /// The document
+    pub dfxml: DFXMLObject,
+}
+
+impl JsonDocument {
+    /// Wraps a document with the current schema identifier.
+    pub fn new(dfxml: DFXMLObject) -> Self {
+        Self {
+            schema: SCHEMA.to_string(),
+            dfxml,
+        }
+    }
+}
+
+/// A file object with the properties of its enclosing containers.
+///
+/// Container properties are copied without their child objects.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileRecord {
+    /// Innermost enclosing disk image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_image: Option<DiskImageObject>,
+    /// Innermost enclosing partition system
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partition_system: Option<PartitionSystemObject>,
+    /// Innermost enclosing partition
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partition: Option<PartitionObject>,
+    /// Innermost enclosing volume
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<VolumeObject>,
+    /// The file itself
+    pub fileobject: FileObject,
+}
+
+/// One line of a JSON Lines stream, tagged by its `record` field.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+pub enum Record {
+    /// Document metadata (always the first line)
+    Document(Box<JsonDocument>),
+    /// A file object and its context
+    File(Box<FileRecord>),
+}
+
+/// A container being rebuilt from JSON Lines context.
+#[derive(PartialEq)]
+enum Container {
+    DiskImage(DiskImageObject),
+    PartitionSystem(PartitionSystemObject),
+    Partition(PartitionObject),
+    Volume(VolumeObject),
+}
+
+impl FileRecord {
+    /// Splits the record into its containers, outermost first, and the file.
+    fn into_parts(self) -> (Vec<Container>, FileObject) {
+        let containers = [
+            self.disk_image.map(Container::DiskImage),
+            self.partition_system.map(Container::PartitionSystem),
+            self.partition.map(Container::Partition),
+            self.volume.map(Container::Volume),
+        ];
+        (containers.into_iter().flatten().collect(), self.fileobject)
+    }
+}
+
+/// Streams a DFXML document to JSON Lines.
+///
+/// The document record is written before the first file, so metadata that
+/// only appears after it in the XML is not included.
+pub fn dfxml_to_json_lines<R: BufRead, W: Write>(input: R, mut output: W) -> Result<()> {
+    let mut reader = DFXMLReader::from_reader(input);
+    let mut header_written = false;
+
+    while let Some(event) = reader.next() {
+        match event? {
+            Event::FileObject(file) => {
+                if !header_written {
+                    let doc = reader.document().cloned().unwrap_or_default();
+                    write_record(
+                        &mut output,
+                        &Record::Document(Box::new(JsonDocument::new(doc))),
+                    )?;
+                    header_written = true;
+                }
+                let record = FileRecord {
+                    disk_image: reader
+                        .current_disk_image()
+                        .map(DiskImageObject::without_children),
+                    partition_system: reader
+                        .current_partition_system()
+                        .map(PartitionSystemObject::without_children),
+                    partition: reader
+                        .current_partition()
+                        .map(PartitionObject::without_children),
+                    volume: reader.current_volume().map(VolumeObject::without_children),
+                    fileobject: *file,
+                };
+                write_record(&mut output, &Record::File(Box::new(record)))?;
+            }
+            Event::DFXMLEnd(doc) if !header_written => {
+                write_record(
+                    &mut output,
+                    &Record::Document(Box::new(JsonDocument::new(*doc))),
+                )?;
+                header_written = true;
+            }
+            _ => {}
+        }
+    }
+
+    output.flush()?;
+    Ok(())
+}
+
+/// Streams JSON Lines back to DFXML with the default writer configuration.
+pub fn json_lines_to_dfxml<R: BufRead, W: Write>(input: R, output: W) -> Result<()> {
+    json_lines_to_dfxml_with_config(input, output, WriterConfig::default())
+}
+
+/// Streams JSON Lines back to DFXML with the specified writer configuration.
+///
+/// Blank lines are ignored. A stream without a document record gets an
+/// empty document header.
+pub fn json_lines_to_dfxml_with_config<R: BufRead, W: Write>(
+    input: R,
+    output: W,
+    config: WriterConfig,
+) -> Result<()> {
+    let mut writer = DFXMLStreamWriter::with_config(output, config)?;
+    let mut started = false;
+    let mut open: Vec<Container> = Vec::new();
+
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Record>(&line)? {
+            Record::Document(doc) => {
+                check_schema(&doc.schema)?;
+                writer.start_document(&doc.dfxml)?;
+                started = true;
+            }
+            Record::File(record) => {
+                if !started {
+                    writer.start_document(&DFXMLObject::new())?;
+                    started = true;
+                }
+                let (containers, file) = (*record).into_parts();
+
+                // Keep the containers shared with the previous file open
+                let common = open
+                    .iter()
+                    .zip(&containers)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                while open.len() > common {
+                    match open.pop() {
+                        Some(Container::DiskImage(_)) => writer.end_disk_image()?,
+                        Some(Container::PartitionSystem(_)) => writer.end_partition_system()?,
+                        Some(Container::Partition(_)) => writer.end_partition()?,
+                        Some(Container::Volume(_)) => writer.end_volume()?,
+                        None => {}
+                    }
+                }
+                for container in containers.into_iter().skip(common) {
+                    match &container {
+                        Container::DiskImage(di) => writer.start_disk_image(di)?,
+                        Container::PartitionSystem(ps) => writer.start_partition_system(ps)?,
+                        Container::Partition(p) => writer.start_partition(p)?,
+                        Container::Volume(vol) => writer.start_volume(vol)?,
+                    }
+                    open.push(container);
+                }
+
+                writer.write_file(&file)?;
+            }
+        }
+    }
+
+    if !started {
+        writer.start_document(&DFXMLObject::new())?;
+    }
+    writer.finish()?.flush()?;
+    Ok(())
+}
+
+/// Serializes a document to a nested JSON string.
+pub fn to_json_string(doc: &DFXMLObject) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&JsonDocument::new(
+        doc.clone(),
+    ))?)
+}
+
+/// Writes a document as nested JSON.
+pub fn write_json<W: Write>(doc: &DFXMLObject, mut output: W, pretty: bool) -> Result<()> {
+    let json = JsonDocument::new(doc.clone());
+    if pretty {
+        serde_json::to_writer_pretty(&mut output, &json)?;
+    } else {
+        serde_json::to_writer(&mut output, &json)?;
+    }
+    output.write_all(b"\n")?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Reads a document from nested JSON.
+pub fn from_json_reader<R: Read>(input: R) -> Result<DFXMLObject> {
+    let json: JsonDocument = serde_json::from_reader(input)?;
+    check_schema(&json.schema)?;
+    Ok(json.dfxml)
+}
+
+/// Reads a document from a nested JSON string.
+pub fn from_json_str(s: &str) -> Result<DFXMLObject> {
+    from_json_reader(s.as_bytes())
+}
+
+/// Parses a DFXML document and writes it as nested JSON.
+///
+/// The whole document is loaded into memory.
+pub fn dfxml_to_json<R: BufRead, W: Write>(input: R, output: W, pretty: bool) -> Result<()> {
+    let doc = parse(input)?;
+    write_json(&doc, output, pretty)
+}
+
+/// Reads nested JSON and writes it as DFXML.
+pub fn json_to_dfxml<R: Read, W: Write>(input: R, output: W, config: WriterConfig) -> Result<()> {
+    let doc = from_json_reader(input)?;
+    crate::writer::DFXMLWriter::with_config(config).write(&doc, output)
+}
+
+fn write_record<W: Write>(output: &mut W, record: &Record) -> Result<()> {
+    serde_json::to_writer(&mut *output, record)?;
+    output.write_all(b"\n")?;
+    Ok(())
+}
+
+fn check_schema(schema: &str) -> Result<()> {
+    if schema == SCHEMA {
+        Ok(())
+    } else {
+        Err(Error::UnsupportedSchema(format!(
+            "{:?}, expected {:?}",
+            schema, SCHEMA
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::HashType;
+    use std::io::Cursor;
+
+    const SAMPLE: &str = r#"<?xml version="1.0"?>
+<dfxml version="1.0">
+  <metadata/>
+  <creator>
+    <program>fiwalk</program>
+  </creator>
+  <source>
+    <image_filename>disk.raw</image_filename>
+  </source>
+  <partitionsystemobject>
+    <pstype_str>dos</pstype_str>
+    <partitionobject>
+      <partition_index>1</partition_index>
+      <volume>
+        <ftype_str>ntfs</ftype_str>
+        <fileobject>
+          <filename>a.txt</filename>
+          <filesize>3</filesize>
+          <hashdigest type="md5">900150983cd24fb0d6963f7d28e17f72</hashdigest>
+        </fileobject>
+        <fileobject>
+          <filename>b.txt</filename>
+        </fileobject>
+      </volume>
+      <volume>
+        <ftype_str>fat16</ftype_str>
+        <fileobject>
+          <filename>c.txt</filename>
+        </fileobject>
+      </volume>
+    </partitionobject>
+  </partitionsystemobject>
+  <fileobject>
+    <filename>loose.txt</filename>
+  </fileobject>
+</dfxml>"#;
+
+    fn records(jsonl: &[u8]) -> Vec<Record> {
+        std::str::from_utf8(jsonl)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_json_lines_records() {
+        let mut out = Vec::new();
+        dfxml_to_json_lines(Cursor::new(SAMPLE), &mut out).unwrap();
+        let records = records(&out);
+        assert_eq!(records.len(), 5);
+
+        let Record::Document(doc) = &records[0] else {
+            panic!("first record should be the document");
+        };
+        assert_eq!(doc.schema, SCHEMA);
+        assert_eq!(doc.dfxml.program.as_deref(), Some("fiwalk"));
+
+        let Record::File(a) = &records[1] else {
+            panic!("expected a file record");
+        };
+        assert_eq!(a.fileobject.filename.as_deref(), Some("a.txt"));
+        assert_eq!(
+            a.fileobject.hashes.get(HashType::Md5),
+            Some("900150983cd24fb0d6963f7d28e17f72")
+        );
+        let vol = a.volume.as_ref().unwrap();
+        assert_eq!(vol.ftype_str.as_deref(), Some("ntfs"));
+        assert_eq!(vol.file_count(), 0);
+        assert_eq!(a.partition.as_ref().unwrap().partition_index, Some(1));
+        assert_eq!(
+            a.partition_system.as_ref().unwrap().pstype_str.as_deref(),
+            Some("dos")
+        );
+
+        let Record::File(loose) = &records[4] else {
+            panic!("expected a file record");
+        };
+        assert!(loose.volume.is_none() && loose.partition.is_none());
+    }
+
+    #[test]
+    fn test_json_lines_roundtrip() {
+        let mut jsonl = Vec::new();
+        dfxml_to_json_lines(Cursor::new(SAMPLE), &mut jsonl).unwrap();
+        let mut xml = Vec::new();
+        json_lines_to_dfxml(Cursor::new(jsonl), &mut xml).unwrap();
+
+        let original = parse(Cursor::new(SAMPLE)).unwrap();
+        let restored = parse(Cursor::new(xml)).unwrap();
+        assert_eq!(restored, original);
+    }
+
+    #[test]
+    fn test_json_lines_without_document() {
+        let line =
+            r#"{"record":"file","volume":{"ftype_str":"ext4"},"fileobject":{"filename":"x"}}"#;
+        let mut xml = Vec::new();
+        json_lines_to_dfxml(Cursor::new(line), &mut xml).unwrap();
+
+        let doc = parse(Cursor::new(xml)).unwrap();
+        let vol = doc.volumes().next().unwrap();
+        assert_eq!(vol.ftype_str.as_deref(), Some("ext4"));
+        assert_eq!(vol.file_count(), 1);
+    }
+
+    #[test]
+    fn test_nested_roundtrip() {
+        let original = parse(Cursor::new(SAMPLE)).unwrap();
+        let json = to_json_string(&original).unwrap();
+        assert_eq!(from_json_str(&json).unwrap(), original);
+
+        let mut xml = Vec::new();
+        json_to_dfxml(json.as_bytes(), &mut xml, WriterConfig::default()).unwrap();
+        assert_eq!(parse(Cursor::new(xml)).unwrap(), original);
+    }
+
+    #[test]
+    fn test_unsupported_schema() {
+        let json = r#"{"schema":"dfxml-json/99","dfxml":{}}"#;
+        assert!(matches!(
+            from_json_str(json),
+            Err(Error::UnsupportedSchema(_))
+        ));
+    }
+}
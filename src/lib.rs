@@ -90,25 +90,73 @@
 //! # Module Structure
 //!
 //! - [`objects`] - Core DFXML data structures
+//! - [`async_io`] - Async reader and writers on tokio (requires `async` feature)
+//! - [`bodyfile`] - Sleuth Kit body file import and export
+//! - [`compression`] - gzip, zstd and xz input and output (requires `compression` feature)
+//! - [`dedup`] - Duplicate detection across documents
+//! - [`diff`] - Differential DFXML between two documents
+//! - [`hashset`] - Known-file hash sets (NSRL RDS, hash lists, DFXML)
+//! - [`index`] - Byte-offset index for random access to file objects
+//! - [`offset_index`] - Interval index from image offsets to the files that own them
+//! - [`lint`] - Semantic checks that need no system libraries
+//! - [`json`] - JSON and JSON Lines conversion (requires `serde` feature)
 //! - [`reader`] - Streaming XML parser
+//! - [`parallel`] - Multi-threaded reader (requires `parallel` feature)
+//! - [`timeline`] - Timelines of file timestamps
+//! - [`unallocated`] - Unallocated space and file slack of a volume
 //! - [`writer`] - XML serialization
+//! - [`extract`] - File content extraction from raw disk images
+//! - [`filesystem`] - Native ext2/3/4 and FAT/exFAT walkers for raw disk images
+//! - [`partition`] - MBR and GPT partition tables of raw disk images
+//! - [`verify`] - Hash verification against a source image (requires `hashing` feature)
 //! - [`error`] - Error types
 //! - [`validation`] - XSD validation (requires `validation` feature)
 //!
 //! # Optional Features
 //!
-//! - `serde` - Enable serde serialization/deserialization support
+//! - `serde` - Enable serde serialization/deserialization support and JSON conversion
+//! - `hashing` - Enable hash computation and verification against images
+//! - `nsrl` - Enable loading NSRL RDSv3 SQLite hash sets
+//! - `parallel` - Enable parsing file objects on a rayon thread pool
+//! - `compression` - Enable reading and writing gzip, zstd and xz compressed DFXML
+//! - `async` - Enable the async (tokio) reader and writers
 //! - `validation` - Enable XSD schema validation (requires libxml2)
 //! - `cli` - Build command-line tools
 
 #![warn(missing_docs)]
 #![warn(rust_2018_idioms)]
 
+pub mod bodyfile;
+pub mod dedup;
+pub mod diff;
 pub mod error;
+pub mod extract;
+pub mod filesystem;
+pub mod hashset;
+pub mod index;
+pub mod lint;
 pub mod objects;
+pub mod offset_index;
+pub mod partition;
 pub mod reader;
+pub mod timeline;
+pub mod unallocated;
 pub mod writer;
 
+#[cfg(feature = "serde")]
+pub mod json;
+
+#[cfg(feature = "hashing")]
+pub mod verify;
+
+#[cfg(feature = "parallel")]
+pub mod parallel;
+
+#[cfg(feature = "async")]
+pub mod async_io;
+#[cfg(feature = "compression")]
+pub mod compression;
+
 #[cfg(feature = "validation")]
 pub mod validation;
 
@@ -117,8 +165,8 @@ pub use error::{Error, Result};
 pub use objects::{
     ByteRun, ByteRuns, DFXMLObject, FileObject, HashType, Hashes, Timestamp, VolumeObject,
 };
-pub use reader::{parse, parse_file_objects, DFXMLReader, Event};
-pub use writer::{to_string, write, DFXMLWriter, WriterConfig};
+pub use reader::{parse, parse_file_objects, DFXMLReader, Event, Position};
+pub use writer::{to_string, write, DFXMLStreamWriter, DFXMLWriter, WriterConfig};
 
 /// Library version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,1113 @@
+//! Semantic checks for DFXML documents.
+//!
+//! Unlike the `validation` module, which checks a document against the XSD
+//! with libxml2, this module is pure Rust and looks at the meaning of the
+//! values: hash digests of the wrong length, byte runs that overlap or fall
+//! outside their image or volume, byte runs that cover more than the file size,
+//! duplicate file `id`s, timestamps outside a plausible range, and allocation
+//! flags that contradict each other.
+//!
+//! Checks run on a parsed [`DFXMLObject`] with [`lint_document`] or on a
+//! stream with [`lint_reader`], and return [`Diagnostic`]s with a [`Rule`]
+//! and a [`Severity`].
+//!
+//! # Example
+//!
+//! ```rust
+//! use dfxml_rs::lint::{lint_document, LintConfig, Rule, Severity};
+//! use dfxml_rs::objects::{DFXMLObject, FileObject, HashType};
+//!
+//! let mut file = FileObject::with_filename("a.txt");
+//! file.hashes.set(HashType::Md5, "d41d8cd98f00b204e9800998ecf842".to_string());
+//! let mut doc = DFXMLObject::new();
+//! doc.append_file(file);
+//!
+//! let diagnostics = lint_document(&doc, &LintConfig::default());
+//! assert_eq!(diagnostics.len(), 1);
+//! assert_eq!(diagnostics[0].rule, Rule::HashLength);
+//! assert_eq!(diagnostics[0].severity, Severity::Error);
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use crate::error::{Error, Result};
+use crate::objects::{
+    ByteRun, ByteRuns, DFXMLObject, DiskImageObject, FileObject, HashType, Hashes, PartitionObject,
+    PartitionSystemObject, VolumeObject,
+};
+use crate::reader::{DFXMLReader, Event};
+use crate::timeline::TIMESTAMP_NAMES;
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Unusual but often legitimate
+    Info,
+    /// Probably wrong
+    Warning,
+    /// Cannot be right
+    Error,
+}
+
+impl Severity {
+    /// Returns the lowercase name of the severity.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Severity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "info" => Ok(Severity::Info),
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(Error::InvalidLintOption(format!("unknown severity: {}", s))),
+        }
+    }
+}
+
+/// A lint check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// Hash digests that are not hex or have the wrong length for their algorithm
+    HashLength,
+    /// Byte runs of one file that overlap in the image or in the file
+    ByteRunOverlap,
+    /// Byte runs that overflow or fall outside their disk image or volume
+    ByteRunBounds,
+    /// Data byte runs that cover more than the file size
+    ByteRunLength,
+    /// File objects sharing an `id`
+    DuplicateId,
+    /// Timestamps before or after the configured range
+    TimestampRange,
+    /// Allocation flags that contradict each other
+    AllocFlags,
+}
+
+impl Rule {
+    /// All rules, in the order they are checked.
+    pub const ALL: [Rule; 7] = [
+        Rule::HashLength,
+        Rule::ByteRunOverlap,
+        Rule::ByteRunBounds,
+        Rule::ByteRunLength,
+        Rule::DuplicateId,
+        Rule::TimestampRange,
+        Rule::AllocFlags,
+    ];
+
+    /// Returns the kebab-case name of the rule.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rule::HashLength => "hash-length",
+            Rule::ByteRunOverlap => "byte-run-overlap",
+            Rule::ByteRunBounds => "byte-run-bounds",
+            Rule::ByteRunLength => "byte-run-length",
+            Rule::DuplicateId => "duplicate-id",
+            Rule::TimestampRange => "timestamp-range",
+            Rule::AllocFlags => "alloc-flags",
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Rule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Rule::ALL
+            .into_iter()
+            .find(|r| r.as_str() == s)
+            .ok_or_else(|| Error::InvalidLintOption(format!("unknown lint rule: {}", s)))
+    }
+}
+
+/// A problem found by a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The rule that found the problem
+    pub rule: Rule,
+    /// How serious the problem is
+    pub severity: Severity,
+    /// Element name of the object, e.g. `fileobject`
+    pub element: &'static str,
+    /// Filename of the object, if any
+    pub filename: Option<String>,
+    /// Inode of the file, if any
+    pub inode: Option<u64>,
+    /// `id` of the file, if any
+    pub id: Option<u64>,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.rule, self.element)?;
+        if let Some(filename) = &self.filename {
+            write!(f, " {:?}", filename)?;
+        }
+        if let Some(inode) = self.inode {
+            write!(f, " (inode {})", inode)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Settings for the lint checks.
+#[derive(Debug, Clone)]
+pub struct LintConfig {
+    /// Timestamps before this are reported by [`Rule::TimestampRange`]
+    pub earliest: DateTime<Utc>,
+    /// Timestamps after this are reported by [`Rule::TimestampRange`]
+    pub latest: DateTime<Utc>,
+    /// Rules that are not checked
+    pub disabled: HashSet<Rule>,
+}
+
+impl Default for LintConfig {
+    /// Accepts timestamps from the Unix epoch to one day from now, with
+    /// every rule enabled.
+    fn default() -> Self {
+        Self {
+            earliest: Utc.timestamp_opt(0, 0).unwrap(),
+            latest: Utc::now() + Duration::days(1),
+            disabled: HashSet::new(),
+        }
+    }
+}
+
+impl LintConfig {
+    /// Creates a configuration with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the earliest plausible timestamp.
+    pub fn with_earliest(mut self, earliest: DateTime<Utc>) -> Self {
+        self.earliest = earliest;
+        self
+    }
+
+    /// Sets the latest plausible timestamp.
+    pub fn with_latest(mut self, latest: DateTime<Utc>) -> Self {
+        self.latest = latest;
+        self
+    }
+
+    /// Turns off a rule.
+    pub fn without_rule(mut self, rule: Rule) -> Self {
+        self.disabled.insert(rule);
+        self
+    }
+
+    /// Returns true if a rule is checked.
+    pub fn is_enabled(&self, rule: Rule) -> bool {
+        !self.disabled.contains(&rule)
+    }
+}
+
+/// Counts of a lint run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LintSummary {
+    /// File objects checked
+    pub files: u64,
+    /// Diagnostics with [`Severity::Info`]
+    pub infos: u64,
+    /// Diagnostics with [`Severity::Warning`]
+    pub warnings: u64,
+    /// Diagnostics with [`Severity::Error`]
+    pub errors: u64,
+}
+
+impl LintSummary {
+    /// Counts a diagnostic.
+    pub fn record(&mut self, diagnostic: &Diagnostic) {
+        match diagnostic.severity {
+            Severity::Info => self.infos += 1,
+            Severity::Warning => self.warnings += 1,
+            Severity::Error => self.errors += 1,
+        }
+    }
+}
+
+/// The extents a file's byte runs must fall within.
+#[derive(Debug, Clone, Copy, Default)]
+struct Bounds {
+    image_size: Option<u64>,
+    volume_offset: Option<u64>,
+    volume_size: Option<u64>,
+    block_size: Option<u64>,
+}
+
+impl Bounds {
+    fn disk_image(image: &DiskImageObject) -> Self {
+        Self {
+            image_size: image.image_size,
+            ..Default::default()
+        }
+    }
+
+    fn volume(self, volume: &VolumeObject) -> Self {
+        let block_size = volume.block_size.map(u64::from);
+        Self {
+            image_size: self.image_size,
+            volume_offset: volume.partition_offset,
+            volume_size: volume
+                .block_count
+                .zip(block_size)
+                .and_then(|(n, s)| n.checked_mul(s)),
+            block_size,
+        }
+    }
+}
+
+/// Runs the checks, keeping the state that spans file objects.
+struct Linter<'a> {
+    config: &'a LintConfig,
+    ids: HashMap<u64, Option<String>>,
+    out: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    fn new(config: &'a LintConfig) -> Self {
+        Self {
+            config,
+            ids: HashMap::new(),
+            out: Vec::new(),
+        }
+    }
+
+    fn report(&mut self, rule: Rule, severity: Severity, file: &FileObject, message: String) {
+        if self.config.is_enabled(rule) {
+            self.out.push(Diagnostic {
+                rule,
+                severity,
+                element: "fileobject",
+                filename: file.filename.clone(),
+                inode: file.inode,
+                id: file.id,
+                message,
+            });
+        }
+    }
+
+    fn check_disk_image(&mut self, image: &DiskImageObject) {
+        if !self.config.is_enabled(Rule::HashLength) {
+            return;
+        }
+        for (severity, message) in hash_problems(&image.hashes, "") {
+            self.out.push(Diagnostic {
+                rule: Rule::HashLength,
+                severity,
+                element: "diskimageobject",
+                filename: image.image_filename.clone(),
+                inode: None,
+                id: None,
+                message,
+            });
+        }
+    }
+
+    fn check_file(&mut self, file: &FileObject, bounds: Bounds) {
+        self.check_hashes(file);
+        self.check_overlap(file);
+        self.check_bounds(file, bounds);
+        self.check_length(file, bounds);
+        self.check_id(file);
+        self.check_timestamps(file);
+        self.check_alloc(file);
+    }
+
+    fn check_hashes(&mut self, file: &FileObject) {
+        for (severity, message) in hash_problems(&file.hashes, "") {
+            self.report(Rule::HashLength, severity, file, message);
+        }
+        for (facet, runs) in facets(file) {
+            for (i, run) in runs.iter().enumerate() {
+                let context = format!("{} byte run {}: ", facet, i);
+                for (severity, message) in hash_problems(&run.hashes, &context) {
+                    self.report(Rule::HashLength, severity, file, message);
+                }
+            }
+        }
+    }
+
+    fn check_overlap(&mut self, file: &FileObject) {
+        let Some(runs) = &file.data_brs else {
+            return;
+        };
+        let spaces = [
+            ("image", overlaps(runs, |r| r.img_offset), Severity::Warning),
+            ("file", overlaps(runs, |r| r.file_offset), Severity::Error),
+        ];
+        for (space, pairs, severity) in spaces {
+            for (i, j, offset) in pairs {
+                self.report(
+                    Rule::ByteRunOverlap,
+                    severity,
+                    file,
+                    format!(
+                        "data byte runs {} and {} overlap in the {} at offset {}",
+                        i, j, space, offset
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_bounds(&mut self, file: &FileObject, bounds: Bounds) {
+        for (facet, runs) in facets(file) {
+            for (i, run) in runs.iter().enumerate() {
+                let len = run.len.unwrap_or(0);
+                let offsets = [
+                    ("img_offset", run.img_offset),
+                    ("fs_offset", run.fs_offset),
+                    ("file_offset", run.file_offset),
+                ];
+                for (name, offset) in offsets {
+                    if let Some(offset) = offset {
+                        if offset.checked_add(len).is_none() {
+                            self.report(
+                                Rule::ByteRunBounds,
+                                Severity::Error,
+                                file,
+                                format!(
+                                    "{} byte run {}: {} {} plus len {} overflows",
+                                    facet, i, name, offset, len
+                                ),
+                            );
+                        }
+                    }
+                }
+
+                let mut limits = Vec::new();
+                if let (Some(offset), Some(size)) = (run.img_offset, bounds.image_size) {
+                    limits.push(("img_offset", offset, 0, size, "disk image"));
+                }
+                if let (Some(offset), Some(start), Some(size)) =
+                    (run.img_offset, bounds.volume_offset, bounds.volume_size)
+                {
+                    limits.push((
+                        "img_offset",
+                        offset,
+                        start,
+                        start.saturating_add(size),
+                        "volume",
+                    ));
+                }
+                if let (Some(offset), Some(size)) = (run.fs_offset, bounds.volume_size) {
+                    limits.push(("fs_offset", offset, 0, size, "volume"));
+                }
+                for (name, offset, start, end, container) in limits {
+                    if offset < start || offset.saturating_add(len) > end {
+                        self.report(
+                            Rule::ByteRunBounds,
+                            Severity::Error,
+                            file,
+                            format!(
+                                "{} byte run {}: {} {} len {} lies outside the {} ({}..{})",
+                                facet, i, name, offset, len, container, start, end
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_length(&mut self, file: &FileObject, bounds: Bounds) {
+        let (Some(runs), Some(filesize)) = (&file.data_brs, file.filesize) else {
+            return;
+        };
+        if runs.is_empty()
+            || file.compressed == Some(true)
+            || runs.iter().any(|r| r.uncompressed_len.is_some())
+        {
+            return;
+        }
+        // Runs may cover less than the file size, as sparse files leave
+        // their holes out of the runs, so only excess coverage is reported
+        let Some(total) = runs.total_len() else {
+            return;
+        };
+        if total > filesize {
+            // Runs usually cover whole blocks, so up to a block of slack is expected
+            let severity = match bounds.block_size {
+                Some(block) if total - filesize < block => return,
+                Some(_) => Severity::Warning,
+                None => Severity::Info,
+            };
+            self.report(
+                Rule::ByteRunLength,
+                severity,
+                file,
+                format!(
+                    "data byte runs cover {} bytes but filesize is {}",
+                    total, filesize
+                ),
+            );
+        }
+    }
+
+    fn check_id(&mut self, file: &FileObject) {
+        let Some(id) = file.id else {
+            return;
+        };
+        if let Some(first) = self.ids.get(&id) {
+            let message = match first {
+                Some(name) => format!("id {} is also used by {:?}", id, name),
+                None => format!("id {} is used more than once", id),
+            };
+            self.report(Rule::DuplicateId, Severity::Error, file, message);
+        } else {
+            self.ids.insert(id, file.filename.clone());
+        }
+    }
+
+    fn check_timestamps(&mut self, file: &FileObject) {
+        for name in TIMESTAMP_NAMES {
+            let Some(time) = file.get_timestamp(name).and_then(|t| t.time) else {
+                continue;
+            };
+            let utc = time.with_timezone(&Utc);
+            if utc < self.config.earliest || utc > self.config.latest {
+                self.report(
+                    Rule::TimestampRange,
+                    Severity::Warning,
+                    file,
+                    format!(
+                        "{} {} is outside the expected range",
+                        name,
+                        time.to_rfc3339()
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_alloc(&mut self, file: &FileObject) {
+        let parts = file.alloc_inode.zip(file.alloc_name);
+        let message = match (file.alloc, parts) {
+            (Some(true), Some((false, false))) => {
+                "file is allocated but neither its inode nor its name is"
+            }
+            (Some(false), Some((true, true))) => {
+                "file is unallocated but both its inode and its name are"
+            }
+            _ => return,
+        };
+        self.report(Rule::AllocFlags, Severity::Error, file, message.to_string());
+    }
+
+    fn walk_disk_image(&mut self, image: &DiskImageObject) {
+        self.check_disk_image(image);
+        let bounds = Bounds::disk_image(image);
+        for ps in image.partition_systems() {
+            self.walk_partition_system(ps, bounds);
+        }
+        for partition in image.partitions() {
+            self.walk_partition(partition, bounds);
+        }
+        for volume in image.volumes() {
+            self.walk_volume(volume, bounds);
+        }
+        for file in image.files() {
+            self.check_file(file, bounds);
+        }
+    }
+
+    fn walk_partition_system(&mut self, ps: &PartitionSystemObject, bounds: Bounds) {
+        for partition in ps.partitions() {
+            self.walk_partition(partition, bounds);
+        }
+        for file in ps.files() {
+            self.check_file(file, bounds);
+        }
+    }
+
+    fn walk_partition(&mut self, partition: &PartitionObject, bounds: Bounds) {
+        for ps in partition.partition_systems() {
+            self.walk_partition_system(ps, bounds);
+        }
+        for nested in partition.partitions() {
+            self.walk_partition(nested, bounds);
+        }
+        for volume in partition.volumes() {
+            self.walk_volume(volume, bounds);
+        }
+        for file in partition.files() {
+            self.check_file(file, bounds);
+        }
+    }
+
+    fn walk_volume(&mut self, volume: &VolumeObject, bounds: Bounds) {
+        let bounds = bounds.volume(volume);
+        for file in volume.files() {
+            self.check_file(file, bounds);
+        }
+        for nested in volume.volumes() {
+            self.walk_volume(nested, bounds);
+        }
+        for image in volume.disk_images() {
+            self.walk_disk_image(image);
+        }
+    }
+}
+
+/// Returns the byte run collections of a file with their facet names.
+fn facets(file: &FileObject) -> impl Iterator<Item = (&'static str, &ByteRuns)> {
+    [
+        ("data", &file.data_brs),
+        ("inode", &file.inode_brs),
+        ("name", &file.name_brs),
+    ]
+    .into_iter()
+    .filter_map(|(facet, runs)| Some((facet, runs.as_ref()?)))
+}
+
+/// Returns `(earlier, later, offset)` for each run that starts inside an
+/// earlier run, comparing the offsets picked by `offset`.
+fn overlaps(runs: &ByteRuns, offset: fn(&ByteRun) -> Option<u64>) -> Vec<(usize, usize, u64)> {
+    let mut extents: Vec<(u64, u64, usize)> = runs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, r)| Some((offset(r)?, r.len?, i)))
+        .filter(|(_, len, _)| *len > 0)
+        .collect();
+    extents.sort_unstable();
+
+    let mut found = Vec::new();
+    // The run reaching furthest so far, as (end, index)
+    let mut furthest: Option<(u64, usize)> = None;
+    for (start, len, i) in extents {
+        let end = start.saturating_add(len);
+        match furthest {
+            Some((reach, j)) if reach > start => {
+                found.push((j.min(i), j.max(i), start));
+                if end > reach {
+                    furthest = Some((end, i));
+                }
+            }
+            _ => furthest = Some((end, i)),
+        }
+    }
+    found
+}
+
+/// Returns the problems with a set of digests, each message prefixed by
+/// `context`.
+fn hash_problems(hashes: &Hashes, context: &str) -> Vec<(Severity, String)> {
+    let mut problems = Vec::new();
+    for (hash_type, digest) in hashes.iter() {
+        let digest = digest.trim();
+        if !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+            problems.push((
+                Severity::Error,
+                format!(
+                    "{}{} digest {:?} is not hexadecimal",
+                    context, hash_type, digest
+                ),
+            ));
+        } else if digest.len() != hash_type.expected_hex_len() {
+            // MD6 output length is configurable, so other lengths may be valid
+            let severity = if hash_type == HashType::Md6 {
+                Severity::Warning
+            } else {
+                Severity::Error
+            };
+            problems.push((
+                severity,
+                format!(
+                    "{}{} digest has {} hex digits, expected {}",
+                    context,
+                    hash_type,
+                    digest.len(),
+                    hash_type.expected_hex_len()
+                ),
+            ));
+        }
+    }
+    problems
+}
+
+/// Checks every object of a parsed document.
+pub fn lint_document(doc: &DFXMLObject, config: &LintConfig) -> Vec<Diagnostic> {
+    let mut linter = Linter::new(config);
+    for image in doc.disk_images() {
+        linter.walk_disk_image(image);
+    }
+    for ps in doc.partition_systems() {
+        linter.walk_partition_system(ps, Bounds::default());
+    }
+    for partition in doc.partitions() {
+        linter.walk_partition(partition, Bounds::default());
+    }
+    for volume in doc.volumes() {
+        linter.walk_volume(volume, Bounds::default());
+    }
+    for file in doc.files() {
+        linter.check_file(file, Bounds::default());
+    }
+    linter.out
+}
+
+/// Streams a DFXML document and checks every file object and disk image,
+/// calling `report` with each diagnostic.
+///
+/// Byte run bounds come from the innermost enclosing disk image and volume.
+pub fn lint_reader<R, F>(input: R, config: &LintConfig, mut report: F) -> Result<LintSummary>
+where
+    R: BufRead,
+    F: FnMut(&Diagnostic),
+{
+    let mut reader = DFXMLReader::from_reader(input);
+    let mut linter = Linter::new(config);
+    let mut summary = LintSummary::default();
+    while let Some(event) = reader.next() {
+        match event? {
+            Event::FileObject(file) => {
+                let mut bounds = reader
+                    .current_disk_image()
+                    .map(Bounds::disk_image)
+                    .unwrap_or_default();
+                if let Some(volume) = reader.current_volume() {
+                    bounds = bounds.volume(volume);
+                }
+                summary.files += 1;
+                linter.check_file(&file, bounds);
+            }
+            Event::DiskImageEnd(image) => linter.check_disk_image(&image),
+            _ => continue,
+        }
+        for diagnostic in linter.out.drain(..) {
+            summary.record(&diagnostic);
+            report(&diagnostic);
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{Timestamp, TimestampName};
+    use std::io::Cursor;
+
+    fn rules(diagnostics: &[Diagnostic]) -> Vec<Rule> {
+        diagnostics.iter().map(|d| d.rule).collect()
+    }
+
+    /// Returns the severities of one rule's diagnostics.
+    fn severities(diagnostics: &[Diagnostic], rule: Rule) -> Vec<Severity> {
+        diagnostics
+            .iter()
+            .filter(|d| d.rule == rule)
+            .map(|d| d.severity)
+            .collect()
+    }
+
+    /// Lints files in a volume of 100 512-byte blocks at image offset 1 MiB.
+    fn lint_in_volume(files: Vec<FileObject>) -> Vec<Diagnostic> {
+        let mut volume = VolumeObject::new();
+        volume.partition_offset = Some(VOLUME_OFFSET);
+        volume.block_size = Some(512);
+        volume.block_count = Some(100);
+        for file in files {
+            volume.append_file(file);
+        }
+        let mut doc = DFXMLObject::new();
+        doc.append_volume(volume);
+        lint_document(&doc, &LintConfig::default())
+    }
+
+    const VOLUME_OFFSET: u64 = 1 << 20;
+
+    fn run(img_offset: u64, file_offset: u64, len: u64) -> ByteRun {
+        ByteRun {
+            file_offset: Some(file_offset),
+            ..ByteRun::with_img_offset(img_offset, len)
+        }
+    }
+
+    fn file_with_runs(filesize: u64, runs: Vec<ByteRun>) -> FileObject {
+        let mut file = FileObject::with_filename("f");
+        file.filesize = Some(filesize);
+        file.data_brs = Some(runs.into_iter().collect());
+        file
+    }
+
+    #[test]
+    fn test_clean_file() {
+        let mut file = FileObject::with_filename("ok.txt");
+        file.id = Some(1);
+        file.filesize = Some(5000);
+        file.alloc_inode = Some(true);
+        file.alloc_name = Some(true);
+        file.hashes.set(
+            HashType::Md5,
+            "D41D8CD98F00B204E9800998ECF8427E".to_string(),
+        );
+        let mut runs = ByteRuns::new();
+        runs.push(ByteRun {
+            file_offset: Some(0),
+            ..ByteRun::with_img_offset(8192, 4096)
+        });
+        runs.push(ByteRun {
+            file_offset: Some(4096),
+            ..ByteRun::with_img_offset(16384, 4096)
+        });
+        file.data_brs = Some(runs);
+
+        let mut volume = VolumeObject::new();
+        volume.partition_offset = Some(4096);
+        volume.block_size = Some(4096);
+        volume.block_count = Some(10);
+        volume.append_file(file);
+        let mut doc = DFXMLObject::new();
+        doc.append_volume(volume);
+
+        assert_eq!(lint_document(&doc, &LintConfig::default()), []);
+    }
+
+    #[test]
+    fn test_file_rules() {
+        let mut file = FileObject::with_filename("bad.txt");
+        file.id = Some(7);
+        file.filesize = Some(100);
+        file.alloc = Some(true);
+        file.alloc_inode = Some(false);
+        file.alloc_name = Some(false);
+        file.hashes.set(HashType::Sha1, "xyz".to_string());
+        file.mtime = Some(Timestamp::with_name_and_time(
+            TimestampName::Mtime,
+            Timestamp::parse_iso8601("1601-01-01T00:00:00Z").unwrap(),
+        ));
+        let mut runs = ByteRuns::new();
+        runs.push(ByteRun::with_img_offset(0, 4096));
+        runs.push(ByteRun::with_img_offset(2048, 4096));
+        file.data_brs = Some(runs);
+        let mut twin = FileObject::with_filename("twin.txt");
+        twin.id = Some(7);
+
+        let mut image = DiskImageObject::with_filename("disk.raw");
+        image.image_size = Some(5000);
+        image.append_file(file);
+        image.append_file(twin);
+        let mut doc = DFXMLObject::new();
+        doc.append_disk_image(image);
+
+        let diagnostics = lint_document(&doc, &LintConfig::default());
+        assert_eq!(
+            rules(&diagnostics),
+            [
+                Rule::HashLength,
+                Rule::ByteRunOverlap,
+                Rule::ByteRunBounds,
+                Rule::ByteRunLength,
+                Rule::TimestampRange,
+                Rule::AllocFlags,
+                Rule::DuplicateId,
+            ]
+        );
+        assert_eq!(diagnostics[6].filename.as_deref(), Some("twin.txt"));
+        assert_eq!(
+            diagnostics[6].to_string(),
+            "error[duplicate-id]: fileobject \"twin.txt\": id 7 is also used by \"bad.txt\""
+        );
+
+        let config = LintConfig::new()
+            .without_rule(Rule::HashLength)
+            .without_rule(Rule::DuplicateId);
+        let diagnostics = lint_document(&doc, &config);
+        assert_eq!(diagnostics.len(), 5);
+    }
+
+    #[test]
+    fn test_hash_length() {
+        let mut file = FileObject::with_filename("f");
+        file.hashes
+            .set(HashType::Md5, "d41d8cd98f00b204e9800998ecf842".to_string());
+        file.hashes.set(HashType::Sha1, "z".repeat(40));
+        file.hashes.set(HashType::Md6, "abcd".to_string());
+        let mut bad_run = run(VOLUME_OFFSET, 0, 512);
+        bad_run.hashes.set(HashType::Sha256, "00".to_string());
+        file.data_brs = Some(vec![bad_run].into_iter().collect());
+        let diagnostics = lint_in_volume(vec![file]);
+        assert_eq!(
+            severities(&diagnostics, Rule::HashLength),
+            [
+                Severity::Error,
+                Severity::Error,
+                Severity::Warning,
+                Severity::Error
+            ]
+        );
+        assert!(diagnostics[3]
+            .message
+            .starts_with("data byte run 0: sha256"));
+
+        let mut file = FileObject::with_filename("f");
+        file.hashes.set(
+            HashType::Md5,
+            " D41D8CD98F00B204E9800998ECF8427E\n".to_string(),
+        );
+        assert_eq!(lint_in_volume(vec![file]), []);
+    }
+
+    #[test]
+    fn test_byte_run_overlap() {
+        let image = file_with_runs(
+            1536,
+            vec![
+                run(VOLUME_OFFSET, 0, 1024),
+                run(VOLUME_OFFSET + 512, 1024, 512),
+            ],
+        );
+        let in_file = file_with_runs(
+            1024,
+            vec![
+                run(VOLUME_OFFSET, 0, 1024),
+                run(VOLUME_OFFSET + 4096, 512, 512),
+            ],
+        );
+        let diagnostics = lint_in_volume(vec![image, in_file]);
+        assert_eq!(
+            severities(&diagnostics, Rule::ByteRunOverlap),
+            [Severity::Warning, Severity::Error]
+        );
+        assert!(diagnostics[0].message.contains("in the image at offset"));
+
+        let adjacent = file_with_runs(
+            1024,
+            vec![
+                run(VOLUME_OFFSET + 512, 512, 512),
+                run(VOLUME_OFFSET, 0, 512),
+            ],
+        );
+        assert_eq!(lint_in_volume(vec![adjacent]), []);
+    }
+
+    #[test]
+    fn test_byte_run_bounds() {
+        let overflow = file_with_runs(10, vec![run(u64::MAX - 5, 0, 10)]);
+        let diagnostics = lint_in_volume(vec![overflow]);
+        assert_eq!(
+            severities(&diagnostics, Rule::ByteRunBounds),
+            [Severity::Error, Severity::Error]
+        );
+        assert!(diagnostics[0].message.contains("plus len 10 overflows"));
+        assert!(diagnostics[1].message.contains("lies outside the volume"));
+
+        let mut past_volume = file_with_runs(1024, Vec::new());
+        past_volume.data_brs = Some(
+            vec![ByteRun {
+                fs_offset: Some(99 * 512),
+                len: Some(1024),
+                ..Default::default()
+            }]
+            .into_iter()
+            .collect(),
+        );
+        let diagnostics = lint_in_volume(vec![past_volume]);
+        assert_eq!(rules(&diagnostics), [Rule::ByteRunBounds]);
+
+        let inside = file_with_runs(512, vec![run(VOLUME_OFFSET + 99 * 512, 0, 512)]);
+        assert_eq!(lint_in_volume(vec![inside]), []);
+    }
+
+    #[test]
+    fn test_byte_run_length() {
+        // More than a block past the file size
+        let over = file_with_runs(100, vec![run(VOLUME_OFFSET, 0, 1024)]);
+        assert_eq!(
+            severities(&lint_in_volume(vec![over.clone()]), Rule::ByteRunLength),
+            [Severity::Warning]
+        );
+        // Without a block size the excess may be legitimate slack
+        let mut doc = DFXMLObject::new();
+        doc.append_file(over);
+        assert_eq!(
+            severities(
+                &lint_document(&doc, &LintConfig::default()),
+                Rule::ByteRunLength
+            ),
+            [Severity::Info]
+        );
+
+        // Slack within the last block, and sparse runs with a gap and no tail
+        let slack = file_with_runs(100, vec![run(VOLUME_OFFSET, 0, 512)]);
+        let sparse = file_with_runs(
+            8192,
+            vec![
+                run(VOLUME_OFFSET, 0, 512),
+                run(VOLUME_OFFSET + 512, 4096, 512),
+            ],
+        );
+        assert_eq!(lint_in_volume(vec![slack, sparse]), []);
+    }
+
+    #[test]
+    fn test_walker_output_is_clean() {
+        use crate::filesystem::{ext::ExtFileSystem, FileSystem};
+
+        let image: &[u8] =
+            include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/ext2.img"));
+        let mut fs = ExtFileSystem::open(Cursor::new(image), 0).unwrap();
+        let mut volume = fs.volume();
+        let mut sparse = false;
+        for file in fs.files() {
+            let file = file.unwrap();
+            sparse |= file.filename.as_deref() == Some("sparse.bin");
+            volume.append_file(file);
+        }
+        assert!(sparse);
+        let mut doc = DFXMLObject::new();
+        doc.append_volume(volume);
+        assert_eq!(lint_document(&doc, &LintConfig::default()), []);
+    }
+
+    #[test]
+    fn test_duplicate_id() {
+        let file = |id: Option<u64>| {
+            let mut file = FileObject::with_filename("f");
+            file.id = id;
+            file
+        };
+        let diagnostics = lint_in_volume(vec![file(Some(3)), file(None), file(Some(3))]);
+        assert_eq!(rules(&diagnostics), [Rule::DuplicateId]);
+        assert_eq!(diagnostics[0].id, Some(3));
+
+        assert_eq!(
+            lint_in_volume(vec![file(Some(1)), file(Some(2)), file(None), file(None)]),
+            []
+        );
+    }
+
+    #[test]
+    fn test_timestamp_range() {
+        let at = |name: TimestampName, time: &str| {
+            Some(Timestamp::with_name_and_time(
+                name,
+                Timestamp::parse_iso8601(time).unwrap(),
+            ))
+        };
+        let mut file = FileObject::with_filename("f");
+        file.mtime = at(TimestampName::Mtime, "1601-01-01T00:00:00Z");
+        file.crtime = at(TimestampName::Crtime, "2999-01-01T00:00:00Z");
+        file.atime = at(TimestampName::Atime, "2020-06-01T12:00:00+02:00");
+        let diagnostics = lint_in_volume(vec![file.clone()]);
+        assert_eq!(
+            severities(&diagnostics, Rule::TimestampRange),
+            [Severity::Warning, Severity::Warning]
+        );
+
+        file.mtime = None;
+        file.crtime = None;
+        assert_eq!(lint_in_volume(vec![file.clone()]), []);
+
+        let config =
+            LintConfig::new().with_earliest(Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap());
+        let mut doc = DFXMLObject::new();
+        doc.append_file(file);
+        assert_eq!(rules(&lint_document(&doc, &config)), [Rule::TimestampRange]);
+    }
+
+    #[test]
+    fn test_alloc_flags() {
+        let file = |alloc: Option<bool>, inode: bool, name: bool| {
+            let mut file = FileObject::with_filename("f");
+            file.alloc = alloc;
+            file.alloc_inode = Some(inode);
+            file.alloc_name = Some(name);
+            file
+        };
+        let diagnostics = lint_in_volume(vec![
+            file(Some(true), false, false),
+            file(Some(false), true, true),
+        ]);
+        assert_eq!(
+            severities(&diagnostics, Rule::AllocFlags),
+            [Severity::Error, Severity::Error]
+        );
+
+        assert_eq!(
+            lint_in_volume(vec![
+                file(Some(true), true, false),
+                file(Some(false), false, false),
+                file(None, false, false),
+            ]),
+            []
+        );
+    }
+
+    #[test]
+    fn test_lint_reader() {
+        let xml = r#"<?xml version="1.0"?>
+<dfxml version="1.0">
+  <volume>
+    <partition_offset>1048576</partition_offset>
+    <block_size>512</block_size>
+    <block_count>100</block_count>
+    <fileobject>
+      <filename>outside.bin</filename>
+      <filesize>512</filesize>
+      <byte_runs>
+        <byte_run img_offset="0" fs_offset="0" len="512"/>
+      </byte_runs>
+    </fileobject>
+    <fileobject>
+      <filename>fine.bin</filename>
+      <filesize>100</filesize>
+      <byte_runs>
+        <byte_run img_offset="1049088" fs_offset="512" len="512"/>
+      </byte_runs>
+    </fileobject>
+  </volume>
+  <fileobject>
+    <filename>flags.bin</filename>
+    <alloc>1</alloc>
+    <alloc_inode>0</alloc_inode>
+    <alloc_name>0</alloc_name>
+  </fileobject>
+</dfxml>"#;
+        let mut seen = Vec::new();
+        let summary = lint_reader(Cursor::new(xml), &LintConfig::default(), |d| {
+            seen.push(d.clone())
+        })
+        .unwrap();
+        assert_eq!(summary.files, 3);
+        assert_eq!(summary.errors, 2);
+        assert_eq!(rules(&seen), [Rule::ByteRunBounds, Rule::AllocFlags]);
+        assert_eq!(seen[0].filename.as_deref(), Some("outside.bin"));
+        assert_eq!("alloc-flags".parse::<Rule>().unwrap(), Rule::AllocFlags);
+        assert!(matches!(
+            "no-such-rule".parse::<Rule>(),
+            Err(Error::InvalidLintOption(_))
+        ));
+    }
+}
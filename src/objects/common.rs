@@ -260,6 +260,17 @@ impl Precision {
     pub fn new(resolution: i32, unit: TimeUnit) -> Self {
         Self { resolution, unit }
     }
+
+    /// Returns the length of time this precision covers.
+    pub fn duration(&self) -> chrono::Duration {
+        let n = i64::from(self.resolution);
+        match self.unit {
+            TimeUnit::Day => chrono::Duration::days(n),
+            TimeUnit::Second => chrono::Duration::seconds(n),
+            TimeUnit::Millisecond => chrono::Duration::milliseconds(n),
+            TimeUnit::Nanosecond => chrono::Duration::nanoseconds(n),
+        }
+    }
 }
 
 impl FromStr for Precision {
@@ -64,8 +64,9 @@ impl Default for LibraryObject {
 /// - Child objects (disk images, volumes, files)
 /// - Build environment information
 /// - External elements from non-DFXML namespaces
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct DFXMLObject {
     // === Document Metadata ===
     /// DFXML schema version
@@ -89,12 +90,12 @@ pub struct DFXMLObject {
 
     // === Dublin Core Metadata ===
     /// Dublin Core metadata elements
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub dc: HashMap<String, String>,
 
     // === Namespaces ===
     /// XML namespaces (prefix -> URI)
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(feature = "serde", serde(default = "default_namespaces"))]
     namespaces: HashMap<String, String>,
 
     // === External Elements ===
@@ -103,19 +104,34 @@ pub struct DFXMLObject {
 
     // === Child Objects ===
     /// Disk images directly attached to this document
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     disk_images: Vec<DiskImageObject>,
     /// Partition systems directly attached to this document
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     partition_systems: Vec<PartitionSystemObject>,
     /// Partitions directly attached to this document
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     partitions: Vec<PartitionObject>,
     /// Volumes directly attached to this document
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     volumes: Vec<VolumeObject>,
     /// Files directly attached to this document (not in a volume)
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     files: Vec<FileObject>,
 
     // === Differential Analysis ===
@@ -123,21 +139,27 @@ pub struct DFXMLObject {
     pub diff_file_ignores: HashSet<String>,
 }
 
+/// Returns the namespaces every new document declares.
+fn default_namespaces() -> HashMap<String, String> {
+    [
+        ("", XMLNS_DFXML),
+        ("dc", XMLNS_DC),
+        ("delta", XMLNS_DELTA),
+        ("dfxmlext", XMLNS_DFXML_EXT),
+    ]
+    .into_iter()
+    .map(|(prefix, uri)| (prefix.to_string(), uri.to_string()))
+    .collect()
+}
+
 impl DFXMLObject {
     /// Creates a new DFXMLObject with default settings.
     pub fn new() -> Self {
-        let mut obj = Self {
+        Self {
             version: DFXML_VERSION.to_string(),
+            namespaces: default_namespaces(),
             ..Default::default()
-        };
-
-        // Add default namespaces
-        obj.add_namespace("", XMLNS_DFXML);
-        obj.add_namespace("dc", XMLNS_DC);
-        obj.add_namespace("delta", XMLNS_DELTA);
-        obj.add_namespace("dfxmlext", XMLNS_DFXML_EXT);
-
-        obj
+        }
     }
 
     /// Creates a DFXMLObject with a specific version.
@@ -376,13 +398,19 @@ impl DFXMLObject {
         // Files in volumes directly attached
         let volume_files = self.volumes.iter().flat_map(|v| v.iter_all_files());
 
-        // Files in disk images
-        let disk_image_files = self.disk_images.iter().flat_map(|di| {
-            di.files()
-                .chain(di.volumes().flat_map(|v| v.iter_all_files()))
-        });
+        // Files in disk images, partition systems and partitions
+        let disk_image_files = self.disk_images.iter().flat_map(|di| di.iter_all_files());
+        let partition_system_files = self
+            .partition_systems
+            .iter()
+            .flat_map(|ps| ps.iter_all_files());
+        let partition_files = self.partitions.iter().flat_map(|p| p.iter_all_files());
 
-        direct_files.chain(volume_files).chain(disk_image_files)
+        direct_files
+            .chain(volume_files)
+            .chain(disk_image_files)
+            .chain(partition_system_files)
+            .chain(partition_files)
     }
 }
 
@@ -422,7 +450,7 @@ pub enum DFXMLChild<'a> {
 /// doc.append(VolumeObject::with_ftype("ntfs").into());
 /// doc.append(FileObject::with_filename("another.txt").into());
 /// ```
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ChildObject {
     /// A disk image object
     DiskImage(DiskImageObject),
@@ -727,4 +755,53 @@ mod tests {
         assert!(matches!(items[0], DFXMLChild::Volume(_)));
         assert!(matches!(items[1], DFXMLChild::File(_)));
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_full_tree() {
+        use crate::objects::{HashType, PartitionObject, PartitionSystemObject};
+
+        let mut doc = DFXMLObject::new();
+        doc.program = Some("serde-test".to_string());
+
+        let mut original = FileObject::with_filename("old.txt");
+        original.filesize = Some(1);
+        let mut file = FileObject::with_filename("new.txt");
+        file.filesize = Some(2);
+        file.hashes.set(
+            HashType::Md5,
+            "d41d8cd98f00b204e9800998ecf8427e".to_string(),
+        );
+        file.original_fileobject = Some(Box::new(original));
+
+        let mut inner = VolumeObject::with_ftype("iso9660");
+        inner.append_file(file);
+        let mut vol = VolumeObject::with_ftype("ext4");
+        vol.append_volume(inner);
+        let mut partition = PartitionObject::new();
+        partition.append_volume(vol);
+        let mut ps = PartitionSystemObject::new();
+        ps.append_partition(partition);
+        let mut di = DiskImageObject::new();
+        di.append_partition_system(ps);
+        doc.append_disk_image(di);
+        doc.append_file(FileObject::with_filename("loose.txt"));
+
+        let json = serde_json::to_string(&doc).unwrap();
+        let restored: DFXMLObject = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored, doc);
+        assert_eq!(restored.iter_files().count(), 2);
+        assert_eq!(restored.namespaces().count(), doc.namespaces().count());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_default_namespaces() {
+        let restored: DFXMLObject = serde_json::from_str(r#"{"version": "1.0"}"#).unwrap();
+        let ns: HashMap<_, _> = restored.namespaces().collect();
+        assert_eq!(ns.get(""), Some(&XMLNS_DFXML));
+        assert_eq!(ns.get("delta"), Some(&XMLNS_DELTA));
+        assert_eq!(ns.len(), DFXMLObject::new().namespaces().count());
+    }
 }
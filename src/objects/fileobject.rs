@@ -159,6 +159,56 @@ impl MetaType {
             _ => MetaType::Unknown,
         }
     }
+
+    /// Returns the TSK meta type code (the inverse of [`MetaType::from_code`]).
+    pub fn code(&self) -> i32 {
+        match self {
+            MetaType::Regular => 1,
+            MetaType::Directory => 2,
+            MetaType::SymbolicLink => 3,
+            MetaType::BlockDevice => 4,
+            MetaType::CharacterDevice => 5,
+            MetaType::Fifo => 6,
+            MetaType::Socket => 7,
+            MetaType::Shadow => 8,
+            MetaType::Virtual => 9,
+            MetaType::Unknown => 0,
+        }
+    }
+
+    /// Creates a MetaType from a single character, as used in the first
+    /// position of TSK's `ls`-style mode strings.
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            'r' | '-' => Some(MetaType::Regular),
+            'd' => Some(MetaType::Directory),
+            'l' => Some(MetaType::SymbolicLink),
+            'b' => Some(MetaType::BlockDevice),
+            'c' => Some(MetaType::CharacterDevice),
+            'p' => Some(MetaType::Fifo),
+            's' => Some(MetaType::Socket),
+            'w' | 'h' => Some(MetaType::Shadow),
+            'v' => Some(MetaType::Virtual),
+            '?' => Some(MetaType::Unknown),
+            _ => None,
+        }
+    }
+
+    /// Returns the single-character string representation.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetaType::Regular => "r",
+            MetaType::Directory => "d",
+            MetaType::SymbolicLink => "l",
+            MetaType::BlockDevice => "b",
+            MetaType::CharacterDevice => "c",
+            MetaType::Fifo => "p",
+            MetaType::Socket => "s",
+            MetaType::Shadow => "w",
+            MetaType::Virtual => "v",
+            MetaType::Unknown => "-",
+        }
+    }
 }
 
 /// Represents a file object in DFXML.
@@ -170,8 +220,9 @@ impl MetaType {
 /// - Size and hash information
 /// - Byte run locations
 /// - Ownership and permissions
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct FileObject {
     // === Identification ===
     /// Unique identifier within the DFXML document
@@ -267,7 +318,10 @@ pub struct FileObject {
     /// Properties that differ from original
     pub diffs: HashSet<String>,
     /// Reference to the original file object (for differencing)
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
     pub original_fileobject: Option<Box<FileObject>>,
 
     // === External Elements ===
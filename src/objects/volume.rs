@@ -12,7 +12,7 @@ use std::collections::HashSet;
 // ============================================================================
 
 /// Child objects that can be appended to a VolumeObject.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum VolumeChild {
     /// A disk image (for forensic images found within a volume)
     DiskImage(DiskImageObject),
@@ -41,7 +41,7 @@ impl From<FileObject> for VolumeChild {
 }
 
 /// Child objects that can be appended to a PartitionObject.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum PartitionChild {
     /// A partition system
     PartitionSystem(PartitionSystemObject),
@@ -78,7 +78,7 @@ impl From<FileObject> for PartitionChild {
 }
 
 /// Child objects that can be appended to a PartitionSystemObject.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum PartitionSystemChild {
     /// A partition (boxed to reduce enum size)
     Partition(Box<PartitionObject>),
@@ -99,7 +99,7 @@ impl From<FileObject> for PartitionSystemChild {
 }
 
 /// Child objects that can be appended to a DiskImageObject.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum DiskImageChild {
     /// A partition system
     PartitionSystem(PartitionSystemObject),
@@ -196,8 +196,9 @@ pub enum DiskImageChildRef<'a> {
 /// - Block/sector geometry
 /// - Child FileObjects
 /// - External elements from non-DFXML namespaces
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct VolumeObject {
     // === Location ===
     /// Offset from the start of the disk image (bytes)
@@ -239,15 +240,24 @@ pub struct VolumeObject {
 
     // === Child Objects ===
     /// Files contained in this volume
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     files: Vec<FileObject>,
 
     /// Nested volumes (e.g., for disk images within volumes)
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     volumes: Vec<VolumeObject>,
 
     /// Disk images contained in this volume (e.g., forensic images found within a volume)
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     disk_images: Vec<DiskImageObject>,
 
     // === Differential Analysis ===
@@ -256,7 +266,10 @@ pub struct VolumeObject {
     /// Properties that differ from original
     pub diffs: HashSet<String>,
     /// Reference to the original volume (for differencing)
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
     pub original_volume: Option<Box<VolumeObject>>,
 }
 
@@ -274,6 +287,33 @@ impl VolumeObject {
         }
     }
 
+    /// Returns a copy of this volume's properties without its child objects.
+    pub fn without_children(&self) -> Self {
+        Self {
+            partition_offset: self.partition_offset,
+            sector_size: self.sector_size,
+            block_size: self.block_size,
+            block_count: self.block_count,
+            first_block: self.first_block,
+            last_block: self.last_block,
+            ftype: self.ftype,
+            ftype_str: self.ftype_str.clone(),
+            allocated_only: self.allocated_only,
+            error: self.error.clone(),
+            byte_runs: self.byte_runs.clone(),
+            externals: self.externals.clone(),
+            files: Vec::new(),
+            volumes: Vec::new(),
+            disk_images: Vec::new(),
+            annos: self.annos.clone(),
+            diffs: self.diffs.clone(),
+            original_volume: self
+                .original_volume
+                .as_deref()
+                .map(|v| Box::new(v.without_children())),
+        }
+    }
+
     /// Appends any valid child object to this volume.
     ///
     /// VolumeObject can contain: DiskImageObject, VolumeObject, FileObject.
@@ -400,8 +440,9 @@ impl VolumeObject {
 /// Represents a disk partition in DFXML.
 ///
 /// Partitions are intermediate containers between disk images and volumes/file systems.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct PartitionObject {
     /// Partition index/number
     pub partition_index: Option<u32>,
@@ -429,13 +470,25 @@ pub struct PartitionObject {
     pub externals: Externals,
 
     // Child objects
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     volumes: Vec<VolumeObject>,
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     files: Vec<FileObject>,
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     partitions: Vec<PartitionObject>,
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     partition_systems: Vec<PartitionSystemObject>,
 }
 
@@ -445,6 +498,27 @@ impl PartitionObject {
         Self::default()
     }
 
+    /// Returns a copy of this partition's properties without its child objects.
+    pub fn without_children(&self) -> Self {
+        Self {
+            partition_index: self.partition_index,
+            ptype: self.ptype,
+            ptype_str: self.ptype_str.clone(),
+            ftype_str: self.ftype_str.clone(),
+            partition_label: self.partition_label.clone(),
+            guid: self.guid.clone(),
+            block_count: self.block_count,
+            block_size: self.block_size,
+            partition_system_offset: self.partition_system_offset,
+            byte_runs: self.byte_runs.clone(),
+            externals: self.externals.clone(),
+            volumes: Vec::new(),
+            files: Vec::new(),
+            partitions: Vec::new(),
+            partition_systems: Vec::new(),
+        }
+    }
+
     /// Appends any valid child object to this partition.
     ///
     /// PartitionObject can contain: PartitionSystemObject, PartitionObject, VolumeObject, FileObject.
@@ -539,8 +613,9 @@ impl PartitionObject {
 }
 
 /// Represents a partition system (e.g., MBR, GPT) in DFXML.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct PartitionSystemObject {
     /// Partition system type string (e.g., "dos", "gpt")
     pub pstype_str: Option<String>,
@@ -559,9 +634,15 @@ pub struct PartitionSystemObject {
     pub externals: Externals,
 
     // Child objects
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     partitions: Vec<PartitionObject>,
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     files: Vec<FileObject>,
 }
 
@@ -579,6 +660,21 @@ impl PartitionSystemObject {
         }
     }
 
+    /// Returns a copy of this partition system's properties without its child objects.
+    pub fn without_children(&self) -> Self {
+        Self {
+            pstype_str: self.pstype_str.clone(),
+            block_size: self.block_size,
+            volume_name: self.volume_name.clone(),
+            guid: self.guid.clone(),
+            error: self.error.clone(),
+            byte_runs: self.byte_runs.clone(),
+            externals: self.externals.clone(),
+            partitions: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
     /// Appends any valid child object to this partition system.
     ///
     /// PartitionSystemObject can contain: PartitionObject, FileObject.
@@ -642,8 +738,9 @@ impl PartitionSystemObject {
 }
 
 /// Represents a disk image in DFXML.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct DiskImageObject {
     /// Image filename
     pub image_filename: Option<String>,
@@ -662,13 +759,25 @@ pub struct DiskImageObject {
     pub externals: Externals,
 
     // Child objects
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     partition_systems: Vec<PartitionSystemObject>,
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     partitions: Vec<PartitionObject>,
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     volumes: Vec<VolumeObject>,
-    #[cfg_attr(feature = "serde", serde(skip))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
     files: Vec<FileObject>,
 }
 
@@ -686,6 +795,23 @@ impl DiskImageObject {
         }
     }
 
+    /// Returns a copy of this disk image's properties without its child objects.
+    pub fn without_children(&self) -> Self {
+        Self {
+            image_filename: self.image_filename.clone(),
+            image_size: self.image_size,
+            sector_size: self.sector_size,
+            byte_runs: self.byte_runs.clone(),
+            hashes: self.hashes.clone(),
+            error: self.error.clone(),
+            externals: self.externals.clone(),
+            partition_systems: Vec::new(),
+            partitions: Vec::new(),
+            volumes: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
     /// Appends any valid child object to this disk image.
     ///
     /// DiskImageObject can contain: PartitionSystemObject, PartitionObject, VolumeObject, FileObject.
@@ -0,0 +1,469 @@
+//! Interval index from image offsets to the file objects that own them.
+//!
+//! An [`OffsetIndex`] holds every located byte run in a [`DFXMLObject`] (data,
+//! inode and name runs alike) as an interval of image offsets, so a byte
+//! found by carving or a keyword search can be traced back to its file, in
+//! the manner of The Sleuth Kit's `ifind` but driven by DFXML.
+//!
+//! Runs are placed in the image as [`ContentReader`] reads them: by their
+//! `img_offset`, or else by their `fs_offset` plus the partition offset of
+//! the enclosing volume. A volume without a `partition_offset` takes the
+//! offset of the partition object it sits in. Runs with a fill byte have no
+//! location and are left out, as are runs that would end past the largest
+//! 64-bit offset.
+//!
+//! The intervals are kept sorted by start in an implicit binary tree, each
+//! node recording the largest end in its subtree, so a query visits
+//! `O(log n + k)` nodes for `k` hits.
+//!
+//! [`ContentReader`]: crate::extract::ContentReader
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use dfxml_rs::offset_index::OffsetIndex;
+//! use dfxml_rs::reader::parse;
+//! use std::fs::File;
+//! use std::io::BufReader;
+//!
+//! let doc = parse(BufReader::new(File::open("fiwalk.dfxml").unwrap())).unwrap();
+//! let index = OffsetIndex::new(&doc);
+//! for hit in index.lookup(1_234_567) {
+//!     println!("{:?} {} at file offset {:?}", hit.file.filename, hit.facet, hit.file_offset);
+//! }
+//! ```
+
+use crate::objects::{
+    ByteRun, ByteRunFacet, ByteRuns, DFXMLObject, DiskImageObject, FileObject, PartitionObject,
+    PartitionSystemObject, VolumeObject,
+};
+
+/// A byte run that overlaps a queried range of image offsets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OffsetHit<'a> {
+    /// The file that owns the run
+    pub file: &'a FileObject,
+    /// The run itself
+    pub run: &'a ByteRun,
+    /// Which aspect of the file the run describes
+    pub facet: ByteRunFacet,
+    /// First image offset of the overlap
+    pub img_offset: u64,
+    /// Length of the overlap in bytes
+    pub len: u64,
+    /// Offset into the file's content of `img_offset`, for data runs that
+    /// are not compressed
+    pub file_offset: Option<u64>,
+}
+
+/// A located byte run.
+#[derive(Debug, Clone, Copy)]
+struct Interval<'a> {
+    start: u64,
+    end: u64,
+    file: &'a FileObject,
+    run: &'a ByteRun,
+    facet: ByteRunFacet,
+    /// File offset of `start`, if the run maps linearly onto the content
+    file_offset: Option<u64>,
+}
+
+/// An interval index over the byte runs of a document.
+#[derive(Debug, Clone, Default)]
+pub struct OffsetIndex<'a> {
+    /// Intervals sorted by start
+    intervals: Vec<Interval<'a>>,
+    /// Largest end in the implicit subtree rooted at each interval
+    max_end: Vec<u64>,
+}
+
+impl<'a> OffsetIndex<'a> {
+    /// Indexes every located byte run of every file in a document.
+    pub fn new(doc: &'a DFXMLObject) -> Self {
+        let mut intervals = Vec::new();
+        for file in doc.files() {
+            add_file(&mut intervals, file, 0);
+        }
+        for vol in doc.volumes() {
+            add_volume(&mut intervals, vol, 0);
+        }
+        for di in doc.disk_images() {
+            add_disk_image(&mut intervals, di, 0);
+        }
+        for ps in doc.partition_systems() {
+            add_partition_system(&mut intervals, ps, 0);
+        }
+        for p in doc.partitions() {
+            add_partition(&mut intervals, p, 0);
+        }
+        Self::from_intervals(intervals)
+    }
+
+    fn from_intervals(mut intervals: Vec<Interval<'a>>) -> Self {
+        intervals.sort_by_key(|i| (i.start, i.end));
+        let mut max_end = vec![0; intervals.len()];
+        fill_max_end(&intervals, &mut max_end, 0, intervals.len());
+        Self { intervals, max_end }
+    }
+
+    /// Returns the number of indexed byte runs.
+    pub fn len(&self) -> usize {
+        self.intervals.len()
+    }
+
+    /// Returns true if no byte runs are indexed.
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Returns the byte runs that contain an image offset, in order of
+    /// their start.
+    pub fn lookup(&self, offset: u64) -> Vec<OffsetHit<'a>> {
+        self.lookup_range(offset, 1)
+    }
+
+    /// Returns the byte runs that overlap `len` bytes from an image offset,
+    /// in order of their start, each clipped to the range.
+    pub fn lookup_range(&self, offset: u64, len: u64) -> Vec<OffsetHit<'a>> {
+        let end = offset.saturating_add(len);
+        let mut hits = Vec::new();
+        if len > 0 {
+            self.search(0, self.intervals.len(), offset, end, &mut hits);
+        }
+        hits
+    }
+
+    fn search(&self, lo: usize, hi: usize, start: u64, end: u64, hits: &mut Vec<OffsetHit<'a>>) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        if self.max_end[mid] <= start {
+            return;
+        }
+        self.search(lo, mid, start, end, hits);
+        let interval = &self.intervals[mid];
+        if interval.start >= end {
+            return;
+        }
+        if interval.end > start {
+            let from = interval.start.max(start);
+            hits.push(OffsetHit {
+                file: interval.file,
+                run: interval.run,
+                facet: interval.facet,
+                img_offset: from,
+                len: interval.end.min(end) - from,
+                file_offset: interval
+                    .file_offset
+                    .and_then(|o| o.checked_add(from - interval.start)),
+            });
+        }
+        self.search(mid + 1, hi, start, end, hits);
+    }
+}
+
+/// Records the largest end of each implicit subtree; returns the largest
+/// end in `lo..hi`.
+fn fill_max_end(intervals: &[Interval<'_>], max_end: &mut [u64], lo: usize, hi: usize) -> u64 {
+    if lo >= hi {
+        return 0;
+    }
+    let mid = lo + (hi - lo) / 2;
+    let left = fill_max_end(intervals, max_end, lo, mid);
+    let right = fill_max_end(intervals, max_end, mid + 1, hi);
+    max_end[mid] = intervals[mid].end.max(left).max(right);
+    max_end[mid]
+}
+
+/// Returns a partition's offset in the image, if it records one.
+fn partition_image_offset(p: &PartitionObject) -> Option<u64> {
+    p.byte_runs
+        .as_ref()
+        .and_then(|brs| brs.get(0))
+        .and_then(|br| br.img_offset)
+        .or(p.partition_system_offset)
+}
+
+fn add_disk_image<'a>(out: &mut Vec<Interval<'a>>, di: &'a DiskImageObject, offset: u64) {
+    for ps in di.partition_systems() {
+        add_partition_system(out, ps, offset);
+    }
+    for p in di.partitions() {
+        add_partition(out, p, offset);
+    }
+    for vol in di.volumes() {
+        add_volume(out, vol, offset);
+    }
+    for file in di.files() {
+        add_file(out, file, offset);
+    }
+}
+
+fn add_partition_system<'a>(
+    out: &mut Vec<Interval<'a>>,
+    ps: &'a PartitionSystemObject,
+    offset: u64,
+) {
+    for p in ps.partitions() {
+        add_partition(out, p, offset);
+    }
+    for file in ps.files() {
+        add_file(out, file, offset);
+    }
+}
+
+fn add_partition<'a>(out: &mut Vec<Interval<'a>>, p: &'a PartitionObject, offset: u64) {
+    let offset = partition_image_offset(p).unwrap_or(offset);
+    for ps in p.partition_systems() {
+        add_partition_system(out, ps, offset);
+    }
+    for sub in p.partitions() {
+        add_partition(out, sub, offset);
+    }
+    for vol in p.volumes() {
+        add_volume(out, vol, offset);
+    }
+    for file in p.files() {
+        add_file(out, file, offset);
+    }
+}
+
+fn add_volume<'a>(out: &mut Vec<Interval<'a>>, vol: &'a VolumeObject, offset: u64) {
+    let offset = vol.partition_offset.unwrap_or(offset);
+    for file in vol.files() {
+        add_file(out, file, offset);
+    }
+    for sub in vol.volumes() {
+        add_volume(out, sub, offset);
+    }
+    for di in vol.disk_images() {
+        add_disk_image(out, di, offset);
+    }
+}
+
+fn add_file<'a>(out: &mut Vec<Interval<'a>>, file: &'a FileObject, partition_offset: u64) {
+    let facets = [
+        (&file.data_brs, ByteRunFacet::Data),
+        (&file.inode_brs, ByteRunFacet::Inode),
+        (&file.name_brs, ByteRunFacet::Name),
+    ];
+    for (runs, default_facet) in facets {
+        if let Some(runs) = runs {
+            add_runs(out, file, runs, default_facet, partition_offset);
+        }
+    }
+}
+
+fn add_runs<'a>(
+    out: &mut Vec<Interval<'a>>,
+    file: &'a FileObject,
+    runs: &'a ByteRuns,
+    default_facet: ByteRunFacet,
+    partition_offset: u64,
+) {
+    let facet = runs.facet.unwrap_or(default_facet);
+    let mut next_offset = 0;
+    for run in runs.iter() {
+        let Some(len) = run.len else {
+            continue;
+        };
+        let file_offset = run.file_offset.unwrap_or(next_offset);
+        next_offset = file_offset.saturating_add(len);
+
+        let start = match (run.fill, run.img_offset, run.fs_offset) {
+            (Some(_), _, _) => continue,
+            (None, Some(img), _) => Some(img),
+            (None, None, Some(fs)) => partition_offset.checked_add(fs),
+            (None, None, None) => continue,
+        };
+        let Some((start, end)) = start.and_then(|s| Some((s, s.checked_add(len)?))) else {
+            continue;
+        };
+        if len == 0 {
+            continue;
+        }
+        let linear = facet == ByteRunFacet::Data && run.uncompressed_len.is_none_or(|u| u == len);
+        out.push(Interval {
+            start,
+            end,
+            file,
+            run,
+            facet,
+            file_offset: linear.then_some(file_offset),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PART: u64 = 1_048_576;
+
+    fn run(img_offset: Option<u64>, fs_offset: Option<u64>, len: u64) -> ByteRun {
+        ByteRun {
+            img_offset,
+            fs_offset,
+            len: Some(len),
+            ..Default::default()
+        }
+    }
+
+    fn file(name: &str, data: Vec<ByteRun>) -> FileObject {
+        let mut file = FileObject::with_filename(name);
+        file.data_brs = Some(data.into_iter().collect());
+        file
+    }
+
+    fn doc() -> DFXMLObject {
+        let mut vol = VolumeObject::new();
+        vol.partition_offset = Some(PART);
+        let mut a = file(
+            "a.txt",
+            vec![run(None, Some(0), 8192), run(None, Some(16384), 4096)],
+        );
+        let mut inode = ByteRuns::with_facet(ByteRunFacet::Inode);
+        inode.push(run(None, Some(40960), 256));
+        a.inode_brs = Some(inode);
+        vol.append_file(a);
+        vol.append_file(file("deleted.txt", vec![run(None, Some(16384), 4096)]));
+        let mut hole = ByteRun::new();
+        hole.len = Some(4096);
+        hole.fill = Some(0);
+        let mut tail = run(Some(PART + 65536), None, 4096);
+        tail.file_offset = Some(4096);
+        vol.append_file(file("sparse.bin", vec![hole, tail]));
+
+        // Volume without a partition offset, inside a partition that has one
+        let mut inner = VolumeObject::new();
+        inner.append_file(file("b.txt", vec![run(None, Some(0), 512)]));
+        let mut p = PartitionObject::new();
+        p.byte_runs = Some(vec![run(Some(10 * PART), None, PART)].into_iter().collect());
+        p.append_volume(inner);
+
+        let mut doc = DFXMLObject::new();
+        doc.append_volume(vol);
+        doc.append_partition(p);
+        doc.append_file(file("loose.txt", vec![run(Some(100), None, 10)]));
+        doc
+    }
+
+    fn names<'a>(hits: &[OffsetHit<'a>]) -> Vec<&'a str> {
+        hits.iter()
+            .map(|h| h.file.filename.as_deref().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_point_lookups() {
+        let doc = doc();
+        let index = OffsetIndex::new(&doc);
+        assert_eq!(index.len(), 7);
+
+        let hits = index.lookup(PART + 100);
+        assert_eq!(names(&hits), vec!["a.txt"]);
+        assert_eq!(hits[0].facet, ByteRunFacet::Data);
+        assert_eq!(hits[0].file_offset, Some(100));
+        assert_eq!(hits[0].len, 1);
+
+        let hits = index.lookup(PART + 16384 + 5);
+        assert_eq!(names(&hits), vec!["a.txt", "deleted.txt"]);
+        assert_eq!(hits[0].file_offset, Some(8197));
+        assert_eq!(hits[1].file_offset, Some(5));
+
+        assert!(index.lookup(PART + 8192).is_empty());
+
+        let hits = index.lookup(PART + 40960);
+        assert_eq!(names(&hits), vec!["a.txt"]);
+        assert_eq!(hits[0].facet, ByteRunFacet::Inode);
+        assert_eq!(hits[0].file_offset, None);
+
+        let hits = index.lookup(PART + 65536 + 1);
+        assert_eq!(hits[0].file_offset, Some(4097));
+
+        assert_eq!(names(&index.lookup(10 * PART + 3)), vec!["b.txt"]);
+        assert_eq!(names(&index.lookup(109)), vec!["loose.txt"]);
+        assert!(index.lookup(110).is_empty());
+    }
+
+    #[test]
+    fn test_range_lookup() {
+        let doc = doc();
+        let index = OffsetIndex::new(&doc);
+        let hits = index.lookup_range(PART + 4096, 16384);
+        assert_eq!(names(&hits), vec!["a.txt", "a.txt", "deleted.txt"]);
+        assert_eq!(
+            (hits[0].img_offset, hits[0].len, hits[0].file_offset),
+            (PART + 4096, 4096, Some(4096))
+        );
+        assert_eq!(
+            (hits[1].img_offset, hits[1].len, hits[1].file_offset),
+            (PART + 16384, 4096, Some(8192))
+        );
+        assert!(index.lookup_range(PART, 0).is_empty());
+    }
+
+    #[test]
+    fn test_overflowing_runs_are_skipped() {
+        let mut vol = VolumeObject::new();
+        vol.partition_offset = Some(u64::MAX - 100);
+        vol.append_file(file("past_end.bin", vec![run(None, Some(200), 10)]));
+        let mut doc = DFXMLObject::new();
+        doc.append_volume(vol);
+        let mut tail = run(Some(u64::MAX - 10), None, 5);
+        tail.file_offset = Some(u64::MAX);
+        doc.append_file(file(
+            "huge.bin",
+            vec![run(Some(u64::MAX - 100), None, 200), tail],
+        ));
+
+        let index = OffsetIndex::new(&doc);
+        assert_eq!(index.len(), 1);
+        let hits = index.lookup(u64::MAX - 7);
+        assert_eq!(names(&hits), vec!["huge.bin"]);
+        assert_eq!(hits[0].file_offset, None);
+    }
+
+    #[test]
+    fn test_matches_linear_scan() {
+        // Deterministic pseudo-random runs of widely varying lengths
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+        let runs: Vec<_> = (0..500)
+            .map(|i| {
+                let len = if i % 50 == 0 {
+                    next(100_000)
+                } else {
+                    next(500)
+                } + 1;
+                run(Some(next(1_000_000)), None, len)
+            })
+            .collect();
+        let mut doc = DFXMLObject::new();
+        for r in &runs {
+            doc.append_file(file("f", vec![r.clone()]));
+        }
+        let index = OffsetIndex::new(&doc);
+
+        for _ in 0..300 {
+            let (start, len) = (next(1_100_000), next(2000));
+            let expected = runs
+                .iter()
+                .filter(|r| {
+                    let (s, e) = (
+                        r.img_offset.unwrap(),
+                        r.img_offset.unwrap() + r.len.unwrap(),
+                    );
+                    len > 0 && s < start + len && start < e
+                })
+                .count();
+            assert_eq!(index.lookup_range(start, len).len(), expected);
+        }
+    }
+}
@@ -0,0 +1,396 @@
+//! Parallel parsing of large DFXML documents.
+//!
+//! [`ParallelReader`] yields the same [`Event`]s as a [`DFXMLReader`], but
+//! hands each top-level `<fileobject>` element to a rayon thread pool to be
+//! parsed. The reader itself only scans for element boundaries and keeps
+//! track of the document and its containers, so a file object is still
+//! attached to the volume (or other container) it appears in, and the
+//! `current_*` accessors of [`ParallelReader::reader`] describe its context.
+//!
+//! File objects are yielded in document order by default. With
+//! [`Order::Completion`] they are yielded as soon as they are parsed, which
+//! may reorder the file objects of one container, both as events and in the
+//! container's list of files. Every other event, such as
+//! [`Event::VolumeEnd`], still follows all file objects before it.
+//!
+//! File objects nested in a delta `<original_fileobject>` or in an element
+//! from another namespace are parsed with their parent. Recovery mode
+//! parses everything on the reader's thread.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use dfxml_rs::parallel::{Order, ParallelReader};
+//! use dfxml_rs::reader::Event;
+//! use std::fs::File;
+//! use std::io::BufReader;
+//!
+//! let file = File::open("large_forensic_output.xml").unwrap();
+//! let reader = ParallelReader::from_reader(BufReader::new(file))
+//!     .with_order(Order::Completion)
+//!     .with_threads(8);
+//!
+//! for result in reader {
+//!     if let Event::FileObject(file) = result.unwrap() {
+//!         println!("File: {:?}", file.filename);
+//!     }
+//! }
+//! ```
+
+use crate::error::{Error, Result};
+use crate::objects::FileObject;
+use crate::reader::{Chunk, ChunkParser, DFXMLReader, Event, Position};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::vec;
+
+/// Consecutive file objects handed to a thread at once.
+const CHUNK_SIZE: usize = 64;
+
+/// File objects that may be parsed at once per thread, unless set with
+/// [`ParallelReader::with_max_in_flight`].
+const IN_FLIGHT_PER_THREAD: usize = 4 * CHUNK_SIZE;
+
+/// The order in which a [`ParallelReader`] yields file objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Order {
+    /// The order they appear in the document, as [`DFXMLReader`] does
+    #[default]
+    Document,
+    /// The order they finish parsing in
+    Completion,
+}
+
+/// A streaming DFXML parser that parses file objects on a thread pool.
+///
+/// Created from a reader (or a configured [`DFXMLReader`]), then used as an
+/// iterator of [`Event`]s exactly like [`DFXMLReader`]. The thread pool is
+/// set up by the first call to `next`.
+pub struct ParallelReader<R: BufRead> {
+    reader: DFXMLReader<R>,
+    order: Order,
+    threads: Option<usize>,
+    max_in_flight: Option<usize>,
+    started: bool,
+}
+
+impl<R: BufRead> ParallelReader<R> {
+    /// Creates a parallel reader from a buffered reader.
+    pub fn from_reader(reader: R) -> Self {
+        Self::new(DFXMLReader::from_reader(reader))
+    }
+
+    /// Creates a parallel reader that splits file objects out of `reader`.
+    pub fn new(reader: DFXMLReader<R>) -> Self {
+        Self {
+            reader,
+            order: Order::default(),
+            threads: None,
+            max_in_flight: None,
+            started: false,
+        }
+    }
+
+    /// Sets the order in which file objects are yielded.
+    pub fn with_order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Parses on a dedicated pool of `threads` threads instead of rayon's
+    /// global pool.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads.max(1));
+        self
+    }
+
+    /// Sets how many file objects may be read ahead of the one being
+    /// yielded, which bounds memory use. Defaults to 256 per thread.
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight);
+        self
+    }
+
+    /// Returns the underlying reader, e.g. for the document, the enclosing
+    /// containers or the position of the last event.
+    pub fn reader(&self) -> &DFXMLReader<R> {
+        &self.reader
+    }
+
+    /// Hands the reader a splitter running on the configured pool.
+    fn start(&mut self) -> Result<()> {
+        let pool = match self.threads {
+            Some(threads) => Some(
+                ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .map_err(|e| Error::Io(std::io::Error::other(e)))?,
+            ),
+            None => None,
+        };
+        let threads = pool
+            .as_ref()
+            .map_or_else(rayon::current_num_threads, |p| p.current_num_threads());
+        let max_in_flight = self.max_in_flight.unwrap_or(threads * IN_FLIGHT_PER_THREAD);
+        self.reader.set_splitter(
+            Box::new(Pool::new(pool, self.order)),
+            CHUNK_SIZE,
+            max_in_flight,
+        );
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Iterator for ParallelReader<R> {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            if let Err(e) = self.start() {
+                return Some(Err(e));
+            }
+        }
+        self.reader.next()
+    }
+}
+
+/// A parsed chunk: its number, and its file objects with the positions of
+/// their start tags.
+type Parsed = (u64, Vec<(Position, Result<FileObject>)>);
+
+/// Parses chunks on a rayon thread pool.
+struct Pool {
+    pool: Option<ThreadPool>,
+    order: Order,
+    sender: Sender<Parsed>,
+    receiver: Receiver<Parsed>,
+    // Chunks submitted but not received, those received out of order, and
+    // the file objects of the chunk being returned
+    outstanding: usize,
+    ready: BTreeMap<u64, Vec<(Position, Result<FileObject>)>>,
+    current: vec::IntoIter<(Position, Result<FileObject>)>,
+    next_seq: u64,
+}
+
+impl Pool {
+    fn new(pool: Option<ThreadPool>, order: Order) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            pool,
+            order,
+            sender,
+            receiver,
+            outstanding: 0,
+            ready: BTreeMap::new(),
+            current: Vec::new().into_iter(),
+            next_seq: 0,
+        }
+    }
+
+    /// Receives one parsed chunk, waiting for it if `wait` is set.
+    fn receive(&mut self, wait: bool) -> Option<Parsed> {
+        if self.outstanding == 0 {
+            return None;
+        }
+        let parsed = if wait {
+            self.receiver.recv().ok()
+        } else {
+            self.receiver.try_recv().ok()
+        };
+        if parsed.is_some() {
+            self.outstanding -= 1;
+        }
+        parsed
+    }
+}
+
+impl ChunkParser for Pool {
+    fn submit(&mut self, chunk: Chunk) {
+        let sender = self.sender.clone();
+        let job = move || {
+            let parsed = chunk.parse();
+            // The reader may have been dropped, leaving nobody to tell
+            let _ = sender.send((chunk.seq, parsed));
+        };
+        self.outstanding += 1;
+        match self.pool {
+            Some(ref pool) => pool.spawn(job),
+            None => rayon::spawn(job),
+        }
+    }
+
+    fn next(&mut self, wait: bool) -> Option<(Position, Result<FileObject>)> {
+        loop {
+            if let Some(parsed) = self.current.next() {
+                return Some(parsed);
+            }
+            let files = match self.order {
+                Order::Completion => self.receive(wait)?.1,
+                Order::Document => match self.ready.remove(&self.next_seq) {
+                    Some(files) => files,
+                    None => {
+                        let (seq, files) = self.receive(wait)?;
+                        self.ready.insert(seq, files);
+                        continue;
+                    }
+                },
+            };
+            self.next_seq += 1;
+            self.current = files.into_iter();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Cursor};
+
+    const NESTED: &str = r#"<?xml version="1.0"?>
+<dfxml version="1.0" xmlns:delta="http://www.forensicswiki.org/wiki/Forensic_Disk_Differencing" xmlns:ex="http://example.com/ns">
+  <creator><program>test</program></creator>
+  <fileobject><filename>top.txt</filename></fileobject>
+  <diskimageobject>
+    <imagesize>1000000</imagesize>
+    <partitionsystemobject>
+      <pstype_str>dos</pstype_str>
+      <partitionobject>
+        <partition_index>1</partition_index>
+        <volume offset="32256">
+          <ftype_str>ntfs</ftype_str>
+          <fileobject delta:changed_file="1">
+            <filename>a.txt</filename>
+            <filesize>10</filesize>
+            <ex:note>first</ex:note>
+            <delta:original_fileobject>
+              <fileobject><filename>a.txt</filename><filesize>5</filesize></fileobject>
+            </delta:original_fileobject>
+            <byte_runs><byte_run img_offset="4096" len="10"/></byte_runs>
+          </fileobject>
+          <!-- a comment with </fileobject> in it -->
+          <fileobject><filename>b.txt</filename><inode>2</inode><ex:note><![CDATA[</fileobject>]]></ex:note></fileobject>
+          <fileobject><filename>c.txt</filename><hashdigest type="md5">d41d8cd98f00b204e9800998ecf8427e</hashdigest></fileobject>
+        </volume>
+        <volume/>
+      </partitionobject>
+    </partitionsystemobject>
+  </diskimageobject>
+  <volume>
+    <fileobject ex:tag="a > b"><filename>d.txt</filename></fileobject>
+    <fileobject><filename>e.txt</filename></fileobject>
+  </volume>
+</dfxml>"#;
+
+    fn events(reader: impl Iterator<Item = Result<(Event, Position)>>) -> Vec<String> {
+        reader
+            .map(|e| {
+                let (event, position) = e.unwrap();
+                let event = format!("{:?} at {}", event, position);
+                // Namespaces are a hash map, so leave them out
+                match event.find("namespaces: {") {
+                    Some(start) => {
+                        let end = start + event[start..].find('}').unwrap();
+                        format!("{}{}", &event[..start], &event[end..])
+                    }
+                    None => event,
+                }
+            })
+            .collect()
+    }
+
+    fn sequential(xml: &str) -> Vec<String> {
+        events(DFXMLReader::from_reader(Cursor::new(xml)).with_positions())
+    }
+
+    fn parallel<R: BufRead>(mut reader: ParallelReader<R>) -> Vec<String> {
+        events(std::iter::from_fn(move || {
+            let event = reader.next()?;
+            Some(event.map(|e| (e, reader.reader().event_position())))
+        }))
+    }
+
+    #[test]
+    fn test_document_order() {
+        let expected = sequential(NESTED);
+        for max_in_flight in [1, 2, 100] {
+            let reader = ParallelReader::from_reader(Cursor::new(NESTED))
+                .with_threads(3)
+                .with_max_in_flight(max_in_flight);
+            assert_eq!(parallel(reader), expected);
+        }
+
+        // Split-out file objects that cross buffer boundaries
+        for capacity in [1, 7, 64] {
+            let input = BufReader::with_capacity(capacity, NESTED.as_bytes());
+            assert_eq!(parallel(ParallelReader::from_reader(input)), expected);
+        }
+    }
+
+    #[test]
+    fn test_completion_order() {
+        let expected = sequential(NESTED);
+        let reader = ParallelReader::from_reader(Cursor::new(NESTED))
+            .with_order(Order::Completion)
+            .with_threads(3);
+
+        // Only runs of file objects may be reordered
+        let runs = |events: &[String]| {
+            let mut runs: Vec<Vec<String>> = vec![Vec::new()];
+            for event in events {
+                if event.starts_with("FileObject") {
+                    runs.last_mut().unwrap().push(event.clone());
+                } else {
+                    // Containers list their files in the order they were yielded
+                    let variant = event.split('(').next().unwrap();
+                    runs.push(vec![variant.to_string()]);
+                    runs.push(Vec::new());
+                }
+            }
+            for run in &mut runs {
+                run.sort();
+            }
+            runs
+        };
+        assert_eq!(runs(&parallel(reader)), runs(&expected));
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let first_error = |xml: &str| {
+            let mut sequential =
+                DFXMLReader::from_reader(Cursor::new(xml)).with_strict_values(true);
+            let sequential_error = sequential.by_ref().find_map(|e| e.err()).unwrap();
+            let mut parallel = ParallelReader::new(
+                DFXMLReader::from_reader(Cursor::new(xml)).with_strict_values(true),
+            );
+            let parallel_error = parallel.by_ref().find_map(|e| e.err()).unwrap();
+            assert_eq!(parallel_error.to_string(), sequential_error.to_string());
+            let position = parallel.reader().error_position().unwrap();
+            assert_eq!(position, sequential.error_position().unwrap());
+            position
+        };
+
+        // Inside a split-out file object
+        let xml = "<dfxml version=\"1.0\">\n  <volume>\n    <fileobject></fileobject> <fileobject>\n      <filename>a</filename>\n      <filesize>big</filesize>\n    </fileobject>\n  </volume>\n</dfxml>";
+        assert_eq!(first_error(xml).line, 5);
+
+        // After one, in markup and in a value
+        let xml = "<dfxml version=\"1.0\">\n  <volume>\n    <fileobject><filename>a</filename></fileobject>\n  </partitionobject>\n</dfxml>";
+        assert_eq!(first_error(xml).line, 4);
+        let xml = "<dfxml version=\"1.0\">\n  <fileobject><filename>a</filename></fileobject>\n  <volume>\n    <block_size>x</block_size>\n  </volume>\n</dfxml>";
+        assert_eq!(first_error(xml).line, 4);
+
+        // Truncated inside a split-out file object
+        let xml = "<dfxml version=\"1.0\">\n  <fileobject><filename>a</filename></fileobject>\n  <fileobject><filename>b</filename>";
+        let events: Vec<_> = ParallelReader::from_reader(Cursor::new(xml))
+            .map(|e| e.unwrap())
+            .collect();
+        assert!(matches!(
+            events.as_slice(),
+            [Event::DFXMLStart(_), Event::FileObject(_)]
+        ));
+    }
+}
@@ -0,0 +1,941 @@
+//! MBR and GPT partition tables of raw disk images.
+//!
+//! [`read_disk_image`] reads the partition table at the start of an image
+//! (or any `Read + Seek` source) and describes it as a [`DiskImageObject`]
+//! holding one [`PartitionSystemObject`] and its [`PartitionObject`]s. Each
+//! partition carries its index, type, sector count and a byte run covering
+//! its sectors; the partition system's byte runs cover the sectors of the
+//! table itself.
+//!
+//! Supported partition systems:
+//!
+//! - DOS/MBR (`dos`), including extended partitions. The logical partitions
+//!   in an extended partition's chain of boot records are nested in the
+//!   extended partition and numbered from 5, as Linux numbers them.
+//! - GPT (`gpt`), found through its protective (or hybrid) MBR. The header
+//!   and entry array checksums are verified, and when the primary copy is
+//!   damaged the partitions are read from the backup at the end of the disk.
+//!   Damage to either copy is recorded in the partition system's `error`.
+//!
+//! The file system in each partition is detected with [`crate::filesystem`]
+//! and named in the partition's `ftype_str` when it is supported.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use dfxml_rs::partition;
+//! use std::fs::File;
+//!
+//! let mut image = File::open("disk.raw").unwrap();
+//! let disk = partition::read_disk_image(&mut image, None).unwrap();
+//! for ps in disk.partition_systems() {
+//!     for p in ps.partitions() {
+//!         println!("{:?} {:?} {:?}", p.partition_index, p.ptype_str, p.byte_runs);
+//!     }
+//! }
+//! ```
+
+use crate::error::{Error, Result};
+use crate::filesystem::{self, le_u16, le_u32, le_u64};
+use crate::objects::{
+    ByteRun, ByteRuns, DFXMLObject, DiskImageObject, PartitionObject, PartitionSystemObject,
+};
+use std::collections::HashSet;
+use std::io::{Read, Seek, SeekFrom};
+
+/// Boot record signature at the end of an MBR or extended boot record.
+const BOOT_SIGNATURE: u16 = 0xaa55;
+/// Offset of the four partition entries in a boot record.
+const MBR_TABLE_OFFSET: usize = 446;
+const MBR_ENTRY_LEN: usize = 16;
+/// Size of the part of a boot record that is read, whatever the sector size.
+const MBR_LEN: usize = 512;
+
+const TYPE_EXTENDED_CHS: u8 = 0x05;
+const TYPE_EXTENDED_LBA: u8 = 0x0f;
+const TYPE_EXTENDED_LINUX: u8 = 0x85;
+const TYPE_GPT_PROTECTIVE: u8 = 0xee;
+
+/// Number of the first logical partition.
+const FIRST_LOGICAL_INDEX: u32 = 5;
+/// Extended boot records followed before a chain is taken to be corrupt.
+const MAX_LOGICAL_PARTITIONS: usize = 256;
+
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+const GPT_MIN_HEADER_LEN: usize = 92;
+const GPT_MIN_ENTRY_LEN: u32 = 128;
+/// Largest partition entry array read (the usual one is 16 KiB).
+const GPT_MAX_ENTRIES_LEN: u64 = 4 * 1024 * 1024;
+
+/// Sector sizes tried, in order, when none is given.
+const SECTOR_SIZES: [u64; 2] = [512, 4096];
+/// Smallest and largest sector sizes accepted.
+const MIN_SECTOR_SIZE: u32 = 512;
+const MAX_SECTOR_SIZE: u32 = 64 * 1024;
+
+/// Reads the partition table of an image into a disk image object.
+///
+/// `sector_size` is the logical sector size of the disk. When it is `None`,
+/// 512 is assumed for MBR disks and GPT disks are probed at 512 and 4096.
+/// The image filename is left for the caller to set.
+pub fn read_disk_image<R: Read + Seek>(
+    image: &mut R,
+    sector_size: Option<u32>,
+) -> Result<DiskImageObject> {
+    let ps = read_partition_system(image, sector_size)?;
+    let mut disk = DiskImageObject::new();
+    disk.image_size = Some(image.seek(SeekFrom::End(0))?);
+    disk.sector_size = ps.block_size;
+    disk.append_partition_system(ps);
+    Ok(disk)
+}
+
+/// Reads the partition table at the start of an image.
+///
+/// Returns [`Error::InvalidPartitionTable`] if the image starts with neither
+/// an MBR nor a GPT (an unpartitioned file system, for example), or if
+/// `sector_size` is not a power of two from 512 to 65536.
+pub fn read_partition_system<R: Read + Seek>(
+    image: &mut R,
+    sector_size: Option<u32>,
+) -> Result<PartitionSystemObject> {
+    if let Some(size) = sector_size {
+        if !size.is_power_of_two() || !(MIN_SECTOR_SIZE..=MAX_SECTOR_SIZE).contains(&size) {
+            return Err(Error::InvalidPartitionTable(format!(
+                "invalid sector size {}",
+                size
+            )));
+        }
+    }
+    let mut mbr = [0u8; MBR_LEN];
+    read_at(image, 0, &mut mbr)?;
+    if le_u16(&mbr, 510) != BOOT_SIGNATURE {
+        return Err(Error::InvalidPartitionTable(
+            "no boot record signature in sector 0".to_string(),
+        ));
+    }
+    let entries = MbrEntry::parse_all(&mbr);
+    if entries.iter().any(|e| e.boot & 0x7f != 0) || entries.iter().all(MbrEntry::is_empty) {
+        return Err(Error::InvalidPartitionTable(
+            "sector 0 holds no partition entries".to_string(),
+        ));
+    }
+    if filesystem::fat::probe(image, 0)? {
+        return Err(Error::InvalidPartitionTable(
+            "sector 0 is a FAT boot sector, not a partition table".to_string(),
+        ));
+    }
+
+    if entries.iter().any(|e| e.ptype == TYPE_GPT_PROTECTIVE) {
+        read_gpt(image, sector_size.map(u64::from))
+    } else {
+        read_mbr(image, &entries, sector_size.map_or(512, u64::from))
+    }
+}
+
+/// Returns the index and image offset of each partition in a document that
+/// holds a file system rather than other partitions (so extended partitions
+/// are skipped), ordered by index.
+///
+/// The offsets are taken from each partition's first byte run, or from its
+/// `partition_system_offset` when it has no byte runs. Partitions without an
+/// index or an offset are left out.
+pub fn partition_offsets(doc: &DFXMLObject) -> Vec<(u32, u64)> {
+    let mut offsets = Vec::new();
+    for di in doc.disk_images() {
+        for ps in di.partition_systems() {
+            collect_offsets(ps.partitions(), &mut offsets);
+        }
+        collect_offsets(di.partitions(), &mut offsets);
+    }
+    for ps in doc.partition_systems() {
+        collect_offsets(ps.partitions(), &mut offsets);
+    }
+    collect_offsets(doc.partitions(), &mut offsets);
+    offsets.sort_unstable();
+    offsets
+}
+
+fn collect_offsets<'a>(
+    partitions: impl Iterator<Item = &'a PartitionObject>,
+    offsets: &mut Vec<(u32, u64)>,
+) {
+    for p in partitions {
+        let extended = p.ptype.is_some_and(|t| {
+            matches!(
+                u8::try_from(t),
+                Ok(TYPE_EXTENDED_CHS | TYPE_EXTENDED_LBA | TYPE_EXTENDED_LINUX)
+            )
+        });
+        if extended || p.partitions().next().is_some() || p.partition_systems().next().is_some() {
+            for ps in p.partition_systems() {
+                collect_offsets(ps.partitions(), offsets);
+            }
+            collect_offsets(p.partitions(), offsets);
+            continue;
+        }
+        let offset = p
+            .byte_runs
+            .as_ref()
+            .and_then(|brs| brs.get(0))
+            .and_then(|br| br.img_offset)
+            .or(p.partition_system_offset);
+        if let (Some(index), Some(offset)) = (p.partition_index, offset) {
+            offsets.push((index, offset));
+        }
+    }
+}
+
+/// One of the four partition entries in an MBR or extended boot record.
+#[derive(Debug, Clone, Copy)]
+struct MbrEntry {
+    boot: u8,
+    ptype: u8,
+    start: u32,
+    count: u32,
+}
+
+impl MbrEntry {
+    fn parse_all(record: &[u8]) -> [MbrEntry; 4] {
+        std::array::from_fn(|i| {
+            let entry = &record[MBR_TABLE_OFFSET + i * MBR_ENTRY_LEN..][..MBR_ENTRY_LEN];
+            MbrEntry {
+                boot: entry[0],
+                ptype: entry[4],
+                start: le_u32(entry, 8),
+                count: le_u32(entry, 12),
+            }
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ptype == 0 || self.count == 0
+    }
+
+    fn is_extended(&self) -> bool {
+        matches!(
+            self.ptype,
+            TYPE_EXTENDED_CHS | TYPE_EXTENDED_LBA | TYPE_EXTENDED_LINUX
+        )
+    }
+
+    /// Describes the entry as a partition starting at sector `start`.
+    fn to_partition(self, index: u32, start: u64, sector_size: u64) -> Result<PartitionObject> {
+        let mut p = sector_partition(index, start, u64::from(self.count), sector_size)?;
+        p.ptype = Some(u32::from(self.ptype));
+        p.ptype_str = Some(match mbr_type_name(self.ptype) {
+            Some(name) => name.to_string(),
+            None => format!("Unknown ({:#04x})", self.ptype),
+        });
+        Ok(p)
+    }
+}
+
+fn read_mbr<R: Read + Seek>(
+    image: &mut R,
+    entries: &[MbrEntry; 4],
+    sector_size: u64,
+) -> Result<PartitionSystemObject> {
+    let mut ps = PartitionSystemObject::with_pstype("dos");
+    ps.block_size = Some(sector_size as u32);
+    let mut table = vec![(0, 1)];
+    let mut next_logical = FIRST_LOGICAL_INDEX;
+
+    for (slot, entry) in entries.iter().enumerate() {
+        if entry.is_empty() {
+            continue;
+        }
+        let mut p = entry.to_partition(slot as u32 + 1, u64::from(entry.start), sector_size)?;
+        if !entry.is_extended() {
+            detect_ftype(image, &mut p);
+        } else if let Err(e) = read_logical_partitions(
+            image,
+            u64::from(entry.start),
+            sector_size,
+            &mut p,
+            &mut table,
+            &mut next_logical,
+        ) {
+            ps.error = Some(e.to_string());
+        }
+        ps.append_partition(p);
+    }
+
+    ps.byte_runs = Some(sector_runs(table, sector_size)?);
+    Ok(ps)
+}
+
+/// Follows the chain of extended boot records in an extended partition,
+/// appending each logical partition to it.
+///
+/// Partitions read before a broken link are kept; the error describes the
+/// link.
+fn read_logical_partitions<R: Read + Seek>(
+    image: &mut R,
+    extended_start: u64,
+    sector_size: u64,
+    extended: &mut PartitionObject,
+    table: &mut Vec<(u64, u64)>,
+    next_index: &mut u32,
+) -> Result<()> {
+    let mut seen = HashSet::new();
+    let mut ebr_sector = extended_start;
+    loop {
+        if !seen.insert(ebr_sector) || seen.len() > MAX_LOGICAL_PARTITIONS {
+            return Err(Error::InvalidPartitionTable(format!(
+                "extended partition chain loops at sector {}",
+                ebr_sector
+            )));
+        }
+        let mut ebr = [0u8; MBR_LEN];
+        read_at(image, sector_offset(ebr_sector, sector_size)?, &mut ebr)?;
+        if le_u16(&ebr, 510) != BOOT_SIGNATURE {
+            return Err(Error::InvalidPartitionTable(format!(
+                "no extended boot record signature in sector {}",
+                ebr_sector
+            )));
+        }
+        table.push((ebr_sector, 1));
+
+        let [logical, link, ..] = MbrEntry::parse_all(&ebr);
+        if !logical.is_empty() {
+            let start = ebr_sector
+                .checked_add(u64::from(logical.start))
+                .ok_or_else(|| overflow("logical partition start"))?;
+            let mut p = logical.to_partition(*next_index, start, sector_size)?;
+            detect_ftype(image, &mut p);
+            extended.append_partition(p);
+            *next_index += 1;
+        }
+        if link.is_empty() || !link.is_extended() {
+            return Ok(());
+        }
+        ebr_sector = extended_start
+            .checked_add(u64::from(link.start))
+            .ok_or_else(|| overflow("extended boot record sector"))?;
+    }
+}
+
+/// A GPT header and its partition entry array, both with valid checksums.
+#[derive(Debug)]
+struct GptCopy {
+    header_lba: u64,
+    backup_lba: u64,
+    disk_guid: [u8; 16],
+    entries_lba: u64,
+    entry_len: usize,
+    entries: Vec<u8>,
+}
+
+impl GptCopy {
+    fn entries_sectors(&self, sector_size: u64) -> u64 {
+        (self.entries.len() as u64).div_ceil(sector_size)
+    }
+}
+
+fn read_gpt<R: Read + Seek>(
+    image: &mut R,
+    sector_size: Option<u64>,
+) -> Result<PartitionSystemObject> {
+    let image_size = image.seek(SeekFrom::End(0))?;
+    let sector_size = match sector_size {
+        Some(size) => size,
+        None => detect_gpt_sector_size(image, image_size)?,
+    };
+    let last_lba = (image_size / sector_size).saturating_sub(1);
+
+    let primary = read_gpt_copy(image, 1, sector_size)?;
+    let backup_lba = primary.as_ref().map_or(last_lba, |p| p.backup_lba);
+    let backup = read_gpt_copy(image, backup_lba, sector_size)?;
+
+    let mut ps = PartitionSystemObject::with_pstype("gpt");
+    ps.block_size = Some(sector_size as u32);
+    let mut table = vec![(0, 1)];
+    for copy in [&primary, &backup].into_iter().flatten() {
+        table.push((copy.header_lba, 1));
+        table.push((copy.entries_lba, copy.entries_sectors(sector_size)));
+    }
+    ps.byte_runs = Some(sector_runs(table, sector_size)?);
+
+    let copy = match (primary, backup) {
+        (Ok(primary), Ok(_)) => primary,
+        (Ok(primary), Err(e)) => {
+            ps.error = Some(format!("backup GPT: {}", e));
+            primary
+        }
+        (Err(e), Ok(backup)) => {
+            ps.error = Some(format!(
+                "primary GPT: {}; partitions read from the backup",
+                e
+            ));
+            backup
+        }
+        (Err(e), Err(_)) => {
+            return Err(Error::InvalidPartitionTable(format!(
+                "protective MBR but no valid GPT: {}",
+                e
+            )))
+        }
+    };
+    ps.guid = Some(format_guid(&copy.disk_guid));
+
+    for (i, entry) in copy.entries.chunks_exact(copy.entry_len).enumerate() {
+        let type_guid = &entry[0..16];
+        if type_guid.iter().all(|&b| b == 0) {
+            continue;
+        }
+        let first = le_u64(entry, 32);
+        let last = le_u64(entry, 40);
+        let Some(count) = last.checked_sub(first) else {
+            continue;
+        };
+        let count = count
+            .checked_add(1)
+            .ok_or_else(|| overflow("partition sector count"))?;
+        let mut p = sector_partition(i as u32 + 1, first, count, sector_size)?;
+        let type_guid = format_guid(type_guid);
+        p.ptype_str = Some(
+            gpt_type_name(&type_guid)
+                .map(str::to_string)
+                .unwrap_or(type_guid),
+        );
+        p.guid = Some(format_guid(&entry[16..32]));
+        let name: Vec<u16> = entry[56..128]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|&u| u != 0)
+            .collect();
+        if !name.is_empty() {
+            p.partition_label = Some(String::from_utf16_lossy(&name));
+        }
+        detect_ftype(image, &mut p);
+        ps.append_partition(p);
+    }
+    Ok(ps)
+}
+
+/// Picks the sector size at which a GPT header (primary, else backup) is
+/// found, falling back to 512.
+fn detect_gpt_sector_size<R: Read + Seek>(image: &mut R, image_size: u64) -> Result<u64> {
+    for backup in [false, true] {
+        for sector_size in SECTOR_SIZES {
+            if image_size < 2 * sector_size {
+                continue;
+            }
+            let lba = if backup {
+                image_size / sector_size - 1
+            } else {
+                1
+            };
+            let mut signature = [0u8; 8];
+            read_at(image, lba * sector_size, &mut signature)?;
+            if &signature == GPT_SIGNATURE {
+                return Ok(sector_size);
+            }
+        }
+    }
+    Ok(SECTOR_SIZES[0])
+}
+
+/// Reads the GPT header at `lba` and its entry array.
+///
+/// The outer result carries I/O errors; the inner one describes why the
+/// copy is not valid.
+fn read_gpt_copy<R: Read + Seek>(
+    image: &mut R,
+    lba: u64,
+    sector_size: u64,
+) -> Result<std::result::Result<GptCopy, String>> {
+    match read_gpt_copy_inner(image, lba, sector_size) {
+        Ok(copy) => Ok(Ok(copy)),
+        Err(Error::InvalidPartitionTable(msg)) => Ok(Err(msg)),
+        Err(e) => Err(e),
+    }
+}
+
+fn read_gpt_copy_inner<R: Read + Seek>(
+    image: &mut R,
+    lba: u64,
+    sector_size: u64,
+) -> Result<GptCopy> {
+    let invalid = |msg: &str| Error::InvalidPartitionTable(format!("{} at LBA {}", msg, lba));
+
+    let mut header = vec![0u8; sector_size as usize];
+    read_at(image, sector_offset(lba, sector_size)?, &mut header)?;
+    if &header[0..8] != GPT_SIGNATURE {
+        return Err(invalid("no header signature"));
+    }
+    let header_len = le_u32(&header, 12) as usize;
+    if header_len < GPT_MIN_HEADER_LEN || header_len > header.len() {
+        return Err(invalid("bad header size"));
+    }
+    let mut checked = header[..header_len].to_vec();
+    checked[16..20].fill(0);
+    if crc32(&checked) != le_u32(&header, 16) {
+        return Err(invalid("header checksum mismatch"));
+    }
+    if le_u64(&header, 24) != lba {
+        return Err(invalid("header records a different location"));
+    }
+
+    let entries_lba = le_u64(&header, 72);
+    let entry_count = le_u32(&header, 80);
+    let entry_len = le_u32(&header, 84);
+    if entry_len < GPT_MIN_ENTRY_LEN || !entry_len.is_multiple_of(8) {
+        return Err(invalid("bad partition entry size"));
+    }
+    let entries_len = u64::from(entry_count) * u64::from(entry_len);
+    if entries_len > GPT_MAX_ENTRIES_LEN {
+        return Err(invalid("partition entry array too large"));
+    }
+    let mut entries = vec![0u8; entries_len as usize];
+    read_at(
+        image,
+        sector_offset(entries_lba, sector_size)?,
+        &mut entries,
+    )?;
+    if crc32(&entries) != le_u32(&header, 88) {
+        return Err(invalid("partition entry array checksum mismatch"));
+    }
+
+    Ok(GptCopy {
+        header_lba: lba,
+        backup_lba: le_u64(&header, 32),
+        disk_guid: header[56..72].try_into().unwrap(),
+        entries_lba,
+        entry_len: entry_len as usize,
+        entries,
+    })
+}
+
+/// Builds a partition covering `count` sectors from sector `start`.
+fn sector_partition(
+    index: u32,
+    start: u64,
+    count: u64,
+    sector_size: u64,
+) -> Result<PartitionObject> {
+    let offset = sector_offset(start, sector_size)?;
+    let len = sector_offset(count, sector_size)?;
+    let mut p = PartitionObject::new();
+    p.partition_index = Some(index);
+    p.block_size = Some(sector_size as u32);
+    p.block_count = Some(count);
+    p.partition_system_offset = Some(offset);
+    let mut runs = ByteRuns::new();
+    runs.push(ByteRun::with_img_offset(offset, len));
+    p.byte_runs = Some(runs);
+    Ok(p)
+}
+
+/// Merges `(first sector, sector count)` extents into byte runs.
+fn sector_runs(mut extents: Vec<(u64, u64)>, sector_size: u64) -> Result<ByteRuns> {
+    extents.sort_unstable();
+    let mut runs = ByteRuns::new();
+    for (sector, count) in extents {
+        runs.glom(ByteRun::with_img_offset(
+            sector_offset(sector, sector_size)?,
+            sector_offset(count, sector_size)?,
+        ));
+    }
+    Ok(runs)
+}
+
+/// Returns the byte offset of a sector (or the length of `sector` sectors).
+fn sector_offset(sector: u64, sector_size: u64) -> Result<u64> {
+    sector
+        .checked_mul(sector_size)
+        .ok_or_else(|| overflow("sector offset"))
+}
+
+/// Returns the error for an offset computed from on-disk values that does
+/// not fit in 64 bits.
+fn overflow(what: &str) -> Error {
+    Error::InvalidPartitionTable(format!("{} overflows", what))
+}
+
+/// Names the file system at the start of a partition, if it is supported.
+fn detect_ftype<R: Read + Seek>(image: &mut R, p: &mut PartitionObject) {
+    if let Some(offset) = p.partition_system_offset {
+        p.ftype_str = filesystem::open(&mut *image, offset)
+            .ok()
+            .and_then(|fs| fs.volume().ftype_str);
+    }
+}
+
+/// Reads exactly `buf.len()` bytes at an image offset, reporting a short
+/// image as a damaged partition table.
+fn read_at<R: Read + Seek>(image: &mut R, offset: u64, buf: &mut [u8]) -> Result<()> {
+    let len = buf.len();
+    filesystem::read_at(image, offset, buf).map_err(|e| match e {
+        Error::InvalidFilesystem(_) => Error::InvalidPartitionTable(format!(
+            "image ends before {} bytes at offset {}",
+            len, offset
+        )),
+        e => e,
+    })
+}
+
+/// Formats a GPT GUID, whose first three fields are little-endian.
+fn format_guid(b: &[u8]) -> String {
+    format!(
+        "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
+        le_u32(b, 0),
+        le_u16(b, 4),
+        le_u16(b, 6),
+        b[8],
+        b[9],
+        b[10],
+        b[11],
+        b[12],
+        b[13],
+        b[14],
+        b[15]
+    )
+}
+
+/// CRC-32 (IEEE 802.3), as used by GPT headers and entry arrays.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Names an MBR partition type the way fdisk does.
+fn mbr_type_name(ptype: u8) -> Option<&'static str> {
+    Some(match ptype {
+        0x01 => "FAT12",
+        0x04 => "FAT16 <32M",
+        0x05 => "Extended",
+        0x06 => "FAT16",
+        0x07 => "HPFS/NTFS/exFAT",
+        0x0b => "W95 FAT32",
+        0x0c => "W95 FAT32 (LBA)",
+        0x0e => "W95 FAT16 (LBA)",
+        0x0f => "W95 Ext'd (LBA)",
+        0x11 => "Hidden FAT12",
+        0x14 => "Hidden FAT16 <32M",
+        0x16 => "Hidden FAT16",
+        0x17 => "Hidden HPFS/NTFS",
+        0x1b => "Hidden W95 FAT32",
+        0x1c => "Hidden W95 FAT32 (LBA)",
+        0x1e => "Hidden W95 FAT16 (LBA)",
+        0x27 => "Hidden NTFS WinRE",
+        0x42 => "SFS",
+        0x82 => "Linux swap / Solaris",
+        0x83 => "Linux",
+        0x85 => "Linux extended",
+        0x8e => "Linux LVM",
+        0xa5 => "FreeBSD",
+        0xa6 => "OpenBSD",
+        0xa8 => "Darwin UFS",
+        0xa9 => "NetBSD",
+        0xaf => "HFS / HFS+",
+        0xee => "GPT",
+        0xef => "EFI (FAT-12/16/32)",
+        0xfb => "VMware VMFS",
+        0xfd => "Linux raid autodetect",
+        _ => return None,
+    })
+}
+
+/// Names a GPT partition type GUID.
+fn gpt_type_name(guid: &str) -> Option<&'static str> {
+    Some(match guid {
+        "C12A7328-F81F-11D2-BA4B-00A0C93EC93B" => "EFI System",
+        "21686148-6449-6E6F-744E-656564454649" => "BIOS boot",
+        "E3C9E316-0B5C-4DB8-817D-F92DF00215AE" => "Microsoft reserved",
+        "EBD0A0A2-B9E5-4433-87C0-68B6B72699C7" => "Microsoft basic data",
+        "5808C8AA-7E8F-42E0-85D2-E1E90434CFB3" => "Microsoft LDM metadata",
+        "AF9B60A0-1431-4F62-BC68-3311714A69AD" => "Microsoft LDM data",
+        "DE94BBA4-06D1-4D40-A16A-BFD50179D6AC" => "Windows recovery environment",
+        "0FC63DAF-8483-4772-8E79-3D69D8477DE4" => "Linux filesystem",
+        "0657FD6D-A4AB-43C4-84E5-0933C84B4F4F" => "Linux swap",
+        "E6D6D379-F507-44C2-A23C-238F2A3DF928" => "Linux LVM",
+        "A19D880F-05FC-4D3B-A006-743F0F84911E" => "Linux RAID",
+        "933AC7E1-2EB4-4F13-B844-0E14E2AEF915" => "Linux home",
+        "4F68BCE3-E8CD-4DB1-96E7-FBCAF984B709" => "Linux root (x86-64)",
+        "48465300-0000-11AA-AA11-00306543ECAC" => "Apple HFS/HFS+",
+        "7C3457EF-0000-11AA-AA11-00306543ECAC" => "Apple APFS",
+        "516E7CB6-6ECF-11D6-8FF8-00022D09712B" => "FreeBSD UFS",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::parse;
+    use crate::writer::to_string;
+    use std::io::Cursor;
+
+    const SECTOR: usize = 512;
+    const EXT2_IMAGE: &[u8] =
+        include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/ext2.img"));
+
+    const LINUX_FS: &str = "0FC63DAF-8483-4772-8E79-3D69D8477DE4";
+    const EFI_SYSTEM: &str = "C12A7328-F81F-11D2-BA4B-00A0C93EC93B";
+    const DISK_GUID: &str = "11223344-5566-7788-99AA-BBCCDDEEFF00";
+
+    fn set_entry(record: &mut [u8], slot: usize, ptype: u8, start: u32, count: u32) {
+        let entry = &mut record[MBR_TABLE_OFFSET + slot * MBR_ENTRY_LEN..][..MBR_ENTRY_LEN];
+        entry[4] = ptype;
+        entry[8..12].copy_from_slice(&start.to_le_bytes());
+        entry[12..16].copy_from_slice(&count.to_le_bytes());
+        record[510..512].copy_from_slice(&BOOT_SIGNATURE.to_le_bytes());
+    }
+
+    fn sector(image: &mut [u8], lba: usize) -> &mut [u8] {
+        &mut image[lba * SECTOR..][..SECTOR]
+    }
+
+    /// A 4 MiB disk with an ext2 primary partition and an extended
+    /// partition holding two logical partitions.
+    fn mbr_image() -> Vec<u8> {
+        let mut image = vec![0u8; 8192 * SECTOR];
+        set_entry(sector(&mut image, 0), 0, 0x83, 2048, 1024);
+        set_entry(sector(&mut image, 0), 1, 0x05, 4096, 2048);
+        image[2048 * SECTOR..][..EXT2_IMAGE.len()].copy_from_slice(EXT2_IMAGE);
+
+        set_entry(sector(&mut image, 4096), 0, 0x07, 63, 100);
+        set_entry(sector(&mut image, 4096), 1, 0x05, 1024, 1024);
+        set_entry(sector(&mut image, 5120), 0, 0x0b, 1, 200);
+        image
+    }
+
+    fn guid_bytes(guid: &str) -> [u8; 16] {
+        let hex: String = guid.chars().filter(|&c| c != '-').collect();
+        let mut b: [u8; 16] =
+            std::array::from_fn(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap());
+        b[0..4].reverse();
+        b[4..6].reverse();
+        b[6..8].reverse();
+        b
+    }
+
+    fn write_gpt_header(
+        image: &mut [u8],
+        lba: u64,
+        backup: u64,
+        entries_lba: u64,
+        entries_crc: u32,
+    ) {
+        let header = sector(image, lba as usize);
+        header[0..8].copy_from_slice(GPT_SIGNATURE);
+        header[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+        header[12..16].copy_from_slice(&92u32.to_le_bytes());
+        header[24..32].copy_from_slice(&lba.to_le_bytes());
+        header[32..40].copy_from_slice(&backup.to_le_bytes());
+        header[40..48].copy_from_slice(&34u64.to_le_bytes());
+        header[56..72].copy_from_slice(&guid_bytes(DISK_GUID));
+        header[72..80].copy_from_slice(&entries_lba.to_le_bytes());
+        header[80..84].copy_from_slice(&128u32.to_le_bytes());
+        header[84..88].copy_from_slice(&128u32.to_le_bytes());
+        header[88..92].copy_from_slice(&entries_crc.to_le_bytes());
+        let crc = crc32(&header[..92]);
+        header[16..20].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    /// A 2 MiB GPT disk with an EFI system partition and an ext2 partition.
+    fn gpt_image() -> Vec<u8> {
+        let sectors = 4096u64;
+        let mut image = vec![0u8; sectors as usize * SECTOR];
+        set_entry(
+            sector(&mut image, 0),
+            0,
+            TYPE_GPT_PROTECTIVE,
+            1,
+            sectors as u32 - 1,
+        );
+
+        let mut entries = vec![0u8; 128 * 128];
+        let parts = [
+            (EFI_SYSTEM, 34, 1057, "EFI"),
+            (LINUX_FS, 2048, 3071, "root"),
+        ];
+        for (i, (ptype, first, last, name)) in parts.into_iter().enumerate() {
+            let entry = &mut entries[i * 128..][..128];
+            entry[0..16].copy_from_slice(&guid_bytes(ptype));
+            entry[16..32].fill(i as u8 + 1);
+            entry[32..40].copy_from_slice(&(first as u64).to_le_bytes());
+            entry[40..48].copy_from_slice(&(last as u64).to_le_bytes());
+            for (j, unit) in name.encode_utf16().enumerate() {
+                entry[56 + j * 2..][..2].copy_from_slice(&unit.to_le_bytes());
+            }
+        }
+        let entries_crc = crc32(&entries);
+        image[2 * SECTOR..][..entries.len()].copy_from_slice(&entries);
+        image[(sectors as usize - 33) * SECTOR..][..entries.len()].copy_from_slice(&entries);
+        write_gpt_header(&mut image, 1, sectors - 1, 2, entries_crc);
+        write_gpt_header(&mut image, sectors - 1, 1, sectors - 33, entries_crc);
+        image[2048 * SECTOR..][..EXT2_IMAGE.len()].copy_from_slice(EXT2_IMAGE);
+        image
+    }
+
+    fn runs(brs: &Option<ByteRuns>) -> Vec<(u64, u64)> {
+        brs.iter()
+            .flatten()
+            .map(|br| (br.img_offset.unwrap(), br.len.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_mbr_with_logical_partitions() {
+        let ps = read_partition_system(&mut Cursor::new(mbr_image()), None).unwrap();
+        assert_eq!(ps.pstype_str.as_deref(), Some("dos"));
+        assert_eq!(ps.block_size, Some(512));
+        assert_eq!(ps.error, None);
+        assert_eq!(
+            runs(&ps.byte_runs),
+            vec![(0, 512), (4096 * 512, 512), (5120 * 512, 512)]
+        );
+
+        let parts: Vec<_> = ps.partitions().collect();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].partition_index, Some(1));
+        assert_eq!(parts[0].ptype, Some(0x83));
+        assert_eq!(parts[0].ptype_str.as_deref(), Some("Linux"));
+        assert_eq!(parts[0].ftype_str.as_deref(), Some("ext2"));
+        assert_eq!(parts[0].block_count, Some(1024));
+        assert_eq!(runs(&parts[0].byte_runs), vec![(1048576, 524288)]);
+
+        let extended = parts[1];
+        assert_eq!(extended.partition_index, Some(2));
+        assert_eq!(extended.ptype_str.as_deref(), Some("Extended"));
+        let logical: Vec<_> = extended.partitions().collect();
+        assert_eq!(logical.len(), 2);
+        assert_eq!(logical[0].partition_index, Some(5));
+        assert_eq!(logical[0].ptype_str.as_deref(), Some("HPFS/NTFS/exFAT"));
+        assert_eq!(logical[0].partition_system_offset, Some((4096 + 63) * 512));
+        assert_eq!(logical[1].partition_index, Some(6));
+        assert_eq!(logical[1].ptype, Some(0x0b));
+        assert_eq!(runs(&logical[1].byte_runs), vec![(5121 * 512, 200 * 512)]);
+        assert_eq!(logical[1].ftype_str, None);
+    }
+
+    #[test]
+    fn test_extended_chain_loop_is_reported() {
+        let mut image = mbr_image();
+        set_entry(sector(&mut image, 5120), 1, 0x05, 0, 1024);
+        let ps = read_partition_system(&mut Cursor::new(image), None).unwrap();
+        assert!(ps.error.as_deref().unwrap().contains("loops"));
+        let extended = ps.partitions().nth(1).unwrap();
+        assert_eq!(extended.partitions().count(), 2);
+    }
+
+    #[test]
+    fn test_gpt() {
+        let disk = read_disk_image(&mut Cursor::new(gpt_image()), None).unwrap();
+        assert_eq!(disk.image_size, Some(4096 * 512));
+        assert_eq!(disk.sector_size, Some(512));
+
+        let ps = disk.partition_systems().next().unwrap();
+        assert_eq!(ps.pstype_str.as_deref(), Some("gpt"));
+        assert_eq!(ps.guid.as_deref(), Some(DISK_GUID));
+        assert_eq!(ps.error, None);
+        assert_eq!(
+            runs(&ps.byte_runs),
+            vec![(0, 34 * 512), (4063 * 512, 33 * 512)]
+        );
+
+        let parts: Vec<_> = ps.partitions().collect();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].partition_index, Some(1));
+        assert_eq!(parts[0].ptype, None);
+        assert_eq!(parts[0].ptype_str.as_deref(), Some("EFI System"));
+        assert_eq!(parts[0].partition_label.as_deref(), Some("EFI"));
+        assert_eq!(
+            parts[0].guid.as_deref(),
+            Some("01010101-0101-0101-0101-010101010101")
+        );
+        assert_eq!(parts[0].block_count, Some(1024));
+        assert_eq!(parts[1].ptype_str.as_deref(), Some("Linux filesystem"));
+        assert_eq!(parts[1].partition_label.as_deref(), Some("root"));
+        assert_eq!(parts[1].ftype_str.as_deref(), Some("ext2"));
+        assert_eq!(runs(&parts[1].byte_runs), vec![(1048576, 524288)]);
+    }
+
+    #[test]
+    fn test_gpt_backup_used_when_primary_damaged() {
+        let mut image = gpt_image();
+        image[SECTOR + 60] ^= 0xff;
+        let ps = read_partition_system(&mut Cursor::new(image), None).unwrap();
+        let error = ps.error.as_deref().unwrap();
+        assert!(error.contains("primary GPT: header checksum mismatch"));
+        assert_eq!(runs(&ps.byte_runs), vec![(0, 512), (4063 * 512, 33 * 512)]);
+        assert_eq!(ps.partitions().count(), 2);
+        assert_eq!(ps.guid.as_deref(), Some(DISK_GUID));
+    }
+
+    #[test]
+    fn test_gpt_damaged_backup_is_reported() {
+        let mut image = gpt_image();
+        image[4064 * SECTOR] ^= 0xff;
+        let ps = read_partition_system(&mut Cursor::new(image), None).unwrap();
+        assert!(ps
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("backup GPT: partition entry array checksum mismatch"));
+        assert_eq!(ps.partitions().count(), 2);
+    }
+
+    #[test]
+    fn test_invalid_sector_sizes_are_rejected() {
+        for size in [0, 4, 511, 1000, 128 * 1024] {
+            let err = read_disk_image(&mut Cursor::new(gpt_image()), Some(size)).unwrap_err();
+            assert!(matches!(err, Error::InvalidPartitionTable(_)));
+        }
+        let ps = read_partition_system(&mut Cursor::new(mbr_image()), Some(4096)).unwrap();
+        assert_eq!(ps.block_size, Some(4096));
+    }
+
+    #[test]
+    fn test_gpt_entry_overflow_is_rejected() {
+        let mut image = gpt_image();
+        let entries_len = 128 * 128;
+        for lba in [2, 4096 - 33] {
+            let entry = &mut image[lba * SECTOR + 128..][..128];
+            entry[32..40].copy_from_slice(&(u64::MAX / 2).to_le_bytes());
+            entry[40..48].copy_from_slice(&u64::MAX.to_le_bytes());
+        }
+        let entries_crc = crc32(&image[2 * SECTOR..][..entries_len]);
+        write_gpt_header(&mut image, 1, 4095, 2, entries_crc);
+        write_gpt_header(&mut image, 4095, 1, 4096 - 33, entries_crc);
+        let err = read_partition_system(&mut Cursor::new(image), None).unwrap_err();
+        assert!(matches!(err, Error::InvalidPartitionTable(_)));
+    }
+
+    #[test]
+    fn test_unpartitioned_images_are_rejected() {
+        for image in [EXT2_IMAGE.to_vec(), vec![0u8; 4096]] {
+            let err = read_partition_system(&mut Cursor::new(image), None).unwrap_err();
+            assert!(matches!(err, Error::InvalidPartitionTable(_)));
+        }
+    }
+
+    #[test]
+    fn test_partition_offsets_round_trip() {
+        let mut doc = DFXMLObject::new();
+        doc.append_disk_image(read_disk_image(&mut Cursor::new(mbr_image()), None).unwrap());
+        let doc = parse(Cursor::new(to_string(&doc).unwrap())).unwrap();
+        assert_eq!(
+            partition_offsets(&doc),
+            vec![(1, 2048 * 512), (5, 4159 * 512), (6, 5121 * 512)]
+        );
+    }
+}
@@ -3,6 +3,20 @@
 //! This module provides a memory-efficient streaming parser for DFXML files.
 //! It uses `quick-xml` for XML parsing and yields objects as they are parsed.
 //!
+//! The reader tracks its [`Position`] in the input.
+//! [`DFXMLReader::error_position`] locates the element behind the last
+//! error, and [`DFXMLReader::event_position`] the start tag behind each
+//! event.
+//!
+//! Values that cannot be parsed are left unset, unless the reader is built
+//! [`with_strict_values`](DFXMLReader::with_strict_values). Otherwise the
+//! first problem in the input is an error. Tools that crash
+//! leave DFXML files without closing tags, so a reader built with
+//! [`DFXMLReader::with_recovery`] reads past problems instead, recording a
+//! [`Warning`] for each: unparsable values are left unset, malformed file
+//! objects are skipped, and open containers are closed at the end of the
+//! input with their usual end events.
+//!
 //! # Example
 //!
 //! ```rust,no_run
@@ -29,20 +43,31 @@
 
 use crate::error::{Error, Result};
 use crate::objects::{
-    ByteRun, ByteRunFacet, ByteRuns, DFXMLObject, DiskImageObject, FileObject, HashType,
-    LibraryObject, PartitionObject, PartitionSystemObject, Timestamp, TimestampName, VolumeObject,
+    ByteRun, ByteRunFacet, ByteRuns, ChildObject, DFXMLObject, DiskImageChild, DiskImageObject,
+    ExternalElement, FileObject, HashType, LibraryObject, PartitionChild, PartitionObject,
+    PartitionSystemChild, PartitionSystemObject, Timestamp, TimestampName, VolumeChild,
+    VolumeObject, XMLNS_DELTA, XMLNS_DFXML,
 };
+use quick_xml::errors::IllFormedError;
 use quick_xml::events::BytesStart;
 use quick_xml::events::Event as XmlEvent;
-use quick_xml::Reader;
-use std::io::BufRead;
+use quick_xml::name::{PrefixDeclaration, ResolveResult};
+use quick_xml::NsReader;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::io::{self, BufRead, Read};
 use std::str;
+use std::task::Poll;
 
 /// Events emitted by the DFXML reader.
 ///
 /// The reader emits start events when container objects (DFXMLObject, VolumeObject, etc.)
 /// are opened, and end events when they are closed. FileObjects are emitted as complete
 /// objects when their closing tag is encountered.
+///
+/// Containers may nest to any depth. A container's end event carries the
+/// files directly inside it; nested containers are reported by their own
+/// start and end events, and [`parse`] reassembles the full tree.
 #[derive(Debug)]
 pub enum Event {
     /// Start of the DFXML document (metadata may not be fully populated yet)
@@ -103,11 +128,14 @@ struct ElementContext {
     text: String,
     /// Current attributes (for elements that need them)
     attrs: Vec<(String, String)>,
+    /// Start tag positions of the elements in `path`
+    positions: Vec<Position>,
 }
 
 impl ElementContext {
-    fn push(&mut self, name: String) {
+    fn push(&mut self, name: String, position: Position) {
         self.path.push(name);
+        self.positions.push(position);
         self.text.clear();
         self.attrs.clear();
     }
@@ -115,11 +143,11 @@ impl ElementContext {
     fn pop(&mut self) -> Option<String> {
         self.text.clear();
         self.attrs.clear();
+        self.positions.pop();
         self.path.pop()
     }
 
-    /// Returns the current element name (for error reporting).
-    #[allow(dead_code)]
+    /// Returns the current element name.
     fn current(&self) -> Option<&str> {
         self.path.last().map(|s| s.as_str())
     }
@@ -131,16 +159,44 @@ impl ElementContext {
     }
 }
 
+/// An object whose `delta:original_*` child is currently being parsed.
+///
+/// A file's original takes the file's place in the reader, and the file
+/// itself waits here until the original is closed. An original volume is
+/// pushed onto the container stack above its volume; `Volume` records the
+/// stack depth of that original.
+enum DeltaParent {
+    File(Box<FileObject>),
+    Volume(usize),
+}
+
+/// A container object being built. The reader keeps these on a stack,
+/// innermost last, so containers can nest to any depth.
+enum Container {
+    DiskImage(DiskImageObject),
+    PartitionSystem(PartitionSystemObject),
+    Partition(PartitionObject),
+    Volume(VolumeObject),
+}
+
 /// Intermediate parsed event data (owned, to avoid borrow conflicts).
+///
+/// Element namespaces are `None` for the DFXML namespace (or no namespace)
+/// and the namespace URI otherwise.
 enum ParsedEvent {
     Start {
+        ns: Option<String>,
         name: String,
         attrs: Vec<(String, String)>,
+        /// Raw tag content of a `<fileobject>` that may be split out
+        raw: Option<Vec<u8>>,
     },
     End {
+        ns: Option<String>,
         name: String,
     },
     Empty {
+        ns: Option<String>,
         name: String,
         attrs: Vec<(String, String)>,
     },
@@ -148,6 +204,484 @@ enum ParsedEvent {
         text: String,
     },
     Eof,
+    /// Markup the XML parser rejected (only kept when recovering)
+    Broken(quick_xml::Error),
+}
+
+/// What a recovering [`DFXMLReader`] did about a problem in its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Recovery {
+    /// A value that could not be parsed was left unset
+    ValueDropped,
+    /// A malformed or truncated file object was not emitted
+    FileSkipped,
+    /// A markup error or an end tag without a start tag was ignored
+    MarkupIgnored,
+    /// An element left open was closed
+    ElementClosed,
+    /// The rest of the input could not be read and the document was closed
+    Truncated,
+}
+
+impl Recovery {
+    /// Returns a short description of the action.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Recovery::ValueDropped => "value dropped",
+            Recovery::FileSkipped => "file skipped",
+            Recovery::MarkupIgnored => "markup ignored",
+            Recovery::ElementClosed => "element closed",
+            Recovery::Truncated => "input truncated",
+        }
+    }
+}
+
+impl fmt::Display for Recovery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A problem a recovering [`DFXMLReader`] read past.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// Start of the element (or markup) with the problem
+    pub position: Position,
+    /// What the reader did about it
+    pub recovery: Recovery,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {} ({})",
+            self.message, self.position, self.recovery
+        )
+    }
+}
+
+/// A location in the input of a [`DFXMLReader`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Position {
+    /// Bytes from the start of the input
+    pub byte_offset: u64,
+    /// Line number, starting at 1
+    pub line: u64,
+    /// Column in bytes from the start of the line, starting at 1
+    pub column: u64,
+}
+
+impl Position {
+    /// The start of the input.
+    pub const START: Position = Position {
+        byte_offset: 0,
+        line: 1,
+        column: 1,
+    };
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {} (byte {})",
+            self.line, self.column, self.byte_offset
+        )
+    }
+}
+
+/// Counts lines in the input as the XML reader consumes it.
+///
+/// Only the newlines consumed since the last [`mark`](Self::mark) are kept,
+/// so positions can be found for any offset read after the mark without
+/// holding on to the whole input.
+///
+/// Element content can also be read past without the XML reader seeing it
+/// (see [`split_element`](Self::split_element)); offsets from the XML
+/// reader are then shifted by the bytes it did not see.
+struct LineTracker<R> {
+    inner: R,
+    /// Bytes consumed
+    offset: u64,
+    /// Newlines consumed before the mark
+    lines: u64,
+    /// Offset just past the last newline before the mark
+    line_start: u64,
+    /// Offsets of the newlines consumed since the mark
+    newlines: Vec<u64>,
+    /// Bytes consumed that the XML reader has not seen
+    skipped: u64,
+    /// Bytes already consumed that are read again before the inner reader
+    replay: Vec<u8>,
+    replay_pos: usize,
+}
+
+impl<R> LineTracker<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            offset: 0,
+            lines: 0,
+            line_start: 0,
+            newlines: Vec::new(),
+            skipped: 0,
+            replay: Vec::new(),
+            replay_pos: 0,
+        }
+    }
+
+    /// Returns the wrapped reader.
+    fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the wrapped reader mutably.
+    fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Forgets the individual newlines read so far.
+    fn mark(&mut self) {
+        // Newlines in bytes to be replayed have not been read yet
+        let read = self.offset - (self.replay.len() - self.replay_pos) as u64;
+        let n = self.newlines.partition_point(|&nl| nl < read);
+        if n > 0 {
+            self.lines += n as u64;
+            self.line_start = self.newlines[n - 1] + 1;
+            self.newlines.drain(..n);
+        }
+    }
+
+    /// Returns the position of an XML reader offset at or after the last
+    /// newline before the mark.
+    fn locate(&self, byte_offset: u64) -> Position {
+        let byte_offset = byte_offset + self.skipped;
+        let n = self.newlines.partition_point(|&nl| nl < byte_offset);
+        let line_start = match n {
+            0 => self.line_start,
+            n => self.newlines[n - 1] + 1,
+        };
+        Position {
+            byte_offset,
+            line: self.lines + n as u64 + 1,
+            column: byte_offset.saturating_sub(line_start) + 1,
+        }
+    }
+
+    /// Records the newlines in bytes consumed at `start`.
+    fn count(newlines: &mut Vec<u64>, start: u64, bytes: &[u8]) {
+        newlines.extend(
+            bytes
+                .iter()
+                .enumerate()
+                .filter(|(_, &b)| b == b'\n')
+                .map(|(i, _)| start + i as u64),
+        );
+    }
+}
+
+impl<R: BufRead> LineTracker<R> {
+    /// Reads past the content of the `<fileobject>` element whose start tag
+    /// `xml` was just read by the XML reader, and returns the whole element.
+    /// The end tag is left for the XML reader, which sees an empty element.
+    /// Returns `None` if the input ends first.
+    fn split_element(&mut self, mut xml: Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+        let content = xml.len();
+        let mut depth = 1;
+        let mut at = content;
+        loop {
+            match find_end_tag(&xml, at, &mut depth) {
+                Scan::Found { start, end } => {
+                    // Everything read from the end tag on is read again
+                    self.skipped += (start - content) as u64;
+                    self.replay = xml[start..].to_vec();
+                    self.replay_pos = 0;
+                    xml.truncate(end);
+                    return Ok(Some(xml));
+                }
+                Scan::More(resume) => at = resume,
+            }
+            let buf = self.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(None);
+            }
+            xml.extend_from_slice(buf);
+            let amt = buf.len();
+            self.consume(amt);
+        }
+    }
+}
+
+impl<R: BufRead> Read for LineTracker<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.fill_buf()?.read(buf)?;
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for LineTracker<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.replay_pos < self.replay.len() {
+            return Ok(&self.replay[self.replay_pos..]);
+        }
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // Replayed bytes were counted when they were first consumed
+        if self.replay_pos < self.replay.len() {
+            self.replay_pos = (self.replay_pos + amt).min(self.replay.len());
+            return;
+        }
+        // The buffer is already filled, so this does no I/O
+        if let Ok(buf) = self.inner.fill_buf() {
+            let amt = amt.min(buf.len());
+            Self::count(&mut self.newlines, self.offset, &buf[..amt]);
+            self.offset += amt as u64;
+        }
+        self.inner.consume(amt);
+    }
+}
+
+/// Result of scanning for the end of an element.
+enum Scan {
+    /// The end tag spans `start..end`
+    Found { start: usize, end: usize },
+    /// More input is needed to scan on from this index
+    More(usize),
+}
+
+/// Scans `xml` from `at` for the end tag closing a `<fileobject>` element
+/// that is `depth` file objects deep, skipping comments, CDATA sections and
+/// processing instructions. Only the markup is looked at; the XML reader or
+/// a [`Chunk`] parse reports any errors in it.
+fn find_end_tag(xml: &[u8], mut at: usize, depth: &mut usize) -> Scan {
+    let find = |from: usize, needle: &[u8]| {
+        xml.get(from..)?
+            .windows(needle.len())
+            .position(|w| w == needle)
+            .map(|i| from + i)
+    };
+    let is_fileobject = |name: &[u8]| {
+        let local = match name.iter().rposition(|&b| b == b':') {
+            Some(colon) => &name[colon + 1..],
+            None => name,
+        };
+        local == b"fileobject"
+    };
+    loop {
+        let Some(lt) = xml[at..].iter().position(|&b| b == b'<').map(|i| at + i) else {
+            return Scan::More(xml.len());
+        };
+        let markup = &xml[lt..];
+        let (close, len) = if markup.starts_with(b"<!--") {
+            (&b"-->"[..], 4)
+        } else if markup.starts_with(b"<![CDATA[") {
+            (&b"]]>"[..], 9)
+        } else if markup.starts_with(b"<?") {
+            (&b"?>"[..], 2)
+        } else if markup.len() < 9 && (b"<![CDATA[".starts_with(markup) || markup == b"<!-") {
+            return Scan::More(lt);
+        } else {
+            (&b">"[..], 1)
+        };
+        if close != b">" {
+            match find(lt + len, close) {
+                Some(end) => at = end + close.len(),
+                None => return Scan::More(lt),
+            }
+            continue;
+        }
+
+        // A start or end tag; attribute values may contain '>'
+        let mut quote = None;
+        let Some(gt) = markup.iter().skip(1).position(|&b| match quote {
+            Some(q) if b == q => {
+                quote = None;
+                false
+            }
+            Some(_) => false,
+            None if b == b'"' || b == b'\'' => {
+                quote = Some(b);
+                false
+            }
+            None => b == b'>',
+        }) else {
+            return Scan::More(lt);
+        };
+        let gt = lt + 1 + gt;
+        at = gt + 1;
+        let end_tag = xml[lt + 1] == b'/';
+        let name_start = if end_tag { lt + 2 } else { lt + 1 };
+        let name_len = xml[name_start..gt]
+            .iter()
+            .position(|b| b.is_ascii_whitespace() || *b == b'/')
+            .unwrap_or(gt - name_start);
+        if !is_fileobject(&xml[name_start..name_start + name_len]) {
+            continue;
+        }
+        if end_tag {
+            *depth -= 1;
+            if *depth == 0 {
+                return Scan::Found { start: lt, end: at };
+            }
+        } else if xml[gt - 1] != b'/' {
+            *depth += 1;
+        }
+    }
+}
+
+/// Consecutive top-level `<fileobject>` elements split out of a document
+/// so they can be parsed away from the reader, e.g. on another thread.
+#[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+pub(crate) struct Chunk {
+    /// Number of the chunk in document order
+    pub seq: u64,
+    /// A `<dfxml>` start tag declaring the namespaces in scope, then the
+    /// elements
+    xml: Vec<u8>,
+    /// Where each element starts, in `xml` and in the document
+    files: Vec<(Position, Position)>,
+    /// Newlines in `xml`, and the offset just past the last one
+    lines: u64,
+    line_start: usize,
+    /// Whether invalid values are errors
+    strict: bool,
+}
+
+#[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+impl Chunk {
+    /// Creates an empty chunk whose elements are in the scope of `root`,
+    /// parsed with [`DFXMLReader::with_strict_values`] set to `strict`.
+    pub fn new(seq: u64, root: String, strict: bool) -> Self {
+        Self {
+            seq,
+            xml: root.into_bytes(),
+            files: Vec::new(),
+            lines: 0,
+            line_start: 0,
+            strict,
+        }
+    }
+
+    /// Adds an element that starts at `position` in the document.
+    pub fn push(&mut self, element: &[u8], position: Position) {
+        let start = self.xml.len();
+        self.files.push((
+            Position {
+                byte_offset: start as u64,
+                line: self.lines + 1,
+                column: (start - self.line_start) as u64 + 1,
+            },
+            position,
+        ));
+        self.xml.extend_from_slice(element);
+        for (i, &b) in element.iter().enumerate() {
+            if b == b'\n' {
+                self.lines += 1;
+                self.line_start = start + i + 1;
+            }
+        }
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Parses the file objects, up to the first error, each with the
+    /// position of its start tag. Errors carry positions in the document.
+    pub fn parse(&self) -> Vec<(Position, Result<FileObject>)> {
+        let mut parsed = Vec::with_capacity(self.files.len());
+        let reader = DFXMLReader::from_reader(self.xml.as_slice()).with_strict_values(self.strict);
+        for event in reader.with_positions() {
+            let Some(&(_, position)) = self.files.get(parsed.len()) else {
+                break;
+            };
+            match event {
+                Ok((Event::FileObject(file), _)) => parsed.push((position, Ok(*file))),
+                Ok(_) => {}
+                Err(Error::AtPosition {
+                    position: at,
+                    source,
+                }) => {
+                    let at = self.locate(at);
+                    parsed.push((
+                        position,
+                        Err(Error::AtPosition {
+                            position: at,
+                            source,
+                        }),
+                    ));
+                    break;
+                }
+                Err(e) => {
+                    parsed.push((position, Err(e)));
+                    break;
+                }
+            }
+        }
+        parsed
+    }
+
+    /// Maps a position in the chunk to the document.
+    fn locate(&self, position: Position) -> Position {
+        let n = self
+            .files
+            .partition_point(|(start, _)| start.byte_offset <= position.byte_offset);
+        let Some(&(start, file)) = self.files.get(n.saturating_sub(1)) else {
+            return position;
+        };
+        let byte_offset = file.byte_offset + position.byte_offset.saturating_sub(start.byte_offset);
+        if position.line == start.line {
+            Position {
+                byte_offset,
+                line: file.line,
+                column: file.column + position.column.saturating_sub(start.column),
+            }
+        } else {
+            Position {
+                byte_offset,
+                line: file.line + position.line - start.line,
+                column: position.column,
+            }
+        }
+    }
+}
+
+/// Parses split-out [`Chunk`]s for a [`DFXMLReader`].
+pub(crate) trait ChunkParser {
+    /// Starts parsing a chunk.
+    fn submit(&mut self, chunk: Chunk);
+
+    /// Returns a parsed file object and its start tag position, waiting for
+    /// one if `wait` is set. Returns `None` if none is ready (or, when
+    /// waiting, if nothing was submitted). A chunk that fails returns its
+    /// file objects up to the error, then the error.
+    fn next(&mut self, wait: bool) -> Option<(Position, Result<FileObject>)>;
+}
+
+/// Returns a `<dfxml>` start tag declaring namespaces.
+pub(crate) fn root_tag<'a>(namespaces: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    let mut root = String::from("<dfxml");
+    for (prefix, uri) in namespaces {
+        match prefix {
+            "" => root.push_str(" xmlns=\""),
+            prefix => {
+                root.push_str(" xmlns:");
+                root.push_str(prefix);
+                root.push_str("=\"");
+            }
+        }
+        root.push_str(&quick_xml::escape::escape(uri));
+        root.push('"');
+    }
+    root.push('>');
+    root
 }
 
 /// A streaming DFXML parser.
@@ -156,7 +690,7 @@ enum ParsedEvent {
 /// are parsed. This is memory-efficient for large DFXML files since it
 /// doesn't load the entire document into memory.
 pub struct DFXMLReader<R: BufRead> {
-    reader: Reader<R>,
+    reader: NsReader<LineTracker<R>>,
     state: ParserState,
     state_stack: Vec<ParserState>,
     buf: Vec<u8>,
@@ -164,10 +698,7 @@ pub struct DFXMLReader<R: BufRead> {
 
     // Objects being built
     dfxml: Option<DFXMLObject>,
-    disk_image: Option<DiskImageObject>,
-    partition_system: Option<PartitionSystemObject>,
-    partition: Option<PartitionObject>,
-    volume: Option<VolumeObject>,
+    containers: Vec<Container>,
     file: Option<FileObject>,
 
     // Nested object building
@@ -180,14 +711,54 @@ pub struct DFXMLReader<R: BufRead> {
     in_byte_runs: bool,
     byte_runs_facet: Option<ByteRunFacet>,
 
-    // Pending events to yield
-    pending_events: Vec<Event>,
+    // Differential (delta namespace) parsing
+    delta_parents: Vec<DeltaParent>,
+    awaiting_original: bool,
+
+    // Non-DFXML namespace elements being captured, outermost first
+    external_stack: Vec<ExternalElement>,
+    // Namespace declarations seen before the <dfxml> root was created
+    pending_namespaces: Vec<(String, String)>,
+
+    // Pending events to yield in order, with their positions
+    pending_events: VecDeque<(Event, Position)>,
+
+    // Whether invalid values are errors
+    strict: bool,
+
+    // Recovery mode, the problems read past, and why the current file
+    // object will be skipped
+    recover: bool,
+    warnings: Vec<Warning>,
+    file_error: Option<String>,
+
+    // Top-level file objects handed to a chunk parser: the chunk being
+    // filled, how many are being parsed, and whether the end tag of the last
+    // one is still to be read
+    splitter: Option<Box<dyn ChunkParser>>,
+    chunk: Option<Chunk>,
+    chunk_size: usize,
+    next_seq: u64,
+    in_flight: usize,
+    max_in_flight: usize,
+    split_end: bool,
+
+    // Start tag of the element being handled, for errors and events
+    element_position: Position,
+    // Start tag of the element that produced the last event
+    event_position: Position,
+    // Where the last error occurred
+    error_position: Option<Position>,
+
+    // Whether the XML reader has consumed the `<` of the next markup, which
+    // it does when it reads the text before it
+    in_markup: bool,
 }
 
 impl<R: BufRead> DFXMLReader<R> {
     /// Creates a new DFXML reader from a buffered reader.
     pub fn from_reader(reader: R) -> Self {
-        let mut xml_reader = Reader::from_reader(reader);
+        let mut xml_reader = NsReader::from_reader(LineTracker::new(reader));
         xml_reader.config_mut().trim_text(true);
 
         Self {
@@ -197,10 +768,7 @@ impl<R: BufRead> DFXMLReader<R> {
             buf: Vec::with_capacity(4096),
             context: ElementContext::default(),
             dfxml: None,
-            disk_image: None,
-            partition_system: None,
-            partition: None,
-            volume: None,
+            containers: Vec::new(),
             file: None,
             byte_runs: None,
             current_byte_run: None,
@@ -208,95 +776,655 @@ impl<R: BufRead> DFXMLReader<R> {
             current_library: None,
             in_byte_runs: false,
             byte_runs_facet: None,
-            pending_events: Vec::new(),
+            delta_parents: Vec::new(),
+            awaiting_original: false,
+            external_stack: Vec::new(),
+            pending_namespaces: Vec::new(),
+            pending_events: VecDeque::new(),
+            strict: false,
+            recover: false,
+            warnings: Vec::new(),
+            file_error: None,
+            splitter: None,
+            chunk: None,
+            chunk_size: 0,
+            next_seq: 0,
+            in_flight: 0,
+            max_in_flight: 0,
+            split_end: false,
+            element_position: Position::START,
+            event_position: Position::START,
+            error_position: None,
+            in_markup: false,
+        }
+    }
+
+    /// Sets whether values that cannot be parsed, such as a `<filesize>`
+    /// that is not a number or an `<mtime>` that is not a timestamp, are
+    /// errors. By default they are left unset.
+    pub fn with_strict_values(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets whether to read past problems in the input instead of failing.
+    ///
+    /// When recovering, a value that cannot be parsed is left unset, a file
+    /// object with broken markup or missing end tags is skipped, and at the
+    /// end of a truncated input the open containers are closed, so their
+    /// end events and [`Event::DFXMLEnd`] are still emitted. Each problem is
+    /// recorded as a [`Warning`]. I/O errors other than a truncated stream
+    /// are still returned.
+    pub fn with_recovery(mut self, recover: bool) -> Self {
+        self.recover = recover;
+        // Mismatched end tags are resolved against the element path instead
+        self.reader.config_mut().check_end_names = !recover;
+        self
+    }
+
+    /// Hands top-level file objects to `splitter` instead of parsing them,
+    /// in chunks of up to `chunk_size` siblings, with up to `max_in_flight`
+    /// of them being parsed at once. File objects are emitted in the order
+    /// the splitter returns them, always before the next event of any other
+    /// kind. Nothing is split out in recovery mode.
+    #[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+    pub(crate) fn set_splitter(
+        &mut self,
+        splitter: Box<dyn ChunkParser>,
+        chunk_size: usize,
+        max_in_flight: usize,
+    ) {
+        self.splitter = Some(splitter);
+        self.max_in_flight = max_in_flight.max(1);
+        self.chunk_size = chunk_size.clamp(1, self.max_in_flight);
+    }
+
+    /// Returns the problems read past so far in recovery mode.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Removes and returns the problems read past so far, so long inputs
+    /// can be reported as they are read.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Returns the document metadata read so far.
+    ///
+    /// Metadata that appears after the first container or file is only
+    /// complete in [`Event::DFXMLEnd`].
+    pub fn document(&self) -> Option<&DFXMLObject> {
+        self.dfxml.as_ref()
+    }
+
+    /// Returns the innermost open disk image, if any.
+    ///
+    /// Between events this reflects the properties read so far, which makes
+    /// it useful for giving a [`Event::FileObject`] its enclosing context.
+    pub fn current_disk_image(&self) -> Option<&DiskImageObject> {
+        self.containers.iter().rev().find_map(|c| match c {
+            Container::DiskImage(di) => Some(di),
+            _ => None,
+        })
+    }
+
+    /// Returns the innermost open partition system, if any.
+    pub fn current_partition_system(&self) -> Option<&PartitionSystemObject> {
+        self.containers.iter().rev().find_map(|c| match c {
+            Container::PartitionSystem(ps) => Some(ps),
+            _ => None,
+        })
+    }
+
+    /// Returns the innermost open partition, if any.
+    pub fn current_partition(&self) -> Option<&PartitionObject> {
+        self.containers.iter().rev().find_map(|c| match c {
+            Container::Partition(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    /// Returns the innermost open volume, if any.
+    pub fn current_volume(&self) -> Option<&VolumeObject> {
+        self.containers.iter().rev().find_map(|c| match c {
+            Container::Volume(vol) => Some(vol),
+            _ => None,
+        })
+    }
+
+    /// Returns the properties of an open container, without its child
+    /// objects. `depth` 0 is the innermost container, 1 its parent, and so on.
+    pub(crate) fn open_container(&self, depth: usize) -> Option<ChildObject> {
+        self.containers.iter().rev().nth(depth).map(|c| match c {
+            Container::DiskImage(di) => ChildObject::DiskImage(di.without_children()),
+            Container::PartitionSystem(ps) => ChildObject::PartitionSystem(ps.without_children()),
+            Container::Partition(p) => ChildObject::Partition(p.without_children()),
+            Container::Volume(vol) => ChildObject::Volume(vol.without_children()),
+        })
+    }
+
+    /// Returns the namespace declarations in scope, from the root element
+    /// down to the current one, as `(prefix, uri)` pairs with `""` for the
+    /// default namespace.
+    pub(crate) fn namespace_scope(&self) -> Result<Vec<(String, String)>> {
+        let mut scope = Vec::new();
+        for (prefix, uri) in self.reader.prefixes() {
+            let prefix = match prefix {
+                PrefixDeclaration::Default => "",
+                PrefixDeclaration::Named(prefix) => str::from_utf8(prefix)?,
+            };
+            let uri = quick_xml::escape::unescape(str::from_utf8(uri.into_inner())?)
+                .map_err(quick_xml::Error::from)?;
+            scope.push((prefix.to_string(), uri.into_owned()));
+        }
+        Ok(scope)
+    }
+
+    /// Returns the position of the start tag of the element that produced
+    /// the last event, e.g. the `<fileobject>` of an [`Event::FileObject`]
+    /// or the `<volume>` of both [`Event::VolumeStart`] and
+    /// [`Event::VolumeEnd`].
+    ///
+    /// Seeking the input to its `byte_offset` lands on the `<` of the tag.
+    /// Before the first event this is [`Position::START`].
+    pub fn event_position(&self) -> Position {
+        self.event_position
+    }
+
+    /// Returns the position up to which the input has been parsed.
+    pub fn position(&self) -> Position {
+        self.reader.get_ref().locate(self.reader.buffer_position())
+    }
+
+    /// Returns the position of the last error: the start tag of the element
+    /// that caused it or, for XML syntax errors, the bad markup.
+    pub fn error_position(&self) -> Option<Position> {
+        self.error_position
+    }
+
+    /// Returns the input being read.
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub(crate) fn input(&self) -> &R {
+        self.reader.get_ref().get_ref()
+    }
+
+    /// Returns the input being read mutably.
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub(crate) fn input_mut(&mut self) -> &mut R {
+        self.reader.get_mut().get_mut()
+    }
+
+    /// Parses the next event from input that may not have arrived yet.
+    ///
+    /// Before each XML token is read, `ready` is given the input and
+    /// whether the `<` opening the token has been consumed already, and
+    /// returns whether the input holds the whole token (or its end). If it
+    /// does not, nothing is read and `Poll::Pending` is returned, so the
+    /// call can be repeated once more input is available.
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub(crate) fn poll_event(
+        &mut self,
+        ready: impl Fn(&R, bool) -> bool,
+    ) -> Poll<Option<Result<Event>>> {
+        match self.parse_until(ready) {
+            Ok(Poll::Ready(event)) => Poll::Ready(event.map(Ok)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(e) => Poll::Ready(Some(Err(self.locate_error(e)))),
+        }
+    }
+
+    /// Turns the reader into an iterator of events paired with
+    /// [`event_position`](Self::event_position), whose errors are wrapped
+    /// in [`Error::AtPosition`].
+    pub fn with_positions(self) -> WithPositions<R> {
+        WithPositions { reader: self }
+    }
+
+    /// Records the position of the problem behind an error, and returns the
+    /// error without it.
+    fn locate_error(&mut self, error: Error) -> Error {
+        let position = match &error {
+            // Errors from split-out file objects are already located
+            Error::AtPosition { position, .. } => *position,
+            // Syntax errors know where the bad markup starts
+            Error::XmlParse(quick_xml::Error::Syntax(_) | quick_xml::Error::IllFormed(_)) => {
+                self.reader.get_ref().locate(self.reader.error_position())
+            }
+            _ => self.element_position,
+        };
+        self.error_position = Some(position);
+        match error {
+            Error::AtPosition { source, .. } => *source,
+            error => error,
         }
     }
 
     /// Parses the next event from the DFXML stream.
     fn parse_next(&mut self) -> Result<Option<Event>> {
-        // Return any pending events first
-        if let Some(event) = self.pending_events.pop() {
-            return Ok(Some(event));
+        match self.parse_until(|_, _| true)? {
+            Poll::Ready(event) => Ok(event),
+            Poll::Pending => unreachable!("blocking input is always ready"),
         }
+    }
 
+    /// Parses the next event, stopping before an XML token that `ready`
+    /// says is not in the input yet.
+    fn parse_until(&mut self, ready: impl Fn(&R, bool) -> bool) -> Result<Poll<Option<Event>>> {
         loop {
+            // Return any pending events first
+            if let Some((event, position)) = self.pending_events.pop_front() {
+                self.event_position = position;
+                return Ok(Poll::Ready(Some(event)));
+            }
+            if self.state == ParserState::Done {
+                return Ok(Poll::Ready(None));
+            }
+            if !ready(self.input(), self.in_markup) {
+                return Ok(Poll::Pending);
+            }
+
             self.buf.clear();
+            self.reader.get_mut().mark();
+            let mut declarations = Vec::new();
 
             // Read the event and immediately extract what we need as owned data
-            let event_data = {
-                let event = self.reader.read_event_into(&mut self.buf)?;
-                match event {
-                    XmlEvent::Start(ref e) => {
-                        let local_name = e.local_name();
-                        let name = str::from_utf8(local_name.as_ref())?.to_string();
-                        let attrs = Self::extract_attrs(e)?;
-                        Some(ParsedEvent::Start { name, attrs })
-                    }
-                    XmlEvent::End(ref e) => {
-                        let local_name = e.local_name();
-                        let name = str::from_utf8(local_name.as_ref())?.to_string();
-                        Some(ParsedEvent::End { name })
-                    }
-                    XmlEvent::Empty(ref e) => {
-                        let local_name = e.local_name();
-                        let name = str::from_utf8(local_name.as_ref())?.to_string();
-                        let attrs = Self::extract_attrs(e)?;
-                        Some(ParsedEvent::Empty { name, attrs })
-                    }
-                    XmlEvent::Text(ref e) => {
-                        let text = e.unescape()?.to_string();
-                        Some(ParsedEvent::Text { text })
-                    }
-                    XmlEvent::CData(ref e) => {
-                        let text = str::from_utf8(e.as_ref())?.to_string();
-                        Some(ParsedEvent::Text { text })
-                    }
-                    XmlEvent::Eof => Some(ParsedEvent::Eof),
-                    _ => None,
-                }
+            let read = self.reader.read_event_into(&mut self.buf);
+            self.in_markup = matches!(read, Ok(XmlEvent::Text(_)));
+            let event_data = match read {
+                Err(e) if self.recover => Ok(Some(ParsedEvent::Broken(e))),
+                Err(e) => Err(e.into()),
+                Ok(event) => Self::extract_event(
+                    &self.reader,
+                    &self.context,
+                    &mut self.element_position,
+                    &mut declarations,
+                    &event,
+                    self.splitter.is_some(),
+                ),
+            };
+            let event_data = match event_data {
+                Ok(data) => data,
+                // Names and values that cannot be decoded leave their element out
+                Err(e) if self.recover => {
+                    self.warn(Recovery::ValueDropped, e.to_string());
+                    continue;
+                }
+                Err(e) => return Err(e),
             };
 
             // Now process the extracted data without borrowing self.buf
+            for (prefix, uri) in declarations {
+                self.declare_namespace(prefix, uri);
+            }
+            let position = self.element_position;
+            let split = match event_data {
+                Some(ParsedEvent::Start {
+                    ns: None,
+                    raw: Some(_),
+                    ..
+                }) => !self.recover && self.external_stack.is_empty() && self.file.is_none(),
+                // The end tag of a split-out file object
+                Some(ParsedEvent::End { .. }) => self.split_end,
+                Some(ParsedEvent::Text { .. }) | None => true,
+                _ => false,
+            };
+            if !split {
+                // Split-out file objects come before anything that follows them
+                self.flush_files()?;
+            }
             match event_data {
-                Some(ParsedEvent::Start { name, attrs }) => {
-                    if let Some(ev) = self.handle_start_owned(&name, attrs)? {
-                        return Ok(Some(ev));
-                    }
+                Some(ParsedEvent::Start { raw: Some(raw), .. }) if split => self.split_file(raw)?,
+                Some(ParsedEvent::Start {
+                    ns, name, attrs, ..
+                }) => {
+                    let result = self.handle_start_owned(ns.as_deref(), &name, attrs);
+                    self.emit(result, position)?;
+                }
+                Some(ParsedEvent::End { .. }) if split => self.split_end = false,
+                Some(ParsedEvent::End { ns, name }) => {
+                    self.end_element(ns.as_deref(), &name)?;
+                }
+                Some(ParsedEvent::Empty { ns, name, attrs }) => {
+                    // Handle self-closing tags like <byte_run ... />
+                    let result = self.handle_start_owned(ns.as_deref(), &name, attrs);
+                    self.emit(result, position)?;
+                    let result = self.handle_end_owned(ns.as_deref(), &name);
+                    self.emit(result, position)?;
                 }
-                Some(ParsedEvent::End { name }) => {
-                    if let Some(ev) = self.handle_end_owned(&name)? {
-                        return Ok(Some(ev));
+                Some(ParsedEvent::Text { text }) => {
+                    if let Some(elem) = self.external_stack.last_mut() {
+                        elem.text.get_or_insert_with(String::new).push_str(&text);
+                    } else {
+                        self.context.text.push_str(&text);
                     }
                 }
-                Some(ParsedEvent::Empty { name, attrs }) => {
-                    // Handle self-closing tags like <byte_run ... />
-                    if let Some(ev) = self.handle_start_owned(&name, attrs)? {
-                        self.pending_events.push(ev);
-                    }
-                    if let Some(ev) = self.handle_end_owned(&name)? {
-                        return Ok(Some(ev));
-                    }
-                    if let Some(ev) = self.pending_events.pop() {
-                        return Ok(Some(ev));
+                Some(ParsedEvent::Eof) => self.finish()?,
+                Some(ParsedEvent::Broken(e)) => self.recover_markup(e)?,
+                None => {}
+            }
+        }
+    }
+
+    /// Queues the event of a handled element. When recovering, a value
+    /// error is recorded as a warning instead of being returned.
+    fn emit(&mut self, result: Result<Option<Event>>, position: Position) -> Result<()> {
+        match result {
+            Ok(Some(event)) => self.pending_events.push_back((event, position)),
+            Ok(None) => {}
+            Err(e) if self.recover => self.warn(Recovery::ValueDropped, e.to_string()),
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+
+    /// Adds the `<fileobject>` element whose start tag was just read to the
+    /// chunk for the splitter, and emits any file objects that are ready.
+    fn split_file(&mut self, tag: Vec<u8>) -> Result<()> {
+        let mut xml = Vec::with_capacity(tag.len() + 2);
+        xml.push(b'<');
+        xml.extend_from_slice(&tag);
+        xml.push(b'>');
+        let Some(xml) = self.reader.get_mut().split_element(xml)? else {
+            // The input ends inside the file object
+            self.flush_files()?;
+            self.state = ParserState::Done;
+            return Ok(());
+        };
+        self.split_end = true;
+
+        if self.chunk.is_none() {
+            // Declare the namespaces in scope, so prefixes resolve the same way
+            let scope = self.namespace_scope()?;
+            let root = root_tag(scope.iter().map(|(p, u)| (p.as_str(), u.as_str())));
+            self.chunk = Some(Chunk::new(self.next_seq, root, self.strict));
+            self.next_seq += 1;
+        }
+        let chunk = self.chunk.as_mut().expect("chunk was just created");
+        chunk.push(&xml, self.element_position);
+        if chunk.len() >= self.chunk_size {
+            self.submit_chunk();
+        }
+        let wait = self.in_flight >= self.max_in_flight;
+        self.receive_files(wait)
+    }
+
+    /// Hands the chunk being filled, if any, to the splitter.
+    fn submit_chunk(&mut self) {
+        if let (Some(chunk), Some(splitter)) = (self.chunk.take(), self.splitter.as_mut()) {
+            self.in_flight += chunk.len();
+            splitter.submit(chunk);
+        }
+    }
+
+    /// Emits the split-out file objects the splitter has ready, waiting for
+    /// the first one if `wait` is set.
+    fn receive_files(&mut self, mut wait: bool) -> Result<()> {
+        while self.in_flight > 0 {
+            let Some(splitter) = self.splitter.as_mut() else {
+                break;
+            };
+            let Some((position, result)) = splitter.next(wait) else {
+                break;
+            };
+            self.in_flight -= 1;
+            wait = false;
+            let event = self.complete_file(result?);
+            self.pending_events.push_back((event, position));
+        }
+        Ok(())
+    }
+
+    /// Emits every split-out file object.
+    fn flush_files(&mut self) -> Result<()> {
+        self.submit_chunk();
+        while self.in_flight > 0 {
+            let before = self.in_flight;
+            self.receive_files(true)?;
+            if self.in_flight == before {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Records a warning at the current element.
+    fn warn(&mut self, recovery: Recovery, message: String) {
+        self.warnings.push(Warning {
+            position: self.element_position,
+            recovery,
+            message,
+        });
+    }
+
+    /// Marks the current file object, if any, to be skipped.
+    fn break_file(&mut self, reason: impl FnOnce() -> String) {
+        if self.file.is_some() && self.file_error.is_none() {
+            self.file_error = Some(reason());
+        }
+    }
+
+    /// Handles an end tag. When recovering, an end tag that closes an outer
+    /// element first closes the elements inside it, and one that matches no
+    /// open element is ignored.
+    fn end_element(&mut self, ns: Option<&str>, name: &str) -> Result<()> {
+        if self.recover && self.context.current() != Some(name) {
+            match self.context.path.iter().rposition(|open| open == name) {
+                Some(depth) => {
+                    self.close_elements(depth + 1)?;
+                    if let Some(&position) = self.context.positions.last() {
+                        self.element_position = position;
                     }
                 }
-                Some(ParsedEvent::Text { text }) => {
-                    self.context.text.push_str(&text);
-                }
-                Some(ParsedEvent::Eof) => {
-                    self.state = ParserState::Done;
-                    return Ok(None);
+                None => {
+                    let message = match self.context.current() {
+                        Some(open) => format!("</{}> does not match <{}>", name, open),
+                        None => format!("</{}> has no start tag", name),
+                    };
+                    self.break_file(|| message.clone());
+                    self.warn(Recovery::MarkupIgnored, message);
+                    return Ok(());
                 }
-                None => {}
             }
         }
+        let position = self.element_position;
+        let result = self.handle_end_owned(ns, name);
+        self.emit(result, position)
+    }
+
+    /// Closes the open elements nested deeper than `depth`, innermost first,
+    /// as if their end tags had been read.
+    fn close_elements(&mut self, depth: usize) -> Result<()> {
+        while self.context.path.len() > depth {
+            let Some(name) = self.context.current().map(str::to_string) else {
+                break;
+            };
+            if let Some(&position) = self.context.positions.last() {
+                self.element_position = position;
+            }
+            self.break_file(|| format!("<{}> is not closed", name));
+            if matches!(
+                name.as_str(),
+                "dfxml"
+                    | "diskimageobject"
+                    | "partitionsystemobject"
+                    | "partitionobject"
+                    | "volume"
+            ) {
+                self.warn(Recovery::ElementClosed, format!("<{}> is not closed", name));
+            }
+            let position = self.element_position;
+            let result = self.handle_end_owned(None, &name);
+            self.emit(result, position)?;
+        }
+        Ok(())
+    }
+
+    /// Handles the end of the input. A document that is still open is
+    /// closed when recovering, and otherwise ends without its end events.
+    fn finish(&mut self) -> Result<()> {
+        if self.recover && self.context.current().is_some() {
+            self.close_elements(0)?;
+        }
+        self.state = ParserState::Done;
+        Ok(())
+    }
+
+    /// Reads past markup the XML parser rejected. Syntax errors only occur
+    /// where the input ends inside markup, so they end the document; other
+    /// errors spoil the current file object.
+    fn recover_markup(&mut self, error: quick_xml::Error) -> Result<()> {
+        // The XML parser counts end tags, not names, so it runs out of open
+        // tags before the element path does after an ignored end tag
+        if let quick_xml::Error::IllFormed(IllFormedError::UnmatchedEndTag(name)) = &error {
+            if let Some(&position) = self.context.positions.last() {
+                self.element_position = position;
+            }
+            let name = name.rsplit(':').next().unwrap_or_default().to_string();
+            return self.end_element(None, &name);
+        }
+        let truncated = match &error {
+            quick_xml::Error::Syntax(_) => true,
+            quick_xml::Error::Io(e) => match e.kind() {
+                io::ErrorKind::UnexpectedEof | io::ErrorKind::InvalidData => true,
+                _ => return Err(error.into()),
+            },
+            _ => false,
+        };
+        self.element_position = self.reader.get_ref().locate(self.reader.error_position());
+        let message = Error::from(error).to_string();
+        if truncated {
+            self.break_file(|| message.clone());
+            self.warn(Recovery::Truncated, message);
+            return self.finish();
+        }
+        self.break_file(|| message.clone());
+        self.warn(Recovery::MarkupIgnored, message);
+        Ok(())
+    }
+
+    /// Extracts what the parser needs from an XML event as owned data, and
+    /// sets `element_position` to the start tag of the element it belongs to.
+    fn extract_event(
+        reader: &NsReader<LineTracker<R>>,
+        context: &ElementContext,
+        element_position: &mut Position,
+        declarations: &mut Vec<(String, String)>,
+        event: &XmlEvent<'_>,
+        split: bool,
+    ) -> Result<Option<ParsedEvent>> {
+        // Offset of the `<` of a tag, from the length of its content
+        let end = reader.buffer_position();
+        let tag = |content: usize, markup: u64| {
+            reader
+                .get_ref()
+                .locate(end.saturating_sub(content as u64 + markup))
+        };
+        Ok(match *event {
+            XmlEvent::Start(ref e) => {
+                *element_position = tag(e.len(), 2);
+                *declarations = Self::extract_namespaces(e)?;
+                let (ns, name) = Self::resolve_name(reader, e)?;
+                let attrs = Self::extract_attrs(reader, e)?;
+                let raw = (split && name == "fileobject").then(|| e.to_vec());
+                Some(ParsedEvent::Start {
+                    ns,
+                    name,
+                    attrs,
+                    raw,
+                })
+            }
+            XmlEvent::End(ref e) => {
+                // Errors and events of an element refer to its start tag
+                *element_position = match context.positions.last() {
+                    Some(&position) => position,
+                    None => tag(e.len(), 3),
+                };
+                let (resolved, local_name) = reader.resolve_element(e.name());
+                let ns = namespace_of(resolved);
+                let name = str::from_utf8(local_name.as_ref())?.to_string();
+                Some(ParsedEvent::End { ns, name })
+            }
+            XmlEvent::Empty(ref e) => {
+                *element_position = tag(e.len(), 3);
+                *declarations = Self::extract_namespaces(e)?;
+                let (ns, name) = Self::resolve_name(reader, e)?;
+                let attrs = Self::extract_attrs(reader, e)?;
+                Some(ParsedEvent::Empty { ns, name, attrs })
+            }
+            XmlEvent::Text(ref e) => {
+                if let Some(&position) = context.positions.last() {
+                    *element_position = position;
+                }
+                let text = e.unescape()?.to_string();
+                Some(ParsedEvent::Text { text })
+            }
+            XmlEvent::CData(ref e) => {
+                if let Some(&position) = context.positions.last() {
+                    *element_position = position;
+                }
+                let text = str::from_utf8(e.as_ref())?.to_string();
+                Some(ParsedEvent::Text { text })
+            }
+            XmlEvent::Eof => Some(ParsedEvent::Eof),
+            _ => None,
+        })
+    }
+
+    /// Resolves the namespace and local name of an element.
+    fn resolve_name(
+        reader: &NsReader<LineTracker<R>>,
+        e: &BytesStart<'_>,
+    ) -> Result<(Option<String>, String)> {
+        let (resolved, local_name) = reader.resolve_element(e.name());
+        let name = str::from_utf8(local_name.as_ref())?.to_string();
+        Ok((namespace_of(resolved), name))
+    }
+
+    /// Extracts the namespace declarations (`xmlns:prefix="uri"`) of an element.
+    fn extract_namespaces(e: &BytesStart<'_>) -> Result<Vec<(String, String)>> {
+        let mut declarations = Vec::new();
+        for attr in e.attributes().flatten() {
+            if let Some(binding) = attr.key.as_namespace_binding() {
+                let prefix = match binding {
+                    PrefixDeclaration::Default => String::new(),
+                    PrefixDeclaration::Named(p) => str::from_utf8(p)?.to_string(),
+                };
+                let uri = attr.unescape_value()?.to_string();
+                declarations.push((prefix, uri));
+            }
+        }
+        Ok(declarations)
+    }
+
+    /// Records a namespace declaration on the document being parsed.
+    fn declare_namespace(&mut self, prefix: String, uri: String) {
+        match self.dfxml {
+            Some(ref mut dfxml) => dfxml.add_namespace(prefix, uri),
+            None => self.pending_namespaces.push((prefix, uri)),
+        }
     }
 
     /// Extracts attributes from a BytesStart element as owned data.
-    fn extract_attrs(e: &BytesStart<'_>) -> Result<Vec<(String, String)>> {
+    ///
+    /// Attributes in the DFXML namespace (or no namespace) are keyed by their
+    /// local name; attributes in other namespaces are keyed as `{uri}name`.
+    /// Namespace declarations are skipped.
+    fn extract_attrs(
+        reader: &NsReader<LineTracker<R>>,
+        e: &BytesStart<'_>,
+    ) -> Result<Vec<(String, String)>> {
         let mut attrs = Vec::new();
         for attr in e.attributes().flatten() {
-            let key = str::from_utf8(attr.key.as_ref())?.to_string();
+            if attr.key.as_namespace_binding().is_some() {
+                continue;
+            }
+            let (resolved, local_name) = reader.resolve_attribute(attr.key);
+            let local_name = str::from_utf8(local_name.as_ref())?;
+            let key = match namespace_of(resolved) {
+                Some(ns) => format!("{{{}}}{}", ns, local_name),
+                None => local_name.to_string(),
+            };
             let value = attr.unescape_value()?.to_string();
             attrs.push((key, value));
         }
@@ -306,12 +1434,37 @@ impl<R: BufRead> DFXMLReader<R> {
     /// Handles a start element event with owned data.
     fn handle_start_owned(
         &mut self,
+        ns: Option<&str>,
         local_name: &str,
         attrs: Vec<(String, String)>,
     ) -> Result<Option<Event>> {
-        self.context.push(local_name.to_string());
+        self.context
+            .push(local_name.to_string(), self.element_position);
+
+        // Everything inside a non-DFXML element belongs to that element
+        if !self.external_stack.is_empty() || ns.is_some_and(|ns| ns != XMLNS_DELTA) {
+            let mut elem = match ns {
+                Some(ns) => ExternalElement::with_namespace(ns, local_name),
+                None => ExternalElement::new(local_name),
+            };
+            elem.attributes = attrs;
+            self.external_stack.push(elem);
+            return Ok(None);
+        }
+
         self.context.attrs = attrs;
 
+        match ns {
+            None => {}
+            Some(XMLNS_DELTA) => {
+                if let "original_fileobject" | "original_volume" = local_name {
+                    self.awaiting_original = true;
+                }
+                return Ok(None);
+            }
+            Some(_) => return Ok(None),
+        }
+
         match local_name {
             "dfxml" => {
                 let mut dfxml = DFXMLObject::new();
@@ -321,6 +1474,9 @@ impl<R: BufRead> DFXMLReader<R> {
                         dfxml.version = value.clone();
                     }
                 }
+                for (prefix, uri) in self.pending_namespaces.drain(..) {
+                    dfxml.add_namespace(prefix, uri);
+                }
                 self.dfxml = Some(dfxml.clone());
                 self.state = ParserState::InDfxml;
                 return Ok(Some(Event::DFXMLStart(Box::new(dfxml))));
@@ -329,34 +1485,50 @@ impl<R: BufRead> DFXMLReader<R> {
                 self.state_stack.push(self.state);
                 self.state = ParserState::InDiskImage;
                 let di = DiskImageObject::new();
-                self.disk_image = Some(di.clone());
+                self.containers.push(Container::DiskImage(di.clone()));
                 return Ok(Some(Event::DiskImageStart(Box::new(di))));
             }
             "partitionsystemobject" => {
                 self.state_stack.push(self.state);
                 self.state = ParserState::InPartitionSystem;
                 let ps = PartitionSystemObject::new();
-                self.partition_system = Some(ps.clone());
+                self.containers.push(Container::PartitionSystem(ps.clone()));
                 return Ok(Some(Event::PartitionSystemStart(Box::new(ps))));
             }
             "partitionobject" => {
                 self.state_stack.push(self.state);
                 self.state = ParserState::InPartition;
                 let p = PartitionObject::new();
-                self.partition = Some(p.clone());
+                self.containers.push(Container::Partition(p.clone()));
                 return Ok(Some(Event::PartitionStart(Box::new(p))));
             }
             "volume" => {
                 self.state_stack.push(self.state);
                 self.state = ParserState::InVolume;
-                let vol = VolumeObject::new();
-                self.volume = Some(vol.clone());
+                let mut vol = VolumeObject::new();
+                apply_delta_annos(&self.context.attrs, &mut vol.annos);
+                // Without an enclosing volume, an original is an ordinary volume
+                let is_original =
+                    std::mem::take(&mut self.awaiting_original) && self.volume_mut().is_some();
+                self.containers.push(Container::Volume(vol.clone()));
+                if is_original {
+                    self.delta_parents
+                        .push(DeltaParent::Volume(self.containers.len()));
+                    return Ok(None);
+                }
                 return Ok(Some(Event::VolumeStart(Box::new(vol))));
             }
             "fileobject" => {
                 self.state_stack.push(self.state);
                 self.state = ParserState::InFileObject;
-                self.file = Some(FileObject::new());
+                let mut file = FileObject::new();
+                apply_delta_annos(&self.context.attrs, &mut file.annos);
+                if std::mem::take(&mut self.awaiting_original) {
+                    if let Some(parent) = self.file.take() {
+                        self.delta_parents.push(DeltaParent::File(Box::new(parent)));
+                    }
+                }
+                self.file = Some(file);
             }
             "creator" => {
                 self.state_stack.push(self.state);
@@ -381,8 +1553,14 @@ impl<R: BufRead> DFXMLReader<R> {
             }
             "byte_run" => {
                 let mut br = ByteRun::new();
-                self.parse_byte_run_attrs(&self.context.attrs.clone(), &mut br)?;
+                let parsed = Self::parse_byte_run_attrs(
+                    &self.context.attrs,
+                    &mut br,
+                    self.strict || self.recover,
+                );
+                // Keep the valid attributes of a run when recovering
                 self.current_byte_run = Some(br);
+                parsed?;
             }
             "library" => {
                 let mut lib = LibraryObject::empty();
@@ -414,10 +1592,58 @@ impl<R: BufRead> DFXMLReader<R> {
     }
 
     /// Handles an end element event with owned data.
-    fn handle_end_owned(&mut self, local_name: &str) -> Result<Option<Event>> {
+    fn handle_end_owned(&mut self, ns: Option<&str>, local_name: &str) -> Result<Option<Event>> {
         let text = self.context.text.trim().to_string();
         let attrs = self.context.attrs.clone();
         self.context.pop();
+        // Invalid values are recorded as warnings when recovering
+        let strict = self.strict || self.recover;
+
+        if let Some(mut elem) = self.external_stack.pop() {
+            if let Some(ref mut text) = elem.text {
+                let trimmed = text.trim();
+                if trimmed.is_empty() {
+                    elem.text = None;
+                } else if trimmed.len() != text.len() {
+                    *text = trimmed.to_string();
+                }
+            }
+            match self.external_stack.last_mut() {
+                Some(parent) => parent.add_child(elem),
+                None => self.attach_external(elem),
+            }
+            return Ok(None);
+        }
+
+        match ns {
+            None => {}
+            Some(XMLNS_DELTA) => {
+                match local_name {
+                    "original_fileobject" | "original_volume" => self.awaiting_original = false,
+                    "changed_properties" => {
+                        for property in text.split_whitespace() {
+                            self.record_diff(property);
+                        }
+                    }
+                    _ => {}
+                }
+                return Ok(None);
+            }
+            Some(_) => return Ok(None),
+        }
+
+        // Python DFXML marks differing properties with delta:changed_property="1"
+        if attrs
+            .iter()
+            .any(|(k, v)| delta_attr(k) == Some("changed_property") && parse_bool(v) == Some(true))
+        {
+            let property = match local_name {
+                "hashdigest" => "hashes",
+                "byte_runs" => "data_brs",
+                other => other,
+            };
+            self.record_diff(property);
+        }
 
         match local_name {
             "dfxml" => {
@@ -428,44 +1654,52 @@ impl<R: BufRead> DFXMLReader<R> {
             }
             "diskimageobject" => {
                 self.state = self.state_stack.pop().unwrap_or(ParserState::InDfxml);
-                if let Some(di) = self.disk_image.take() {
+                if let Some(Container::DiskImage(di)) = self.containers.pop() {
                     return Ok(Some(Event::DiskImageEnd(Box::new(di))));
                 }
             }
             "partitionsystemobject" => {
                 self.state = self.state_stack.pop().unwrap_or(ParserState::InDfxml);
-                if let Some(ps) = self.partition_system.take() {
+                if let Some(Container::PartitionSystem(ps)) = self.containers.pop() {
                     return Ok(Some(Event::PartitionSystemEnd(Box::new(ps))));
                 }
             }
             "partitionobject" => {
                 self.state = self.state_stack.pop().unwrap_or(ParserState::InDfxml);
-                if let Some(p) = self.partition.take() {
+                if let Some(Container::Partition(p)) = self.containers.pop() {
                     return Ok(Some(Event::PartitionEnd(Box::new(p))));
                 }
             }
             "volume" => {
                 self.state = self.state_stack.pop().unwrap_or(ParserState::InDfxml);
-                if let Some(vol) = self.volume.take() {
+                let depth = self.containers.len();
+                let is_original = matches!(self.delta_parents.last(), Some(DeltaParent::Volume(d)) if *d == depth);
+                if let Some(Container::Volume(vol)) = self.containers.pop() {
+                    if is_original {
+                        self.delta_parents.pop();
+                        if let Some(parent) = self.volume_mut() {
+                            parent.original_volume = Some(Box::new(vol));
+                        }
+                        return Ok(None);
+                    }
                     return Ok(Some(Event::VolumeEnd(Box::new(vol))));
                 }
             }
             "fileobject" => {
                 self.state = self.state_stack.pop().unwrap_or(ParserState::InDfxml);
+                if let Some(DeltaParent::File(_)) = self.delta_parents.last() {
+                    if let Some(DeltaParent::File(mut parent)) = self.delta_parents.pop() {
+                        parent.original_fileobject = self.file.take().map(Box::new);
+                        self.file = Some(*parent);
+                    }
+                    return Ok(None);
+                }
                 if let Some(file) = self.file.take() {
-                    // Attach to parent container if one exists
-                    // The clone is needed because we also emit the event for streaming users
-                    if let Some(ref mut vol) = self.volume {
-                        vol.append_file(file.clone());
-                    } else if let Some(ref mut p) = self.partition {
-                        p.append_file(file.clone());
-                    } else if let Some(ref mut ps) = self.partition_system {
-                        ps.append_file(file.clone());
-                    } else if let Some(ref mut di) = self.disk_image {
-                        di.append_file(file.clone());
+                    if let Some(reason) = self.file_error.take() {
+                        self.warn(Recovery::FileSkipped, reason);
+                        return Ok(None);
                     }
-                    // Files without a container will be handled by parse()
-                    return Ok(Some(Event::FileObject(Box::new(file))));
+                    return Ok(Some(self.complete_file(file)));
                 }
             }
             "creator" => {
@@ -508,7 +1742,7 @@ impl<R: BufRead> DFXMLReader<R> {
             "mtime" | "atime" | "ctime" | "crtime" | "dtime" | "bkup_time" => {
                 if let Some((name, mut ts)) = self.current_timestamp.take() {
                     if !text.is_empty() {
-                        ts.time = Timestamp::parse_iso8601(&text).ok();
+                        ts.time = parse_value(Timestamp::parse_iso8601(&text), strict)?;
                     }
                     if let Some(ref mut file) = self.file {
                         file.set_timestamp(name, ts);
@@ -524,11 +1758,13 @@ impl<R: BufRead> DFXMLReader<R> {
 
                 if let Some(type_str) = hash_type_str {
                     if let Ok(hash_type) = type_str.parse::<HashType>() {
-                        // Apply to current byte_run or file
+                        // Apply to current byte_run, file or disk image
                         if let Some(ref mut br) = self.current_byte_run {
                             br.hashes.set(hash_type, text);
                         } else if let Some(ref mut file) = self.file {
                             file.hashes.set(hash_type, text);
+                        } else if let Some(di) = self.disk_image_mut() {
+                            di.hashes.set(hash_type, text);
                         }
                     }
                 }
@@ -541,22 +1777,22 @@ impl<R: BufRead> DFXMLReader<R> {
             }
             "filesize" => {
                 if let Some(ref mut file) = self.file {
-                    file.filesize = text.parse().ok();
+                    file.filesize = parse_number(&text, strict)?;
                 }
             }
             "inode" => {
                 if let Some(ref mut file) = self.file {
-                    file.inode = text.parse().ok();
+                    file.inode = parse_number(&text, strict)?;
                 }
             }
             "partition" => {
                 if let Some(ref mut file) = self.file {
-                    file.partition = text.parse().ok();
+                    file.partition = parse_number(&text, strict)?;
                 }
             }
             "id" => {
                 if let Some(ref mut file) = self.file {
-                    file.id = text.parse().ok();
+                    file.id = parse_number(&text, strict)?;
                 }
             }
             "name_type" => {
@@ -566,7 +1802,7 @@ impl<R: BufRead> DFXMLReader<R> {
             }
             "meta_type" => {
                 if let Some(ref mut file) = self.file {
-                    if let Ok(code) = text.parse::<i32>() {
+                    if let Some(code) = parse_number::<i32>(&text, strict)? {
                         file.meta_type = Some(crate::objects::MetaType::from_code(code));
                     }
                 }
@@ -575,25 +1811,31 @@ impl<R: BufRead> DFXMLReader<R> {
                 if let Some(ref mut file) = self.file {
                     // Parse octal or decimal mode
                     file.mode = if text.starts_with('0') {
-                        u32::from_str_radix(text.trim_start_matches('0'), 8).ok()
+                        match text.trim_start_matches('0') {
+                            "" => Some(0),
+                            digits => parse_value(
+                                u32::from_str_radix(digits, 8).map_err(Error::from),
+                                strict,
+                            )?,
+                        }
                     } else {
-                        text.parse().ok()
+                        parse_number(&text, strict)?
                     };
                 }
             }
             "nlink" => {
                 if let Some(ref mut file) = self.file {
-                    file.nlink = text.parse().ok();
+                    file.nlink = parse_number(&text, strict)?;
                 }
             }
             "uid" => {
                 if let Some(ref mut file) = self.file {
-                    file.uid = text.parse().ok();
+                    file.uid = parse_number(&text, strict)?;
                 }
             }
             "gid" => {
                 if let Some(ref mut file) = self.file {
-                    file.gid = text.parse().ok();
+                    file.gid = parse_number(&text, strict)?;
                 }
             }
             "link_target" => {
@@ -608,7 +1850,7 @@ impl<R: BufRead> DFXMLReader<R> {
             }
             "seq" => {
                 if let Some(ref mut file) = self.file {
-                    file.seq = text.parse().ok();
+                    file.seq = parse_number(&text, strict)?;
                 }
             }
             "alloc" => {
@@ -636,75 +1878,74 @@ impl<R: BufRead> DFXMLReader<R> {
                     file.compressed = parse_bool(&text);
                 }
             }
-            "error" => match self.state {
-                ParserState::InFileObject => {
+            "error" => {
+                if self.state == ParserState::InFileObject {
                     if let Some(ref mut file) = self.file {
                         file.error = Some(text);
                     }
-                }
-                ParserState::InVolume => {
-                    if let Some(ref mut vol) = self.volume {
-                        vol.error = Some(text);
-                    }
-                }
-                ParserState::InPartitionSystem => {
-                    if let Some(ref mut ps) = self.partition_system {
-                        ps.error = Some(text);
-                    }
-                }
-                ParserState::InDiskImage => {
-                    if let Some(ref mut di) = self.disk_image {
-                        di.error = Some(text);
+                } else {
+                    match self.containers.last_mut() {
+                        Some(Container::DiskImage(di)) => di.error = Some(text),
+                        Some(Container::PartitionSystem(ps)) => ps.error = Some(text),
+                        Some(Container::Volume(vol)) => vol.error = Some(text),
+                        _ => {}
                     }
                 }
+            }
+            // Container properties (applied to the innermost container)
+            "ftype_str" => match self.containers.last_mut() {
+                Some(Container::Volume(vol)) => vol.ftype_str = Some(text),
+                Some(Container::Partition(p)) => p.ftype_str = Some(text),
                 _ => {}
             },
-            // Volume properties
-            "ftype_str" => {
-                if let Some(ref mut vol) = self.volume {
-                    vol.ftype_str = Some(text);
-                }
-            }
             "ftype" => {
-                if let Some(ref mut vol) = self.volume {
-                    vol.ftype = text.parse().ok();
+                if let Some(vol) = self.volume_mut() {
+                    vol.ftype = parse_number(&text, strict)?;
                 }
             }
-            "block_size" => {
-                if let Some(ref mut vol) = self.volume {
-                    vol.block_size = text.parse().ok();
+            "block_size" => match self.containers.last_mut() {
+                Some(Container::Volume(vol)) => vol.block_size = parse_number(&text, strict)?,
+                Some(Container::Partition(p)) => p.block_size = parse_number(&text, strict)?,
+                Some(Container::PartitionSystem(ps)) => {
+                    ps.block_size = parse_number(&text, strict)?
                 }
-            }
-            "block_count" => {
-                if let Some(ref mut vol) = self.volume {
-                    vol.block_count = text.parse().ok();
-                }
-            }
+                _ => {}
+            },
+            "block_count" => match self.containers.last_mut() {
+                Some(Container::Volume(vol)) => vol.block_count = parse_number(&text, strict)?,
+                Some(Container::Partition(p)) => p.block_count = parse_number(&text, strict)?,
+                _ => {}
+            },
             "first_block" => {
-                if let Some(ref mut vol) = self.volume {
-                    vol.first_block = text.parse().ok();
+                if let Some(vol) = self.volume_mut() {
+                    vol.first_block = parse_number(&text, strict)?;
                 }
             }
             "last_block" => {
-                if let Some(ref mut vol) = self.volume {
-                    vol.last_block = text.parse().ok();
+                if let Some(vol) = self.volume_mut() {
+                    vol.last_block = parse_number(&text, strict)?;
                 }
             }
             "partition_offset" => {
-                if let Some(ref mut vol) = self.volume {
-                    vol.partition_offset = text.parse().ok();
-                }
-            }
-            "sector_size" => {
-                if let Some(ref mut vol) = self.volume {
-                    vol.sector_size = text.parse().ok();
+                if let Some(vol) = self.volume_mut() {
+                    vol.partition_offset = parse_number(&text, strict)?;
                 }
             }
+            "sector_size" => match self.containers.last_mut() {
+                Some(Container::Volume(vol)) => vol.sector_size = parse_number(&text, strict)?,
+                Some(Container::DiskImage(di)) => di.sector_size = parse_number(&text, strict)?,
+                _ => {}
+            },
             "allocated_only" => {
-                if let Some(ref mut vol) = self.volume {
+                if let Some(vol) = self.volume_mut() {
                     vol.allocated_only = parse_bool(&text);
                 }
             }
+            "guid" => match self.containers.last_mut() {
+                Some(Container::Partition(p)) => p.guid = Some(text),
+                Some(Container::PartitionSystem(ps)) => ps.guid = Some(text),
+                _ => {}
+            },
             // DFXML metadata
             "program" => {
                 if let Some(ref mut dfxml) = self.dfxml {
@@ -721,37 +1962,56 @@ impl<R: BufRead> DFXMLReader<R> {
                     dfxml.command_line = Some(text);
                 }
             }
-            "image_filename" => {
-                if let Some(ref mut dfxml) = self.dfxml {
-                    dfxml.sources.push(text);
+            "image_filename" => match self.containers.last_mut() {
+                Some(Container::DiskImage(di)) => di.image_filename = Some(text),
+                None => {
+                    if let Some(ref mut dfxml) = self.dfxml {
+                        dfxml.sources.push(text);
+                    }
                 }
-            }
+                _ => {}
+            },
             // Partition system properties
             "pstype_str" => {
-                if let Some(ref mut ps) = self.partition_system {
+                if let Some(ps) = self.partition_system_mut() {
                     ps.pstype_str = Some(text);
                 }
             }
+            "volume_name" => {
+                if let Some(ps) = self.partition_system_mut() {
+                    ps.volume_name = Some(text);
+                }
+            }
             // Partition properties
             "ptype" => {
-                if let Some(ref mut p) = self.partition {
-                    p.ptype = text.parse().ok();
+                if let Some(p) = self.partition_mut() {
+                    p.ptype = parse_number(&text, strict)?;
                 }
             }
             "ptype_str" => {
-                if let Some(ref mut p) = self.partition {
+                if let Some(p) = self.partition_mut() {
                     p.ptype_str = Some(text);
                 }
             }
             "partition_index" => {
-                if let Some(ref mut p) = self.partition {
-                    p.partition_index = text.parse().ok();
+                if let Some(p) = self.partition_mut() {
+                    p.partition_index = parse_number(&text, strict)?;
+                }
+            }
+            "partition_system_offset" => {
+                if let Some(p) = self.partition_mut() {
+                    p.partition_system_offset = parse_number(&text, strict)?;
+                }
+            }
+            "partition_label" => {
+                if let Some(p) = self.partition_mut() {
+                    p.partition_label = Some(text);
                 }
             }
             // Disk image properties
             "imagesize" => {
-                if let Some(ref mut di) = self.disk_image {
-                    di.image_size = text.parse().ok();
+                if let Some(di) = self.disk_image_mut() {
+                    di.image_size = parse_number(&text, strict)?;
                 }
             }
             _ => {}
@@ -761,59 +2021,136 @@ impl<R: BufRead> DFXMLReader<R> {
     }
 
     /// Parses byte_run element attributes into a ByteRun struct.
-    fn parse_byte_run_attrs(&self, attrs: &[(String, String)], br: &mut ByteRun) -> Result<()> {
+    ///
+    /// Every attribute is parsed; with `strict` set, the first invalid one
+    /// is returned as the error.
+    fn parse_byte_run_attrs(
+        attrs: &[(String, String)],
+        br: &mut ByteRun,
+        strict: bool,
+    ) -> Result<()> {
+        let mut first_error = None;
         for (key, value) in attrs {
-            match key.as_str() {
-                "img_offset" => br.img_offset = value.parse().ok(),
-                "fs_offset" => br.fs_offset = value.parse().ok(),
-                "file_offset" => br.file_offset = value.parse().ok(),
-                "len" => br.len = value.parse().ok(),
-                "fill" => {
-                    if let Ok(v) = value.parse::<u8>() {
-                        br.fill = Some(v);
-                    }
-                }
-                "type" => br.run_type = value.parse().ok(),
-                "uncompressed_len" => br.uncompressed_len = value.parse().ok(),
-                _ => {}
+            let parsed = match key.as_str() {
+                "img_offset" => parse_number(value, strict).map(|n| br.img_offset = n),
+                "fs_offset" => parse_number(value, strict).map(|n| br.fs_offset = n),
+                "file_offset" => parse_number(value, strict).map(|n| br.file_offset = n),
+                "len" => parse_number(value, strict).map(|n| br.len = n),
+                "fill" => parse_number(value, strict).map(|n| br.fill = n),
+                "type" => {
+                    br.run_type = value.parse().ok();
+                    Ok(())
+                }
+                "uncompressed_len" => parse_number(value, strict).map(|n| br.uncompressed_len = n),
+                _ => Ok(()),
+            };
+            if let Err(e) = parsed {
+                first_error.get_or_insert(e);
             }
         }
-        Ok(())
+        first_error.map_or(Ok(()), Err)
     }
 
-    /// Applies parsed byte runs to the current object.
-    fn apply_byte_runs(&mut self, brs: ByteRuns) {
+    /// Attaches a completed file object to the innermost container, if one
+    /// exists, and returns its event.
+    fn complete_file(&mut self, file: FileObject) -> Event {
+        // The clone is needed because we also emit the event for streaming users
+        match self.containers.last_mut() {
+            Some(Container::DiskImage(di)) => di.append_file(file.clone()),
+            Some(Container::PartitionSystem(ps)) => ps.append_file(file.clone()),
+            Some(Container::Partition(p)) => p.append_file(file.clone()),
+            Some(Container::Volume(vol)) => vol.append_file(file.clone()),
+            None => {}
+        }
+        // Files without a container will be handled by parse()
+        Event::FileObject(Box::new(file))
+    }
+
+    /// Returns the innermost container if it is a disk image.
+    fn disk_image_mut(&mut self) -> Option<&mut DiskImageObject> {
+        match self.containers.last_mut() {
+            Some(Container::DiskImage(di)) => Some(di),
+            _ => None,
+        }
+    }
+
+    /// Returns the innermost container if it is a partition system.
+    fn partition_system_mut(&mut self) -> Option<&mut PartitionSystemObject> {
+        match self.containers.last_mut() {
+            Some(Container::PartitionSystem(ps)) => Some(ps),
+            _ => None,
+        }
+    }
+
+    /// Returns the innermost container if it is a partition.
+    fn partition_mut(&mut self) -> Option<&mut PartitionObject> {
+        match self.containers.last_mut() {
+            Some(Container::Partition(p)) => Some(p),
+            _ => None,
+        }
+    }
+
+    /// Returns the innermost container if it is a volume.
+    fn volume_mut(&mut self) -> Option<&mut VolumeObject> {
+        match self.containers.last_mut() {
+            Some(Container::Volume(vol)) => Some(vol),
+            _ => None,
+        }
+    }
+
+    /// Attaches a completed external element to the enclosing object.
+    fn attach_external(&mut self, elem: ExternalElement) {
+        let externals = match (self.state, self.containers.last_mut()) {
+            (ParserState::InFileObject, _) => self.file.as_mut().map(|f| &mut f.externals),
+            (ParserState::InCreator | ParserState::InBuildEnvironment, _) | (_, None) => {
+                self.dfxml.as_mut().map(|d| &mut d.externals)
+            }
+            (_, Some(Container::DiskImage(di))) => Some(&mut di.externals),
+            (_, Some(Container::PartitionSystem(ps))) => Some(&mut ps.externals),
+            (_, Some(Container::Partition(p))) => Some(&mut p.externals),
+            (_, Some(Container::Volume(vol))) => Some(&mut vol.externals),
+        };
+        if let Some(externals) = externals {
+            // The reader never produces DFXML-namespace externals
+            let _ = externals.try_push(elem);
+        }
+    }
+
+    /// Records a differing property on the current file or volume.
+    fn record_diff(&mut self, property: &str) {
         match self.state {
             ParserState::InFileObject => {
                 if let Some(ref mut file) = self.file {
-                    match self.byte_runs_facet {
-                        Some(ByteRunFacet::Inode) => file.inode_brs = Some(brs),
-                        Some(ByteRunFacet::Name) => file.name_brs = Some(brs),
-                        _ => file.data_brs = Some(brs),
-                    }
+                    file.diffs.insert(property.to_string());
                 }
             }
             ParserState::InVolume => {
-                if let Some(ref mut vol) = self.volume {
-                    vol.byte_runs = Some(brs);
-                }
-            }
-            ParserState::InPartition => {
-                if let Some(ref mut p) = self.partition {
-                    p.byte_runs = Some(brs);
-                }
-            }
-            ParserState::InPartitionSystem => {
-                if let Some(ref mut ps) = self.partition_system {
-                    ps.byte_runs = Some(brs);
+                if let Some(vol) = self.volume_mut() {
+                    vol.diffs.insert(property.to_string());
                 }
             }
-            ParserState::InDiskImage => {
-                if let Some(ref mut di) = self.disk_image {
-                    di.byte_runs = Some(brs);
+            _ => {}
+        }
+    }
+
+    /// Applies parsed byte runs to the current object.
+    fn apply_byte_runs(&mut self, brs: ByteRuns) {
+        if self.state == ParserState::InFileObject {
+            if let Some(ref mut file) = self.file {
+                match self.byte_runs_facet {
+                    Some(ByteRunFacet::Inode) => file.inode_brs = Some(brs),
+                    Some(ByteRunFacet::Name) => file.name_brs = Some(brs),
+                    _ => file.data_brs = Some(brs),
                 }
             }
-            _ => {}
+            return;
+        }
+        match self.containers.last_mut() {
+            Some(Container::DiskImage(di)) => di.byte_runs = Some(brs),
+            Some(Container::PartitionSystem(ps)) => ps.byte_runs = Some(brs),
+            Some(Container::Partition(p)) => p.byte_runs = Some(brs),
+            Some(Container::Volume(vol)) => vol.byte_runs = Some(brs),
+            None => {}
         }
     }
 }
@@ -822,15 +2159,103 @@ impl<R: BufRead> Iterator for DFXMLReader<R> {
     type Item = Result<Event>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.state == ParserState::Done {
-            return None;
-        }
-
         match self.parse_next() {
             Ok(Some(event)) => Some(Ok(event)),
             Ok(None) => None,
-            Err(e) => Some(Err(e)),
+            Err(e) => Some(Err(self.locate_error(e))),
+        }
+    }
+}
+
+/// An iterator over the events of a [`DFXMLReader`] paired with the
+/// positions of their start tags.
+///
+/// Created by [`DFXMLReader::with_positions`].
+pub struct WithPositions<R: BufRead> {
+    reader: DFXMLReader<R>,
+}
+
+impl<R: BufRead> WithPositions<R> {
+    /// Returns the underlying reader, e.g. for its enclosing containers.
+    pub fn reader(&self) -> &DFXMLReader<R> {
+        &self.reader
+    }
+}
+
+impl<R: BufRead> Iterator for WithPositions<R> {
+    type Item = Result<(Event, Position)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.reader.next()? {
+            Ok(event) => Some(Ok((event, self.reader.event_position()))),
+            Err(e) => Some(Err(Error::AtPosition {
+                position: self.reader.error_position().unwrap_or(Position::START),
+                source: Box::new(e),
+            })),
+        }
+    }
+}
+
+/// Maps a resolved namespace to `None` for DFXML (or no namespace) and the
+/// namespace URI otherwise.
+fn namespace_of(resolved: ResolveResult<'_>) -> Option<String> {
+    match resolved {
+        ResolveResult::Bound(ns) if ns.as_ref() != XMLNS_DFXML.as_bytes() => {
+            Some(String::from_utf8_lossy(ns.as_ref()).into_owned())
+        }
+        ResolveResult::Unknown(prefix) => Some(String::from_utf8_lossy(&prefix).into_owned()),
+        _ => None,
+    }
+}
+
+/// Returns the local name of a delta namespace attribute key (`{uri}name`).
+fn delta_attr(key: &str) -> Option<&str> {
+    key.strip_prefix('{')?
+        .strip_prefix(XMLNS_DELTA)?
+        .strip_prefix('}')
+}
+
+/// Collects differential annotations from `delta:` attributes.
+///
+/// `delta:new_file="1"` and `delta:new_volume="1"` both map to `new`, and
+/// `delta:matched="1"` maps to `matched`.
+fn apply_delta_annos(attrs: &[(String, String)], annos: &mut HashSet<String>) {
+    for (key, value) in attrs {
+        let Some(name) = delta_attr(key) else {
+            continue;
+        };
+        if parse_bool(value) != Some(true) {
+            continue;
         }
+        let anno = name
+            .strip_suffix("_file")
+            .or_else(|| name.strip_suffix("_volume"))
+            .unwrap_or(name);
+        annos.insert(anno.to_string());
+    }
+}
+
+/// Parses an integer element or attribute value. Empty values are absent;
+/// anything else that is not an integer is an error if `strict` is set, and
+/// absent otherwise.
+fn parse_number<T: str::FromStr<Err = std::num::ParseIntError>>(
+    s: &str,
+    strict: bool,
+) -> Result<Option<T>> {
+    if s.is_empty() {
+        Ok(None)
+    } else {
+        parse_value(s.parse().map_err(Error::from), strict)
+    }
+}
+
+/// Returns a parsed value, or `None` for an invalid one unless `strict` is
+/// set.
+fn parse_value<T>(parsed: Result<T>, strict: bool) -> Result<Option<T>> {
+    match parsed {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if strict => Err(e),
+        Err(_) => Ok(None),
     }
 }
 
@@ -864,20 +2289,48 @@ pub fn parse_file_objects<R: BufRead>(reader: R) -> Result<Vec<FileObject>> {
 /// Returns the DFXMLObject with all child objects attached.
 /// This loads the entire document into memory.
 pub fn parse<R: BufRead>(reader: R) -> Result<DFXMLObject> {
-    let mut dfxml: Option<DFXMLObject> = None;
-    // Track nesting depth to know if files should be attached to dfxml directly
-    let mut container_depth = 0;
+    build_document(&mut DFXMLReader::from_reader(reader))
+}
 
-    for event in DFXMLReader::from_reader(reader) {
-        match event? {
+/// Parses a complete DFXML document in recovery mode (see
+/// [`DFXMLReader::with_recovery`]), returning what could be read and the
+/// problems read past.
+pub fn parse_recovering<R: BufRead>(reader: R) -> Result<(DFXMLObject, Vec<Warning>)> {
+    let mut reader = DFXMLReader::from_reader(reader).with_recovery(true);
+    let dfxml = build_document(&mut reader)?;
+    Ok((dfxml, reader.take_warnings()))
+}
+
+/// Builds a document from the events of a reader.
+fn build_document<R: BufRead>(reader: &mut DFXMLReader<R>) -> Result<DFXMLObject> {
+    let mut builder = DocumentBuilder::default();
+    for event in reader {
+        builder.add(event?);
+    }
+    builder.finish()
+}
+
+/// Assembles a document from reader events.
+#[derive(Default)]
+pub(crate) struct DocumentBuilder {
+    dfxml: Option<DFXMLObject>,
+    // Child containers collected for each open container, innermost last
+    pending: Vec<Vec<ChildObject>>,
+}
+
+impl DocumentBuilder {
+    /// Adds the next event of the document.
+    pub(crate) fn add(&mut self, event: Event) {
+        let mut completed = match event {
             Event::DFXMLStart(d) => {
                 // Use DFXMLStart to initialize the object so children can be attached
-                dfxml = Some(*d);
+                self.dfxml = Some(*d);
+                return;
             }
             Event::DFXMLEnd(d) => {
                 // Merge metadata from DFXMLEnd (which has all parsed creator info)
                 // into our existing dfxml that has the children attached
-                if let Some(ref mut existing) = dfxml {
+                if let Some(existing) = &mut self.dfxml {
                     // Copy creator and build libraries first (before moving d.sources)
                     for lib in d.creator_libraries() {
                         existing.add_creator_library(lib.clone());
@@ -890,57 +2343,101 @@ pub fn parse<R: BufRead>(reader: R) -> Result<DFXMLObject> {
                     existing.program_version = d.program_version.clone();
                     existing.command_line = d.command_line.clone();
                     existing.sources = d.sources.clone();
+                    existing.externals = d.externals.clone();
+                    for (prefix, uri) in d.namespaces() {
+                        existing.add_namespace(prefix, uri);
+                    }
                 }
+                return;
             }
-            Event::DiskImageStart(_) => {
-                container_depth += 1;
+            Event::DiskImageStart(_)
+            | Event::PartitionSystemStart(_)
+            | Event::PartitionStart(_)
+            | Event::VolumeStart(_) => {
+                self.pending.push(Vec::new());
+                return;
             }
-            Event::DiskImageEnd(di) => {
-                container_depth -= 1;
-                if let Some(ref mut d) = dfxml {
-                    d.append_disk_image(*di);
+            Event::DiskImageEnd(di) => ChildObject::DiskImage(*di),
+            Event::PartitionSystemEnd(ps) => ChildObject::PartitionSystem(*ps),
+            Event::PartitionEnd(p) => ChildObject::Partition(*p),
+            Event::VolumeEnd(v) => ChildObject::Volume(*v),
+            Event::FileObject(f) => {
+                // Files inside containers are already attached by the reader
+                // Only attach files that are directly under dfxml (no parent container)
+                if self.pending.is_empty() {
+                    if let Some(d) = &mut self.dfxml {
+                        d.append_file(*f);
+                    }
                 }
+                return;
             }
-            Event::PartitionSystemStart(_) => {
-                container_depth += 1;
-            }
-            Event::PartitionSystemEnd(ps) => {
-                container_depth -= 1;
-                if let Some(ref mut d) = dfxml {
-                    d.append_partition_system(*ps);
+        };
+
+        // Attach the completed container to its parent, or to the document
+        let children = self.pending.pop().unwrap_or_default();
+        let rejected = adopt_children(&mut completed, children);
+        match self.pending.last_mut() {
+            Some(siblings) => {
+                siblings.extend(rejected);
+                siblings.push(completed);
+            }
+            None => {
+                if let Some(d) = &mut self.dfxml {
+                    for child in rejected {
+                        d.append(child);
+                    }
+                    d.append(completed);
                 }
             }
-            Event::PartitionStart(_) => {
-                container_depth += 1;
+        }
+    }
+
+    /// Returns the document built from the events added.
+    pub(crate) fn finish(self) -> Result<DFXMLObject> {
+        self.dfxml
+            .ok_or_else(|| Error::MissingField("dfxml root element".to_string()))
+    }
+}
+
+/// Appends child containers to a completed container using its child enum.
+///
+/// Children that the container cannot hold (for example a partition system
+/// inside a volume) are returned so the caller can attach them further up.
+fn adopt_children(parent: &mut ChildObject, children: Vec<ChildObject>) -> Vec<ChildObject> {
+    let mut rejected = Vec::new();
+    for child in children {
+        match (&mut *parent, child) {
+            (ChildObject::DiskImage(di), ChildObject::PartitionSystem(ps)) => {
+                di.append(DiskImageChild::PartitionSystem(ps))
             }
-            Event::PartitionEnd(p) => {
-                container_depth -= 1;
-                if let Some(ref mut d) = dfxml {
-                    d.append_partition(*p);
-                }
+            (ChildObject::DiskImage(di), ChildObject::Partition(p)) => {
+                di.append(DiskImageChild::Partition(p))
             }
-            Event::VolumeStart(_) => {
-                container_depth += 1;
+            (ChildObject::DiskImage(di), ChildObject::Volume(v)) => {
+                di.append(DiskImageChild::Volume(v))
             }
-            Event::VolumeEnd(v) => {
-                container_depth -= 1;
-                if let Some(ref mut d) = dfxml {
-                    d.append_volume(*v);
-                }
+            (ChildObject::PartitionSystem(ps), ChildObject::Partition(p)) => {
+                ps.append(PartitionSystemChild::Partition(Box::new(p)))
             }
-            Event::FileObject(f) => {
-                // Files inside containers are already attached by the reader
-                // Only attach files that are directly under dfxml (no parent container)
-                if container_depth == 0 {
-                    if let Some(ref mut d) = dfxml {
-                        d.append_file(*f);
-                    }
-                }
+            (ChildObject::Partition(p), ChildObject::PartitionSystem(ps)) => {
+                p.append(PartitionChild::PartitionSystem(ps))
+            }
+            (ChildObject::Partition(p), ChildObject::Partition(sub)) => {
+                p.append(PartitionChild::Partition(sub))
+            }
+            (ChildObject::Partition(p), ChildObject::Volume(v)) => {
+                p.append(PartitionChild::Volume(v))
+            }
+            (ChildObject::Volume(vol), ChildObject::DiskImage(di)) => {
+                vol.append(VolumeChild::DiskImage(di))
             }
+            (ChildObject::Volume(vol), ChildObject::Volume(v)) => {
+                vol.append(VolumeChild::Volume(v))
+            }
+            (_, child) => rejected.push(child),
         }
     }
-
-    dfxml.ok_or_else(|| Error::MissingField("dfxml root element".to_string()))
+    rejected
 }
 
 #[cfg(test)]
@@ -1023,4 +2520,290 @@ mod tests {
         assert_eq!(parse_bool("TRUE"), Some(true));
         assert_eq!(parse_bool("invalid"), None);
     }
+
+    #[test]
+    fn test_parse_delta_namespace() {
+        let xml = r#"<?xml version="1.0"?>
+<dfxml version="1.0" xmlns:delta="http://www.forensicswiki.org/wiki/Forensic_Disk_Differencing">
+  <volume delta:changed_volume="1">
+    <ftype_str>ntfs</ftype_str>
+    <delta:original_volume>
+      <volume>
+        <ftype_str>fat32</ftype_str>
+      </volume>
+    </delta:original_volume>
+    <fileobject delta:modified_file="1">
+      <filename>a.txt</filename>
+      <filesize delta:changed_property="1">20</filesize>
+      <hashdigest type="md5" delta:changed_property="1">d41d8cd98f00b204e9800998ecf8427e</hashdigest>
+      <delta:original_fileobject>
+        <fileobject>
+          <filename>a.txt</filename>
+          <filesize>10</filesize>
+        </fileobject>
+      </delta:original_fileobject>
+    </fileobject>
+  </volume>
+</dfxml>"#;
+
+        let doc = parse(Cursor::new(xml)).unwrap();
+        assert_eq!(doc.volume_count(), 1);
+        let vol = doc.volumes().next().unwrap();
+        assert!(vol.annos.contains("changed"));
+        assert_eq!(vol.ftype_str, Some("ntfs".to_string()));
+        let original = vol.original_volume.as_deref().unwrap();
+        assert_eq!(original.ftype_str, Some("fat32".to_string()));
+
+        let files: Vec<_> = doc.iter_files().collect();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].annos.contains("modified"));
+        assert!(files[0].diffs.contains("filesize"));
+        assert!(files[0].diffs.contains("hashes"));
+        assert_eq!(files[0].filesize, Some(20));
+        let original = files[0].original_fileobject.as_deref().unwrap();
+        assert_eq!(original.filesize, Some(10));
+    }
+
+    #[test]
+    fn test_parse_externals() {
+        let xml = r#"<?xml version="1.0"?>
+<dfxml version="1.0" xmlns:ourtool="http://example.org/ourtool">
+  <ourtool:run id="7"/>
+  <volume>
+    <fileobject>
+      <filename>a.txt</filename>
+      <ourtool:classification ourtool:confidence="0.9">
+        <ourtool:label>invoice</ourtool:label>
+        <filesize>99</filesize>
+      </ourtool:classification>
+      <filesize>5</filesize>
+    </fileobject>
+  </volume>
+</dfxml>"#;
+
+        let doc = parse(Cursor::new(xml)).unwrap();
+        let ns: std::collections::HashMap<_, _> = doc.namespaces().collect();
+        assert_eq!(ns.get("ourtool"), Some(&"http://example.org/ourtool"));
+
+        let run = doc.externals.iter().next().unwrap();
+        assert_eq!(run.qualified_name(), "{http://example.org/ourtool}run");
+        assert_eq!(run.attributes, vec![("id".to_string(), "7".to_string())]);
+
+        let files: Vec<_> = doc.iter_files().collect();
+        assert_eq!(files.len(), 1);
+        // DFXML elements inside an external subtree are not file properties
+        assert_eq!(files[0].filesize, Some(5));
+        assert_eq!(files[0].externals.len(), 1);
+
+        let class = files[0].externals.iter().next().unwrap();
+        assert_eq!(class.tag_name, "classification");
+        assert_eq!(
+            class.attributes,
+            vec![(
+                "{http://example.org/ourtool}confidence".to_string(),
+                "0.9".to_string()
+            )]
+        );
+        assert_eq!(class.children.len(), 2);
+        assert_eq!(class.children[0].text, Some("invoice".to_string()));
+        assert_eq!(class.children[1].namespace, None);
+        assert_eq!(class.children[1].text, Some("99".to_string()));
+    }
+
+    #[test]
+    fn test_parse_nested_containers() {
+        let xml = r#"<?xml version="1.0"?>
+<dfxml version="1.0">
+  <diskimageobject>
+    <image_filename>disk.raw</image_filename>
+    <partitionsystemobject>
+      <pstype_str>gpt</pstype_str>
+      <partitionobject>
+        <partition_index>1</partition_index>
+        <block_size>512</block_size>
+        <volume>
+          <ftype_str>ext4</ftype_str>
+          <block_size>4096</block_size>
+          <volume>
+            <ftype_str>iso9660</ftype_str>
+            <fileobject><filename>inner.txt</filename></fileobject>
+          </volume>
+          <fileobject><filename>outer.txt</filename></fileobject>
+        </volume>
+      </partitionobject>
+    </partitionsystemobject>
+  </diskimageobject>
+</dfxml>"#;
+
+        let doc = parse(Cursor::new(xml)).unwrap();
+        assert_eq!(doc.disk_images().count(), 1);
+        assert_eq!(doc.partition_systems().count(), 0);
+        assert_eq!(doc.volume_count(), 0);
+        assert!(doc.sources.is_empty());
+
+        let di = doc.disk_images().next().unwrap();
+        assert_eq!(di.image_filename, Some("disk.raw".to_string()));
+        let ps = di.partition_systems().next().unwrap();
+        assert_eq!(ps.pstype_str, Some("gpt".to_string()));
+        let p = ps.partitions().next().unwrap();
+        assert_eq!(p.partition_index, Some(1));
+        assert_eq!(p.block_size, Some(512));
+
+        let outer = p.volumes().next().unwrap();
+        assert_eq!(outer.ftype_str, Some("ext4".to_string()));
+        assert_eq!(outer.block_size, Some(4096));
+        assert_eq!(outer.files().count(), 1);
+        let inner = outer.volumes().next().unwrap();
+        assert_eq!(inner.ftype_str, Some("iso9660".to_string()));
+        assert_eq!(
+            inner.files().next().unwrap().filename,
+            Some("inner.txt".to_string())
+        );
+
+        assert_eq!(doc.iter_files().count(), 2);
+    }
+
+    #[test]
+    fn test_event_positions() {
+        let mut reader = DFXMLReader::from_reader(Cursor::new(SIMPLE_DFXML)).with_positions();
+        let mut positions = Vec::new();
+        for result in &mut reader {
+            let (event, position) = result.unwrap();
+            let tag = &SIMPLE_DFXML[position.byte_offset as usize..];
+            match event {
+                Event::DFXMLStart(_) | Event::DFXMLEnd(_) => assert!(tag.starts_with("<dfxml ")),
+                Event::VolumeStart(_) | Event::VolumeEnd(_) => assert!(tag.starts_with("<volume>")),
+                Event::FileObject(_) => assert!(tag.starts_with("<fileobject>")),
+                other => panic!("unexpected event {:?}", other),
+            }
+            positions.push((position.line, position.column));
+        }
+        assert_eq!(positions, [(2, 1), (7, 3), (10, 5), (7, 3), (2, 1)]);
+        assert_eq!(reader.reader().position().line, 21);
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let first_error = |xml: &str| {
+            DFXMLReader::from_reader(Cursor::new(xml))
+                .with_strict_values(true)
+                .with_positions()
+                .find_map(|e| e.err())
+                .unwrap()
+        };
+
+        let xml = "<dfxml version=\"1.0\">\n  <fileobject>\n    <filename>a</filename>\n    <filesize>12x</filesize>\n  </fileobject>\n</dfxml>";
+        let err = first_error(xml);
+        let position = err.position().unwrap();
+        assert_eq!((position.line, position.column), (4, 5));
+        assert!(xml[position.byte_offset as usize..].starts_with("<filesize>"));
+        assert!(matches!(err.inner(), Error::ParseInt(_)));
+        assert!(err.to_string().ends_with("at line 4, column 5 (byte 68)"));
+
+        // Invalid values are left unset by default
+        let doc = parse(Cursor::new(xml)).unwrap();
+        assert_eq!(doc.iter_files().next().unwrap().filesize, None);
+
+        let xml =
+            "<dfxml>\n  <fileobject>\n    <mtime>yesterday</mtime>\n  </fileobject>\n</dfxml>";
+        let err = first_error(xml);
+        assert!(matches!(err.inner(), Error::InvalidTimestamp(_)));
+        assert_eq!(err.position().unwrap().line, 3);
+
+        // The reader itself returns the error unwrapped
+        let xml = "<dfxml>\n  <fileobject>\n    <filename>a</wrong>\n</dfxml>";
+        let mut reader = DFXMLReader::from_reader(Cursor::new(xml));
+        let err = reader.by_ref().find_map(|e| e.err()).unwrap();
+        assert!(matches!(err, Error::XmlParse(_)));
+        assert_eq!(reader.error_position().unwrap().line, 3);
+    }
+
+    #[test]
+    fn test_recover_truncated() {
+        let xml = "<dfxml version=\"1.0\">\n<volume>\n<fileobject><filename>a</filename></fileobject>\n<fileobject><filename>b</filename>\n";
+        // Without recovery the events simply stop
+        let events: Vec<_> = DFXMLReader::from_reader(Cursor::new(xml))
+            .map(|e| e.unwrap())
+            .collect();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                Event::DFXMLStart(_),
+                Event::VolumeStart(_),
+                Event::FileObject(_),
+            ]
+        ));
+
+        let mut reader = DFXMLReader::from_reader(Cursor::new(xml)).with_recovery(true);
+        let events: Vec<_> = reader.by_ref().map(|e| e.unwrap()).collect();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                Event::DFXMLStart(_),
+                Event::VolumeStart(_),
+                Event::FileObject(_),
+                Event::VolumeEnd(vol),
+                Event::DFXMLEnd(_),
+            ] if vol.files().count() == 1
+        ));
+        let recoveries: Vec<_> = reader.warnings().iter().map(|w| w.recovery).collect();
+        assert_eq!(
+            recoveries,
+            [
+                Recovery::FileSkipped,
+                Recovery::ElementClosed,
+                Recovery::ElementClosed,
+            ]
+        );
+        assert_eq!(reader.warnings()[0].position.line, 4);
+        assert_eq!(reader.warnings()[0].message, "<fileobject> is not closed");
+
+        // Cut off inside a tag
+        let (doc, warnings) = parse_recovering(Cursor::new(&xml[..xml.len() - 8])).unwrap();
+        assert_eq!(doc.iter_files().count(), 1);
+        assert_eq!(warnings[0].recovery, Recovery::Truncated);
+    }
+
+    #[test]
+    fn test_recover_malformed() {
+        let xml = r#"<dfxml version="1.0">
+  <fileobject>
+    <filename>a</filename>
+    <filesize>12x</filesize>
+    <mtime>yesterday</mtime>
+    <byte_runs><byte_run img_offset="512" len="ten"/></byte_runs>
+  </fileobject>
+  <fileobject>
+    <filename>b</wrong>
+  </fileobject>
+  <fileobject><filename>c</filename></fileobject>
+  </volume>
+</dfxml>"#;
+        let (doc, warnings) = parse_recovering(Cursor::new(xml)).unwrap();
+        let files: Vec<_> = doc.iter_files().collect();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].filename.as_deref(), Some("a"));
+        assert_eq!(files[0].filesize, None);
+        assert!(files[0].mtime.is_none());
+        let run = files[0].data_brs.as_ref().unwrap().get(0).unwrap();
+        assert_eq!((run.img_offset, run.len), (Some(512), None));
+        assert_eq!(files[1].filename.as_deref(), Some("c"));
+
+        let summary: Vec<_> = warnings
+            .iter()
+            .map(|w| (w.recovery, w.position.line))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Recovery::ValueDropped, 4),
+                (Recovery::ValueDropped, 5),
+                (Recovery::ValueDropped, 6),
+                (Recovery::MarkupIgnored, 9),
+                (Recovery::FileSkipped, 8),
+                (Recovery::MarkupIgnored, 1),
+            ]
+        );
+        assert_eq!(warnings[3].message, "</wrong> does not match <filename>");
+    }
 }
@@ -0,0 +1,430 @@
+//! File system timelines.
+//!
+//! This module turns the timestamps of file objects into timeline events.
+//! All six DFXML timestamp kinds are covered (`mtime`, `atime`, `ctime`,
+//! `crtime`, `dtime` and `bkup_time`). By default, timestamps of one file that
+//! fall on the same instant with the same precision are merged into a single
+//! event, whose [`TimelineEvent::macb`] flags show which kinds it holds.
+//!
+//! Timestamps with a precision (such as the two-second resolution of FAT
+//! modification times) cover an interval rather than an instant, and date
+//! range filters keep an event when any part of that interval falls in range.
+//!
+//! # Example
+//!
+//! ```rust
+//! use dfxml_rs::objects::{FileObject, Timestamp, TimestampName};
+//! use dfxml_rs::timeline::Timeline;
+//!
+//! let time = Timestamp::parse_iso8601("2020-01-01T12:00:00Z").unwrap();
+//! let mut file = FileObject::with_filename("report.doc");
+//! file.set_timestamp(
+//!     TimestampName::Mtime,
+//!     Timestamp::with_name_and_time(TimestampName::Mtime, time),
+//! );
+//! file.set_timestamp(
+//!     TimestampName::Crtime,
+//!     Timestamp::with_name_and_time(TimestampName::Crtime, time),
+//! );
+//!
+//! let mut timeline = Timeline::new();
+//! timeline.add_file(file);
+//! timeline.sort();
+//!
+//! let entry = &timeline.entries()[0];
+//! assert_eq!(entry.event.macb(), "M..B");
+//! ```
+
+use crate::objects::{FileObject, Precision, TimestampName};
+use chrono::{DateTime, FixedOffset};
+use std::sync::Arc;
+
+/// All timestamp kinds, in the order they appear in events.
+pub const TIMESTAMP_NAMES: [TimestampName; 6] = [
+    TimestampName::Mtime,
+    TimestampName::Atime,
+    TimestampName::Ctime,
+    TimestampName::Crtime,
+    TimestampName::Dtime,
+    TimestampName::BkupTime,
+];
+
+/// Configuration options for timeline generation.
+#[derive(Debug, Clone, Default)]
+pub struct TimelineConfig {
+    /// Earliest time to include (inclusive)
+    pub start: Option<DateTime<FixedOffset>>,
+    /// Latest time to include (exclusive)
+    pub end: Option<DateTime<FixedOffset>>,
+    /// Timestamp kinds to include; empty means all kinds
+    pub kinds: Vec<TimestampName>,
+    /// Whether to emit one event per timestamp instead of merging
+    pub separate: bool,
+}
+
+impl TimelineConfig {
+    /// Creates a new configuration with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the earliest time to include.
+    pub fn with_start(mut self, start: DateTime<FixedOffset>) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    /// Sets the time before which events must start.
+    pub fn with_end(mut self, end: DateTime<FixedOffset>) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Adds a timestamp kind to include. Without any, all kinds are included.
+    pub fn with_kind(mut self, kind: TimestampName) -> Self {
+        if !self.kinds.contains(&kind) {
+            self.kinds.push(kind);
+        }
+        self
+    }
+
+    /// Sets whether each timestamp becomes its own event.
+    pub fn with_separate(mut self, separate: bool) -> Self {
+        self.separate = separate;
+        self
+    }
+
+    fn includes_kind(&self, kind: TimestampName) -> bool {
+        self.kinds.is_empty() || self.kinds.contains(&kind)
+    }
+
+    fn in_range(&self, event: &TimelineEvent) -> bool {
+        let after_start = self.start.is_none_or(|start| event.interval_end() > start);
+        let before_end = self.end.is_none_or(|end| event.time < end);
+        after_start && before_end
+    }
+}
+
+/// One or more timestamps of a file that share the same time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineEvent {
+    /// The time of the event
+    pub time: DateTime<FixedOffset>,
+    /// The precision of the timestamps, if recorded
+    pub prec: Option<Precision>,
+    /// The timestamp kinds at this time, in [`TIMESTAMP_NAMES`] order
+    pub kinds: Vec<TimestampName>,
+}
+
+impl TimelineEvent {
+    /// Returns whether the event includes a timestamp kind.
+    pub fn contains(&self, kind: TimestampName) -> bool {
+        self.kinds.contains(&kind)
+    }
+
+    /// Returns the MACB flags, e.g. `M.C.` for a modification and change.
+    ///
+    /// Deletion and backup times have no MACB letter; an event holding only
+    /// those kinds is `....`.
+    pub fn macb(&self) -> String {
+        [
+            (TimestampName::Mtime, 'M'),
+            (TimestampName::Atime, 'A'),
+            (TimestampName::Ctime, 'C'),
+            (TimestampName::Crtime, 'B'),
+        ]
+        .iter()
+        .map(|&(kind, c)| if self.contains(kind) { c } else { '.' })
+        .collect()
+    }
+
+    /// Returns a description of the event kinds, e.g.
+    /// `Modification Time; Deletion Time`.
+    pub fn description(&self) -> String {
+        self.kinds
+            .iter()
+            .map(|kind| describe(*kind))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// Returns the end of the interval the event covers: its time plus
+    /// its precision, or its time when no precision is recorded.
+    pub fn interval_end(&self) -> DateTime<FixedOffset> {
+        match self.prec {
+            Some(prec) => self.time + prec.duration(),
+            None => self.time,
+        }
+    }
+}
+
+/// A timeline event with the file it belongs to.
+#[derive(Debug, Clone)]
+pub struct TimelineEntry {
+    /// The event
+    pub event: TimelineEvent,
+    /// The file, shared between all of its events
+    pub file: Arc<FileObject>,
+}
+
+/// Builds timeline events for a file.
+///
+/// Events are returned in time order, and only events in the configured
+/// range and of the configured kinds are included.
+pub fn file_events(file: &FileObject, config: &TimelineConfig) -> Vec<TimelineEvent> {
+    let mut events: Vec<TimelineEvent> = Vec::new();
+
+    for kind in TIMESTAMP_NAMES {
+        if !config.includes_kind(kind) {
+            continue;
+        }
+        let Some(ts) = file.get_timestamp(kind) else {
+            continue;
+        };
+        let Some(time) = ts.time else {
+            continue;
+        };
+
+        let existing = if config.separate {
+            None
+        } else {
+            events
+                .iter_mut()
+                .find(|e| e.time == time && e.prec == ts.prec)
+        };
+        match existing {
+            Some(event) => event.kinds.push(kind),
+            None => events.push(TimelineEvent {
+                time,
+                prec: ts.prec,
+                kinds: vec![kind],
+            }),
+        }
+    }
+
+    events.retain(|e| config.in_range(e));
+    events.sort_by_key(|e| e.time);
+    events
+}
+
+/// A timeline built from many files.
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    config: TimelineConfig,
+    entries: Vec<TimelineEntry>,
+}
+
+impl Timeline {
+    /// Creates an empty timeline with default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty timeline with the specified configuration.
+    pub fn with_config(config: TimelineConfig) -> Self {
+        Self {
+            config,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds the events of a file to the timeline.
+    pub fn add_file(&mut self, file: FileObject) {
+        let events = file_events(&file, &self.config);
+        if events.is_empty() {
+            return;
+        }
+        let file = Arc::new(file);
+        self.entries
+            .extend(events.into_iter().map(|event| TimelineEntry {
+                event,
+                file: Arc::clone(&file),
+            }));
+    }
+
+    /// Sorts the entries by time, then filename, then timestamp kinds.
+    pub fn sort(&mut self) {
+        self.entries.sort_by(|a, b| {
+            a.event
+                .time
+                .cmp(&b.event.time)
+                .then_with(|| a.file.filename.cmp(&b.file.filename))
+                .then_with(|| kind_rank(&a.event).cmp(&kind_rank(&b.event)))
+        });
+    }
+
+    /// Returns the entries in their current order.
+    pub fn entries(&self) -> &[TimelineEntry] {
+        &self.entries
+    }
+
+    /// Consumes the timeline, returning its entries.
+    pub fn into_entries(self) -> Vec<TimelineEntry> {
+        self.entries
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the timeline has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Returns the log2timeline description of a timestamp kind.
+fn describe(kind: TimestampName) -> &'static str {
+    match kind {
+        TimestampName::Mtime => "Modification Time",
+        TimestampName::Atime => "Last Access Time",
+        TimestampName::Ctime => "Metadata Modification Time",
+        TimestampName::Crtime => "Creation Time",
+        TimestampName::Dtime => "Deletion Time",
+        TimestampName::BkupTime => "Backup Time",
+    }
+}
+
+/// Orders events by the positions of their kinds in [`TIMESTAMP_NAMES`].
+fn kind_rank(event: &TimelineEvent) -> Vec<usize> {
+    event
+        .kinds
+        .iter()
+        .filter_map(|k| TIMESTAMP_NAMES.iter().position(|n| n == k))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{TimeUnit, Timestamp};
+
+    fn at(s: &str) -> DateTime<FixedOffset> {
+        Timestamp::parse_iso8601(s).unwrap()
+    }
+
+    fn stamp(file: &mut FileObject, kind: TimestampName, time: &str, prec: Option<Precision>) {
+        let mut ts = Timestamp::with_name_and_time(kind, at(time));
+        ts.prec = prec;
+        file.set_timestamp(kind, ts);
+    }
+
+    #[test]
+    fn test_merge_macb() {
+        let mut file = FileObject::with_filename("a");
+        stamp(
+            &mut file,
+            TimestampName::Mtime,
+            "2020-01-01T00:00:00Z",
+            None,
+        );
+        stamp(
+            &mut file,
+            TimestampName::Ctime,
+            "2020-01-01T00:00:00Z",
+            None,
+        );
+        stamp(
+            &mut file,
+            TimestampName::Atime,
+            "2020-02-01T00:00:00Z",
+            None,
+        );
+        stamp(
+            &mut file,
+            TimestampName::Dtime,
+            "2020-03-01T00:00:00Z",
+            None,
+        );
+
+        let events = file_events(&file, &TimelineConfig::new());
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].macb(), "M.C.");
+        assert_eq!(
+            events[0].description(),
+            "Modification Time; Metadata Modification Time"
+        );
+        assert_eq!(events[1].macb(), ".A..");
+        assert_eq!(events[2].macb(), "....");
+        assert!(events[2].contains(TimestampName::Dtime));
+
+        let separate = file_events(&file, &TimelineConfig::new().with_separate(true));
+        assert_eq!(separate.len(), 4);
+    }
+
+    #[test]
+    fn test_precision_separates_and_filters() {
+        let day = Precision::new(1, TimeUnit::Day);
+        let mut file = FileObject::with_filename("fat.txt");
+        stamp(
+            &mut file,
+            TimestampName::Mtime,
+            "2020-01-01T00:00:00Z",
+            None,
+        );
+        stamp(
+            &mut file,
+            TimestampName::Atime,
+            "2020-01-01T00:00:00Z",
+            Some(day),
+        );
+
+        let events = file_events(&file, &TimelineConfig::new());
+        assert_eq!(events.len(), 2);
+
+        // The access day overlaps a noon start; the exact mtime does not
+        let config = TimelineConfig::new().with_start(at("2020-01-01T12:00:00Z"));
+        let events = file_events(&file, &config);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].macb(), ".A..");
+        assert_eq!(events[0].interval_end(), at("2020-01-02T00:00:00Z"));
+
+        let config = TimelineConfig::new().with_end(at("2020-01-01T00:00:00Z"));
+        assert!(file_events(&file, &config).is_empty());
+    }
+
+    #[test]
+    fn test_kinds_and_sorting() {
+        let mut a = FileObject::with_filename("b");
+        stamp(&mut a, TimestampName::Mtime, "2020-01-02T00:00:00Z", None);
+        stamp(
+            &mut a,
+            TimestampName::BkupTime,
+            "2020-01-01T00:00:00Z",
+            None,
+        );
+        let mut b = FileObject::with_filename("a");
+        stamp(
+            &mut b,
+            TimestampName::Mtime,
+            "2020-01-02T00:00:00+02:00",
+            None,
+        );
+
+        let mut timeline = Timeline::new();
+        timeline.add_file(a.clone());
+        timeline.add_file(b);
+        timeline.sort();
+        // Instants are compared across offsets: 00:00+02:00 is 22:00 UTC the day before
+        let names: Vec<_> = timeline
+            .entries()
+            .iter()
+            .map(|e| (e.file.filename.clone().unwrap(), e.event.macb()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("b".to_string(), "....".to_string()),
+                ("a".to_string(), "M...".to_string()),
+                ("b".to_string(), "M...".to_string()),
+            ]
+        );
+
+        let config = TimelineConfig::new().with_kind(TimestampName::BkupTime);
+        let mut timeline = Timeline::with_config(config);
+        timeline.add_file(a);
+        assert_eq!(timeline.len(), 1);
+        assert_eq!(timeline.entries()[0].event.description(), "Backup Time");
+    }
+}
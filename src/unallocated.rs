@@ -0,0 +1,471 @@
+//! Unallocated space and file slack of a volume.
+//!
+//! A [`SpaceMap`] takes a volume's extent from its `block_size` and
+//! `block_count`, and the space its files claim from their data byte runs.
+//! From these it derives two sets of byte runs:
+//!
+//! - **Unallocated space**: the blocks of the volume that no allocated file's
+//!   data runs touch.
+//! - **File slack**: the parts of the blocks allocated files claim that lie
+//!   past the end of their content, whether in runs that extend beyond
+//!   `filesize` or in the rest of the block after a run that stops short.
+//!
+//! Space is claimed in allocation units, which are the volume's blocks
+//! unless [`SpaceMap::with_allocation_unit`] sets them (to a FAT volume's
+//! clusters, for example, from [`FileSystem::allocation_unit`]), so slack
+//! runs to the end of a file's last unit. Metadata that no file object
+//! describes, such as ext inode tables, is reported as unallocated.
+//!
+//! Both sets can be written as a virtual file object with
+//! [`virtual_file`], so carving tools can read them with
+//! [`ContentReader`](crate::extract::ContentReader).
+//!
+//! [`FileSystem::allocation_unit`]: crate::filesystem::FileSystem::allocation_unit
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use dfxml_rs::reader::parse;
+//! use dfxml_rs::unallocated::{self, SpaceMap};
+//! use std::fs::File;
+//! use std::io::BufReader;
+//!
+//! let doc = parse(BufReader::new(File::open("fiwalk.dfxml").unwrap())).unwrap();
+//! for vol in doc.volumes() {
+//!     let space = SpaceMap::from_volume(vol).unwrap();
+//!     for run in space.unallocated().iter() {
+//!         println!("{:?} {:?}", run.img_offset, run.len);
+//!     }
+//!     let slack = unallocated::virtual_file(unallocated::SLACK_NAME, space.slack());
+//!     println!("{:?} bytes of slack", slack.filesize);
+//! }
+//! ```
+
+use crate::error::{Error, Result};
+use crate::objects::{
+    ByteRun, ByteRunFacet, ByteRunType, ByteRuns, FileObject, MetaType, NameType, VolumeObject,
+};
+
+/// Filename of the virtual file holding a volume's unallocated space.
+pub const UNALLOCATED_NAME: &str = "$Unalloc";
+
+/// Filename of the virtual file holding a volume's file slack.
+pub const SLACK_NAME: &str = "$Slack";
+
+/// A half-open range of file system offsets.
+type Extent = (u64, u64);
+
+/// The allocated, unallocated and slack space of one volume.
+#[derive(Debug, Clone)]
+pub struct SpaceMap {
+    block_size: u64,
+    /// File system offset of the first allocation unit
+    unit_offset: u64,
+    unit_size: u64,
+    volume_len: u64,
+    partition_offset: u64,
+    /// Blocks touched by allocated files' data runs
+    claimed: Vec<Extent>,
+    /// Bytes of allocated files' content
+    content: Vec<Extent>,
+}
+
+impl SpaceMap {
+    /// Creates an empty map for a volume's geometry, ignoring its files.
+    ///
+    /// The volume needs a nonzero `block_size` and a `block_count`.
+    pub fn new(volume: &VolumeObject) -> Result<Self> {
+        let block_size = match volume.block_size {
+            Some(size) if size > 0 => u64::from(size),
+            _ => return Err(Error::MissingField("block_size".to_string())),
+        };
+        let block_count = volume
+            .block_count
+            .ok_or_else(|| Error::MissingField("block_count".to_string()))?;
+        Ok(Self {
+            block_size,
+            unit_offset: 0,
+            unit_size: block_size,
+            volume_len: block_size.saturating_mul(block_count),
+            partition_offset: volume.partition_offset.unwrap_or(0),
+            claimed: Vec::new(),
+            content: Vec::new(),
+        })
+    }
+
+    /// Sets the allocation units that files claim space in: `size` bytes
+    /// each, from file system offset `offset`. Space before `offset` is
+    /// claimed in blocks. A zero `size` is ignored.
+    pub fn with_allocation_unit(mut self, offset: u64, size: u64) -> Self {
+        if size > 0 {
+            self.unit_offset = offset;
+            self.unit_size = size;
+        }
+        self
+    }
+
+    /// Creates a map for a volume and adds its files.
+    pub fn from_volume(volume: &VolumeObject) -> Result<Self> {
+        let mut map = Self::new(volume)?;
+        for file in volume.files() {
+            map.add_file(file);
+        }
+        Ok(map)
+    }
+
+    /// Adds the space claimed by a file.
+    ///
+    /// Files known to be unallocated are ignored, as are the virtual files
+    /// written by [`virtual_file`], so a document can be mapped again after
+    /// they are added. Runs without a location in the image (sparse fills)
+    /// and resident runs claim no space, and runs whose offsets overflow are
+    /// skipped.
+    pub fn add_file(&mut self, file: &FileObject) {
+        if file.is_allocated() == Some(false) || is_space_file(file) {
+            return;
+        }
+        let Some(runs) = file.byte_runs() else {
+            return;
+        };
+
+        let filesize = file.filesize.unwrap_or(u64::MAX);
+        let mut next_offset = 0;
+        for run in runs.iter() {
+            let file_offset = run.file_offset.unwrap_or(next_offset);
+            let len = run.len.unwrap_or(0);
+            next_offset = file_offset.saturating_add(len);
+            if len == 0 || run.run_type == Some(ByteRunType::Resident) {
+                continue;
+            }
+            let Some(start) = self.fs_offset(run) else {
+                continue;
+            };
+            let Some(claimed) = start
+                .checked_add(len)
+                .and_then(|end| self.round_out(start, end))
+            else {
+                continue;
+            };
+
+            self.claimed.push(claimed);
+            let content_len = filesize.saturating_sub(file_offset).min(len);
+            if content_len > 0 {
+                self.content.push((start, start + content_len));
+            }
+        }
+    }
+
+    /// Returns the volume's unallocated space.
+    pub fn unallocated(&self) -> ByteRuns {
+        let claimed = merge(self.claimed.clone());
+        self.to_byte_runs(&subtract(&[(0, self.volume_len)], &claimed))
+    }
+
+    /// Returns the slack of the volume's allocated files.
+    pub fn slack(&self) -> ByteRuns {
+        let claimed = merge(self.claimed.clone());
+        let content = merge(self.content.clone());
+        let slack = subtract(&claimed, &content);
+        self.to_byte_runs(&subtract(&slack, &[(self.volume_len, u64::MAX)]))
+    }
+
+    /// Resolves a run to an offset from the start of the file system,
+    /// preferring its image offset as [`ContentReader`] does.
+    ///
+    /// [`ContentReader`]: crate::extract::ContentReader
+    fn fs_offset(&self, run: &ByteRun) -> Option<u64> {
+        match (run.img_offset, run.fs_offset) {
+            (Some(img), _) => img.checked_sub(self.partition_offset),
+            (None, Some(fs)) => Some(fs),
+            (None, None) => None,
+        }
+    }
+
+    /// Widens a range to the allocation units it touches, or to blocks if
+    /// it starts before the first unit.
+    fn round_out(&self, start: u64, end: u64) -> Option<Extent> {
+        let (origin, size) = if start >= self.unit_offset {
+            (self.unit_offset, self.unit_size)
+        } else {
+            (0, self.block_size)
+        };
+        let first = origin + (start - origin) / size * size;
+        let last = (end - origin)
+            .div_ceil(size)
+            .checked_mul(size)?
+            .checked_add(origin)?;
+        Some((first, last))
+    }
+
+    fn to_byte_runs(&self, extents: &[Extent]) -> ByteRuns {
+        extents
+            .iter()
+            .filter_map(|&(start, end)| {
+                Some(ByteRun {
+                    img_offset: Some(self.partition_offset.checked_add(start)?),
+                    fs_offset: Some(start),
+                    len: Some(end - start),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+/// Describes a set of byte runs as a virtual file, such as
+/// [`UNALLOCATED_NAME`] for [`SpaceMap::unallocated`].
+///
+/// The runs are numbered with file offsets in order, so the file's content
+/// is the runs' bytes end to end.
+pub fn virtual_file(name: &str, runs: ByteRuns) -> FileObject {
+    let mut file = FileObject::with_filename(name);
+    file.name_type = Some(NameType::Virtual);
+    file.meta_type = Some(MetaType::Virtual);
+
+    let mut data = ByteRuns::with_facet(ByteRunFacet::Data);
+    let mut file_offset = 0;
+    for mut run in runs {
+        run.file_offset = Some(file_offset);
+        file_offset += run.len.unwrap_or(0);
+        data.push(run);
+    }
+    file.filesize = Some(file_offset);
+    file.data_brs = Some(data);
+    file
+}
+
+fn is_space_file(file: &FileObject) -> bool {
+    file.name_type == Some(NameType::Virtual)
+        && matches!(
+            file.filename.as_deref(),
+            Some(UNALLOCATED_NAME | SLACK_NAME)
+        )
+}
+
+/// Sorts extents and merges those that overlap or touch.
+fn merge(mut extents: Vec<Extent>) -> Vec<Extent> {
+    extents.sort_unstable();
+    let mut merged: Vec<Extent> = Vec::with_capacity(extents.len());
+    for (start, end) in extents {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Removes sorted, disjoint extents `b` from sorted, disjoint extents `a`.
+fn subtract(a: &[Extent], b: &[Extent]) -> Vec<Extent> {
+    let mut out = Vec::new();
+    let mut j = 0;
+    for &(start, end) in a {
+        let mut pos = start;
+        while j < b.len() && b[j].1 <= pos {
+            j += 1;
+        }
+        let mut k = j;
+        while pos < end && k < b.len() && b[k].0 < end {
+            if b[k].0 > pos {
+                out.push((pos, b[k].0));
+            }
+            pos = pos.max(b[k].1);
+            k += 1;
+        }
+        if pos < end {
+            out.push((pos, end));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::ContentReader;
+    use crate::filesystem::{ext::ExtFileSystem, FileSystem};
+    use std::io::{Cursor, Read};
+
+    const EXT2_IMAGE: &[u8] =
+        include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/ext2.img"));
+
+    fn run(img_offset: Option<u64>, fs_offset: Option<u64>, len: u64) -> ByteRun {
+        ByteRun {
+            img_offset,
+            fs_offset,
+            len: Some(len),
+            ..Default::default()
+        }
+    }
+
+    fn file(filesize: u64, allocated: bool, runs: Vec<ByteRun>) -> FileObject {
+        let mut file = FileObject::new();
+        file.filesize = Some(filesize);
+        file.alloc_inode = Some(allocated);
+        file.alloc_name = Some(allocated);
+        file.data_brs = Some(runs.into_iter().collect());
+        file
+    }
+
+    fn extents(runs: &ByteRuns) -> Vec<(u64, u64, u64)> {
+        runs.iter()
+            .map(|r| (r.img_offset.unwrap(), r.fs_offset.unwrap(), r.len.unwrap()))
+            .collect()
+    }
+
+    /// A 16-block volume at image offset 1000.
+    fn volume() -> VolumeObject {
+        let mut vol = VolumeObject::new();
+        vol.block_size = Some(512);
+        vol.block_count = Some(16);
+        vol.partition_offset = Some(1000);
+
+        // Run stops short of its last block
+        vol.append_file(file(700, true, vec![run(None, Some(1024), 700)]));
+        // Whole-block run past the end of the content, located by image offset
+        vol.append_file(file(100, true, vec![run(Some(5096), None, 1024)]));
+        // Deleted file
+        vol.append_file(file(512, false, vec![run(None, Some(6144), 512)]));
+        // Sparse hole followed by a full block
+        let mut hole = ByteRun::new();
+        hole.len = Some(4096);
+        hole.fill = Some(0);
+        let mut tail = run(None, Some(7680), 512);
+        tail.file_offset = Some(4096);
+        vol.append_file(file(4608, true, vec![hole, tail]));
+        // Resident data
+        let mut resident = run(None, Some(3000), 60);
+        resident.run_type = Some(ByteRunType::Resident);
+        vol.append_file(file(60, true, vec![resident]));
+        vol
+    }
+
+    #[test]
+    fn test_unallocated_and_slack() {
+        let space = SpaceMap::from_volume(&volume()).unwrap();
+        assert_eq!(
+            extents(&space.unallocated()),
+            vec![(1000, 0, 1024), (3048, 2048, 2048), (6120, 5120, 2560)]
+        );
+        assert_eq!(
+            extents(&space.slack()),
+            vec![(2724, 1724, 324), (5196, 4196, 924)]
+        );
+    }
+
+    #[test]
+    fn test_virtual_file_is_ignored_when_mapped_again() {
+        let mut vol = volume();
+        let space = SpaceMap::from_volume(&vol).unwrap();
+        let unalloc = virtual_file(UNALLOCATED_NAME, space.unallocated());
+        assert_eq!(unalloc.filename.as_deref(), Some("$Unalloc"));
+        assert_eq!(unalloc.name_type, Some(NameType::Virtual));
+        assert_eq!(unalloc.filesize, Some(1024 + 2048 + 2560));
+        let runs = unalloc.data_brs.as_ref().unwrap();
+        assert_eq!(runs.facet, Some(ByteRunFacet::Data));
+        assert_eq!(runs[1].file_offset, Some(1024));
+        assert_eq!(runs[2].file_offset, Some(3072));
+
+        vol.append_file(unalloc);
+        let again = SpaceMap::from_volume(&vol).unwrap();
+        assert_eq!(again.unallocated(), space.unallocated());
+    }
+
+    #[test]
+    fn test_allocation_units() {
+        let mut vol = VolumeObject::new();
+        vol.block_size = Some(512);
+        vol.block_count = Some(16);
+        vol.append_file(file(100, true, vec![run(None, Some(0), 100)]));
+        vol.append_file(file(700, true, vec![run(None, Some(2560), 700)]));
+        let mut space = SpaceMap::new(&vol).unwrap().with_allocation_unit(512, 2048);
+        for file in vol.files() {
+            space.add_file(file);
+        }
+        assert_eq!(
+            extents(&space.unallocated()),
+            vec![(512, 512, 2048), (4608, 4608, 3584)]
+        );
+        assert_eq!(
+            extents(&space.slack()),
+            vec![(100, 100, 412), (3260, 3260, 1348)]
+        );
+    }
+
+    #[test]
+    fn test_overflowing_runs_are_skipped() {
+        let mut vol = volume();
+        let mut past_end = run(None, Some(u64::MAX - 100), 200);
+        past_end.file_offset = Some(u64::MAX);
+        vol.append_file(file(
+            u64::MAX,
+            true,
+            vec![past_end, run(None, Some(u64::MAX - 100), 50)],
+        ));
+        let space = SpaceMap::from_volume(&vol).unwrap();
+        let expected = SpaceMap::from_volume(&volume()).unwrap();
+        assert_eq!(space.unallocated(), expected.unallocated());
+        assert_eq!(space.slack(), expected.slack());
+    }
+
+    #[test]
+    fn test_missing_geometry() {
+        let mut vol = volume();
+        vol.block_count = None;
+        assert!(matches!(
+            SpaceMap::from_volume(&vol),
+            Err(Error::MissingField(_))
+        ));
+        vol.block_count = Some(16);
+        vol.block_size = Some(0);
+        assert!(SpaceMap::new(&vol).is_err());
+    }
+
+    #[test]
+    fn test_ext2_image() {
+        let mut fs = ExtFileSystem::open(Cursor::new(EXT2_IMAGE), 0).unwrap();
+        let mut space = SpaceMap::new(&fs.volume()).unwrap();
+        let files: Vec<_> = fs.files().map(|f| f.unwrap()).collect();
+        for file in &files {
+            space.add_file(file);
+        }
+        let unallocated = space.unallocated();
+        let slack = space.slack();
+
+        // No allocated file's content lies in unallocated space or slack
+        let free: Vec<_> = unallocated
+            .iter()
+            .chain(slack.iter())
+            .map(|r| (r.fs_offset.unwrap(), r.fs_offset.unwrap() + r.len.unwrap()))
+            .collect();
+        for file in files.iter().filter(|f| f.is_allocated() == Some(true)) {
+            for r in file.data_brs.iter().flatten() {
+                let (Some(start), Some(len)) = (r.fs_offset, r.len) else {
+                    continue;
+                };
+                assert!(free.iter().all(|&(s, e)| start + len <= s || e <= start));
+            }
+        }
+
+        // hello.txt ends partway into its block; the rest is slack
+        let hello = files
+            .iter()
+            .find(|f| f.filename.as_deref() == Some("hello.txt"))
+            .unwrap();
+        let last = hello.data_brs.as_ref().unwrap().iter().last().unwrap();
+        let end = last.fs_offset.unwrap() + last.len.unwrap();
+        assert!(slack
+            .iter()
+            .any(|r| r.fs_offset == Some(end) && end + r.len.unwrap() == 1024 * (end / 1024 + 1)));
+
+        // The deleted file's blocks are unallocated, and readable as such
+        let unalloc = virtual_file(UNALLOCATED_NAME, unallocated);
+        let mut content = Vec::new();
+        ContentReader::new(Cursor::new(EXT2_IMAGE), &unalloc)
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content.len() as u64, unalloc.filesize.unwrap());
+        assert!(content.len() > 100 * 1024);
+    }
+}
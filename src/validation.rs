@@ -44,8 +44,15 @@
 //! validate_str(xml, None)?;
 //! ```
 
+#[cfg(feature = "compression")]
+use std::fs::File;
+#[cfg(feature = "compression")]
+use std::io::{self, Read, Write};
 use std::path::Path;
 
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+
 use libxml::parser::Parser;
 use libxml::schemas::{SchemaParserContext, SchemaValidationContext};
 
@@ -114,18 +121,43 @@ pub fn validate_file<P: AsRef<Path>>(xml_path: P, schema_path: Option<&str>) ->
             Error::Validation(format!("Failed to parse schema: {}", msg))
         })?;
 
-    // Parse the XML document
-    let parser = Parser::default();
-    let doc = parser
-        .parse_file(xml_path.to_string_lossy().as_ref())
-        .map_err(|e| Error::Validation(format!("Failed to parse XML document: {:?}", e)))?;
+    // libxml2 streams the file itself, decompressing gzip
+    #[cfg(feature = "compression")]
+    let decompressed = decompress_to_temp(xml_path)?;
+    #[cfg(feature = "compression")]
+    let xml_path = decompressed.as_ref().map_or(xml_path, |xml| xml.path());
+    validate_path(&mut validation_context, xml_path)
+}
 
-    // Validate
-    validation_context
-        .validate_document(&doc)
-        .map_err(|e| Error::Validation(format!("Validation failed: {:?}", e)))?;
+/// Decompresses a file that libxml2 cannot read itself, i.e. one that is
+/// neither plain nor gzip, to a temporary file.
+#[cfg(feature = "compression")]
+fn decompress_to_temp(path: &Path) -> Result<Option<tempfile::NamedTempFile>> {
+    let mut magic = Vec::new();
+    File::open(path)?.take(8).read_to_end(&mut magic)?;
+    match Compression::detect(&magic) {
+        Compression::None | Compression::Gzip => Ok(None),
+        _ => {
+            let mut xml = tempfile::NamedTempFile::new()?;
+            io::copy(&mut crate::compression::open(path)?, &mut xml)?;
+            xml.flush()?;
+            Ok(Some(xml))
+        }
+    }
+}
 
-    Ok(())
+/// Validates a file as libxml2 reads it, without loading the whole document.
+fn validate_path(validation_context: &mut SchemaValidationContext, path: &Path) -> Result<()> {
+    validation_context
+        .validate_file(path.to_string_lossy().as_ref())
+        .map_err(|errors| {
+            let msg = errors
+                .iter()
+                .map(|e| e.message.clone().unwrap_or_default())
+                .collect::<Vec<_>>()
+                .join("; ");
+            Error::Validation(format!("Validation failed: {}", msg))
+        })
 }
 
 /// Validates a DFXML string against the DFXML XML Schema.
@@ -0,0 +1,565 @@
+//! Hash verification of file objects against a source image.
+//!
+//! The verifier reads each file's content from a raw image through its data
+//! byte runs (see [`crate::extract`]) and recomputes every algorithm recorded
+//! in [`FileObject::hashes`]. Runs that carry their own hashes
+//! ([`ByteRun::hashes`]) are checked individually against the bytes the run
+//! covers in the image.
+//!
+//! MD6 digests cannot be recomputed and are reported as unsupported.
+//!
+//! This module requires the `hashing` feature.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use dfxml_rs::verify::{verify_dfxml, VerifyStatus};
+//! use std::fs::File;
+//! use std::io::BufReader;
+//!
+//! let input = BufReader::new(File::open("fiwalk.dfxml").unwrap());
+//! let mut image = File::open("disk.raw").unwrap();
+//! let summary = verify_dfxml(input, &mut image, |result| {
+//!     if result.status() == VerifyStatus::Mismatch {
+//!         println!("MISMATCH: {:?}", result.filename);
+//!     }
+//! })
+//! .unwrap();
+//! assert!(summary.is_clean());
+//! ```
+
+use crate::error::Result;
+use crate::extract::ContentReader;
+use crate::objects::{ByteRun, FileObject, HashType, Hashes};
+use crate::reader::{DFXMLReader, Event};
+use digest::Digest;
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+
+/// Size of the buffer used when hashing content.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// A single digest computation.
+enum Hasher {
+    Md5(md5::Md5),
+    Sha1(sha1::Sha1),
+    Sha224(sha2::Sha224),
+    Sha256(sha2::Sha256),
+    Sha384(sha2::Sha384),
+    Sha512(sha2::Sha512),
+}
+
+impl Hasher {
+    fn new(hash_type: HashType) -> Option<Self> {
+        match hash_type {
+            HashType::Md5 => Some(Hasher::Md5(md5::Md5::new())),
+            HashType::Sha1 => Some(Hasher::Sha1(sha1::Sha1::new())),
+            HashType::Sha224 => Some(Hasher::Sha224(sha2::Sha224::new())),
+            HashType::Sha256 => Some(Hasher::Sha256(sha2::Sha256::new())),
+            HashType::Sha384 => Some(Hasher::Sha384(sha2::Sha384::new())),
+            HashType::Sha512 => Some(Hasher::Sha512(sha2::Sha512::new())),
+            HashType::Md6 => None,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(h) => h.update(data),
+            Hasher::Sha1(h) => h.update(data),
+            Hasher::Sha224(h) => h.update(data),
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Sha384(h) => h.update(data),
+            Hasher::Sha512(h) => h.update(data),
+        }
+    }
+
+    fn finalize_hex(self) -> String {
+        match self {
+            Hasher::Md5(h) => format!("{:x}", h.finalize()),
+            Hasher::Sha1(h) => format!("{:x}", h.finalize()),
+            Hasher::Sha224(h) => format!("{:x}", h.finalize()),
+            Hasher::Sha256(h) => format!("{:x}", h.finalize()),
+            Hasher::Sha384(h) => format!("{:x}", h.finalize()),
+            Hasher::Sha512(h) => format!("{:x}", h.finalize()),
+        }
+    }
+}
+
+/// Computes several digests over the same data in one pass.
+pub struct MultiHasher {
+    hashers: Vec<(HashType, Hasher)>,
+}
+
+impl MultiHasher {
+    /// Creates a hasher for the given algorithms.
+    ///
+    /// Algorithms that cannot be computed (see [`MultiHasher::supports`])
+    /// and duplicates are ignored.
+    pub fn new(hash_types: impl IntoIterator<Item = HashType>) -> Self {
+        let mut hashers: Vec<(HashType, Hasher)> = Vec::new();
+        for hash_type in hash_types {
+            if hashers.iter().any(|(t, _)| *t == hash_type) {
+                continue;
+            }
+            if let Some(hasher) = Hasher::new(hash_type) {
+                hashers.push((hash_type, hasher));
+            }
+        }
+        Self { hashers }
+    }
+
+    /// Returns true if the algorithm can be computed.
+    pub fn supports(hash_type: HashType) -> bool {
+        hash_type != HashType::Md6
+    }
+
+    /// Feeds data to every digest.
+    pub fn update(&mut self, data: &[u8]) {
+        for (_, hasher) in &mut self.hashers {
+            hasher.update(data);
+        }
+    }
+
+    /// Feeds everything from a reader to every digest.
+    ///
+    /// Returns the number of bytes read.
+    pub fn update_reader<R: Read>(&mut self, mut reader: R) -> io::Result<u64> {
+        let mut buf = vec![0u8; BUFFER_SIZE];
+        let mut total = 0;
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => return Ok(total),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            self.update(&buf[..n]);
+            total += n as u64;
+        }
+    }
+
+    /// Finishes every digest, returning them as lowercase hex.
+    pub fn finalize(self) -> Hashes {
+        let mut hashes = Hashes::new();
+        for (hash_type, hasher) in self.hashers {
+            hashes.set(hash_type, hasher.finalize_hex());
+        }
+        hashes
+    }
+}
+
+/// Result of comparing one recorded hash with the recomputed one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HashStatus {
+    /// The recomputed digest equals the recorded one
+    Match,
+    /// The recomputed digest differs; holds the recomputed value
+    Mismatch(String),
+    /// The algorithm cannot be recomputed
+    Unsupported,
+}
+
+/// One recorded hash and how it compared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashCheck {
+    /// Hash algorithm
+    pub hash_type: HashType,
+    /// Value recorded in the DFXML
+    pub expected: String,
+    /// Comparison result
+    pub status: HashStatus,
+}
+
+/// Verification result for one byte run carrying its own hashes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunVerification {
+    /// Position of the run in the file's data byte runs
+    pub index: usize,
+    /// Offset of the run within the file, if recorded
+    pub file_offset: Option<u64>,
+    /// Checks of the run's hashes (empty when the run is unreadable)
+    pub hashes: Vec<HashCheck>,
+    /// Why the run could not be read
+    pub error: Option<String>,
+}
+
+/// Verification result for one file object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileVerification {
+    /// File name
+    pub filename: Option<String>,
+    /// Inode number
+    pub inode: Option<u64>,
+    /// Checks of the file's hashes (empty when the content is unreadable)
+    pub hashes: Vec<HashCheck>,
+    /// Results for byte runs that carry hashes
+    pub runs: Vec<RunVerification>,
+    /// Why the file content could not be read
+    pub error: Option<String>,
+}
+
+/// Overall status of a file's verification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VerifyStatus {
+    /// At least one hash was recomputed and every recomputed hash matched
+    Verified,
+    /// At least one recomputed hash differs
+    Mismatch,
+    /// The content or a hashed run could not be read
+    Unreadable,
+    /// No hash could be checked
+    NoHashes,
+}
+
+impl VerifyStatus {
+    /// Returns the status name used in reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VerifyStatus::Verified => "verified",
+            VerifyStatus::Mismatch => "mismatch",
+            VerifyStatus::Unreadable => "unreadable",
+            VerifyStatus::NoHashes => "no_hashes",
+        }
+    }
+}
+
+impl std::fmt::Display for VerifyStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FileVerification {
+    /// Iterates over every hash check, file-level first.
+    pub fn checks(&self) -> impl Iterator<Item = &HashCheck> {
+        self.hashes
+            .iter()
+            .chain(self.runs.iter().flat_map(|r| r.hashes.iter()))
+    }
+
+    /// Returns the overall status.
+    ///
+    /// A mismatch takes precedence over unreadable content, so that a file
+    /// with one bad run and one altered run is reported as altered.
+    pub fn status(&self) -> VerifyStatus {
+        if self
+            .checks()
+            .any(|c| matches!(c.status, HashStatus::Mismatch(_)))
+        {
+            VerifyStatus::Mismatch
+        } else if self.error.is_some() || self.runs.iter().any(|r| r.error.is_some()) {
+            VerifyStatus::Unreadable
+        } else if self.checks().any(|c| c.status == HashStatus::Match) {
+            VerifyStatus::Verified
+        } else {
+            VerifyStatus::NoHashes
+        }
+    }
+}
+
+/// Counts of verification results.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifySummary {
+    /// Files examined
+    pub files: u64,
+    /// Files whose hashes all matched
+    pub verified: u64,
+    /// Files with at least one mismatching hash
+    pub mismatched: u64,
+    /// Files whose content or runs could not be read
+    pub unreadable: u64,
+    /// Files without checkable hashes
+    pub no_hashes: u64,
+}
+
+impl VerifySummary {
+    /// Creates an empty summary.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts one file's result.
+    pub fn record(&mut self, result: &FileVerification) {
+        self.files += 1;
+        match result.status() {
+            VerifyStatus::Verified => self.verified += 1,
+            VerifyStatus::Mismatch => self.mismatched += 1,
+            VerifyStatus::Unreadable => self.unreadable += 1,
+            VerifyStatus::NoHashes => self.no_hashes += 1,
+        }
+    }
+
+    /// Returns true if nothing mismatched and everything was readable.
+    pub fn is_clean(&self) -> bool {
+        self.mismatched == 0 && self.unreadable == 0
+    }
+}
+
+/// Verifies one file object against an image.
+///
+/// File system offsets in the file's byte runs are resolved against
+/// `partition_offset`. Read errors are recorded in the result rather than
+/// returned.
+pub fn verify_file<R: Read + Seek>(
+    image: &mut R,
+    file: &FileObject,
+    partition_offset: u64,
+) -> FileVerification {
+    let mut result = FileVerification {
+        filename: file.filename.clone(),
+        inode: file.inode,
+        hashes: Vec::new(),
+        runs: Vec::new(),
+        error: None,
+    };
+
+    if file.hashes.has_any() {
+        match hash_content(image, file, partition_offset) {
+            Ok(computed) => result.hashes = compare(&file.hashes, &computed),
+            Err(e) => result.error = Some(e.to_string()),
+        }
+    }
+
+    if let Some(runs) = file.byte_runs() {
+        for (index, run) in runs.iter().enumerate() {
+            if !run.has_hashes() {
+                continue;
+            }
+            let mut run_result = RunVerification {
+                index,
+                file_offset: run.file_offset,
+                hashes: Vec::new(),
+                error: None,
+            };
+            match hash_run(image, run, partition_offset) {
+                Ok(computed) => run_result.hashes = compare(&run.hashes, &computed),
+                Err(e) => run_result.error = Some(e.to_string()),
+            }
+            result.runs.push(run_result);
+        }
+    }
+
+    result
+}
+
+/// Streams a DFXML document and verifies every file object against an
+/// image, calling `report` with each result.
+///
+/// Each file's partition offset is taken from its enclosing volume.
+pub fn verify_dfxml<R, I, F>(input: R, image: &mut I, mut report: F) -> Result<VerifySummary>
+where
+    R: BufRead,
+    I: Read + Seek,
+    F: FnMut(&FileVerification),
+{
+    let mut reader = DFXMLReader::from_reader(input);
+    let mut summary = VerifySummary::new();
+    while let Some(event) = reader.next() {
+        if let Event::FileObject(file) = event? {
+            let offset = reader
+                .current_volume()
+                .and_then(|v| v.partition_offset)
+                .unwrap_or(0);
+            let result = verify_file(image, &file, offset);
+            summary.record(&result);
+            report(&result);
+        }
+    }
+    Ok(summary)
+}
+
+/// Recomputes the recorded hashes of a file's content.
+fn hash_content<R: Read + Seek>(
+    image: &mut R,
+    file: &FileObject,
+    partition_offset: u64,
+) -> Result<Hashes> {
+    let content = ContentReader::with_partition_offset(image, file, partition_offset)?;
+    let mut hasher = MultiHasher::new(file.hashes.iter().map(|(t, _)| t));
+    hasher.update_reader(content)?;
+    Ok(hasher.finalize())
+}
+
+/// Recomputes the recorded hashes of the bytes a run covers.
+fn hash_run<R: Read + Seek>(image: &mut R, run: &ByteRun, partition_offset: u64) -> Result<Hashes> {
+    let len = run.len.ok_or_else(|| io_error("run has no length"))?;
+    let mut hasher = MultiHasher::new(run.hashes.iter().map(|(t, _)| t));
+
+    let read = if let Some(fill) = run.fill {
+        hasher.update_reader(io::repeat(fill).take(len))?
+    } else {
+        let offset = match (run.img_offset, run.fs_offset) {
+            (Some(img), _) => img,
+            (None, Some(fs)) => partition_offset
+                .checked_add(fs)
+                .ok_or_else(|| io_error("run's file system offset overflows"))?,
+            (None, None) => return Err(io_error("run has no image or file system offset").into()),
+        };
+        image.seek(SeekFrom::Start(offset))?;
+        hasher.update_reader(image.by_ref().take(len))?
+    };
+    if read < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("image ends {} bytes into a {} byte run", read, len),
+        )
+        .into());
+    }
+    Ok(hasher.finalize())
+}
+
+/// Compares recorded hashes with recomputed ones.
+fn compare(expected: &Hashes, computed: &Hashes) -> Vec<HashCheck> {
+    expected
+        .iter()
+        .map(|(hash_type, value)| {
+            let status = match computed.get(hash_type) {
+                None => HashStatus::Unsupported,
+                Some(actual) if actual.eq_ignore_ascii_case(value.trim()) => HashStatus::Match,
+                Some(actual) => HashStatus::Mismatch(actual.to_string()),
+            };
+            HashCheck {
+                hash_type,
+                expected: value.to_string(),
+                status,
+            }
+        })
+        .collect()
+}
+
+fn io_error(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::ByteRuns;
+    use std::io::Cursor;
+
+    const HELLO_MD5: &str = "5d41402abc4b2a76b9719d911017c592";
+    const HELLO_SHA1: &str = "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d";
+
+    fn image() -> Cursor<Vec<u8>> {
+        let mut data = vec![0u8; 1024];
+        data[100..105].copy_from_slice(b"hello");
+        Cursor::new(data)
+    }
+
+    fn hello_file() -> FileObject {
+        let mut run = ByteRun::with_img_offset(100, 5);
+        run.file_offset = Some(0);
+        run.hashes.set(HashType::Md5, HELLO_MD5.to_string());
+        let mut brs = ByteRuns::new();
+        brs.push(run);
+
+        let mut file = FileObject::with_filename("hello.txt");
+        file.filesize = Some(5);
+        file.hashes.set(HashType::Md5, HELLO_MD5.to_string());
+        file.hashes.set(HashType::Sha1, HELLO_SHA1.to_uppercase());
+        file.set_byte_runs(brs);
+        file
+    }
+
+    #[test]
+    fn test_verified_and_mismatch() {
+        let file = hello_file();
+        let result = verify_file(&mut image(), &file, 0);
+        assert_eq!(result.status(), VerifyStatus::Verified);
+        assert_eq!(result.hashes.len(), 2);
+        assert_eq!(result.runs.len(), 1);
+        assert!(result.checks().all(|c| c.status == HashStatus::Match));
+
+        let mut altered = image();
+        altered.get_mut()[101] = b'a';
+        let result = verify_file(&mut altered, &file, 0);
+        assert_eq!(result.status(), VerifyStatus::Mismatch);
+        assert!(matches!(result.hashes[0].status, HashStatus::Mismatch(_)));
+    }
+
+    #[test]
+    fn test_unreadable_and_unsupported() {
+        let mut file = hello_file();
+        for run in file.data_brs.as_mut().unwrap().iter_mut() {
+            run.img_offset = Some(1022);
+        }
+        let result = verify_file(&mut image(), &file, 0);
+        assert_eq!(result.status(), VerifyStatus::Unreadable);
+        assert!(result.error.is_some());
+        assert!(result.runs[0].error.is_some());
+
+        let mut md6_only = FileObject::with_filename("x");
+        md6_only.hashes.set(HashType::Md6, "00".to_string());
+        let result = verify_file(&mut image(), &md6_only, 0);
+        assert_eq!(result.hashes[0].status, HashStatus::Unsupported);
+        assert_eq!(result.status(), VerifyStatus::NoHashes);
+    }
+
+    #[test]
+    fn test_verify_dfxml_uses_partition_offset() {
+        let xml = format!(
+            r#"<?xml version="1.0"?>
+<dfxml version="1.0">
+  <volume offset="64">
+    <partition_offset>64</partition_offset>
+    <fileobject>
+      <filename>hello.txt</filename>
+      <filesize>5</filesize>
+      <byte_runs><byte_run file_offset="0" fs_offset="36" len="5"/></byte_runs>
+      <hashdigest type="md5">{}</hashdigest>
+    </fileobject>
+    <fileobject>
+      <filename>empty</filename>
+    </fileobject>
+  </volume>
+</dfxml>"#,
+            HELLO_MD5
+        );
+        let mut statuses = Vec::new();
+        let summary = verify_dfxml(Cursor::new(xml), &mut image(), |r| {
+            statuses.push(r.status())
+        })
+        .unwrap();
+        assert_eq!(statuses, [VerifyStatus::Verified, VerifyStatus::NoHashes]);
+        assert_eq!(summary.files, 2);
+        assert_eq!(summary.verified, 1);
+        assert!(summary.is_clean());
+    }
+
+    #[test]
+    fn test_overflowing_runs_are_unreadable() {
+        let xml = format!(
+            r#"<?xml version="1.0"?>
+<dfxml version="1.0">
+  <volume offset="64">
+    <partition_offset>64</partition_offset>
+    <fileobject>
+      <filename>past_end</filename>
+      <byte_runs>
+        <byte_run file_offset="18446744073709551615" img_offset="100" len="10"/>
+        <byte_run fs_offset="18446744073709551610" len="5">
+          <hashdigest type="md5">{0}</hashdigest>
+        </byte_run>
+      </byte_runs>
+      <hashdigest type="md5">{0}</hashdigest>
+    </fileobject>
+    <fileobject>
+      <filename>hello.txt</filename>
+      <filesize>5</filesize>
+      <byte_runs><byte_run file_offset="0" fs_offset="36" len="5"/></byte_runs>
+      <hashdigest type="md5">{0}</hashdigest>
+    </fileobject>
+  </volume>
+</dfxml>"#,
+            HELLO_MD5
+        );
+        let mut results = Vec::new();
+        let summary =
+            verify_dfxml(Cursor::new(xml), &mut image(), |r| results.push(r.clone())).unwrap();
+        assert_eq!(results[0].status(), VerifyStatus::Unreadable);
+        assert!(results[0].error.is_some());
+        assert!(results[0].runs[0].error.is_some());
+        assert_eq!(results[1].status(), VerifyStatus::Verified);
+        assert_eq!(summary.unreadable, 1);
+        assert_eq!(summary.verified, 1);
+    }
+}
@@ -24,15 +24,41 @@
 //! let xml = writer.write_to_string(&doc).unwrap();
 //! println!("{}", xml);
 //! ```
+//!
+//! # Streaming
+//!
+//! [`DFXMLStreamWriter`] writes a document incrementally, so file objects
+//! never need to be held in memory:
+//!
+//! ```rust
+//! use dfxml_rs::objects::{DFXMLObject, FileObject, VolumeObject};
+//! use dfxml_rs::writer::DFXMLStreamWriter;
+//!
+//! let mut doc = DFXMLObject::new();
+//! doc.program = Some("my-tool".to_string());
+//!
+//! let mut writer = DFXMLStreamWriter::new(Vec::new());
+//! writer.start_document(&doc).unwrap();
+//! writer.start_volume(&VolumeObject::with_ftype("ntfs")).unwrap();
+//! for name in ["a.txt", "b.txt"] {
+//!     writer.write_file(&FileObject::with_filename(name)).unwrap();
+//! }
+//! writer.end_volume().unwrap();
+//! let output = writer.finish().unwrap();
+//! assert!(String::from_utf8(output).unwrap().ends_with("</dfxml>"));
+//! ```
 
-use crate::error::Result;
+#[cfg(feature = "compression")]
+use crate::compression::{Compression, Encoder};
+use crate::error::{Error, Result};
 use crate::objects::{
-    ByteRun, ByteRunFacet, ByteRuns, DFXMLObject, DiskImageObject, FileObject, HashType,
-    LibraryObject, PartitionObject, PartitionSystemObject, Timestamp, VolumeObject, XMLNS_DC,
-    XMLNS_DFXML,
+    ByteRun, ByteRunFacet, ByteRuns, DFXMLObject, DiskImageObject, ExternalElement, Externals,
+    FileObject, HashType, LibraryObject, PartitionObject, PartitionSystemObject, Timestamp,
+    VolumeObject, XMLNS_DC, XMLNS_DFXML,
 };
 use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::Writer;
+use std::collections::{HashMap, HashSet};
 use std::io::Write;
 
 /// Configuration options for the DFXML writer.
@@ -44,14 +70,24 @@ pub struct WriterConfig {
     pub indent_string: String,
     /// Whether to include the XML declaration
     pub xml_declaration: bool,
+    /// Compression of the output (`()` without the `compression` feature)
+    pub compression: OutputCompression,
 }
 
+/// Compression of the output, if the `compression` feature is enabled.
+#[cfg(feature = "compression")]
+pub type OutputCompression = Compression;
+/// Compression of the output, if the `compression` feature is enabled.
+#[cfg(not(feature = "compression"))]
+pub type OutputCompression = ();
+
 impl Default for WriterConfig {
     fn default() -> Self {
         Self {
             indent: true,
             indent_string: "  ".to_string(),
             xml_declaration: true,
+            compression: OutputCompression::default(),
         }
     }
 }
@@ -68,6 +104,7 @@ impl WriterConfig {
             indent: false,
             indent_string: String::new(),
             xml_declaration: true,
+            compression: OutputCompression::default(),
         }
     }
 
@@ -82,6 +119,92 @@ impl WriterConfig {
         self.indent_string = s.into();
         self
     }
+
+    /// Sets the compression of the output (requires `compression` feature).
+    ///
+    /// Strings from [`DFXMLWriter::write_to_string`] are never compressed.
+    #[cfg(feature = "compression")]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Wraps an output so it is compressed as configured.
+    fn output<W: Write>(&self, writer: W) -> Result<Output<W>> {
+        #[cfg(feature = "compression")]
+        return Encoder::new(writer, self.compression);
+        #[cfg(not(feature = "compression"))]
+        Ok(writer)
+    }
+}
+
+/// An output being written, compressed if configured.
+#[cfg(feature = "compression")]
+type Output<W> = Encoder<W>;
+#[cfg(not(feature = "compression"))]
+type Output<W> = W;
+
+/// Ends the compressed stream, if any, and returns the underlying writer.
+fn finish_output<W: Write>(output: Output<W>) -> Result<W> {
+    #[cfg(feature = "compression")]
+    return output.finish();
+    #[cfg(not(feature = "compression"))]
+    Ok(output)
+}
+
+/// Namespace prefixes declared on the root element of the document being written.
+struct Namespaces {
+    /// Namespace URI -> prefix
+    prefixes: HashMap<String, String>,
+}
+
+impl Namespaces {
+    fn from_doc(doc: &DFXMLObject) -> Self {
+        let mut prefixes = HashMap::new();
+        prefixes.insert(XMLNS_DC.to_string(), "dc".to_string());
+        for (prefix, uri) in doc.namespaces() {
+            if !prefix.is_empty() && uri != XMLNS_DFXML {
+                prefixes
+                    .entry(uri.to_string())
+                    .or_insert_with(|| prefix.to_string());
+            }
+        }
+        Self { prefixes }
+    }
+
+    /// Returns the prefixed name for `local` in namespace `uri`.
+    ///
+    /// Namespaces not declared on the root element are given a generated
+    /// prefix, which is added to `local_decls` so it can be declared on the
+    /// element being written.
+    fn qualify(
+        &self,
+        uri: Option<&str>,
+        local: &str,
+        local_decls: &mut Vec<(String, String)>,
+    ) -> String {
+        let uri = match uri {
+            Some(uri) if uri != XMLNS_DFXML => uri,
+            _ => return local.to_string(),
+        };
+        if let Some(prefix) = self.prefixes.get(uri) {
+            return format!("{}:{}", prefix, local);
+        }
+        if let Some((prefix, _)) = local_decls.iter().find(|(_, u)| u == uri) {
+            return format!("{}:{}", prefix, local);
+        }
+        let mut n = local_decls.len();
+        let prefix = loop {
+            let candidate = format!("ns{}", n);
+            if !self.prefixes.values().any(|p| *p == candidate) {
+                break candidate;
+            }
+            n += 1;
+        };
+        let name = format!("{}:{}", prefix, local);
+        local_decls.push((prefix, uri.to_string()));
+        name
+    }
 }
 
 /// DFXML XML writer.
@@ -107,18 +230,73 @@ impl DFXMLWriter {
     /// Writes a DFXMLObject to a string.
     pub fn write_to_string(&self, doc: &DFXMLObject) -> Result<String> {
         let mut buffer = Vec::new();
-        self.write(doc, &mut buffer)?;
+        self.write_xml(doc, &mut buffer)?;
         Ok(String::from_utf8(buffer).expect("Generated XML should be valid UTF-8"))
     }
 
-    /// Writes a DFXMLObject to any Write implementation.
+    /// Writes a DFXMLObject to any Write implementation, compressed if
+    /// configured.
     pub fn write<W: Write>(&self, doc: &DFXMLObject, writer: W) -> Result<()> {
-        let mut xml_writer = if self.config.indent {
+        let mut output = self.config.output(writer)?;
+        self.write_xml(doc, &mut output)?;
+        finish_output(output)?;
+        Ok(())
+    }
+
+    /// Writes a DFXMLObject as uncompressed XML.
+    fn write_xml<W: Write>(&self, doc: &DFXMLObject, writer: W) -> Result<()> {
+        let mut xml_writer = self.xml_writer(writer);
+        let namespaces = Namespaces::from_doc(doc);
+        self.write_document_start(&mut xml_writer, &namespaces, doc)?;
+
+        // Write disk images
+        for di in doc.disk_images() {
+            self.write_disk_image(&mut xml_writer, &namespaces, di)?;
+        }
+
+        // Write partition systems
+        for ps in doc.partition_systems() {
+            self.write_partition_system(&mut xml_writer, &namespaces, ps)?;
+        }
+
+        // Write partitions
+        for p in doc.partitions() {
+            self.write_partition(&mut xml_writer, &namespaces, p)?;
+        }
+
+        // Write volumes
+        for vol in doc.volumes() {
+            self.write_volume(&mut xml_writer, &namespaces, vol)?;
+        }
+
+        // Write files directly attached to document
+        for file in doc.files() {
+            self.write_file(&mut xml_writer, &namespaces, file)?;
+        }
+
+        // Close dfxml
+        xml_writer.write_event(Event::End(BytesEnd::new("dfxml")))?;
+
+        Ok(())
+    }
+
+    /// Wraps an output in a quick-xml writer configured for this writer.
+    fn xml_writer<W: Write>(&self, writer: W) -> Writer<W> {
+        if self.config.indent {
             Writer::new_with_indent(writer, b' ', self.config.indent_string.len())
         } else {
             Writer::new(writer)
-        };
+        }
+    }
 
+    /// Writes the XML declaration, the opening `<dfxml>` tag and the document
+    /// metadata (everything before the first child object).
+    fn write_document_start<W: Write>(
+        &self,
+        xml_writer: &mut Writer<W>,
+        namespaces: &Namespaces,
+        doc: &DFXMLObject,
+    ) -> Result<()> {
         // XML declaration
         if self.config.xml_declaration {
             xml_writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
@@ -132,44 +310,38 @@ impl DFXMLWriter {
         dfxml_start.push_attribute(("version", doc.version.as_str()));
         dfxml_start.push_attribute(("xmlns", XMLNS_DFXML));
         dfxml_start.push_attribute(("xmlns:dc", XMLNS_DC));
+        let mut prefixes: Vec<_> = doc
+            .namespaces()
+            .filter(|(prefix, _)| !prefix.is_empty() && *prefix != "dc")
+            .collect();
+        prefixes.sort_unstable();
+        for (prefix, uri) in prefixes {
+            dfxml_start.push_attribute((format!("xmlns:{}", prefix).as_str(), uri));
+        }
         xml_writer.write_event(Event::Start(dfxml_start))?;
 
         // Write metadata/creator section
-        self.write_creator(&mut xml_writer, doc)?;
+        self.write_creator(xml_writer, doc)?;
 
         // Write source images
         for source in &doc.sources {
-            self.write_simple_element(&mut xml_writer, "image_filename", source)?;
-        }
-
-        // Write disk images
-        for di in doc.disk_images() {
-            self.write_disk_image(&mut xml_writer, di)?;
-        }
-
-        // Write partition systems
-        for ps in doc.partition_systems() {
-            self.write_partition_system(&mut xml_writer, ps)?;
-        }
-
-        // Write partitions
-        for p in doc.partitions() {
-            self.write_partition(&mut xml_writer, p)?;
+            self.write_simple_element(xml_writer, "image_filename", source)?;
         }
 
-        // Write volumes
-        for vol in doc.volumes() {
-            self.write_volume(&mut xml_writer, vol)?;
-        }
+        self.write_externals(xml_writer, namespaces, &doc.externals)
+    }
 
-        // Write files directly attached to document
-        for file in doc.files() {
-            self.write_file(&mut xml_writer, file)?;
+    /// Writes the closing tag of a container, preceded by its error message.
+    fn write_container_end<W: Write>(
+        &self,
+        writer: &mut Writer<W>,
+        name: &str,
+        error: Option<&str>,
+    ) -> Result<()> {
+        if let Some(error) = error {
+            self.write_simple_element(writer, "error", error)?;
         }
-
-        // Close dfxml
-        xml_writer.write_event(Event::End(BytesEnd::new("dfxml")))?;
-
+        writer.write_event(Event::End(BytesEnd::new(name)))?;
         Ok(())
     }
 
@@ -232,6 +404,39 @@ impl DFXMLWriter {
     fn write_disk_image<W: Write>(
         &self,
         writer: &mut Writer<W>,
+        ns: &Namespaces,
+        di: &DiskImageObject,
+    ) -> Result<()> {
+        self.write_disk_image_start(writer, ns, di)?;
+
+        // Write child partition systems
+        for ps in di.partition_systems() {
+            self.write_partition_system(writer, ns, ps)?;
+        }
+
+        // Write child partitions
+        for p in di.partitions() {
+            self.write_partition(writer, ns, p)?;
+        }
+
+        // Write child volumes
+        for vol in di.volumes() {
+            self.write_volume(writer, ns, vol)?;
+        }
+
+        // Write child files
+        for file in di.files() {
+            self.write_file(writer, ns, file)?;
+        }
+
+        self.write_container_end(writer, "diskimageobject", di.error.as_deref())
+    }
+
+    /// Writes a disk image's opening tag and properties.
+    fn write_disk_image_start<W: Write>(
+        &self,
+        writer: &mut Writer<W>,
+        ns: &Namespaces,
         di: &DiskImageObject,
     ) -> Result<()> {
         writer.write_event(Event::Start(BytesStart::new("diskimageobject")))?;
@@ -254,38 +459,36 @@ impl DFXMLWriter {
             self.write_byte_runs(writer, brs)?;
         }
 
-        // Write child partition systems
-        for ps in di.partition_systems() {
-            self.write_partition_system(writer, ps)?;
-        }
+        self.write_externals(writer, ns, &di.externals)
+    }
 
-        // Write child partitions
-        for p in di.partitions() {
-            self.write_partition(writer, p)?;
-        }
+    /// Writes a partition system object.
+    fn write_partition_system<W: Write>(
+        &self,
+        writer: &mut Writer<W>,
+        ns: &Namespaces,
+        ps: &PartitionSystemObject,
+    ) -> Result<()> {
+        self.write_partition_system_start(writer, ns, ps)?;
 
-        // Write child volumes
-        for vol in di.volumes() {
-            self.write_volume(writer, vol)?;
+        // Write child partitions
+        for p in ps.partitions() {
+            self.write_partition(writer, ns, p)?;
         }
 
         // Write child files
-        for file in di.files() {
-            self.write_file(writer, file)?;
-        }
-
-        if let Some(ref error) = di.error {
-            self.write_simple_element(writer, "error", error)?;
+        for file in ps.files() {
+            self.write_file(writer, ns, file)?;
         }
 
-        writer.write_event(Event::End(BytesEnd::new("diskimageobject")))?;
-        Ok(())
+        self.write_container_end(writer, "partitionsystemobject", ps.error.as_deref())
     }
 
-    /// Writes a partition system object.
-    fn write_partition_system<W: Write>(
+    /// Writes a partition system's opening tag and properties.
+    fn write_partition_system_start<W: Write>(
         &self,
         writer: &mut Writer<W>,
+        ns: &Namespaces,
         ps: &PartitionSystemObject,
     ) -> Result<()> {
         writer.write_event(Event::Start(BytesStart::new("partitionsystemobject")))?;
@@ -307,26 +510,48 @@ impl DFXMLWriter {
             self.write_byte_runs(writer, brs)?;
         }
 
-        // Write child partitions
-        for p in ps.partitions() {
-            self.write_partition(writer, p)?;
+        self.write_externals(writer, ns, &ps.externals)
+    }
+
+    /// Writes a partition object.
+    fn write_partition<W: Write>(
+        &self,
+        writer: &mut Writer<W>,
+        ns: &Namespaces,
+        p: &PartitionObject,
+    ) -> Result<()> {
+        self.write_partition_start(writer, ns, p)?;
+
+        // Write child partition systems
+        for ps in p.partition_systems() {
+            self.write_partition_system(writer, ns, ps)?;
         }
 
-        // Write child files
-        for file in ps.files() {
-            self.write_file(writer, file)?;
+        // Write child volumes
+        for vol in p.volumes() {
+            self.write_volume(writer, ns, vol)?;
         }
 
-        if let Some(ref error) = ps.error {
-            self.write_simple_element(writer, "error", error)?;
+        // Write child partitions
+        for sub_p in p.partitions() {
+            self.write_partition(writer, ns, sub_p)?;
         }
 
-        writer.write_event(Event::End(BytesEnd::new("partitionsystemobject")))?;
-        Ok(())
+        // Write child files
+        for file in p.files() {
+            self.write_file(writer, ns, file)?;
+        }
+
+        self.write_container_end(writer, "partitionobject", None)
     }
 
-    /// Writes a partition object.
-    fn write_partition<W: Write>(&self, writer: &mut Writer<W>, p: &PartitionObject) -> Result<()> {
+    /// Writes a partition's opening tag and properties.
+    fn write_partition_start<W: Write>(
+        &self,
+        writer: &mut Writer<W>,
+        ns: &Namespaces,
+        p: &PartitionObject,
+    ) -> Result<()> {
         writer.write_event(Event::Start(BytesStart::new("partitionobject")))?;
 
         if let Some(idx) = p.partition_index {
@@ -353,33 +578,54 @@ impl DFXMLWriter {
         if let Some(block_size) = p.block_size {
             self.write_simple_element(writer, "block_size", &block_size.to_string())?;
         }
+        if let Some(offset) = p.partition_system_offset {
+            self.write_simple_element(writer, "partition_system_offset", &offset.to_string())?;
+        }
 
         if let Some(ref brs) = p.byte_runs {
             self.write_byte_runs(writer, brs)?;
         }
 
-        // Write child volumes
-        for vol in p.volumes() {
-            self.write_volume(writer, vol)?;
+        self.write_externals(writer, ns, &p.externals)
+    }
+
+    /// Writes a volume object.
+    fn write_volume<W: Write>(
+        &self,
+        writer: &mut Writer<W>,
+        ns: &Namespaces,
+        vol: &VolumeObject,
+    ) -> Result<()> {
+        self.write_volume_start(writer, ns, vol)?;
+
+        // Write nested disk images
+        for di in vol.disk_images() {
+            self.write_disk_image(writer, ns, di)?;
         }
 
-        // Write child partitions
-        for sub_p in p.partitions() {
-            self.write_partition(writer, sub_p)?;
+        // Write nested volumes
+        for nested in vol.volumes() {
+            self.write_volume(writer, ns, nested)?;
         }
 
-        // Write child files
-        for file in p.files() {
-            self.write_file(writer, file)?;
+        // Write files
+        for file in vol.files() {
+            self.write_file(writer, ns, file)?;
         }
 
-        writer.write_event(Event::End(BytesEnd::new("partitionobject")))?;
-        Ok(())
+        self.write_container_end(writer, "volume", vol.error.as_deref())
     }
 
-    /// Writes a volume object.
-    fn write_volume<W: Write>(&self, writer: &mut Writer<W>, vol: &VolumeObject) -> Result<()> {
-        writer.write_event(Event::Start(BytesStart::new("volume")))?;
+    /// Writes a volume's opening tag and properties.
+    fn write_volume_start<W: Write>(
+        &self,
+        writer: &mut Writer<W>,
+        ns: &Namespaces,
+        vol: &VolumeObject,
+    ) -> Result<()> {
+        let mut elem = BytesStart::new("volume");
+        self.push_delta_annos(&mut elem, &vol.annos, "_volume");
+        writer.write_event(Event::Start(elem))?;
 
         if let Some(offset) = vol.partition_offset {
             self.write_simple_element(writer, "partition_offset", &offset.to_string())?;
@@ -417,27 +663,27 @@ impl DFXMLWriter {
             self.write_byte_runs(writer, brs)?;
         }
 
-        // Write nested volumes
-        for nested in vol.volumes() {
-            self.write_volume(writer, nested)?;
-        }
-
-        // Write files
-        for file in vol.files() {
-            self.write_file(writer, file)?;
-        }
-
-        if let Some(ref error) = vol.error {
-            self.write_simple_element(writer, "error", error)?;
+        // Differential annotations
+        self.write_changed_properties(writer, &vol.diffs)?;
+        if let Some(ref original) = vol.original_volume {
+            writer.write_event(Event::Start(BytesStart::new("delta:original_volume")))?;
+            self.write_volume(writer, ns, original)?;
+            writer.write_event(Event::End(BytesEnd::new("delta:original_volume")))?;
         }
 
-        writer.write_event(Event::End(BytesEnd::new("volume")))?;
-        Ok(())
+        self.write_externals(writer, ns, &vol.externals)
     }
 
     /// Writes a file object.
-    fn write_file<W: Write>(&self, writer: &mut Writer<W>, file: &FileObject) -> Result<()> {
-        writer.write_event(Event::Start(BytesStart::new("fileobject")))?;
+    fn write_file<W: Write>(
+        &self,
+        writer: &mut Writer<W>,
+        ns: &Namespaces,
+        file: &FileObject,
+    ) -> Result<()> {
+        let mut elem = BytesStart::new("fileobject");
+        self.push_delta_annos(&mut elem, &file.annos, "_file");
+        writer.write_event(Event::Start(elem))?;
 
         // Write properties in DFXML schema order
         if let Some(ref filename) = file.filename {
@@ -494,23 +740,7 @@ impl DFXMLWriter {
             self.write_simple_element(writer, "inode", &inode.to_string())?;
         }
         if let Some(ref meta_type) = file.meta_type {
-            self.write_simple_element(
-                writer,
-                "meta_type",
-                &(crate::objects::MetaType::from_code(match meta_type {
-                    crate::objects::MetaType::Regular => 1,
-                    crate::objects::MetaType::Directory => 2,
-                    crate::objects::MetaType::SymbolicLink => 3,
-                    crate::objects::MetaType::BlockDevice => 4,
-                    crate::objects::MetaType::CharacterDevice => 5,
-                    crate::objects::MetaType::Fifo => 6,
-                    crate::objects::MetaType::Socket => 7,
-                    crate::objects::MetaType::Shadow => 8,
-                    crate::objects::MetaType::Virtual => 9,
-                    crate::objects::MetaType::Unknown => 0,
-                }) as u8)
-                    .to_string(),
-            )?;
+            self.write_simple_element(writer, "meta_type", &meta_type.code().to_string())?;
         }
         if let Some(mode) = file.mode {
             self.write_simple_element(writer, "mode", &format!("{:o}", mode))?;
@@ -580,10 +810,110 @@ impl DFXMLWriter {
         // Hashes
         self.write_hashes(writer, &file.hashes)?;
 
+        // Differential annotations
+        self.write_changed_properties(writer, &file.diffs)?;
+        if let Some(ref original) = file.original_fileobject {
+            writer.write_event(Event::Start(BytesStart::new("delta:original_fileobject")))?;
+            self.write_file(writer, ns, original)?;
+            writer.write_event(Event::End(BytesEnd::new("delta:original_fileobject")))?;
+        }
+
+        self.write_externals(writer, ns, &file.externals)?;
+
         writer.write_event(Event::End(BytesEnd::new("fileobject")))?;
         Ok(())
     }
 
+    /// Adds `delta:` annotation attributes (e.g. `delta:new_file="1"`).
+    ///
+    /// The `matched` annotation is written as `delta:matched` without a suffix.
+    fn push_delta_annos(&self, elem: &mut BytesStart<'_>, annos: &HashSet<String>, suffix: &str) {
+        let mut annos: Vec<_> = annos.iter().collect();
+        annos.sort_unstable();
+        for anno in annos {
+            let key = if anno == "matched" {
+                "delta:matched".to_string()
+            } else {
+                format!("delta:{}{}", anno, suffix)
+            };
+            elem.push_attribute((key.as_str(), "1"));
+        }
+    }
+
+    /// Writes the `delta:changed_properties` element listing differing properties.
+    fn write_changed_properties<W: Write>(
+        &self,
+        writer: &mut Writer<W>,
+        diffs: &HashSet<String>,
+    ) -> Result<()> {
+        if diffs.is_empty() {
+            return Ok(());
+        }
+        let mut diffs: Vec<_> = diffs.iter().map(String::as_str).collect();
+        diffs.sort_unstable();
+        self.write_simple_element(writer, "delta:changed_properties", &diffs.join(" "))
+    }
+
+    /// Writes external (non-DFXML namespace) elements.
+    fn write_externals<W: Write>(
+        &self,
+        writer: &mut Writer<W>,
+        ns: &Namespaces,
+        externals: &Externals,
+    ) -> Result<()> {
+        for elem in externals.iter() {
+            self.write_external(writer, ns, elem)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a single external element and its children.
+    fn write_external<W: Write>(
+        &self,
+        writer: &mut Writer<W>,
+        ns: &Namespaces,
+        elem: &ExternalElement,
+    ) -> Result<()> {
+        let mut local_decls = Vec::new();
+        let name = ns.qualify(elem.namespace.as_deref(), &elem.tag_name, &mut local_decls);
+
+        // Attribute names may be in Clark notation (`{uri}name`)
+        let attributes: Vec<(String, &str)> = elem
+            .attributes
+            .iter()
+            .map(|(key, value)| {
+                let key = match key.strip_prefix('{').and_then(|k| k.split_once('}')) {
+                    Some((uri, local)) => ns.qualify(Some(uri), local, &mut local_decls),
+                    None => key.clone(),
+                };
+                (key, value.as_str())
+            })
+            .collect();
+
+        let mut start = BytesStart::new(name.as_str());
+        for (prefix, uri) in &local_decls {
+            start.push_attribute((format!("xmlns:{}", prefix).as_str(), uri.as_str()));
+        }
+        for (key, value) in &attributes {
+            start.push_attribute((key.as_str(), *value));
+        }
+
+        if elem.text.is_none() && elem.children.is_empty() {
+            writer.write_event(Event::Empty(start))?;
+            return Ok(());
+        }
+
+        writer.write_event(Event::Start(start))?;
+        if let Some(ref text) = elem.text {
+            writer.write_event(Event::Text(BytesText::new(text)))?;
+        }
+        for child in &elem.children {
+            self.write_external(writer, ns, child)?;
+        }
+        writer.write_event(Event::End(BytesEnd::new(name.as_str())))?;
+        Ok(())
+    }
+
     /// Writes a timestamp element.
     fn write_timestamp<W: Write>(
         &self,
@@ -721,6 +1051,173 @@ impl Default for DFXMLWriter {
     }
 }
 
+/// Incremental DFXML writer.
+///
+/// Writes the document header, containers and file objects as they are
+/// produced, in constant memory. Writing the same objects in the same order
+/// as [`DFXMLWriter`] produces identical output.
+///
+/// Containers are opened with `start_*` and closed with the matching `end_*`
+/// method; the properties of the object passed to `start_*` are written
+/// immediately, and any child objects it holds are ignored.
+pub struct DFXMLStreamWriter<W: Write> {
+    writer: Writer<Output<W>>,
+    inner: DFXMLWriter,
+    namespaces: Option<Namespaces>,
+    /// Open containers, innermost last, with the error to write on close
+    open: Vec<(&'static str, Option<String>)>,
+}
+
+impl<W: Write> DFXMLStreamWriter<W> {
+    /// Creates a streaming writer with default configuration.
+    pub fn new(writer: W) -> Self {
+        #[cfg(feature = "compression")]
+        let output = Encoder::None(writer);
+        #[cfg(not(feature = "compression"))]
+        let output = writer;
+        Self::with_output(DFXMLWriter::new(), output)
+    }
+
+    /// Creates a streaming writer with the specified configuration. Fails
+    /// if the compressed output cannot be set up.
+    pub fn with_config(writer: W, config: WriterConfig) -> Result<Self> {
+        let inner = DFXMLWriter::with_config(config);
+        let output = inner.config.output(writer)?;
+        Ok(Self::with_output(inner, output))
+    }
+
+    fn with_output(inner: DFXMLWriter, output: Output<W>) -> Self {
+        Self {
+            writer: inner.xml_writer(output),
+            inner,
+            namespaces: None,
+            open: Vec::new(),
+        }
+    }
+
+    /// Writes the XML declaration, the opening `<dfxml>` tag and the
+    /// document metadata (creator, sources and externals).
+    ///
+    /// Child objects of `doc` are not written.
+    pub fn start_document(&mut self, doc: &DFXMLObject) -> Result<()> {
+        if self.namespaces.is_some() {
+            return Err(Error::WriterState("document already started".to_string()));
+        }
+        let namespaces = Namespaces::from_doc(doc);
+        self.inner
+            .write_document_start(&mut self.writer, &namespaces, doc)?;
+        self.namespaces = Some(namespaces);
+        Ok(())
+    }
+
+    /// Opens a disk image.
+    pub fn start_disk_image(&mut self, di: &DiskImageObject) -> Result<()> {
+        let ns = self.namespaces.as_ref().ok_or_else(not_started)?;
+        self.inner
+            .write_disk_image_start(&mut self.writer, ns, di)?;
+        self.open.push(("diskimageobject", di.error.clone()));
+        Ok(())
+    }
+
+    /// Closes the innermost container, which must be a disk image.
+    pub fn end_disk_image(&mut self) -> Result<()> {
+        self.end_container("diskimageobject")
+    }
+
+    /// Opens a partition system.
+    pub fn start_partition_system(&mut self, ps: &PartitionSystemObject) -> Result<()> {
+        let ns = self.namespaces.as_ref().ok_or_else(not_started)?;
+        self.inner
+            .write_partition_system_start(&mut self.writer, ns, ps)?;
+        self.open.push(("partitionsystemobject", ps.error.clone()));
+        Ok(())
+    }
+
+    /// Closes the innermost container, which must be a partition system.
+    pub fn end_partition_system(&mut self) -> Result<()> {
+        self.end_container("partitionsystemobject")
+    }
+
+    /// Opens a partition.
+    pub fn start_partition(&mut self, p: &PartitionObject) -> Result<()> {
+        let ns = self.namespaces.as_ref().ok_or_else(not_started)?;
+        self.inner.write_partition_start(&mut self.writer, ns, p)?;
+        self.open.push(("partitionobject", None));
+        Ok(())
+    }
+
+    /// Closes the innermost container, which must be a partition.
+    pub fn end_partition(&mut self) -> Result<()> {
+        self.end_container("partitionobject")
+    }
+
+    /// Opens a volume.
+    pub fn start_volume(&mut self, vol: &VolumeObject) -> Result<()> {
+        let ns = self.namespaces.as_ref().ok_or_else(not_started)?;
+        self.inner.write_volume_start(&mut self.writer, ns, vol)?;
+        self.open.push(("volume", vol.error.clone()));
+        Ok(())
+    }
+
+    /// Closes the innermost container, which must be a volume.
+    pub fn end_volume(&mut self) -> Result<()> {
+        self.end_container("volume")
+    }
+
+    /// Writes a file object into the innermost open container (or the
+    /// document itself).
+    pub fn write_file(&mut self, file: &FileObject) -> Result<()> {
+        let ns = self.namespaces.as_ref().ok_or_else(not_started)?;
+        self.inner.write_file(&mut self.writer, ns, file)
+    }
+
+    /// Closes any open containers and the `<dfxml>` element, and ends the
+    /// compressed stream if the output is compressed, returning the
+    /// underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.namespaces.as_ref().ok_or_else(not_started)?;
+        while let Some((name, error)) = self.open.pop() {
+            self.inner
+                .write_container_end(&mut self.writer, name, error.as_deref())?;
+        }
+        self.writer
+            .write_event(Event::End(BytesEnd::new("dfxml")))?;
+        finish_output(self.writer.into_inner())
+    }
+
+    /// Returns a mutable reference to the underlying writer. When the
+    /// output is compressed, anything written to it directly bypasses the
+    /// compression.
+    pub fn get_mut(&mut self) -> &mut W {
+        #[cfg(feature = "compression")]
+        return self.writer.get_mut().get_mut();
+        #[cfg(not(feature = "compression"))]
+        self.writer.get_mut()
+    }
+
+    fn end_container(&mut self, name: &str) -> Result<()> {
+        match self.open.last() {
+            Some((open, _)) if *open == name => {}
+            Some((open, _)) => {
+                return Err(Error::WriterState(format!(
+                    "cannot close {} while {} is open",
+                    name, open
+                )))
+            }
+            None => return Err(Error::WriterState(format!("no open {}", name))),
+        }
+        if let Some((name, error)) = self.open.pop() {
+            self.inner
+                .write_container_end(&mut self.writer, name, error.as_deref())?;
+        }
+        Ok(())
+    }
+}
+
+fn not_started() -> Error {
+    Error::WriterState("document not started".to_string())
+}
+
 /// Convenience function to write a DFXMLObject to a string.
 pub fn to_string(doc: &DFXMLObject) -> Result<String> {
     DFXMLWriter::new().write_to_string(doc)
@@ -867,4 +1364,174 @@ mod tests {
         assert_eq!(files[0].filesize, Some(2048));
         assert_eq!(files[0].inode, Some(12345));
     }
+
+    #[test]
+    fn test_delta_roundtrip() {
+        let mut doc = DFXMLObject::new();
+        let mut vol = VolumeObject::with_ftype("ntfs");
+
+        let mut original = FileObject::with_filename("old.txt");
+        original.filesize = Some(10);
+        let mut file = FileObject::with_filename("new.txt");
+        file.filesize = Some(20);
+        file.annos.insert("renamed".to_string());
+        file.annos.insert("modified".to_string());
+        file.diffs.insert("filename".to_string());
+        file.diffs.insert("filesize".to_string());
+        file.original_fileobject = Some(Box::new(original));
+
+        vol.append_file(file);
+        doc.append_volume(vol);
+
+        let xml = to_string(&doc).unwrap();
+        assert!(xml.contains("xmlns:delta="));
+        assert!(xml.contains("delta:modified_file=\"1\""));
+        assert!(xml.contains("delta:renamed_file=\"1\""));
+        assert!(
+            xml.contains("<delta:changed_properties>filename filesize</delta:changed_properties>")
+        );
+        assert!(xml.contains("<delta:original_fileobject>"));
+
+        use std::io::Cursor;
+        let parsed = crate::reader::parse(Cursor::new(xml.as_bytes())).unwrap();
+        let files: Vec<_> = parsed.iter_files().collect();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].filename, Some("new.txt".to_string()));
+        assert!(files[0].annos.contains("renamed"));
+        assert!(files[0].annos.contains("modified"));
+        assert!(files[0].diffs.contains("filesize"));
+        let original = files[0].original_fileobject.as_deref().unwrap();
+        assert_eq!(original.filename, Some("old.txt".to_string()));
+        assert_eq!(original.filesize, Some(10));
+    }
+
+    #[test]
+    fn test_externals_roundtrip() {
+        use crate::objects::ExternalElement;
+
+        let mut doc = DFXMLObject::new();
+        doc.add_namespace("ourtool", "http://example.org/ourtool");
+
+        let mut class = ExternalElement::with_namespace("http://example.org/ourtool", "class");
+        class.add_attribute("{http://example.org/ourtool}confidence", "0.9");
+        let mut label = ExternalElement::with_namespace("http://example.org/ourtool", "label");
+        label.set_text("invoice");
+        class.add_child(label);
+
+        let mut file = FileObject::with_filename("a.txt");
+        file.externals.push(class.clone());
+        doc.append_file(file);
+
+        // A namespace that the document does not declare gets a generated prefix
+        doc.externals.push(ExternalElement::with_namespace(
+            "http://example.org/other",
+            "note",
+        ));
+
+        let xml = to_string(&doc).unwrap();
+        assert!(xml.contains("xmlns:ourtool=\"http://example.org/ourtool\""));
+        assert!(xml.contains("<ourtool:class ourtool:confidence=\"0.9\">"));
+        assert!(xml.contains("<ourtool:label>invoice</ourtool:label>"));
+        assert!(xml.contains("<ns0:note xmlns:ns0=\"http://example.org/other\"/>"));
+
+        use std::io::Cursor;
+        let parsed = crate::reader::parse(Cursor::new(xml.as_bytes())).unwrap();
+        let files: Vec<_> = parsed.iter_files().collect();
+        assert_eq!(files[0].externals.iter().next(), Some(&class));
+        let note = parsed.externals.iter().next().unwrap();
+        assert_eq!(note.qualified_name(), "{http://example.org/other}note");
+    }
+
+    #[test]
+    fn test_stream_matches_batch() {
+        use crate::objects::{DiskImageObject, PartitionObject};
+
+        let mut doc = DFXMLObject::new();
+        doc.program = Some("stream-test".to_string());
+        doc.sources.push("disk.raw".to_string());
+
+        let mut di = DiskImageObject::new();
+        di.image_filename = Some("disk.raw".to_string());
+        di.error = Some("short read".to_string());
+        let mut partition = PartitionObject::new();
+        partition.partition_index = Some(1);
+        let mut vol = VolumeObject::with_ftype("ext4");
+        let mut file = FileObject::with_filename("a.txt");
+        file.filesize = Some(3);
+        let top = FileObject::with_filename("top.txt");
+
+        let mut stream = DFXMLStreamWriter::new(Vec::new());
+        stream.start_document(&doc).unwrap();
+        stream.start_disk_image(&di).unwrap();
+        stream.start_partition(&partition).unwrap();
+        stream.start_volume(&vol).unwrap();
+        stream.write_file(&file).unwrap();
+        stream.end_volume().unwrap();
+        stream.end_partition().unwrap();
+        stream.end_disk_image().unwrap();
+        stream.write_file(&top).unwrap();
+        let streamed = String::from_utf8(stream.finish().unwrap()).unwrap();
+
+        vol.append_file(file);
+        partition.append_volume(vol);
+        di.append_partition(partition);
+        doc.append_disk_image(di);
+        doc.append_file(top);
+
+        assert_eq!(streamed, to_string(&doc).unwrap());
+    }
+
+    #[test]
+    fn test_stream_state_errors() {
+        let mut stream = DFXMLStreamWriter::new(Vec::new());
+        assert!(stream.write_file(&FileObject::new()).is_err());
+
+        stream.start_document(&DFXMLObject::new()).unwrap();
+        assert!(stream.start_document(&DFXMLObject::new()).is_err());
+        stream.start_volume(&VolumeObject::new()).unwrap();
+        assert!(stream.end_partition().is_err());
+        stream.end_volume().unwrap();
+        assert!(stream.end_volume().is_err());
+
+        // finish() closes anything still open
+        stream.start_volume(&VolumeObject::new()).unwrap();
+        let xml = String::from_utf8(stream.finish().unwrap()).unwrap();
+        assert!(xml.ends_with("</dfxml>"));
+        assert_eq!(xml.matches("</volume>").count(), 2);
+    }
+
+    #[test]
+    fn test_nested_roundtrip() {
+        use crate::objects::{DiskImageObject, PartitionObject, PartitionSystemObject};
+
+        let mut inner = VolumeObject::with_ftype("iso9660");
+        inner.append_file(FileObject::with_filename("inner.txt"));
+        let mut nested_image = DiskImageObject::new();
+        nested_image.image_filename = Some("nested.raw".to_string());
+        let mut outer = VolumeObject::with_ftype("ext4");
+        outer.append_volume(inner);
+        outer.append_disk_image(nested_image);
+        outer.append_file(FileObject::with_filename("outer.txt"));
+
+        let mut partition = PartitionObject::new();
+        partition.partition_index = Some(1);
+        partition.append_volume(outer);
+        partition.append_partition_system(PartitionSystemObject::new());
+        let mut ps = PartitionSystemObject::new();
+        ps.pstype_str = Some("gpt".to_string());
+        ps.append_partition(partition);
+        let mut di = DiskImageObject::new();
+        di.image_filename = Some("disk.raw".to_string());
+        di.append_partition_system(ps);
+
+        let mut doc = DFXMLObject::new();
+        doc.append_disk_image(di);
+        doc.append_volume(VolumeObject::with_ftype("fat32"));
+
+        let xml = to_string(&doc).unwrap();
+        use std::io::Cursor;
+        let parsed = crate::reader::parse(Cursor::new(xml.as_bytes())).unwrap();
+        assert_eq!(to_string(&parsed).unwrap(), xml);
+        assert_eq!(parsed.iter_files().count(), 2);
+    }
 }
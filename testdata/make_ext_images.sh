@@ -0,0 +1,44 @@
+#!/bin/sh
+# Regenerates the ext2 and ext4 fixture images used by the filesystem tests.
+# Needs mke2fs and debugfs from e2fsprogs 1.43 or later.
+set -eu
+
+cd "$(dirname "$0")"
+root=$(mktemp -d)
+trap 'rm -rf "$root"' EXIT
+
+mkdir -p "$root/docs/deep" "$root/empty"
+printf 'hello, world\n' > "$root/hello.txt"
+yes 'notes' | head -n 100 > "$root/docs/deep/notes.txt"
+# 40000 bytes, enough to need indirect blocks on ext2
+yes '0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ' | head -c 40000 > "$root/docs/big.bin"
+yes 'this file will be deleted' | head -n 20 > "$root/deleted.txt"
+yes 'orphaned' | head -n 10 > "$root/orphan.txt"
+yes 'unlinked and freed' | head -n 10 > "$root/gone.txt"
+# A hole between two 100-byte extents
+head -c 100 /dev/zero | tr '\0' 'A' > "$root/sparse.bin"
+head -c 100 /dev/zero | tr '\0' 'Z' | dd of="$root/sparse.bin" bs=1 seek=30000 conv=notrunc 2>/dev/null
+ln -s hello.txt "$root/link"
+ln -s docs/deep/../deep/../deep/../deep/../deep/../deep/notes.txt-long-target-name "$root/longlink"
+find "$root" -exec touch -h -d '2024-01-02 03:04:05 UTC' {} +
+
+export E2FSPROGS_FAKE_TIME=1704164645
+for fs in ext2 ext4; do
+    image=$fs.img
+    rm -f "$image"
+    mke2fs -q -F -t $fs -b 1024 -g 256 -N 64 -I 256 -O ^has_journal -L $fs-fixture \
+        -U 6d2b6f53-56b2-4e0c-9b47-5b0d8f2a1c3e -E hash_seed=6d2b6f53-56b2-4e0c-9b47-5b0d8f2a1c3e \
+        -d "$root" "$image" 512k
+    # A deleted file, an allocated orphan and an unallocated orphan
+    debugfs -w -R 'rm /deleted.txt' "$image" >/dev/null 2>&1
+    debugfs -w -R 'unlink /orphan.txt' "$image" >/dev/null 2>&1
+    ino=$(debugfs -R 'stat /gone.txt' "$image" 2>/dev/null | sed -n 's/^Inode: \([0-9]*\).*/\1/p')
+    debugfs -w -R 'unlink /gone.txt' "$image" >/dev/null 2>&1
+    debugfs -w -R "kill_file <$ino>" "$image" >/dev/null 2>&1
+    # Clear the inode numbers of the two unlinked names, so nothing names
+    # their inodes any more (this leaves stale directory block checksums)
+    for name in orphan.txt gone.txt; do
+        offset=$(grep -obUa "$name" "$image" | head -n 1 | cut -d: -f1)
+        dd if=/dev/zero of="$image" bs=1 seek=$((offset - 8)) count=4 conv=notrunc 2>/dev/null
+    done
+done